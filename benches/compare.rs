@@ -0,0 +1,315 @@
+//! Compares the basic operations and the data structures of CIRC with `crossbeam-epoch` and
+//! `std::sync::Arc`.
+//!
+//! Run with `cargo bench --features bench-compare --bench compare`.
+
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Barrier, Mutex};
+use std::time::{Duration, Instant};
+
+use circ::ds::{ArrayQueue, Stack};
+use circ::{cs, AtomicRc, Rc, RcObject};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_epoch as epoch;
+use crossbeam_utils::thread::scope;
+
+struct Item;
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+/// The numbers of threads of the throughput benchmarks.
+const THREADS: [usize; 3] = [1, 4, 8];
+
+/// The number of operations each thread performs in an iteration of a throughput benchmark.
+const OPS_PER_THREAD: u64 = 10_000;
+
+fn init() {
+    unsafe { dyntls_host::get().initialize() };
+}
+
+/// Runs `op` `OPS_PER_THREAD` times on each of `threads` threads `iters` times, and returns the
+/// time from the moment all threads are ready until they have finished.
+///
+/// `op` is given whether it should add or remove an element. Half of the calls of each thread
+/// do either, so that the structure stays small.
+fn run_threads<F: Fn(bool) + Sync>(threads: usize, iters: u64, op: F) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        let barrier = Barrier::new(threads + 1);
+        let elapsed = scope(|s| {
+            for t in 0..threads {
+                let (barrier, op) = (&barrier, &op);
+                s.spawn(move |_| {
+                    init();
+                    barrier.wait();
+                    for i in 0..OPS_PER_THREAD {
+                        op((t as u64 + i).is_multiple_of(2));
+                    }
+                    barrier.wait();
+                });
+            }
+            barrier.wait();
+            let start = Instant::now();
+            barrier.wait();
+            start.elapsed()
+        })
+        .unwrap();
+        total += elapsed;
+    }
+    total
+}
+
+fn pin(c: &mut Criterion) {
+    init();
+    let mut group = c.benchmark_group("pin");
+    group.bench_function("circ", |b| b.iter(|| drop(black_box(cs()))));
+    group.bench_function("crossbeam-epoch", |b| {
+        b.iter(|| drop(black_box(epoch::pin())))
+    });
+    group.finish();
+}
+
+fn load(c: &mut Criterion) {
+    init();
+    let mut group = c.benchmark_group("load");
+
+    let circ = AtomicRc::new(Item);
+    group.bench_function("circ", |b| {
+        let guard = &cs();
+        b.iter(|| black_box(circ.load(Ordering::Acquire, guard)))
+    });
+
+    let crossbeam = epoch::Atomic::new(Item);
+    group.bench_function("crossbeam-epoch", |b| {
+        let guard = &epoch::pin();
+        b.iter(|| black_box(crossbeam.load(Ordering::Acquire, guard)))
+    });
+    group.finish();
+
+    // SAFETY: no other thread accesses the pointer anymore.
+    unsafe { drop(crossbeam.into_owned()) };
+}
+
+/// Replaces the object with a new one by a compare-and-swap, which includes the allocation of
+/// the new object and the retirement of the old one.
+fn cas(c: &mut Criterion) {
+    init();
+    let mut group = c.benchmark_group("cas");
+
+    let circ = AtomicRc::new(Item);
+    group.bench_function("circ", |b| {
+        b.iter(|| {
+            let guard = &cs();
+            let current = circ.load(Ordering::Acquire, guard);
+            let _ = circ.compare_exchange(
+                current,
+                Rc::new(Item),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            );
+        })
+    });
+
+    let crossbeam = epoch::Atomic::new(Item);
+    group.bench_function("crossbeam-epoch", |b| {
+        b.iter(|| {
+            let guard = &epoch::pin();
+            let current = crossbeam.load(Ordering::Acquire, guard);
+            if crossbeam
+                .compare_exchange(
+                    current,
+                    epoch::Owned::new(Item),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                )
+                .is_ok()
+            {
+                // SAFETY: `current` has been unlinked and is not reachable anymore.
+                unsafe { guard.defer_destroy(current) };
+            }
+        })
+    });
+    group.finish();
+
+    // SAFETY: no other thread accesses the pointer anymore.
+    unsafe { drop(crossbeam.into_owned()) };
+}
+
+fn clone_drop(c: &mut Criterion) {
+    init();
+    let mut group = c.benchmark_group("clone_drop");
+
+    let circ = Rc::new(Item);
+    group.bench_function("circ", |b| b.iter(|| drop(black_box(circ.clone()))));
+
+    let arc = Arc::new(Item);
+    group.bench_function("std-arc", |b| b.iter(|| drop(black_box(arc.clone()))));
+    group.finish();
+}
+
+/// A Treiber stack on `crossbeam-epoch`, the counterpart of [`Stack`].
+struct EpochStack {
+    head: epoch::Atomic<EpochNode>,
+}
+
+struct EpochNode {
+    item: Arc<Item>,
+    next: epoch::Atomic<EpochNode>,
+}
+
+impl EpochStack {
+    fn new() -> Self {
+        Self {
+            head: epoch::Atomic::null(),
+        }
+    }
+
+    fn push(&self, item: Arc<Item>) {
+        let guard = &epoch::pin();
+        let mut node = epoch::Owned::new(EpochNode {
+            item,
+            next: epoch::Atomic::null(),
+        });
+        loop {
+            let head = self.head.load(Ordering::Relaxed, guard);
+            node.next.store(head, Ordering::Relaxed);
+            match self.head.compare_exchange(
+                head,
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(_) => return,
+                Err(e) => node = e.new,
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<Arc<Item>> {
+        let guard = &epoch::pin();
+        loop {
+            let head = self.head.load(Ordering::Acquire, guard);
+            // SAFETY: `head` is protected by `guard`.
+            let node = unsafe { head.as_ref() }?;
+            let next = node.next.load(Ordering::Relaxed, guard);
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                let item = node.item.clone();
+                // SAFETY: `head` has been unlinked and is not reachable anymore.
+                unsafe { guard.defer_destroy(head) };
+                return Some(item);
+            }
+        }
+    }
+}
+
+impl Drop for EpochStack {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+fn stack(c: &mut Criterion) {
+    init();
+    let mut group = c.benchmark_group("stack");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64 * OPS_PER_THREAD));
+
+        let circ = Stack::new();
+        group.bench_with_input(
+            BenchmarkId::new("circ", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |add| {
+                        let guard = &cs();
+                        if add {
+                            circ.push(Rc::new(Item), guard);
+                        } else {
+                            black_box(circ.pop(guard));
+                        }
+                    })
+                })
+            },
+        );
+
+        let crossbeam = EpochStack::new();
+        group.bench_with_input(
+            BenchmarkId::new("crossbeam-epoch", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |add| {
+                        if add {
+                            crossbeam.push(Arc::new(Item));
+                        } else {
+                            black_box(crossbeam.pop());
+                        }
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn queue(c: &mut Criterion) {
+    const CAPACITY: usize = 1024;
+
+    init();
+    let mut group = c.benchmark_group("queue");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64 * OPS_PER_THREAD));
+
+        let circ = ArrayQueue::new(CAPACITY);
+        group.bench_with_input(
+            BenchmarkId::new("circ", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |add| {
+                        if add {
+                            let _ = circ.try_push(Rc::new(Item));
+                        } else {
+                            black_box(circ.try_pop());
+                        }
+                    })
+                })
+            },
+        );
+
+        let mutex = Mutex::new(VecDeque::with_capacity(CAPACITY));
+        group.bench_with_input(
+            BenchmarkId::new("std-arc-mutex", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |add| {
+                        let mut queue = mutex.lock().unwrap();
+                        if add {
+                            if queue.len() < CAPACITY {
+                                queue.push_back(Arc::new(Item));
+                            }
+                        } else {
+                            black_box(queue.pop_front());
+                        }
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pin, load, cas, clone_drop, stack, queue);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+//! Measures the cost of registering participants and advancing the epoch while many short-lived
+//! threads come and go.
+//!
+//! Run with `cargo bench --bench participants`.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use circ::{cs, Collector, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+struct Item;
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+const ROUNDS: usize = 16;
+const RETIRES: usize = 64;
+
+/// Spawns `threads` short-lived workers `ROUNDS` times, while another thread keeps trying to
+/// advance the epoch. Returns the elapsed time and the number of epoch-advance attempts.
+fn churn(threads: usize) -> (Duration, usize) {
+    let context = dyntls_host::get();
+    unsafe { context.initialize() };
+
+    let collector = Collector::new();
+    let done = AtomicBool::new(false);
+    let advances = AtomicUsize::new(0);
+    let start = Instant::now();
+    scope(|s| {
+        s.spawn(|_| {
+            unsafe { context.initialize() };
+            let handle = collector.register();
+            while !done.load(Ordering::Relaxed) {
+                let _ = collector.try_advance(&handle.pin());
+                advances.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        for _ in 0..ROUNDS {
+            scope(|s| {
+                for _ in 0..threads {
+                    s.spawn(|_| {
+                        unsafe { context.initialize() };
+                        collector.enter(|| {
+                            for _ in 0..RETIRES {
+                                let guard = &cs();
+                                Rc::new(Item).finalize(guard);
+                            }
+                        });
+                    });
+                }
+            })
+            .unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    })
+    .unwrap();
+    (start.elapsed(), advances.load(Ordering::Relaxed))
+}
+
+fn main() {
+    println!("{:>8} {:>12} {:>16}", "threads", "elapsed", "advances/ms");
+    for threads in [16, 64, 128, 256] {
+        let (elapsed, advances) = churn(threads);
+        println!(
+            "{:>8} {:>10.2?} {:>16.1}",
+            threads,
+            elapsed,
+            advances as f64 / elapsed.as_secs_f64() / 1000.0
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Interprets the input as sequences of operations on a few shared `AtomicRc`s, one sequence per
+//! thread, and runs the threads concurrently.
+//!
+//! Run with `cargo fuzz run atomic_rc_ops` from the root of the crate.
+
+#![no_main]
+
+use std::sync::atomic::Ordering;
+
+use arbitrary::Arbitrary;
+use circ::{cs, AtomicRc, Rc, RcObject};
+use libfuzzer_sys::fuzz_target;
+
+/// The number of shared atomic pointers.
+const SLOTS: usize = 4;
+
+/// The number of `Rc`s held by each thread.
+const LOCALS: usize = 4;
+
+/// The maximum number of threads.
+const THREADS: usize = 4;
+
+/// The tags must fit into the alignment of `Node`.
+const TAGS: u8 = 8;
+
+#[repr(align(8))]
+struct Node {
+    value: u8,
+    next: Rc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.next.clone());
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    /// Allocates a node that points to a local and holds it in another local.
+    New { local: u8, next: u8, value: u8 },
+    /// Loads a slot into a local.
+    Load { slot: u8, local: u8 },
+    /// Stores a local into a slot.
+    Store { slot: u8, local: u8 },
+    /// Swaps a local with a slot.
+    Swap { slot: u8, local: u8 },
+    /// Replaces a slot with a local if the slot still holds what has been loaded from it.
+    CompareExchange { slot: u8, local: u8, weak: bool },
+    /// Replaces the tag of a slot if the slot still holds what has been loaded from it.
+    CompareExchangeTag { slot: u8, tag: u8 },
+    /// Clones a local into another local.
+    Clone { from: u8, to: u8 },
+    /// Retags a local.
+    Tag { local: u8, tag: u8 },
+    /// Drops a local.
+    Drop { local: u8 },
+    /// Drops a local without pinning again.
+    Finalize { local: u8 },
+    /// Replaces a local with the next node of its object.
+    Next { local: u8 },
+    /// Ends the critical section and starts a new one.
+    Repin,
+    /// Flushes the garbage of the thread.
+    Flush,
+}
+
+fuzz_target!(|threads: Vec<Vec<Op>>| {
+    let context = &dyntls_host::get();
+    unsafe { context.initialize() };
+
+    let slots: [AtomicRc<Node>; SLOTS] = Default::default();
+    std::thread::scope(|s| {
+        for ops in threads.iter().take(THREADS) {
+            let slots = &slots;
+            s.spawn(move || {
+                unsafe { context.initialize() };
+                run(ops, slots);
+            });
+        }
+    });
+});
+
+fn run(ops: &[Op], slots: &[AtomicRc<Node>; SLOTS]) {
+    let mut locals: [Rc<Node>; LOCALS] = Default::default();
+    let slot = |i: &u8| &slots[*i as usize % SLOTS];
+    let local = |i: &u8| *i as usize % LOCALS;
+
+    let mut guard = cs();
+    for op in ops {
+        match op {
+            Op::New {
+                local: l,
+                next,
+                value,
+            } => {
+                let next = locals[local(next)].clone();
+                locals[local(l)] = Rc::new(Node {
+                    value: *value,
+                    next,
+                });
+                assert_eq!(locals[local(l)].as_ref().unwrap().value, *value);
+            }
+            Op::Load { slot: s, local: l } => {
+                let snapshot = slot(s).load(Ordering::Acquire, &guard);
+                if let Some(node) = snapshot.as_ref() {
+                    // Touch the object, which must still be alive.
+                    let _ = node.value;
+                }
+                let tag = snapshot.tag();
+                let rc = Rc::from(snapshot);
+                assert_eq!(rc.tag(), tag);
+                locals[local(l)] = rc;
+            }
+            Op::Store { slot: s, local: l } => {
+                slot(s).store(locals[local(l)].clone(), Ordering::Release, &guard);
+            }
+            Op::Swap { slot: s, local: l } => {
+                let new = std::mem::take(&mut locals[local(l)]);
+                locals[local(l)] = slot(s).swap(new, Ordering::AcqRel);
+            }
+            Op::CompareExchange {
+                slot: s,
+                local: l,
+                weak,
+            } => {
+                let current = slot(s).load(Ordering::Acquire, &guard);
+                let desired = locals[local(l)].clone();
+                let result = if *weak {
+                    slot(s).compare_exchange_weak(
+                        current,
+                        desired,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        &guard,
+                    )
+                } else {
+                    slot(s).compare_exchange(
+                        current,
+                        desired,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        &guard,
+                    )
+                };
+                match result {
+                    Ok(old) => assert!(old.ptr_eq(&Rc::from(current))),
+                    Err(e) => assert!(e.desired.ptr_eq(&locals[local(l)])),
+                }
+            }
+            Op::CompareExchangeTag { slot: s, tag } => {
+                let current = slot(s).load(Ordering::Acquire, &guard);
+                let tag = (*tag % TAGS) as usize;
+                if let Ok(new) = slot(s).compare_exchange_tag(
+                    current,
+                    tag,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    &guard,
+                ) {
+                    assert_eq!(new.tag(), tag);
+                }
+            }
+            Op::Clone { from, to } => {
+                let clone = locals[local(from)].clone();
+                assert!(clone.ptr_eq(&locals[local(from)]));
+                assert_eq!(clone.tag(), locals[local(from)].tag());
+                locals[local(to)] = clone;
+            }
+            Op::Tag { local: l, tag } => {
+                let tag = (*tag % TAGS) as usize;
+                let rc = std::mem::take(&mut locals[local(l)]).with_tag(tag);
+                assert_eq!(rc.tag(), tag);
+                locals[local(l)] = rc;
+            }
+            Op::Drop { local: l } => drop(std::mem::take(&mut locals[local(l)])),
+            Op::Finalize { local: l } => {
+                std::mem::take(&mut locals[local(l)]).finalize(&guard);
+            }
+            Op::Next { local: l } => {
+                let next = locals[local(l)].as_ref().map(|node| node.next.clone());
+                locals[local(l)] = next.unwrap_or_default();
+            }
+            Op::Repin => guard.reactivate(),
+            Op::Flush => guard.flush(),
+        }
+    }
+}
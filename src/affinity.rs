@@ -0,0 +1,245 @@
+//! Destructor thread affinity for payloads that must be dropped on the thread that created them.
+//!
+//! The collector destructs an object on whichever thread happens to reclaim it, which is wrong
+//! for payloads bound to a thread, such as GUI handles or objects of a thread-bound foreign
+//! library. Wrapping such a payload in a [`ThreadBound`] opts it into thread affinity: when the
+//! wrapper is dropped on another thread, the payload is not dropped there but sent to the mailbox
+//! of its owner thread, which drops it when the owner calls [`drain`], typically from its event
+//! loop, or when the owner thread exits.
+//!
+//! The payload is only accessible on the owner thread, so a `ThreadBound` is [`Send`] and
+//! [`Sync`] even if the payload is not, and an object holding one can be shared through an
+//! [`Rc`](crate::Rc).
+//!
+//! A payload that is sent to a thread that has already exited is leaked, as there is no thread
+//! left to drop it on.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::affinity::{self, ThreadBound};
+//! use circ::{Rc, RcObject};
+//! use std::time::Duration;
+//!
+//! /// A payload that must be dropped on the thread that created it.
+//! struct Window(*mut ());
+//!
+//! struct Node {
+//!     window: ThreadBound<Window>,
+//! }
+//!
+//! unsafe impl RcObject for Node {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let node = Rc::new(Node {
+//!     window: ThreadBound::new(Window(std::ptr::null_mut())),
+//! });
+//! assert!(node.as_ref().unwrap().window.get().is_some());
+//!
+//! std::thread::spawn(move || {
+//!     # let context = dyntls_host::get();
+//!     # unsafe { context.initialize() };
+//!     // The payload is not accessible here, and dropping it defers to the owner thread.
+//!     assert!(node.as_ref().unwrap().window.get().is_none());
+//!     drop(node);
+//!     circ::default_collector().collect_all(Duration::from_secs(1));
+//! })
+//! .join()
+//! .unwrap();
+//!
+//! // Back on the owner thread, drop the payloads sent to it.
+//! affinity::drain();
+//! ```
+
+use std::fmt::{Debug, Formatter};
+use std::mem::ManuallyDrop;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A payload that has been sent to its owner thread to be dropped.
+struct Pending {
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+/// The payload is only dropped on its owner thread, which drains the mailbox.
+unsafe impl Send for Pending {}
+
+impl Pending {
+    fn new<T>(value: T) -> Self {
+        unsafe fn drop_boxed<T>(ptr: *mut ()) {
+            drop(Box::from_raw(ptr.cast::<T>()));
+        }
+        Self {
+            ptr: Box::into_raw(Box::new(value)).cast(),
+            drop: drop_boxed::<T>,
+        }
+    }
+
+    /// Drops the payload.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on the owner thread of the payload.
+    unsafe fn run(self) {
+        (self.drop)(self.ptr);
+    }
+}
+
+/// The payloads sent to a thread, or `None` once the thread has exited.
+struct Mailbox {
+    pending: Mutex<Option<Vec<Pending>>>,
+}
+
+impl Mailbox {
+    fn lock(&self) -> MutexGuard<'_, Option<Vec<Pending>>> {
+        // The mailbox is never left inconsistent, so poisoning is harmless.
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Drops the payloads sent to the thread so far, returning how many there were.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on the owner thread of the mailbox.
+    unsafe fn drain(&self) -> usize {
+        let mut count = 0;
+        loop {
+            // A payload may itself send payloads when dropped, so the lock is not held meanwhile.
+            let pending = match self.lock().as_mut() {
+                Some(pending) if !pending.is_empty() => std::mem::take(pending),
+                _ => return count,
+            };
+            count += pending.len();
+            for p in pending {
+                p.run();
+            }
+        }
+    }
+}
+
+/// The mailbox of a thread, which is closed and drained when the thread exits.
+struct Owner(Arc<Mailbox>);
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        unsafe { self.0.drain() };
+        // Close the mailbox. Payloads sent while dropping the last ones have nowhere left to go.
+        let pending = self.0.lock().take();
+        for p in pending.into_iter().flatten() {
+            unsafe { p.run() };
+        }
+    }
+}
+
+dyntls::thread_local! {
+    /// The mailbox of the current thread.
+    static MAILBOX: Owner = Owner(Arc::new(Mailbox {
+        pending: Mutex::new(Some(Vec::new())),
+    }));
+}
+
+/// Drops the payloads of [`ThreadBound`]s owned by the current thread that have been dropped on
+/// other threads, returning how many have been dropped.
+pub fn drain() -> usize {
+    MAILBOX
+        .try_with(|owner| unsafe { owner.0.drain() })
+        .unwrap_or(0)
+}
+
+/// A payload that is only accessed and dropped on the thread that created it.
+///
+/// See the [module documentation](self) for details.
+pub struct ThreadBound<T> {
+    value: ManuallyDrop<T>,
+    owner: Arc<Mailbox>,
+}
+
+/// The payload is only accessed and dropped on its owner thread.
+unsafe impl<T> Send for ThreadBound<T> {}
+unsafe impl<T> Sync for ThreadBound<T> {}
+
+impl<T> ThreadBound<T> {
+    /// Wraps `value`, making the current thread its owner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called while the thread-local storage of the current thread is being destroyed.
+    pub fn new(value: T) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            owner: MAILBOX.with(|owner| owner.0.clone()),
+        }
+    }
+
+    /// Returns `true` if the current thread is the owner of the payload.
+    pub fn is_owner(&self) -> bool {
+        MAILBOX
+            .try_with(|owner| Arc::ptr_eq(&owner.0, &self.owner))
+            .unwrap_or(false)
+    }
+
+    /// Returns the payload, or `None` if the current thread is not its owner.
+    pub fn get(&self) -> Option<&T> {
+        self.is_owner().then_some(&*self.value)
+    }
+
+    /// Returns the payload without checking the current thread.
+    ///
+    /// # Safety
+    ///
+    /// The current thread must be the owner of the payload.
+    #[inline]
+    pub(crate) unsafe fn get_unchecked(&self) -> &T {
+        debug_assert!(self.is_owner());
+        &self.value
+    }
+
+    /// Returns the payload mutably, or `None` if the current thread is not its owner.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.is_owner() {
+            Some(&mut *self.value)
+        } else {
+            None
+        }
+    }
+
+    /// Unwraps the payload, or returns the wrapper back if the current thread is not its owner.
+    pub fn into_inner(self) -> Result<T, Self> {
+        if !self.is_owner() {
+            return Err(self);
+        }
+        let mut this = ManuallyDrop::new(self);
+        unsafe {
+            let value = ManuallyDrop::take(&mut this.value);
+            std::ptr::drop_in_place(&mut this.owner);
+            Ok(value)
+        }
+    }
+}
+
+impl<T> Drop for ThreadBound<T> {
+    fn drop(&mut self) {
+        if self.is_owner() {
+            unsafe { ManuallyDrop::drop(&mut self.value) };
+            return;
+        }
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        match self.owner.lock().as_mut() {
+            Some(pending) => pending.push(Pending::new(value)),
+            // The owner thread has exited.
+            None => std::mem::forget(value),
+        }
+    }
+}
+
+impl<T: Debug> Debug for ThreadBound<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("ThreadBound").field(value).finish(),
+            None => f.pad("ThreadBound(<other thread>)"),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Biased reference counting for objects that are mostly used by one thread.
+//!
+//! Cloning and dropping an [`Rc`] always updates the shared reference count of the object with an
+//! atomic read-modify-write. When most of the clones and drops of an object happen on one thread,
+//! a [`BiasedRc`] avoids this cost: its clones on the owner thread share a single strong reference
+//! of the object and are counted by a non-atomic counter, while the shared count of the object
+//! only accounts for the references that have left the owner thread through
+//! [`BiasedRc::to_rc`]. This follows "Biased Reference Counting" (Choi et al., PACT 2018).
+//!
+//! A [`BiasedRc`] is neither [`Send`] nor [`Sync`], so its non-atomic counter is only ever touched
+//! by the owner thread. The shared reference is released when the last clone on the owner thread
+//! is dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::biased::BiasedRc;
+//! use circ::{Rc, RcObject};
+//!
+//! struct Node(usize);
+//!
+//! unsafe impl RcObject for Node {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let local = BiasedRc::new(Node(42));
+//! // Clones on the owner thread do not touch the shared count.
+//! let clones: Vec<_> = (0..8).map(|_| local.clone()).collect();
+//! assert_eq!(local.local_count(), 9);
+//!
+//! // An `Rc` is needed to share the object with other threads.
+//! let shared: Rc<Node> = local.to_rc();
+//! std::thread::spawn(move || {
+//!     # let context = dyntls_host::get();
+//!     # unsafe { context.initialize() };
+//!     assert_eq!(shared.as_ref().unwrap().0, 42);
+//! })
+//! .join()
+//! .unwrap();
+//! drop(clones);
+//! ```
+
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::{Rc, RcObject};
+
+/// The references of the owner thread to an object, which share one strong reference.
+struct Group<T: RcObject> {
+    /// The number of `BiasedRc`s of the group.
+    count: Cell<usize>,
+    /// The strong reference that the group holds.
+    rc: Rc<T>,
+}
+
+/// A reference-counted pointer whose clones and drops on the owner thread are not atomic.
+///
+/// See the [module documentation](self) for details.
+pub struct BiasedRc<T: RcObject> {
+    group: NonNull<Group<T>>,
+    /// A `BiasedRc` is `!Send` and `!Sync`, as its counter is not atomic.
+    _marker: PhantomData<*const Group<T>>,
+}
+
+impl<T: RcObject> BiasedRc<T> {
+    /// Constructs a new `BiasedRc` by allocating a new reference-counted object.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self::from_rc(Rc::new(obj))
+    }
+
+    /// Constructs a new `BiasedRc` that takes over the strong reference of `rc`.
+    ///
+    /// This allocates the counter of the owner thread, so it pays off when the result is cloned
+    /// and dropped several times.
+    #[inline]
+    pub fn from_rc(rc: Rc<T>) -> Self {
+        let group = Box::new(Group {
+            count: Cell::new(1),
+            rc,
+        });
+        Self {
+            group: NonNull::from(Box::leak(group)),
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn group(&self) -> &Group<T> {
+        // SAFETY: The group is alive while any of its `BiasedRc`s is.
+        unsafe { self.group.as_ref() }
+    }
+
+    /// Returns the strong reference shared by the clones of this `BiasedRc`.
+    #[inline]
+    pub fn as_rc(&self) -> &Rc<T> {
+        &self.group().rc
+    }
+
+    /// Returns a new [`Rc`] to the object, which may be sent to other threads.
+    ///
+    /// This increments the shared reference count of the object.
+    #[inline]
+    pub fn to_rc(&self) -> Rc<T> {
+        self.group().rc.clone()
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(&self) -> Option<&T> {
+        self.group().rc.as_ref()
+    }
+
+    /// Returns the number of clones of this `BiasedRc` on the owner thread.
+    #[inline]
+    pub fn local_count(&self) -> usize {
+        self.group().count.get()
+    }
+
+    /// Returns `true` if the two pointers point to the same object.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.group().rc.ptr_eq(&other.group().rc)
+    }
+}
+
+impl<T: RcObject> Clone for BiasedRc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        let count = &self.group().count;
+        count.set(count.get().checked_add(1).unwrap());
+        Self {
+            group: self.group,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RcObject> Drop for BiasedRc<T> {
+    #[inline]
+    fn drop(&mut self) {
+        let count = &self.group().count;
+        count.set(count.get() - 1);
+        if count.get() == 0 {
+            // SAFETY: This was the last `BiasedRc` of the group, which was leaked from a `Box` in
+            // `from_rc`. Dropping the group releases its strong reference.
+            drop(unsafe { Box::from_raw(self.group.as_ptr()) });
+        }
+    }
+}
+
+impl<T: RcObject> From<Rc<T>> for BiasedRc<T> {
+    #[inline]
+    fn from(rc: Rc<T>) -> Self {
+        Self::from_rc(rc)
+    }
+}
+
+impl<T: RcObject + Debug> Debug for BiasedRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.group().rc, f)
+    }
+}
+
+impl<T: RcObject> Pointer for BiasedRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.group().rc, f)
+    }
+}
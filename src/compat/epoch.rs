@@ -0,0 +1,548 @@
+//! A subset of the [crossbeam-epoch](https://docs.rs/crossbeam-epoch) API backed by the collector
+//! of CIRC.
+//!
+//! Data structures written against crossbeam-epoch can be ported by replacing
+//! `use crossbeam_epoch::...` with `use circ::compat::epoch::...`. Objects retired with
+//! [`Guard::defer_destroy`] then share the collector with [`Rc`](crate::Rc), so the two kinds of
+//! pointers can be mixed in a single structure.
+//!
+//! Only sized types are supported, and a pointer can be tagged in the unused least significant
+//! bits of its alignment, as in crossbeam-epoch.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::compat::epoch::{self, Atomic, Owned};
+//! use std::sync::atomic::Ordering;
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let atomic = Atomic::new(1);
+//! let guard = &epoch::pin();
+//! let old = atomic.swap(Owned::new(2), Ordering::AcqRel, guard);
+//! assert_eq!(unsafe { old.deref() }, &1);
+//! unsafe { guard.defer_destroy(old) };
+//!
+//! assert_eq!(unsafe { atomic.load(Ordering::Acquire, guard).deref() }, &2);
+//! # unsafe { drop(atomic.into_owned()) };
+//! ```
+
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem::{align_of, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::ebr_impl;
+
+/// Returns a bitmask containing the unused least significant bits of an aligned pointer to `T`.
+#[inline]
+const fn low_bits<T>() -> usize {
+    align_of::<T>() - 1
+}
+
+#[inline]
+fn compose_tag<T>(data: usize, tag: usize) -> usize {
+    (data & !low_bits::<T>()) | (tag & low_bits::<T>())
+}
+
+#[inline]
+fn decompose_tag<T>(data: usize) -> (*mut T, usize) {
+    ((data & !low_bits::<T>()) as *mut T, data & low_bits::<T>())
+}
+
+/// A guard that keeps the current thread pinned.
+///
+/// This wraps [`crate::Guard`] and offers the deferral methods of crossbeam-epoch.
+#[repr(transparent)]
+pub struct Guard {
+    inner: ebr_impl::Guard,
+}
+
+impl Guard {
+    /// Stores a function so that it can be executed after all currently pinned threads get
+    /// unpinned.
+    pub fn defer<F, R>(&self, f: F)
+    where
+        F: FnOnce() -> R + Send + 'static,
+    {
+        unsafe { self.defer_unchecked(f) }
+    }
+
+    /// Stores a function so that it can be executed after all currently pinned threads get
+    /// unpinned.
+    ///
+    /// # Safety
+    ///
+    /// The function must not hold references onto the stack, and anything it accesses must be
+    /// `Send`.
+    pub unsafe fn defer_unchecked<F, R>(&self, f: F)
+    where
+        F: FnOnce() -> R,
+    {
+        self.inner.defer_unchecked(f)
+    }
+
+    /// Stores a destructor for the object pointed to by `ptr`, to be run after all currently
+    /// pinned threads get unpinned.
+    ///
+    /// # Safety
+    ///
+    /// The object must not be reachable by other threads anymore, and must be sendable to other
+    /// threads.
+    pub unsafe fn defer_destroy<T>(&self, ptr: Shared<'_, T>) {
+        let raw = ptr.as_raw() as usize;
+        self.defer_unchecked(move || drop(Box::from_raw(raw as *mut T)));
+    }
+
+    /// Moves the locally deferred functions to the global cache.
+    pub fn flush(&self) {
+        self.inner.flush();
+    }
+
+    /// Unpins and then immediately re-pins the thread.
+    pub fn repin(&mut self) {
+        self.inner.reactivate();
+    }
+
+    /// Temporarily unpins the thread, executes the given function and then re-pins the thread.
+    pub fn repin_after<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.inner.reactivate_after(f)
+    }
+
+    /// Returns the underlying guard of CIRC.
+    #[inline]
+    pub fn as_circ(&self) -> &crate::Guard {
+        &self.inner
+    }
+}
+
+impl Debug for Guard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Guard { .. }")
+    }
+}
+
+/// Pins the current thread.
+#[inline]
+pub fn pin() -> Guard {
+    Guard { inner: crate::cs() }
+}
+
+/// Returns a reference to a dummy guard that allows unprotected access to [`Atomic`]s.
+///
+/// # Safety
+///
+/// Loading and dereferencing data from [`Atomic`]s using this guard is safe only if the pointers
+/// are not being concurrently modified by other threads.
+#[inline]
+pub unsafe fn unprotected() -> &'static Guard {
+    struct Unprotected(Guard);
+    unsafe impl Sync for Unprotected {}
+
+    static UNPROTECTED: Unprotected = Unprotected(Guard {
+        inner: ebr_impl::Guard {
+            local: std::ptr::null(),
+        },
+    });
+    &UNPROTECTED.0
+}
+
+/// Types that can be stored in an [`Atomic`] as a tagged pointer.
+pub trait Pointer<T> {
+    /// Returns the machine representation of the pointer.
+    fn into_usize(self) -> usize;
+
+    /// Returns a new pointer from its machine representation.
+    ///
+    /// # Safety
+    ///
+    /// The given `data` must have been created by [`Pointer::into_usize`] of the same type, and
+    /// must not be used to create another pointer of an owning type.
+    unsafe fn from_usize(data: usize) -> Self;
+}
+
+/// An owned heap-allocated object, which can be tagged.
+pub struct Owned<T> {
+    data: usize,
+    _marker: PhantomData<Box<T>>,
+}
+
+impl<T> Owned<T> {
+    /// Allocates `init` on the heap.
+    pub fn new(init: T) -> Self {
+        Self::from(Box::new(init))
+    }
+
+    /// Converts the owned pointer into a [`Shared`].
+    pub fn into_shared<'g>(self, _: &'g Guard) -> Shared<'g, T> {
+        unsafe { Shared::from_usize(self.into_usize()) }
+    }
+
+    /// Converts the owned pointer into a [`Box`], dropping the tag.
+    pub fn into_box(self) -> Box<T> {
+        let (raw, _) = decompose_tag::<T>(self.into_usize());
+        unsafe { Box::from_raw(raw) }
+    }
+
+    /// Returns the tag stored within the pointer.
+    pub fn tag(&self) -> usize {
+        decompose_tag::<T>(self.data).1
+    }
+
+    /// Returns the same pointer, but tagged with `tag`.
+    pub fn with_tag(self, tag: usize) -> Self {
+        let data = self.into_usize();
+        unsafe { Self::from_usize(compose_tag::<T>(data, tag)) }
+    }
+}
+
+impl<T> Pointer<T> for Owned<T> {
+    #[inline]
+    fn into_usize(self) -> usize {
+        ManuallyDrop::new(self).data
+    }
+
+    #[inline]
+    unsafe fn from_usize(data: usize) -> Self {
+        debug_assert!(data != 0, "converting zero into `Owned`");
+        Self {
+            data,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<Box<T>> for Owned<T> {
+    fn from(b: Box<T>) -> Self {
+        unsafe { Self::from_usize(Box::into_raw(b) as usize) }
+    }
+}
+
+impl<T> From<T> for Owned<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
+    }
+}
+
+impl<T> Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*decompose_tag::<T>(self.data).0 }
+    }
+}
+
+impl<T> DerefMut for Owned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *decompose_tag::<T>(self.data).0 }
+    }
+}
+
+impl<T> Drop for Owned<T> {
+    fn drop(&mut self) {
+        let (raw, _) = decompose_tag::<T>(self.data);
+        unsafe { drop(Box::from_raw(raw)) };
+    }
+}
+
+impl<T: Debug> Debug for Owned<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Owned")
+            .field("value", &**self)
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+/// A pointer to an object protected by the epoch GC, valid during the lifetime `'g`.
+pub struct Shared<'g, T> {
+    data: usize,
+    _marker: PhantomData<(&'g (), *const T)>,
+}
+
+impl<'g, T> Clone for Shared<'g, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'g, T> Copy for Shared<'g, T> {}
+
+impl<'g, T> Pointer<T> for Shared<'g, T> {
+    #[inline]
+    fn into_usize(self) -> usize {
+        self.data
+    }
+
+    #[inline]
+    unsafe fn from_usize(data: usize) -> Self {
+        Self {
+            data,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'g, T> Shared<'g, T> {
+    /// Returns a new null pointer.
+    pub fn null() -> Self {
+        unsafe { Self::from_usize(0) }
+    }
+
+    /// Returns `true` if the pointer is null, ignoring the tag.
+    pub fn is_null(&self) -> bool {
+        self.as_raw().is_null()
+    }
+
+    /// Converts the pointer to a raw pointer, without the tag.
+    pub fn as_raw(&self) -> *const T {
+        decompose_tag::<T>(self.data).0
+    }
+
+    /// Dereferences the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null and point to a valid object.
+    pub unsafe fn deref(&self) -> &'g T {
+        &*self.as_raw()
+    }
+
+    /// Mutably dereferences the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null, point to a valid object, and not be accessed by other
+    /// threads.
+    pub unsafe fn deref_mut(&mut self) -> &'g mut T {
+        &mut *(self.as_raw() as *mut T)
+    }
+
+    /// Converts the pointer to a reference, or `None` if it is null.
+    ///
+    /// # Safety
+    ///
+    /// A non-null pointer must point to a valid object.
+    pub unsafe fn as_ref(&self) -> Option<&'g T> {
+        self.as_raw().as_ref()
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null, and no other thread may use it anymore.
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        debug_assert!(!self.is_null(), "converting a null `Shared` into `Owned`");
+        Owned::from_usize(self.data)
+    }
+
+    /// Returns the tag stored within the pointer.
+    pub fn tag(&self) -> usize {
+        decompose_tag::<T>(self.data).1
+    }
+
+    /// Returns the same pointer, but tagged with `tag`.
+    pub fn with_tag(&self, tag: usize) -> Self {
+        unsafe { Self::from_usize(compose_tag::<T>(self.data, tag)) }
+    }
+}
+
+impl<'g, T> From<*const T> for Shared<'g, T> {
+    fn from(raw: *const T) -> Self {
+        unsafe { Self::from_usize(raw as usize) }
+    }
+}
+
+impl<'g, T> Default for Shared<'g, T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<'g, T> PartialEq for Shared<'g, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<'g, T> Eq for Shared<'g, T> {}
+
+impl<'g, T> Debug for Shared<'g, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("raw", &self.as_raw())
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+/// The error returned on a failed compare-and-exchange of an [`Atomic`].
+pub struct CompareExchangeError<'g, T, P: Pointer<T>> {
+    /// The value in the atomic pointer at the time of the failed operation.
+    pub current: Shared<'g, T>,
+    /// The new value, which the operation failed to store.
+    pub new: P,
+}
+
+impl<'g, T, P: Pointer<T> + Debug> Debug for CompareExchangeError<'g, T, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompareExchangeError")
+            .field("current", &self.current)
+            .field("new", &self.new)
+            .finish()
+    }
+}
+
+/// An atomic pointer that can be safely shared between threads.
+pub struct Atomic<T> {
+    data: AtomicUsize,
+    _marker: PhantomData<*mut T>,
+}
+
+unsafe impl<T: Send + Sync> Send for Atomic<T> {}
+unsafe impl<T: Send + Sync> Sync for Atomic<T> {}
+
+impl<T> Atomic<T> {
+    /// Allocates `init` on the heap and returns a new atomic pointer to it.
+    pub fn new(init: T) -> Self {
+        Self::from(Owned::new(init))
+    }
+
+    /// Returns a new null atomic pointer.
+    pub const fn null() -> Self {
+        Self {
+            data: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    fn from_usize(data: usize) -> Self {
+        Self {
+            data: AtomicUsize::new(data),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Loads a [`Shared`] from the atomic pointer.
+    pub fn load<'g>(&self, order: Ordering, _: &'g Guard) -> Shared<'g, T> {
+        unsafe { Shared::from_usize(self.data.load(order)) }
+    }
+
+    /// Stores a [`Shared`] or [`Owned`] pointer into the atomic pointer.
+    pub fn store<P: Pointer<T>>(&self, new: P, order: Ordering) {
+        self.data.store(new.into_usize(), order);
+    }
+
+    /// Stores a [`Shared`] or [`Owned`] pointer into the atomic pointer, returning the previous
+    /// value.
+    pub fn swap<'g, P: Pointer<T>>(&self, new: P, order: Ordering, _: &'g Guard) -> Shared<'g, T> {
+        unsafe { Shared::from_usize(self.data.swap(new.into_usize(), order)) }
+    }
+
+    /// Stores `new` into the atomic pointer if the current value is the same as `current`.
+    ///
+    /// On success the previous value is returned. On failure the current value and `new` are
+    /// returned.
+    pub fn compare_exchange<'g, P: Pointer<T>>(
+        &self,
+        current: Shared<'_, T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+        _: &'g Guard,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        let new = new.into_usize();
+        self.data
+            .compare_exchange(current.into_usize(), new, success, failure)
+            .map(|_| unsafe { Shared::from_usize(new) })
+            .map_err(|current| unsafe {
+                CompareExchangeError {
+                    current: Shared::from_usize(current),
+                    new: P::from_usize(new),
+                }
+            })
+    }
+
+    /// Stores `new` into the atomic pointer if the current value is the same as `current`.
+    ///
+    /// Unlike [`Atomic::compare_exchange`], this method may spuriously fail.
+    pub fn compare_exchange_weak<'g, P: Pointer<T>>(
+        &self,
+        current: Shared<'_, T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+        _: &'g Guard,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        let new = new.into_usize();
+        self.data
+            .compare_exchange_weak(current.into_usize(), new, success, failure)
+            .map(|_| unsafe { Shared::from_usize(new) })
+            .map_err(|current| unsafe {
+                CompareExchangeError {
+                    current: Shared::from_usize(current),
+                    new: P::from_usize(new),
+                }
+            })
+    }
+
+    /// Bitwise "and" with the current tag, returning the previous value.
+    pub fn fetch_and<'g>(&self, val: usize, order: Ordering, _: &'g Guard) -> Shared<'g, T> {
+        let val = val | !low_bits::<T>();
+        unsafe { Shared::from_usize(self.data.fetch_and(val, order)) }
+    }
+
+    /// Bitwise "or" with the current tag, returning the previous value.
+    pub fn fetch_or<'g>(&self, val: usize, order: Ordering, _: &'g Guard) -> Shared<'g, T> {
+        let val = val & low_bits::<T>();
+        unsafe { Shared::from_usize(self.data.fetch_or(val, order)) }
+    }
+
+    /// Bitwise "xor" with the current tag, returning the previous value.
+    pub fn fetch_xor<'g>(&self, val: usize, order: Ordering, _: &'g Guard) -> Shared<'g, T> {
+        let val = val & low_bits::<T>();
+        unsafe { Shared::from_usize(self.data.fetch_xor(val, order)) }
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null, and no other thread may use it anymore.
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        Owned::from_usize(self.data.into_inner())
+    }
+}
+
+impl<T> From<Owned<T>> for Atomic<T> {
+    fn from(owned: Owned<T>) -> Self {
+        Self::from_usize(owned.into_usize())
+    }
+}
+
+impl<'g, T> From<Shared<'g, T>> for Atomic<T> {
+    fn from(shared: Shared<'g, T>) -> Self {
+        Self::from_usize(shared.into_usize())
+    }
+}
+
+impl<T> Default for Atomic<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> Debug for Atomic<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (raw, tag) = decompose_tag::<T>(self.data.load(Ordering::SeqCst));
+        f.debug_struct("Atomic")
+            .field("raw", &raw)
+            .field("tag", &tag)
+            .finish()
+    }
+}
@@ -0,0 +1,3 @@
+//! Compatibility layers for code written against other memory reclamation libraries.
+
+pub mod epoch;
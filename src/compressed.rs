@@ -0,0 +1,629 @@
+//! Compressed 32-bit reference-counted pointers into an arena.
+//!
+//! In a graph with hundreds of millions of nodes, the 64-bit pointers of the edges dominate the
+//! memory footprint. An [`Arena`] stores objects in slots that are allocated in chunks and
+//! recycled after a grace period, so an edge between two objects of the same arena can be an
+//! [`AtomicCompactRc`], which stores a 32-bit index of a slot instead of a pointer.
+//!
+//! The operations on an [`AtomicCompactRc`] take the arena that it points into, and return
+//! [`CompactRc`]s and [`CompactSnapshot`]s that borrow the arena. These behave like [`Rc`]s and
+//! [`Snapshot`]s: the object of a snapshot stays valid until the end of the critical section,
+//! and an object is destroyed after the last `CompactRc` and `AtomicCompactRc` to it is gone.
+//! An `AtomicCompactRc` does not know its arena, so its operations are `unsafe`: it must always
+//! be used with the same arena.
+//!
+//! Unlike [`Rc`]s, compact pointers are not traced, so cycles among them are never reclaimed.
+//!
+//! [`Rc`]: crate::Rc
+//! [`Snapshot`]: crate::Snapshot
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::compressed::{Arena, ArenaObject, AtomicCompactRc};
+//! use circ::cs;
+//! use std::sync::atomic::Ordering;
+//!
+//! struct Node {
+//!     value: usize,
+//!     next: AtomicCompactRc<Node>,
+//! }
+//!
+//! unsafe impl ArenaObject for Node {
+//!     fn pop_edges(&mut self, out: &mut Vec<AtomicCompactRc<Self>>) {
+//!         out.push(std::mem::take(&mut self.next));
+//!     }
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! assert_eq!(std::mem::size_of::<AtomicCompactRc<Node>>(), 4);
+//!
+//! let arena = Arena::new();
+//! let tail = arena.alloc(Node { value: 2, next: AtomicCompactRc::null() });
+//! let head = arena.alloc(Node { value: 1, next: AtomicCompactRc::new(tail) });
+//!
+//! let guard = &cs();
+//! // SAFETY: The pointer only ever stores objects of `arena`.
+//! let next = unsafe { head.as_ref().unwrap().next.load(Ordering::Acquire, &arena, guard) };
+//! assert_eq!(next.as_ref().unwrap().value, 2);
+//! ```
+
+use std::cell::UnsafeCell;
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::mem::{forget, MaybeUninit};
+use std::ptr;
+use std::sync::atomic::{fence, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::pool::{bucket_len, locate, BUCKETS, FIRST_BUCKET_BITS, INDEX_MASK};
+use crate::{cs, CompareExchangeError, Guard};
+
+/// An object that is stored in an [`Arena`].
+///
+/// # Safety
+///
+/// `pop_edges` must take out all the [`AtomicCompactRc`]s of the object, as the ones that are
+/// left behind are never released.
+pub unsafe trait ArenaObject: Sized {
+    /// Takes out the outgoing edges of the object, which are released after it is destroyed.
+    fn pop_edges(&mut self, out: &mut Vec<AtomicCompactRc<Self>>);
+}
+
+struct Slot<T> {
+    /// The number of strong references to the object, or zero if the slot is free.
+    count: AtomicU32,
+    /// The index of the next free slot plus one, or zero at the end of the free list.
+    next_free: AtomicU32,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            next_free: AtomicU32::new(0),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+struct Inner<T> {
+    /// Buckets of doubling sizes, allocated on demand.
+    buckets: [AtomicPtr<Slot<T>>; BUCKETS],
+    /// The number of slots that have ever been handed out.
+    len: AtomicUsize,
+    /// The head of the free list. The low 32 bits hold the index of the head plus one, and the
+    /// high 32 bits hold a counter that is bumped on every update to prevent ABA.
+    free: AtomicU64,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send + Sync> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    /// Returns the slot of a compact index, which is the index of the slot plus one.
+    #[inline]
+    fn slot(&self, index: u32) -> &Slot<T> {
+        let (bucket, offset) = locate(index as usize - 1);
+        let ptr = self.buckets[bucket].load(Ordering::Acquire);
+        debug_assert!(!ptr.is_null());
+        // SAFETY: A bucket is never deallocated while the arena is alive, and a compact index
+        // only refers to a slot that has been handed out.
+        unsafe { &*ptr.add(offset) }
+    }
+
+    /// Hands out a slot that has never been used.
+    fn grow(&self) -> u32 {
+        let index = self.len.fetch_add(1, Ordering::Relaxed);
+        let (bucket, _) = locate(index);
+        assert!(bucket < BUCKETS, "too many slots in an arena");
+
+        let atomic = &self.buckets[bucket];
+        if atomic.load(Ordering::Acquire).is_null() {
+            let new: Box<[Slot<T>]> = (0..bucket_len(bucket)).map(|_| Slot::new()).collect();
+            let new = Box::into_raw(new) as *mut Slot<T>;
+            if atomic
+                .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+            {
+                // SAFETY: `new` has not been shared.
+                let new = ptr::slice_from_raw_parts_mut(new, bucket_len(bucket));
+                drop(unsafe { Box::from_raw(new) });
+            }
+        }
+        index as u32 + 1
+    }
+
+    fn push_free(&self, index: u32) {
+        let slot = self.slot(index);
+        let mut head = self.free.load(Ordering::Relaxed);
+        loop {
+            slot.next_free
+                .store((head & INDEX_MASK) as u32, Ordering::Relaxed);
+            let new = (((head >> 32) + 1) << 32) | index as u64;
+            match self
+                .free
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn pop_free(&self) -> Option<u32> {
+        let mut head = self.free.load(Ordering::Acquire);
+        loop {
+            let index = (head & INDEX_MASK) as u32;
+            if index == 0 {
+                return None;
+            }
+            // The slot may be popped and pushed again concurrently, in which case the counter
+            // makes the compare-and-swap fail.
+            let next = self.slot(index).next_free.load(Ordering::Relaxed) as u64;
+            let new = (((head >> 32) + 1) << 32) | next;
+            match self
+                .free
+                .compare_exchange_weak(head, new, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => return Some(index),
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let len = *self.len.get_mut();
+        for (bucket, atomic) in self.buckets.iter_mut().enumerate() {
+            let ptr = *atomic.get_mut();
+            if ptr.is_null() {
+                continue;
+            }
+            let start = bucket_len(bucket) - (1 << FIRST_BUCKET_BITS);
+            let mut slots =
+                unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, bucket_len(bucket))) };
+            // The slots of destroyed objects are freed by deferred functions, which keep the
+            // arena alive until they run.
+            for slot in slots.iter_mut().take(len.saturating_sub(start)) {
+                if *slot.count.get_mut() != 0 {
+                    unsafe { slot.value.get_mut().assume_init_drop() };
+                }
+            }
+        }
+    }
+}
+
+/// An arena of reference-counted objects that are addressed by 32-bit indices.
+///
+/// See the [module documentation](self) for details.
+pub struct Arena<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: ArenaObject + Send + 'static> Arena<T> {
+    /// Creates a new empty arena.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                buckets: Default::default(),
+                len: AtomicUsize::new(0),
+                free: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Stores `obj` in a free slot of the arena and returns a strong reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena already has `2^32 - 32` slots.
+    pub fn alloc(&self, obj: T) -> CompactRc<'_, T> {
+        let index = self.inner.pop_free().unwrap_or_else(|| self.inner.grow());
+        let slot = self.inner.slot(index);
+        unsafe { (*slot.value.get()).write(obj) };
+        slot.count.store(1, Ordering::Release);
+        CompactRc { arena: self, index }
+    }
+
+    /// Returns the object at a non-null compact index.
+    ///
+    /// # Safety
+    ///
+    /// The object must be protected by a strong reference or a critical section for `'a`.
+    #[inline]
+    unsafe fn get(&self, index: u32) -> &T {
+        (*self.inner.slot(index).value.get()).assume_init_ref()
+    }
+
+    /// Increments the count of the object at `index` unless it has dropped to zero.
+    fn try_increment(&self, index: u32) -> bool {
+        let count = &self.inner.slot(index).count;
+        let mut curr = count.load(Ordering::Relaxed);
+        while curr != 0 {
+            let next = curr
+                .checked_add(1)
+                .expect("too many references to an object");
+            match count.compare_exchange_weak(curr, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(actual) => curr = actual,
+            }
+        }
+        false
+    }
+
+    /// Releases a strong reference to the object at a non-null compact index.
+    fn release(&self, index: u32, guard: &Guard) {
+        let slot = self.inner.slot(index);
+        if slot.count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        fence(Ordering::Acquire);
+        let inner = self.inner.clone();
+        // SAFETY: Readers may still be reading the object in their critical sections, so it is
+        // destroyed and its slot recycled only after they leave.
+        unsafe {
+            guard.defer_sized_unchecked(
+                move || {
+                    let arena = Arena { inner };
+                    let slot = arena.inner.slot(index);
+                    let mut obj = (*slot.value.get()).assume_init_read();
+                    let mut edges = Vec::new();
+                    obj.pop_edges(&mut edges);
+                    arena.inner.push_free(index);
+                    drop(obj);
+                    let guard = &cs();
+                    for mut edge in edges {
+                        let index = *edge.link.get_mut();
+                        if index != 0 {
+                            arena.release(index, guard);
+                        }
+                    }
+                },
+                std::mem::size_of::<T>(),
+            )
+        };
+    }
+
+    /// Returns the number of slots that have ever been handed out, including the free ones.
+    pub fn capacity(&self) -> usize {
+        self.inner.len.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: ArenaObject + Send + 'static> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for Arena<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Arena { .. }")
+    }
+}
+
+/// A strong reference to an object in an [`Arena`].
+pub struct CompactRc<'a, T: ArenaObject + Send + 'static> {
+    arena: &'a Arena<T>,
+    /// The index of the slot plus one, or zero for a null pointer.
+    index: u32,
+}
+
+impl<'a, T: ArenaObject + Send + 'static> CompactRc<'a, T> {
+    /// Constructs a null `CompactRc` into `arena`.
+    #[inline]
+    pub fn null(arena: &'a Arena<T>) -> Self {
+        Self { arena, index: 0 }
+    }
+
+    /// Returns `true` if the pointer is null.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.index == 0
+    }
+
+    /// Returns the compact index of the object, which is zero for a null pointer.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(&self) -> Option<&T> {
+        // SAFETY: The object is protected by this strong reference.
+        (!self.is_null()).then(|| unsafe { self.arena.get(self.index) })
+    }
+
+    /// Returns `true` if the two pointers are identical.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+
+    /// Returns a snapshot of the pointer that is protected by the critical section of `guard`.
+    #[inline]
+    pub fn snapshot<'g>(&self, _guard: &'g Guard) -> CompactSnapshot<'g, T>
+    where
+        'a: 'g,
+    {
+        CompactSnapshot {
+            arena: self.arena,
+            index: self.index,
+        }
+    }
+
+    /// Consumes this pointer and releases its strong reference in the critical section of
+    /// `guard`.
+    #[inline]
+    pub fn finalize(self, guard: &Guard) {
+        if !self.is_null() {
+            self.arena.release(self.index, guard);
+        }
+        forget(self);
+    }
+}
+
+impl<'a, T: ArenaObject + Send + 'static> Clone for CompactRc<'a, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        if !self.is_null() {
+            self.arena
+                .inner
+                .slot(self.index)
+                .count
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            arena: self.arena,
+            index: self.index,
+        }
+    }
+}
+
+impl<'a, T: ArenaObject + Send + 'static> Drop for CompactRc<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.is_null() {
+            self.arena.release(self.index, &cs());
+        }
+    }
+}
+
+impl<'a, T: ArenaObject + Send + 'static + Debug> Debug for CompactRc<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.as_ref() {
+            Some(obj) => f.debug_tuple("CompactRc").field(obj).finish(),
+            None => f.write_str("Null"),
+        }
+    }
+}
+
+impl<'a, T: ArenaObject + Send + 'static> Pointer for CompactRc<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.as_ref().map_or(ptr::null(), |obj| obj as *const T), f)
+    }
+}
+
+/// A local pointer to an object in an [`Arena`], which is protected by a critical section.
+pub struct CompactSnapshot<'g, T> {
+    arena: &'g Arena<T>,
+    index: u32,
+}
+
+impl<'g, T> Clone for CompactSnapshot<'g, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'g, T> Copy for CompactSnapshot<'g, T> {}
+
+impl<'g, T: ArenaObject + Send + 'static> CompactSnapshot<'g, T> {
+    /// Returns `true` if the pointer is null.
+    #[inline]
+    pub fn is_null(self) -> bool {
+        self.index == 0
+    }
+
+    /// Returns the compact index of the object, which is zero for a null pointer.
+    #[inline]
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(self) -> Option<&'g T> {
+        // SAFETY: The object is destroyed only after the critical section ends.
+        (!self.is_null()).then(|| unsafe { self.arena.get(self.index) })
+    }
+
+    /// Returns `true` if the two pointers are identical.
+    #[inline]
+    pub fn ptr_eq(self, other: Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<'g, T: ArenaObject + Send + 'static + Debug> Debug for CompactSnapshot<'g, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.as_ref() {
+            Some(obj) => f.debug_tuple("CompactSnapshot").field(obj).finish(),
+            None => f.write_str("Null"),
+        }
+    }
+}
+
+/// A thread-safe (atomic) mutable memory location that contains a 32-bit [`CompactRc<T>`].
+///
+/// An `AtomicCompactRc` that is dropped without being taken out by [`ArenaObject::pop_edges`] or
+/// [`AtomicCompactRc::into_rc`] leaks its strong reference.
+pub struct AtomicCompactRc<T> {
+    /// The index of the slot plus one, or zero for a null pointer.
+    link: AtomicU32,
+    _marker: PhantomData<*const T>,
+}
+
+unsafe impl<T: Send + Sync> Send for AtomicCompactRc<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicCompactRc<T> {}
+
+impl<T: ArenaObject + Send + 'static> AtomicCompactRc<T> {
+    /// Constructs a new `AtomicCompactRc` that takes over the strong reference of `ptr`.
+    #[inline]
+    pub fn new(ptr: CompactRc<'_, T>) -> Self {
+        Self {
+            link: AtomicU32::new(Self::into_index(ptr)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constructs a new `AtomicCompactRc` containing a null pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self {
+            link: AtomicU32::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn into_index(ptr: CompactRc<'_, T>) -> u32 {
+        let index = ptr.index;
+        forget(ptr);
+        index
+    }
+
+    /// Loads a snapshot of the pointer into `arena`.
+    ///
+    /// # Safety
+    ///
+    /// `arena` must be the arena of the objects that are stored in this pointer.
+    #[inline]
+    pub unsafe fn load<'g>(
+        &self,
+        order: Ordering,
+        arena: &'g Arena<T>,
+        _guard: &'g Guard,
+    ) -> CompactSnapshot<'g, T> {
+        CompactSnapshot {
+            arena,
+            index: self.link.load(order),
+        }
+    }
+
+    /// Loads the pointer and returns a new strong reference to the object.
+    ///
+    /// # Safety
+    ///
+    /// `arena` must be the arena of the objects that are stored in this pointer.
+    pub unsafe fn load_rc<'a>(
+        &self,
+        order: Ordering,
+        arena: &'a Arena<T>,
+        guard: &Guard,
+    ) -> CompactRc<'a, T> {
+        loop {
+            let index = self.load(order, arena, guard).index;
+            // The object may have been replaced and released after it was loaded, in which case
+            // the next load sees the replacement.
+            if index == 0 || arena.try_increment(index) {
+                return CompactRc { arena, index };
+            }
+        }
+    }
+
+    /// Stores `ptr` into the atomic pointer, releasing the previous one in the critical section
+    /// of `guard`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be in the arena of the objects that are stored in this pointer.
+    #[inline]
+    pub unsafe fn store(&self, ptr: CompactRc<'_, T>, order: Ordering, guard: &Guard) {
+        self.swap(ptr, order).finalize(guard);
+    }
+
+    /// Stores `ptr` into the atomic pointer, returning the previous one.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be in the arena of the objects that are stored in this pointer.
+    #[inline]
+    pub unsafe fn swap<'a>(&self, ptr: CompactRc<'a, T>, order: Ordering) -> CompactRc<'a, T> {
+        let arena = ptr.arena;
+        let index = self.link.swap(Self::into_index(ptr), order);
+        CompactRc { arena, index }
+    }
+
+    /// Stores `desired` into the atomic pointer if the current value is `expected`.
+    ///
+    /// On success, returns the previous pointer. On failure, returns `desired` back with the
+    /// current value.
+    ///
+    /// # Safety
+    ///
+    /// `expected` and `desired` must be in the arena of the objects that are stored in this
+    /// pointer.
+    pub unsafe fn compare_exchange<'a, 'g>(
+        &self,
+        expected: CompactSnapshot<'g, T>,
+        desired: CompactRc<'a, T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<CompactRc<'a, T>, CompareExchangeError<CompactRc<'a, T>, CompactSnapshot<'g, T>>>
+    {
+        match self
+            .link
+            .compare_exchange(expected.index, desired.index, success, failure)
+        {
+            Ok(index) => {
+                let arena = desired.arena;
+                forget(desired);
+                Ok(CompactRc { arena, index })
+            }
+            Err(index) => Err(CompareExchangeError {
+                desired,
+                current: CompactSnapshot {
+                    arena: expected.arena,
+                    index,
+                },
+            }),
+        }
+    }
+
+    /// Consumes the atomic pointer and returns its strong reference into `arena`.
+    ///
+    /// # Safety
+    ///
+    /// `arena` must be the arena of the objects that are stored in this pointer.
+    #[inline]
+    pub unsafe fn into_rc(self, arena: &Arena<T>) -> CompactRc<'_, T> {
+        CompactRc {
+            arena,
+            index: self.link.into_inner(),
+        }
+    }
+}
+
+impl<T> Default for AtomicCompactRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            link: AtomicU32::new(0),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for AtomicCompactRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AtomicCompactRc")
+            .field(&self.link.load(Ordering::Relaxed))
+            .finish()
+    }
+}
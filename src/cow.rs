@@ -0,0 +1,249 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{cs, AtomicRc, Guard, Rc, RcObject};
+
+/// The object of a [`CowRc`].
+struct CowNode<T> {
+    value: T,
+    /// Whether the object has been stored in an [`AtomicCowRc`], where readers may access it
+    /// without holding a reference.
+    published: AtomicBool,
+}
+
+unsafe impl<T> RcObject for CowNode<T> {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl<T> CowNode<T> {
+    #[inline]
+    fn new(value: T) -> Rc<Self> {
+        Rc::new(Self {
+            value,
+            published: AtomicBool::new(false),
+        })
+    }
+}
+
+/// A reference-counted pointer with clone-on-write semantics.
+///
+/// A `CowRc` is never null. [`CowRc::make_mut`] returns a mutable reference to the value, cloning
+/// it into a new object first if it is shared with other `CowRc`s or has been stored in an
+/// [`AtomicCowRc`].
+///
+/// # Examples
+///
+/// ```
+/// use circ::CowRc;
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let mut a = CowRc::new(vec![1, 2, 3]);
+/// let b = a.clone();
+///
+/// // `a` is shared, so its value is cloned before it is modified.
+/// a.make_mut().push(4);
+/// assert_eq!(*a, [1, 2, 3, 4]);
+/// assert_eq!(*b, [1, 2, 3]);
+///
+/// // `a` is unique now, so it is modified in place.
+/// a.make_mut().push(5);
+/// assert_eq!(*a, [1, 2, 3, 4, 5]);
+/// ```
+pub struct CowRc<T> {
+    rc: Rc<CowNode<T>>,
+}
+
+impl<T> CowRc<T> {
+    /// Constructs a new `CowRc` by allocating a new reference-counted object.
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self {
+            rc: CowNode::new(value),
+        }
+    }
+
+    #[inline]
+    fn node(&self) -> &CowNode<T> {
+        self.rc.as_ref().unwrap()
+    }
+
+    /// Returns `true` if the two pointers point to the same object.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.rc.ptr_eq(&other.rc)
+    }
+
+    /// Returns a mutable reference to the value if no one else can access it, without cloning it.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.rc.is_unique() && !self.node().published.load(Ordering::Acquire) {
+            // SAFETY: No other pointer to the object exists, and no reader can have loaded it
+            // from an `AtomicCowRc`.
+            Some(&mut unsafe { self.rc.as_mut() }.unwrap().value)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value, cloning it into a new object first if someone
+    /// else can access it.
+    pub fn make_mut(&mut self) -> &mut T
+    where
+        T: Clone,
+    {
+        if self.get_mut().is_none() {
+            *self = Self::new(self.node().value.clone());
+        }
+        self.get_mut().unwrap()
+    }
+
+    /// Marks the object as stored in an [`AtomicCowRc`] and returns its [`Rc`].
+    #[inline]
+    fn publish(self) -> Rc<CowNode<T>> {
+        self.node().published.store(true, Ordering::Release);
+        self.rc
+    }
+}
+
+impl<T> Clone for CowRc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            rc: self.rc.clone(),
+        }
+    }
+}
+
+impl<T> Deref for CowRc<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.node().value
+    }
+}
+
+impl<T: Debug> Debug for CowRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CowRc").field(&**self).finish()
+    }
+}
+
+/// A thread-safe (atomic) mutable memory location that contains a [`CowRc<T>`].
+///
+/// Readers access the current value without touching the reference counter, and writers update
+/// it with [`AtomicCowRc::update`], which modifies a clone of the value and installs it with a
+/// compare-and-swap, so users do not have to write the loop themselves.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, AtomicCowRc};
+/// use std::collections::BTreeMap;
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let routes = AtomicCowRc::new(BTreeMap::new());
+/// routes.update(|routes| {
+///     routes.insert("/", 1);
+/// });
+///
+/// let guard = &cs();
+/// assert_eq!(routes.read(guard).get("/"), Some(&1));
+/// ```
+pub struct AtomicCowRc<T> {
+    inner: AtomicRc<CowNode<T>>,
+}
+
+impl<T> AtomicCowRc<T> {
+    /// Constructs a new `AtomicCowRc` by allocating a new reference-counted object.
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self::from(CowRc::new(value))
+    }
+
+    /// Returns a reference to the current value, which is valid until the end of the critical
+    /// section of `guard`.
+    #[inline]
+    pub fn read<'g>(&self, guard: &'g Guard) -> &'g T {
+        &self
+            .inner
+            .load(Ordering::Acquire, guard)
+            .as_ref()
+            .unwrap()
+            .value
+    }
+
+    /// Returns a [`CowRc`] to the current value.
+    #[inline]
+    pub fn load(&self) -> CowRc<T> {
+        let guard = &cs();
+        CowRc {
+            rc: self.inner.load(Ordering::Acquire, guard).counted(),
+        }
+    }
+
+    /// Replaces the current value with `new`.
+    #[inline]
+    pub fn store(&self, new: CowRc<T>) {
+        self.inner.store(new.publish(), Ordering::Release, &cs());
+    }
+
+    /// Replaces the current value with `new`, returning the previous value.
+    #[inline]
+    pub fn swap(&self, new: CowRc<T>) -> CowRc<T> {
+        CowRc {
+            rc: self.inner.swap(new.publish(), Ordering::AcqRel),
+        }
+    }
+
+    /// Modifies a clone of the current value with `f` and installs it, retrying until no other
+    /// writer interferes. Returns the replaced value.
+    ///
+    /// The current value is always cloned, as readers may be reading it. `f` may be called
+    /// multiple times, each time with a clone of the latest value.
+    pub fn update<F>(&self, mut f: F) -> CowRc<T>
+    where
+        T: Clone,
+        F: FnMut(&mut T),
+    {
+        let guard = &cs();
+        let mut current = self.inner.load(Ordering::Acquire, guard);
+        loop {
+            let mut new = CowRc::new(current.as_ref().unwrap().value.clone());
+            f(new.get_mut().unwrap());
+            match self.inner.compare_exchange(
+                current,
+                new.publish(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(old) => return CowRc { rc: old },
+                Err(e) => {
+                    e.desired.finalize(guard);
+                    current = e.current;
+                }
+            }
+        }
+    }
+}
+
+impl<T> From<CowRc<T>> for AtomicCowRc<T> {
+    #[inline]
+    fn from(value: CowRc<T>) -> Self {
+        Self {
+            inner: AtomicRc::from(value.publish()),
+        }
+    }
+}
+
+impl<T: Debug> Debug for AtomicCowRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AtomicCowRc")
+            .field(self.read(&cs()))
+            .finish()
+    }
+}
@@ -0,0 +1,326 @@
+//! An append-only concurrent log.
+//!
+//! The entries are stored in fixed-size chunks, which are the leaves of a radix tree indexed by
+//! the position of the entry. A push reserves a position with a fetch-and-add and installs the
+//! missing nodes on its path with compare-and-swaps. When the positions outgrow the tree, a new
+//! root is installed on top of the old one, so the existing nodes never move. A read follows a
+//! path of bounded length without any retry, so it is wait-free.
+//!
+//! Trimming the front of the log unlinks the subtrees whose entries are all trimmed, and hands
+//! them to the collector, so that readers that are still looking at them stay safe.
+
+use std::array;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::heap_size::{HeapSize, Visited};
+use crate::{AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// The number of index bits consumed by each level of the tree.
+const BITS: u32 = 6;
+
+/// The number of children of an inner node and of entries of a chunk.
+const FANOUT: usize = 1 << BITS;
+
+enum Node<T: RcObject> {
+    Inner {
+        /// The number of levels of the subtree, which is at least 2.
+        height: u32,
+        children: [AtomicRc<Node<T>>; FANOUT],
+    },
+    /// A chunk of entries.
+    Leaf([AtomicRc<T>; FANOUT]),
+}
+
+unsafe impl<T: RcObject> RcObject for Node<T> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        if let Node::Inner { children, .. } = self {
+            out.extend(children.iter_mut().map(AtomicRc::take));
+        }
+    }
+}
+
+impl<T: RcObject> Node<T> {
+    fn leaf() -> Rc<Self> {
+        Rc::new(Node::Leaf(array::from_fn(|_| AtomicRc::null())))
+    }
+
+    fn inner(height: u32) -> Rc<Self> {
+        Rc::new(Node::Inner {
+            height,
+            children: array::from_fn(|_| AtomicRc::null()),
+        })
+    }
+
+    fn height(&self) -> u32 {
+        match self {
+            Node::Inner { height, .. } => *height,
+            Node::Leaf(_) => 1,
+        }
+    }
+}
+
+/// Returns `true` if a subtree of `height` levels has room for the entry at `index`.
+fn covers(height: u32, index: usize) -> bool {
+    BITS * height >= usize::BITS || index >> (BITS * height) == 0
+}
+
+/// Returns the number of entries of a subtree of `height` levels, or `None` if it overflows.
+fn span(height: u32) -> Option<usize> {
+    1usize.checked_shl(BITS * height)
+}
+
+/// Returns the index of the child of a node of `height` levels on the path to `index`.
+fn child_index(height: u32, index: usize) -> usize {
+    (index >> (BITS * (height - 1))) & (FANOUT - 1)
+}
+
+/// A lock-free append-only log of [`Rc`] pointers.
+///
+/// Each pushed entry gets the next position, and can be read by its position without waiting
+/// for other threads. The front of the log can be trimmed, which releases the chunks of the
+/// trimmed entries once no thread is reading them anymore. This suits an event log, or the list
+/// of versions of a multi-version store.
+///
+/// An entry becomes visible when its push completes, so a reader may see a later entry while an
+/// earlier one is still being pushed.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::AppendLog, RcObject, Rc};
+///
+/// struct Event(usize);
+///
+/// unsafe impl RcObject for Event {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let log = AppendLog::new();
+/// let guard = &cs();
+/// for i in 0..100 {
+///     assert_eq!(log.push(Rc::new(Event(i)), guard), i);
+/// }
+/// assert_eq!(log.get(42, guard).unwrap().as_ref().unwrap().0, 42);
+///
+/// log.trim(50, guard);
+/// assert!(log.get(42, guard).is_none());
+/// assert_eq!(log.iter(guard).count(), 50);
+/// ```
+pub struct AppendLog<T: RcObject> {
+    root: AtomicRc<Node<T>>,
+    /// The number of positions given to pushes.
+    len: AtomicUsize,
+    /// The position of the first entry that has not been trimmed.
+    start: AtomicUsize,
+}
+
+impl<T: RcObject> AppendLog<T> {
+    /// Creates a new empty log.
+    pub fn new() -> Self {
+        Self {
+            root: AtomicRc::null(),
+            len: AtomicUsize::new(0),
+            start: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of entries pushed to the log, including the trimmed ones and the ones
+    /// being pushed. This is the position of the next entry.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if no entry has been pushed to the log.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the position of the first entry that has not been trimmed.
+    pub fn start(&self) -> usize {
+        self.start.load(Ordering::Acquire)
+    }
+
+    /// Appends `value` to the log, returning its position.
+    pub fn push(&self, value: Rc<T>, guard: &Guard) -> usize {
+        let index = self.len.fetch_add(1, Ordering::AcqRel);
+        self.chunk(index, guard)[index % FANOUT].store(value, Ordering::Release, guard);
+        index
+    }
+
+    /// Returns the root of the tree, after growing it to have room for `index`.
+    fn grow<'g>(&self, index: usize, guard: &'g Guard) -> &'g Node<T> {
+        loop {
+            let root = self.root.get_or_init(guard, Node::leaf);
+            let node = root.as_ref().unwrap();
+            if covers(node.height(), index) {
+                return node;
+            }
+            let new = Node::inner(node.height() + 1);
+            match new.as_ref().unwrap() {
+                Node::Inner { children, .. } => {
+                    children[0].store(root.counted(), Ordering::Relaxed, guard)
+                }
+                Node::Leaf(_) => unreachable!(),
+            }
+            let _ =
+                self.root
+                    .compare_exchange(root, new, Ordering::AcqRel, Ordering::Acquire, guard);
+        }
+    }
+
+    /// Returns the chunk holding the entry at `index`, installing the missing nodes on its path.
+    fn chunk<'g>(&self, index: usize, guard: &'g Guard) -> &'g [AtomicRc<T>; FANOUT] {
+        let mut node = self.grow(index, guard);
+        loop {
+            match node {
+                Node::Leaf(entries) => return entries,
+                Node::Inner { height, children } => {
+                    let init = || match *height - 1 {
+                        1 => Node::leaf(),
+                        height => Node::inner(height),
+                    };
+                    node = children[child_index(*height, index)]
+                        .get_or_init(guard, init)
+                        .as_ref()
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    /// Returns the entry at `index`, or `None` if it has been trimmed or is not pushed yet.
+    pub fn get<'g>(&self, index: usize, guard: &'g Guard) -> Option<Snapshot<'g, T>> {
+        if index < self.start() {
+            return None;
+        }
+        let mut node = self.root.load(Ordering::Acquire, guard).as_ref()?;
+        if !covers(node.height(), index) {
+            return None;
+        }
+        loop {
+            match node {
+                Node::Leaf(entries) => {
+                    let entry = entries[index % FANOUT].load(Ordering::Acquire, guard);
+                    return (!entry.is_null()).then_some(entry);
+                }
+                Node::Inner { height, children } => {
+                    node = children[child_index(*height, index)]
+                        .load(Ordering::Acquire, guard)
+                        .as_ref()?;
+                }
+            }
+        }
+    }
+
+    /// Trims the entries before the position `until`, which is clamped to [`AppendLog::len`].
+    ///
+    /// The chunks whose entries are all trimmed are unlinked and retired to the collector. The
+    /// trimmed entries of the first remaining chunk are released together with the chunk later.
+    /// An entry that is still being pushed when its position is trimmed is dropped by a later
+    /// trim.
+    pub fn trim(&self, until: usize, guard: &Guard) {
+        let until = until.min(self.len());
+        self.start.fetch_max(until, Ordering::AcqRel);
+        if let Some(root) = self.root.load(Ordering::Acquire, guard).as_ref() {
+            trim_subtrees(root, 0, until, guard);
+        }
+    }
+
+    /// Returns an iterator over the positions and the entries of the log that have not been
+    /// trimmed, in order.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            log: self,
+            next: self.start(),
+            end: self.len(),
+            guard,
+        }
+    }
+}
+
+/// Unlinks the children of `node`, whose first position is `base`, that only hold positions
+/// before `until`.
+fn trim_subtrees<T: RcObject>(node: &Node<T>, base: usize, until: usize, guard: &Guard) {
+    let Node::Inner { height, children } = node else {
+        return;
+    };
+    let span = span(height - 1).unwrap();
+    for (i, child) in children.iter().enumerate() {
+        let Some(child_base) = i
+            .checked_mul(span)
+            .and_then(|offset| base.checked_add(offset))
+        else {
+            break;
+        };
+        if child_base >= until {
+            break;
+        }
+        if child_base.saturating_add(span) <= until {
+            if !child.load(Ordering::Relaxed, guard).is_null() {
+                child.swap(Rc::null(), Ordering::AcqRel).finalize(guard);
+            }
+        } else if let Some(child) = child.load(Ordering::Acquire, guard).as_ref() {
+            trim_subtrees(child, child_base, until, guard);
+        }
+    }
+}
+
+impl<T: RcObject> Default for AppendLog<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RcObject> Debug for AppendLog<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AppendLog")
+            .field("start", &self.start())
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for Node<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        // The recursion is bounded by the height of the tree, which is logarithmic.
+        match self {
+            Node::Inner { children, .. } => children.heap_size(visited),
+            Node::Leaf(entries) => entries.heap_size(visited),
+        }
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for AppendLog<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.root.heap_size(visited)
+    }
+}
+
+/// An iterator over the entries of an [`AppendLog`].
+///
+/// This is created by [`AppendLog::iter`]. It visits the positions that were pushed when it was
+/// created, and skips the entries that are trimmed or not pushed yet when it reaches them.
+pub struct Iter<'g, T: RcObject> {
+    log: &'g AppendLog<T>,
+    next: usize,
+    end: usize,
+    guard: &'g Guard,
+}
+
+impl<'g, T: RcObject> Iterator for Iter<'g, T> {
+    type Item = (usize, Snapshot<'g, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.end {
+            let index = self.next;
+            self.next += 1;
+            if let Some(entry) = self.log.get(index, self.guard) {
+                return Some((index, entry));
+            }
+        }
+        None
+    }
+}
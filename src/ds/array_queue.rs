@@ -0,0 +1,247 @@
+//! A bounded multi-producer multi-consumer queue.
+//!
+//! This is Dmitry Vyukov's bounded MPMC queue
+//! (<https://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue>). Each slot
+//! carries a stamp which tells whether the slot is ready to be written by a producer or read by a
+//! consumer in the current lap, and holds the element in an [`AtomicRc`].
+//!
+//! As in crossbeam's `ArrayQueue`, a position keeps the index of a slot in its lower bits and
+//! the lap in the upper bits. A lap is a power of two larger than the capacity, so that the
+//! stamp of a full slot never equals the position of the next lap, even with a single slot.
+
+use std::fmt::{Debug, Formatter};
+use std::mem::size_of_val;
+use std::sync::atomic::{self, AtomicUsize, Ordering};
+
+use crossbeam_utils::{Backoff, CachePadded};
+
+use crate::heap_size::{HeapSize, Visited};
+use crate::{AtomicRc, Rc, RcObject};
+
+/// A slot in the ring buffer.
+struct Slot<T: RcObject> {
+    /// The stamp of the slot, made of a lap and an index like the positions of the queue.
+    ///
+    /// If `stamp == pos`, the slot is empty and can be written by the producer at `pos`.
+    /// If `stamp == pos + 1`, the slot is full and can be read by the consumer at `pos`.
+    stamp: AtomicUsize,
+    value: AtomicRc<T>,
+}
+
+/// A bounded multi-producer multi-consumer queue of [`Rc`] pointers.
+///
+/// The queue has a fixed capacity determined on construction. Pushing to a full queue and
+/// popping from an empty queue fail immediately instead of blocking, so callers can apply their
+/// own backpressure.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{ds::ArrayQueue, RcObject, Rc};
+///
+/// struct Job(usize);
+///
+/// unsafe impl RcObject for Job {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let queue = ArrayQueue::new(1);
+/// assert!(queue.try_push(Rc::new(Job(1))).is_ok());
+/// assert!(queue.try_push(Rc::new(Job(2))).is_err());
+///
+/// let job = queue.try_pop().unwrap();
+/// assert_eq!(job.as_ref().unwrap().0, 1);
+/// assert!(queue.try_pop().is_none());
+/// ```
+pub struct ArrayQueue<T: RcObject> {
+    /// The position of the next element to pop.
+    head: CachePadded<AtomicUsize>,
+    /// The position of the next element to push.
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[Slot<T>]>,
+    /// The distance between the same index in two consecutive laps.
+    one_lap: usize,
+}
+
+impl<T: RcObject> ArrayQueue<T> {
+    /// Creates a new queue that holds at most `cap` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cap` is zero.
+    pub fn new(cap: usize) -> Self {
+        assert!(cap > 0, "the capacity of a queue must be positive");
+        let buffer = (0..cap)
+            .map(|i| Slot {
+                stamp: AtomicUsize::new(i),
+                value: AtomicRc::null(),
+            })
+            .collect();
+        Self {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            buffer,
+            one_lap: (cap + 1).next_power_of_two(),
+        }
+    }
+
+    /// Returns the position that follows `pos`, moving on to the next lap after the last slot.
+    #[inline]
+    fn next_pos(&self, pos: usize) -> usize {
+        let index = pos & (self.one_lap - 1);
+        if index + 1 < self.buffer.len() {
+            pos + 1
+        } else {
+            (pos & !(self.one_lap - 1)).wrapping_add(self.one_lap)
+        }
+    }
+
+    /// Attempts to push an element into the queue.
+    ///
+    /// If the queue is full, the element is returned back as an error.
+    pub fn try_push(&self, value: Rc<T>) -> Result<(), Rc<T>> {
+        let backoff = Backoff::new();
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[tail & (self.one_lap - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if stamp == tail {
+                match self.tail.compare_exchange_weak(
+                    tail,
+                    self.next_pos(tail),
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let old = slot.value.swap(value, Ordering::Relaxed);
+                        debug_assert!(old.is_null());
+                        // Publish the element to the consumer at `tail`.
+                        slot.stamp.store(tail + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => {
+                        tail = current;
+                        backoff.spin();
+                    }
+                }
+            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+                // The slot still holds the element of the previous lap.
+                atomic::fence(Ordering::SeqCst);
+                let head = self.head.load(Ordering::Relaxed);
+                if head.wrapping_add(self.one_lap) == tail {
+                    return Err(value);
+                }
+                backoff.spin();
+                tail = self.tail.load(Ordering::Relaxed);
+            } else {
+                backoff.snooze();
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Attempts to pop an element from the queue.
+    ///
+    /// If the queue is empty, `None` is returned.
+    pub fn try_pop(&self) -> Option<Rc<T>> {
+        let backoff = Backoff::new();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[head & (self.one_lap - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if stamp == head + 1 {
+                match self.head.compare_exchange_weak(
+                    head,
+                    self.next_pos(head),
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = slot.value.swap(Rc::null(), Ordering::Relaxed);
+                        // Hand the slot over to the producer of the next lap.
+                        slot.stamp
+                            .store(head.wrapping_add(self.one_lap), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => {
+                        head = current;
+                        backoff.spin();
+                    }
+                }
+            } else if stamp == head {
+                // The slot has not been written in this lap yet.
+                atomic::fence(Ordering::SeqCst);
+                let tail = self.tail.load(Ordering::Relaxed);
+                if tail == head {
+                    return None;
+                }
+                backoff.spin();
+                head = self.head.load(Ordering::Relaxed);
+            } else {
+                backoff.snooze();
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the capacity of the queue.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the number of elements in the queue.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            // Retry if `tail` has changed while reading `head`, so that the two are consistent.
+            if self.tail.load(Ordering::SeqCst) == tail {
+                let head_index = head & (self.one_lap - 1);
+                let tail_index = tail & (self.one_lap - 1);
+                return if head_index < tail_index {
+                    tail_index - head_index
+                } else if head_index > tail_index {
+                    self.capacity() - head_index + tail_index
+                } else if tail == head {
+                    0
+                } else {
+                    self.capacity()
+                };
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for ArrayQueue<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let values: usize = self
+            .buffer
+            .iter()
+            .map(|slot| slot.value.heap_size(visited))
+            .sum();
+        size_of_val(&*self.buffer) + values
+    }
+}
+
+impl<T: RcObject> Debug for ArrayQueue<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("ArrayQueue { .. }")
+    }
+}
@@ -0,0 +1,674 @@
+//! A lock-free external binary search tree.
+//!
+//! This is the tree of Ellen, Fatourou, Ruppert and van Breugel ("Non-blocking binary search
+//! trees", PODC 2010). The entries are stored in the leaves, and each internal node routes a
+//! search by its key: the smaller keys are in its left subtree and the others in its right
+//! subtree. Two sentinel keys, greater than every other key, keep the root and its left child
+//! in place.
+//!
+//! An update changes a single child pointer. Before that, it flags the `update` field of the
+//! parent (and, for a deletion, marks the removed parent) with a descriptor of the operation,
+//! whose state is kept in the tag bits of the pointer. A thread that runs into a flagged or
+//! marked node helps to complete the operation of the descriptor before retrying its own, so a
+//! stalled thread never blocks the others.
+//!
+//! The descriptors hold weak references only, so they never form cycles with the nodes. A node
+//! referenced by a descriptor stays reachable until the operation is done, so a helper that fails
+//! to upgrade one knows that there is nothing left to help with.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering::{self as CmpOrdering, Equal, Greater};
+use std::fmt::{Debug, Formatter};
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::Ordering;
+
+use crate::heap_size::{HeapSize, Visited};
+use crate::{AtomicRc, Guard, Rc, RcObject, Snapshot, Weak};
+
+/// The tag of an `update` field with no operation in progress.
+const CLEAN: usize = 0;
+/// The tag of an `update` field flagged by an insertion below the node.
+const IFLAG: usize = 1;
+/// The tag of an `update` field flagged by a deletion of a grandchild of the node.
+const DFLAG: usize = 2;
+/// The tag of an `update` field of a node that is being removed.
+const MARK: usize = 3;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Key<K> {
+    Fin(K),
+    /// The sentinels, which are greater than every finite key.
+    Inf1,
+    Inf2,
+}
+
+impl<K> Key<K> {
+    fn cmp_with<Q>(&self, key: &Q) -> CmpOrdering
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self {
+            Key::Fin(k) => k.borrow().cmp(key),
+            Key::Inf1 | Key::Inf2 => Greater,
+        }
+    }
+}
+
+/// A reference-counted value, which is shared by a leaf and its copies.
+struct Value<V>(V);
+
+unsafe impl<V> RcObject for Value<V> {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+enum Node<K, V> {
+    Internal {
+        key: Key<K>,
+        left: AtomicRc<Node<K, V>>,
+        right: AtomicRc<Node<K, V>>,
+        update: AtomicRc<Info<K, V>>,
+    },
+    /// A leaf, whose value is null for the sentinels.
+    Leaf { key: Key<K>, value: Rc<Value<V>> },
+}
+
+unsafe impl<K, V> RcObject for Node<K, V> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        if let Node::Internal { left, right, .. } = self {
+            out.push(left.take());
+            out.push(right.take());
+        }
+    }
+}
+
+impl<K, V> Node<K, V> {
+    fn key(&self) -> &Key<K> {
+        match self {
+            Node::Internal { key, .. } | Node::Leaf { key, .. } => key,
+        }
+    }
+
+    fn update(&self) -> &AtomicRc<Info<K, V>> {
+        match self {
+            Node::Internal { update, .. } => update,
+            Node::Leaf { .. } => unreachable!("a leaf has no update field"),
+        }
+    }
+
+    fn children(&self) -> (&AtomicRc<Self>, &AtomicRc<Self>) {
+        match self {
+            Node::Internal { left, right, .. } => (left, right),
+            Node::Leaf { .. } => unreachable!("a leaf has no children"),
+        }
+    }
+
+    /// Returns the value of this node if it is the leaf of `key`.
+    fn value_of<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self {
+            Node::Leaf { key: k, value } if k.cmp_with(key) == Equal => {
+                value.as_ref().map(|value| &value.0)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A descriptor of an operation in progress.
+enum Info<K, V> {
+    Insert {
+        p: Weak<Node<K, V>>,
+        l: Weak<Node<K, V>>,
+        new_internal: Weak<Node<K, V>>,
+    },
+    Delete {
+        gp: Weak<Node<K, V>>,
+        p: Weak<Node<K, V>>,
+        l: Weak<Node<K, V>>,
+        /// The `update` field of `p` when the deletion started, which is null if `p` has never
+        /// been flagged.
+        pupdate: Weak<Info<K, V>>,
+    },
+}
+
+unsafe impl<K, V> RcObject for Info<K, V> {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+/// The result of a search: the leaf where the key is or would be, with its parent and
+/// grandparent and their `update` fields.
+struct Position<'g, K, V> {
+    gp: Snapshot<'g, Node<K, V>>,
+    p: Snapshot<'g, Node<K, V>>,
+    l: Snapshot<'g, Node<K, V>>,
+    gpupdate: Snapshot<'g, Info<K, V>>,
+    pupdate: Snapshot<'g, Info<K, V>>,
+}
+
+/// A lock-free ordered map based on an external binary search tree.
+///
+/// Compared to [`PriorityQueue`](crate::ds::PriorityQueue) and other skiplists, an update
+/// allocates a constant number of small nodes and a search follows a single path without any
+/// randomness. On the other hand, the tree is not balanced, so keys that are inserted in order
+/// degrade it to a list. A key is never replaced once inserted: it has to be removed first.
+///
+/// [`BstMap::range`] scans the entries in order. The scan is not atomic: it sees the entries
+/// that stay in the map during the scan, and may or may not see the others.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::BstMap};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let map = BstMap::new();
+/// let guard = &cs();
+///
+/// assert!(map.insert(2, "two", guard));
+/// assert!(map.insert(1, "one", guard));
+/// assert!(map.insert(3, "three", guard));
+/// assert!(!map.insert(3, "drei", guard));
+///
+/// assert_eq!(map.get(&3, guard), Some(&"three"));
+/// assert_eq!(map.remove(&1, guard), Some(&"one"));
+///
+/// let keys: Vec<_> = map.range(2.., guard).map(|(k, _)| *k).collect();
+/// assert_eq!(keys, [2, 3]);
+/// ```
+pub struct BstMap<K, V> {
+    root: Rc<Node<K, V>>,
+}
+
+impl<K, V> BstMap<K, V> {
+    /// Creates a new empty map.
+    pub fn new() -> Self {
+        let leaf = |key| {
+            AtomicRc::from(Rc::new(Node::Leaf {
+                key,
+                value: Rc::null(),
+            }))
+        };
+        Self {
+            root: Rc::new(Node::Internal {
+                key: Key::Inf2,
+                left: leaf(Key::Inf1),
+                right: leaf(Key::Inf2),
+                update: AtomicRc::null(),
+            }),
+        }
+    }
+
+    /// Returns the leaf where `key` is or would be, with its ancestors.
+    fn search<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Position<'g, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut gp = Snapshot::null();
+        let mut gpupdate = Snapshot::null();
+        let mut p = Snapshot::null();
+        let mut pupdate = Snapshot::null();
+        let mut l = self.root.snapshot(guard);
+        while let Some(Node::Internal {
+            key: k,
+            left,
+            right,
+            update,
+        }) = l.as_ref()
+        {
+            gp = p;
+            gpupdate = pupdate;
+            p = l;
+            pupdate = update.load(Ordering::Acquire, guard);
+            let child = if k.cmp_with(key) == Greater {
+                left
+            } else {
+                right
+            };
+            l = child.load(Ordering::Acquire, guard);
+        }
+        Position {
+            gp,
+            p,
+            l,
+            gpupdate,
+            pupdate,
+        }
+    }
+
+    /// Returns a reference to the value for `key`.
+    pub fn get<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q> + 'g,
+        V: 'g,
+        Q: Ord + ?Sized,
+    {
+        self.search(key, guard).l.as_ref()?.value_of(key)
+    }
+
+    /// Returns `true` if the map contains `key`.
+    pub fn contains_key<Q>(&self, key: &Q, guard: &Guard) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key, guard).is_some()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        // The finite keys are all in the left subtree of the root, which is the first sentinel
+        // leaf alone when there are none.
+        let (left, _) = self.root.as_ref().unwrap().children();
+        matches!(
+            left.load(Ordering::Acquire, guard).as_ref(),
+            Some(Node::Leaf { .. })
+        )
+    }
+
+    /// Returns an iterator over the entries of the map in ascending order of keys.
+    pub fn iter<'g>(&self, guard: &'g Guard) -> Range<'g, K, V> {
+        Range {
+            stack: vec![self.root.snapshot(guard).as_ref().unwrap()],
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+            guard,
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> BstMap<K, V> {
+    /// Inserts `value` for `key` if the map does not contain `key` yet, returning `true` if it
+    /// was inserted.
+    pub fn insert(&self, key: K, value: V, guard: &Guard) -> bool {
+        let value = Rc::new(Value(value));
+        loop {
+            let pos = self.search(&key, guard);
+            let (leaf_key, leaf_value) = match pos.l.as_ref().unwrap() {
+                Node::Leaf { key, value } => (key, value),
+                Node::Internal { .. } => unreachable!("a search ends at a leaf"),
+            };
+            if leaf_key.cmp_with(&key) == Equal {
+                return false;
+            }
+            if pos.pupdate.tag() != CLEAN {
+                help(pos.pupdate, guard);
+                continue;
+            }
+
+            let new_leaf = Rc::new(Node::Leaf {
+                key: Key::Fin(key.clone()),
+                value: value.clone(),
+            });
+            // The leaf is replaced by a copy, so that a stalled helper of an older insertion
+            // cannot find the leaf under `p` again and install its node a second time.
+            let sibling = Rc::new(Node::Leaf {
+                key: leaf_key.clone(),
+                value: leaf_value.clone(),
+            });
+            let (new_key, left, right) = if leaf_key.cmp_with(&key) == Greater {
+                (leaf_key.clone(), new_leaf, sibling)
+            } else {
+                (Key::Fin(key.clone()), sibling, new_leaf)
+            };
+            let new_internal = Rc::new(Node::Internal {
+                key: new_key,
+                left: AtomicRc::from(left),
+                right: AtomicRc::from(right),
+                update: AtomicRc::null(),
+            });
+            let op = Rc::new(Info::Insert {
+                p: pos.p.into(),
+                l: pos.l.into(),
+                new_internal: new_internal.downgrade(),
+            });
+            let flagged = op.snapshot(guard).with_tag(IFLAG);
+
+            match pos.p.as_ref().unwrap().update().compare_exchange(
+                pos.pupdate,
+                op.with_tag(IFLAG),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => {
+                    // `new_internal` is kept alive until it is installed.
+                    help_insert(flagged, guard);
+                    return true;
+                }
+                Err(e) => help(e.current, guard),
+            }
+        }
+    }
+
+    /// Removes `key` from the map, returning its value if it was present.
+    pub fn remove<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q> + 'g,
+        V: 'g,
+        Q: Ord + ?Sized,
+    {
+        loop {
+            let pos = self.search(key, guard);
+            let value = pos.l.as_ref().unwrap().value_of(key)?;
+            if pos.gpupdate.tag() != CLEAN {
+                help(pos.gpupdate, guard);
+                continue;
+            }
+            if pos.pupdate.tag() != CLEAN {
+                help(pos.pupdate, guard);
+                continue;
+            }
+
+            let op = Rc::new(Info::Delete {
+                gp: pos.gp.into(),
+                p: pos.p.into(),
+                l: pos.l.into(),
+                pupdate: pos.pupdate.into(),
+            });
+            let flagged = op.snapshot(guard).with_tag(DFLAG);
+
+            match pos.gp.as_ref().unwrap().update().compare_exchange(
+                pos.gpupdate,
+                op.with_tag(DFLAG),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => {
+                    if help_delete(flagged, guard) {
+                        return Some(value);
+                    }
+                }
+                Err(e) => help(e.current, guard),
+            }
+        }
+    }
+
+    /// Returns an iterator over the entries of the map whose keys are in `range`, in ascending
+    /// order of keys.
+    pub fn range<'g, R: RangeBounds<K>>(&self, range: R, guard: &'g Guard) -> Range<'g, K, V> {
+        Range {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            ..self.iter(guard)
+        }
+    }
+}
+
+/// Helps the operation of the `update` field `update` to complete.
+fn help<K: Ord, V>(update: Snapshot<'_, Info<K, V>>, guard: &Guard) {
+    match update.tag() {
+        IFLAG => help_insert(update, guard),
+        DFLAG => {
+            help_delete(update, guard);
+        }
+        MARK => help_marked(update, guard),
+        _ => {}
+    }
+}
+
+/// Replaces the child `old` of `parent` with `new`.
+fn cas_child<K: Ord, V>(
+    parent: Snapshot<'_, Node<K, V>>,
+    old: Snapshot<'_, Node<K, V>>,
+    new: Rc<Node<K, V>>,
+    guard: &Guard,
+) {
+    let parent = parent.as_ref().unwrap();
+    let (left, right) = parent.children();
+    let child = if new.as_ref().unwrap().key() < parent.key() {
+        left
+    } else {
+        right
+    };
+    let _ = child.compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire, guard);
+}
+
+fn help_insert<K: Ord, V>(op: Snapshot<'_, Info<K, V>>, guard: &Guard) {
+    let Some(Info::Insert { p, l, new_internal }) = op.as_ref() else {
+        unreachable!("an insertion flag holds an insertion")
+    };
+    // `p` cannot be removed while it is flagged, and `l` and `new_internal` are only released
+    // once `new_internal` has been installed.
+    let Some(p) = p.upgrade_in(guard) else {
+        return;
+    };
+    if let (Some(l), Some(new_internal)) = (l.upgrade_in(guard), new_internal.upgrade_in(guard)) {
+        cas_child(p, l, new_internal.counted(), guard);
+    }
+    let _ = p.as_ref().unwrap().update().compare_exchange_tag(
+        op,
+        CLEAN,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        guard,
+    );
+}
+
+/// Tries to mark the parent of the deleted leaf, and completes the deletion if it succeeds.
+/// Otherwise, unflags the grandparent so that the deletion can be retried.
+///
+/// Returns `true` if the deletion has succeeded.
+fn help_delete<K: Ord, V>(op: Snapshot<'_, Info<K, V>>, guard: &Guard) -> bool {
+    let Some(Info::Delete { gp, p, pupdate, .. }) = op.as_ref() else {
+        unreachable!("a deletion flag holds a deletion")
+    };
+    let Some(p) = p.upgrade_in(guard) else {
+        // Only this deletion can remove `p` while the grandparent is flagged.
+        help_marked(op, guard);
+        return true;
+    };
+    let update = p.as_ref().unwrap().update();
+    let expected = if pupdate.is_null() {
+        Some(Snapshot::null())
+    } else {
+        pupdate.upgrade_in(guard)
+    };
+    let result = match expected {
+        Some(expected) => update
+            .compare_exchange(
+                expected,
+                op.counted().with_tag(MARK),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            )
+            .map(|_| ())
+            .map_err(|e| e.current),
+        // The old `update` field has been released, so `p` has been flagged or marked since.
+        None => Err(update.load(Ordering::Acquire, guard)),
+    };
+    match result {
+        Ok(()) => {
+            help_marked(op, guard);
+            true
+        }
+        Err(current) if current.ptr_eq(op.with_tag(MARK)) => {
+            help_marked(op, guard);
+            true
+        }
+        Err(current) => {
+            help(current, guard);
+            if let Some(gp) = gp.upgrade_in(guard) {
+                let _ = gp.as_ref().unwrap().update().compare_exchange_tag(
+                    op.with_tag(DFLAG),
+                    CLEAN,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                );
+            }
+            false
+        }
+    }
+}
+
+/// Unlinks the marked parent of the deleted leaf and unflags the grandparent.
+fn help_marked<K: Ord, V>(op: Snapshot<'_, Info<K, V>>, guard: &Guard) {
+    let Some(Info::Delete { gp, p, l, .. }) = op.as_ref() else {
+        unreachable!("a mark holds a deletion")
+    };
+    // The grandparent cannot be removed while it is flagged.
+    let Some(gp) = gp.upgrade_in(guard) else {
+        return;
+    };
+    // The children of a marked node never change, and it only becomes unreachable once it has
+    // been unlinked.
+    if let (Some(p), Some(l)) = (p.upgrade_in(guard), l.upgrade_in(guard)) {
+        let (left, right) = p.as_ref().unwrap().children();
+        let right_child = right.load(Ordering::Acquire, guard);
+        let other = if right_child.ptr_eq(l) {
+            left.load(Ordering::Acquire, guard)
+        } else {
+            right_child
+        };
+        cas_child(gp, p, other.counted(), guard);
+    }
+    let _ = gp.as_ref().unwrap().update().compare_exchange_tag(
+        op.with_tag(DFLAG),
+        CLEAN,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        guard,
+    );
+}
+
+impl<K, V> Default for BstMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Debug for BstMap<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("BstMap { .. }")
+    }
+}
+
+impl<V: HeapSize> HeapSize for Value<V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.0.heap_size(visited)
+    }
+}
+
+impl<K, V> HeapSize for Info<K, V> {
+    fn heap_size(&self, _: &mut Visited) -> usize {
+        0
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for Node<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        // The recursion is bounded by the height of the tree.
+        match self {
+            Node::Internal {
+                key,
+                left,
+                right,
+                update,
+            } => {
+                key_heap_size(key, visited)
+                    + left.heap_size(visited)
+                    + right.heap_size(visited)
+                    + update.heap_size(visited)
+            }
+            Node::Leaf { key, value } => key_heap_size(key, visited) + value.heap_size(visited),
+        }
+    }
+}
+
+fn key_heap_size<K: HeapSize>(key: &Key<K>, visited: &mut Visited) -> usize {
+    match key {
+        Key::Fin(key) => key.heap_size(visited),
+        Key::Inf1 | Key::Inf2 => 0,
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BstMap<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.root.heap_size(visited)
+    }
+}
+
+/// An iterator over the entries of a [`BstMap`] in a range of keys.
+///
+/// This is created by [`BstMap::iter`] and [`BstMap::range`].
+pub struct Range<'g, K, V> {
+    /// The subtrees left to visit, with the leftmost one at the top.
+    stack: Vec<&'g Node<K, V>>,
+    start: Bound<K>,
+    end: Bound<K>,
+    guard: &'g Guard,
+}
+
+impl<'g, K: Ord, V> Range<'g, K, V> {
+    /// Returns `true` if a key less than `key` may be in the range.
+    fn below_start(&self, key: &Key<K>) -> bool {
+        match (&self.start, key) {
+            (Bound::Included(start) | Bound::Excluded(start), Key::Fin(key)) => start < key,
+            _ => true,
+        }
+    }
+
+    /// Returns `true` if a key greater than or equal to `key` may be in the range.
+    fn above_end(&self, key: &Key<K>) -> bool {
+        match (&self.end, key) {
+            (_, Key::Inf1 | Key::Inf2) => false,
+            (Bound::Included(end), Key::Fin(key)) => key <= end,
+            (Bound::Excluded(end), Key::Fin(key)) => key < end,
+            (Bound::Unbounded, Key::Fin(_)) => true,
+        }
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        let after_start = match &self.start {
+            Bound::Included(start) => start <= key,
+            Bound::Excluded(start) => start < key,
+            Bound::Unbounded => true,
+        };
+        let before_end = match &self.end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+}
+
+impl<'g, K: Ord, V> Iterator for Range<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf {
+                    key: Key::Fin(key),
+                    value,
+                } if self.contains(key) => return Some((key, &value.as_ref().unwrap().0)),
+                Node::Leaf { .. } => {}
+                Node::Internal {
+                    key, left, right, ..
+                } => {
+                    // The right subtree is pushed first, so that the left one is visited first.
+                    if self.above_end(key) {
+                        if let Some(right) = right.load(Ordering::Acquire, self.guard).as_ref() {
+                            self.stack.push(right);
+                        }
+                    }
+                    if self.below_start(key) {
+                        if let Some(left) = left.load(Ordering::Acquire, self.guard).as_ref() {
+                            self.stack.push(left);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
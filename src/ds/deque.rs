@@ -0,0 +1,540 @@
+//! A Chase-Lev work-stealing deque.
+//!
+//! David Chase and Yossi Lev. Dynamic Circular Work-Stealing Deque. SPAA 2005.
+//! <https://doi.org/10.1145/1073970.1073974>
+//!
+//! Nhat Minh Lê, Antoniu Pop, Albert Cohen, and Francesco Zappa Nardelli. Correct and Efficient
+//! Work-Stealing for Weak Memory Models. PPoPP 2013. <https://doi.org/10.1145/2442516.2442524>
+//!
+//! The owner of the deque pushes and pops tasks at the back, and other threads steal tasks from
+//! the front. When the circular buffer becomes full, the owner replaces it with a larger one and
+//! retires the old buffer to the collector, because stealers may still be reading from it.
+
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ptr;
+use core::sync::atomic::{self, AtomicIsize, Ordering};
+use std::sync::Arc;
+
+use crossbeam_utils::CachePadded;
+
+use crate::cs;
+use crate::ebr_impl::{unprotected, RawAtomic, RawShared};
+
+/// Minimum capacity of the buffer.
+const MIN_CAP: usize = 64;
+
+/// Maximum number of tasks that can be stolen in `steal_batch_and_pop()`.
+const MAX_BATCH: usize = 32;
+
+/// If the buffer of this capacity or larger is retired, the local garbage is flushed so that the
+/// buffer is reclaimed sooner.
+const FLUSH_THRESHOLD_BYTES: usize = 1 << 10;
+
+/// A circular buffer of tasks.
+struct Buffer<T> {
+    /// Pointer to the allocated memory.
+    ptr: *mut T,
+    /// Capacity of the buffer. Always a power of two.
+    cap: usize,
+}
+
+unsafe impl<T: Send> Send for Buffer<T> {}
+unsafe impl<T: Send> Sync for Buffer<T> {}
+
+impl<T> Buffer<T> {
+    /// Allocates a new buffer with the specified capacity.
+    fn alloc(cap: usize) -> Self {
+        debug_assert_eq!(cap, cap.next_power_of_two());
+        let mut v = ManuallyDrop::new(Vec::<T>::with_capacity(cap));
+        let ptr = v.as_mut_ptr();
+        Buffer { ptr, cap }
+    }
+
+    /// Deallocates the buffer.
+    unsafe fn dealloc(self) {
+        drop(Vec::from_raw_parts(self.ptr, 0, self.cap));
+    }
+
+    /// Returns a pointer to the task at the specified `index`.
+    unsafe fn at(&self, index: isize) -> *mut T {
+        // `self.cap` is always a power of two.
+        self.ptr.offset(index & (self.cap - 1) as isize)
+    }
+
+    /// Writes `task` into the specified `index`.
+    ///
+    /// This method might be concurrently called with another `read` at the same index, which is
+    /// technically speaking a data race and therefore UB. We should use an atomic store here, but
+    /// that would be more expensive and difficult to implement generically for all types `T`.
+    /// Hence, as a hack, we use a volatile write instead.
+    unsafe fn write(&self, index: isize, task: MaybeUninit<T>) {
+        ptr::write_volatile(self.at(index).cast::<MaybeUninit<T>>(), task)
+    }
+
+    /// Reads a task from the specified `index`.
+    ///
+    /// This method might be concurrently called with another `write` at the same index, which is
+    /// technically speaking a data race and therefore UB. We should use an atomic load here, but
+    /// that would be more expensive and difficult to implement generically for all types `T`.
+    /// Hence, as a hack, we use a volatile load instead.
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        ptr::read_volatile(self.at(index).cast::<MaybeUninit<T>>())
+    }
+}
+
+impl<T> Clone for Buffer<T> {
+    fn clone(&self) -> Buffer<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Buffer<T> {}
+
+/// Internal data shared between the worker and stealers.
+struct Inner<T> {
+    /// The front index.
+    front: AtomicIsize,
+
+    /// The back index.
+    back: AtomicIsize,
+
+    /// The underlying buffer.
+    buffer: CachePadded<RawAtomic<Buffer<T>>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        // Load the back index, front index, and buffer.
+        let b = *self.back.get_mut();
+        let f = *self.front.get_mut();
+
+        unsafe {
+            let guard = unprotected();
+            let buffer = self.buffer.load(Ordering::Relaxed, &guard);
+
+            // Go through the buffer from front to back and drop all tasks in the queue.
+            let mut i = f;
+            while i != b {
+                buffer.deref().at(i).drop_in_place();
+                i = i.wrapping_add(1);
+            }
+
+            // Free the memory allocated by the buffer.
+            buffer.deref().dealloc();
+            buffer.drop();
+        }
+    }
+}
+
+/// The worker side of a deque.
+///
+/// Workers push tasks into the back and pop them from the back, in LIFO order. Stealers steal
+/// tasks from the front, so the oldest tasks are handed over to other threads.
+///
+/// A deque has only one worker, which cannot be shared among threads, but it can be sent to
+/// another thread. Use [`Worker::stealer`] to create stealers that can be shared.
+///
+/// # Examples
+///
+/// ```
+/// use circ::ds::{Steal, Worker};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let w = Worker::new();
+/// let s = w.stealer();
+///
+/// w.push(1);
+/// w.push(2);
+/// w.push(3);
+///
+/// assert_eq!(s.steal(), Steal::Success(1));
+/// assert_eq!(w.pop(), Some(3));
+/// assert_eq!(w.pop(), Some(2));
+/// assert_eq!(w.pop(), None);
+/// ```
+pub struct Worker<T> {
+    /// A reference to the inner representation of the deque.
+    inner: Arc<CachePadded<Inner<T>>>,
+
+    /// A copy of `inner.buffer` for quick access.
+    buffer: Cell<Buffer<T>>,
+
+    /// Indicates that the worker cannot be shared among threads.
+    _marker: PhantomData<*mut ()>, // !Send + !Sync
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+
+impl<T> Worker<T> {
+    /// Creates a new deque.
+    pub fn new() -> Worker<T> {
+        let buffer = Buffer::alloc(MIN_CAP);
+
+        let inner = Arc::new(CachePadded::new(Inner {
+            front: AtomicIsize::new(0),
+            back: AtomicIsize::new(0),
+            buffer: CachePadded::new(RawAtomic::null()),
+        }));
+        inner
+            .buffer
+            .store(RawShared::from_owned(buffer), Ordering::Relaxed);
+
+        Worker {
+            inner,
+            buffer: Cell::new(buffer),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a stealer for this deque.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Resizes the internal buffer to the new capacity of `new_cap`.
+    #[cold]
+    unsafe fn resize(&self, new_cap: usize) {
+        // Load the back index, front index, and buffer.
+        let b = self.inner.back.load(Ordering::Relaxed);
+        let f = self.inner.front.load(Ordering::Relaxed);
+        let buffer = self.buffer.get();
+
+        // Allocate a new buffer and copy data from the old buffer to the new one.
+        let new = Buffer::alloc(new_cap);
+        let mut i = f;
+        while i != b {
+            ptr::copy_nonoverlapping(buffer.at(i), new.at(i), 1);
+            i = i.wrapping_add(1);
+        }
+
+        let guard = &cs();
+
+        // Replace the old buffer with the new one. Only the worker writes the buffer, so a load
+        // followed by a store does not race with other writers.
+        self.buffer.replace(new);
+        let old = self.inner.buffer.load(Ordering::Relaxed, guard);
+        self.inner
+            .buffer
+            .store(RawShared::from_owned(new), Ordering::Release);
+
+        // Stealers may still be reading from the old buffer, so destroy it later.
+        guard.defer_unchecked(move || {
+            old.deref().dealloc();
+            old.drop();
+        });
+
+        // If the buffer is very large, then flush the thread-local garbage in order to deallocate
+        // it as soon as possible.
+        if mem::size_of::<T>() * new_cap >= FLUSH_THRESHOLD_BYTES {
+            guard.flush();
+        }
+    }
+
+    /// Reserves enough capacity so that `reserve_cap` tasks can be pushed without growing the
+    /// buffer.
+    fn reserve(&self, reserve_cap: usize) {
+        if reserve_cap > 0 {
+            // Compute the current length.
+            let b = self.inner.back.load(Ordering::Relaxed);
+            let f = self.inner.front.load(Ordering::SeqCst);
+            let len = b.wrapping_sub(f) as usize;
+
+            // The current capacity.
+            let cap = self.buffer.get().cap;
+
+            // Is there enough capacity to push `reserve_cap` tasks?
+            if cap - len < reserve_cap {
+                // Keep doubling the capacity as much as is needed.
+                let mut new_cap = cap * 2;
+                while new_cap - len < reserve_cap {
+                    new_cap *= 2;
+                }
+
+                // Resize the buffer.
+                unsafe {
+                    self.resize(new_cap);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        let b = self.inner.back.load(Ordering::Relaxed);
+        let f = self.inner.front.load(Ordering::SeqCst);
+        b.wrapping_sub(f) <= 0
+    }
+
+    /// Returns the number of tasks in the deque.
+    pub fn len(&self) -> usize {
+        let b = self.inner.back.load(Ordering::Relaxed);
+        let f = self.inner.front.load(Ordering::SeqCst);
+        b.wrapping_sub(f).max(0) as usize
+    }
+
+    /// Pushes a task into the back of the deque.
+    pub fn push(&self, task: T) {
+        // Load the back index, front index, and buffer.
+        let b = self.inner.back.load(Ordering::Relaxed);
+        let f = self.inner.front.load(Ordering::Acquire);
+        let mut buffer = self.buffer.get();
+
+        // Calculate the length of the deque.
+        let len = b.wrapping_sub(f);
+
+        // Is the deque full?
+        if len >= buffer.cap as isize {
+            // Yes. Grow the underlying buffer.
+            unsafe {
+                self.resize(2 * buffer.cap);
+            }
+            buffer = self.buffer.get();
+        }
+
+        // Write `task` into the slot.
+        unsafe {
+            buffer.write(b, MaybeUninit::new(task));
+        }
+
+        atomic::fence(Ordering::Release);
+
+        // Increment the back index.
+        //
+        // This ordering could be `Relaxed`, but then thread sanitizer would falsely report data
+        // races because it doesn't understand fences.
+        self.inner.back.store(b.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Pops a task from the back of the deque.
+    pub fn pop(&self) -> Option<T> {
+        // Load the back and front index.
+        let b = self.inner.back.load(Ordering::Relaxed);
+        let f = self.inner.front.load(Ordering::Relaxed);
+
+        // Calculate the length of the deque.
+        let len = b.wrapping_sub(f);
+
+        // Is the deque empty?
+        if len <= 0 {
+            return None;
+        }
+
+        // Decrement the back index.
+        let b = b.wrapping_sub(1);
+        self.inner.back.store(b, Ordering::Relaxed);
+
+        atomic::fence(Ordering::SeqCst);
+
+        // Load the front index.
+        let f = self.inner.front.load(Ordering::Relaxed);
+
+        // Compute the length after the back index was decremented.
+        let len = b.wrapping_sub(f);
+
+        if len < 0 {
+            // The deque is empty. Restore the back index to the original task.
+            self.inner.back.store(b.wrapping_add(1), Ordering::Relaxed);
+            None
+        } else {
+            // Read the task to be popped.
+            let buffer = self.buffer.get();
+            let mut task = unsafe { Some(buffer.read(b)) };
+
+            // Are we popping the last task from the deque?
+            if len == 0 {
+                // Try incrementing the front index.
+                if self
+                    .inner
+                    .front
+                    .compare_exchange(f, f.wrapping_add(1), Ordering::SeqCst, Ordering::Relaxed)
+                    .is_err()
+                {
+                    // Failed. We didn't pop anything. Reset to `None`.
+                    task.take();
+                }
+
+                // Restore the back index to the original task.
+                self.inner.back.store(b.wrapping_add(1), Ordering::Relaxed);
+            } else {
+                // Shrink the buffer if `len` is less than one fourth of the capacity.
+                if buffer.cap > MIN_CAP && len < buffer.cap as isize / 4 {
+                    unsafe {
+                        self.resize(buffer.cap / 2);
+                    }
+                }
+            }
+
+            task.map(|t| unsafe { t.assume_init() })
+        }
+    }
+}
+
+impl<T> Default for Worker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Worker<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Worker { .. }")
+    }
+}
+
+/// The stealer side of a deque.
+///
+/// Stealers can be shared among threads and steal tasks from the front of the deque.
+pub struct Stealer<T> {
+    /// A reference to the inner representation of the deque.
+    inner: Arc<CachePadded<Inner<T>>>,
+}
+
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Stealer<T> {
+    /// Returns `true` if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        let f = self.inner.front.load(Ordering::Acquire);
+        atomic::fence(Ordering::SeqCst);
+        let b = self.inner.back.load(Ordering::Acquire);
+        b.wrapping_sub(f) <= 0
+    }
+
+    /// Returns the number of tasks in the deque.
+    pub fn len(&self) -> usize {
+        let f = self.inner.front.load(Ordering::Acquire);
+        atomic::fence(Ordering::SeqCst);
+        let b = self.inner.back.load(Ordering::Acquire);
+        b.wrapping_sub(f).max(0) as usize
+    }
+
+    /// Steals a task from the front of the deque.
+    ///
+    /// [`Steal::Retry`] is returned if the operation lost a race with another thread, in which
+    /// case it may be worth retrying.
+    pub fn steal(&self) -> Steal<T> {
+        // Load the front index.
+        let f = self.inner.front.load(Ordering::Acquire);
+
+        // The buffer must be protected from being retired while we read from it.
+        let guard = &cs();
+
+        // A `SeqCst` fence is needed here. If the current thread was already in a critical
+        // section, pinning did not issue one.
+        atomic::fence(Ordering::SeqCst);
+
+        // Load the back index.
+        let b = self.inner.back.load(Ordering::Acquire);
+
+        // Is the deque empty?
+        if b.wrapping_sub(f) <= 0 {
+            return Steal::Empty;
+        }
+
+        // Load the buffer and read the task at the front.
+        let buffer = self.inner.buffer.load(Ordering::Acquire, guard);
+        let task = unsafe { buffer.deref().read(f) };
+
+        // Try incrementing the front index to steal the task.
+        // If the buffer has been swapped or the increment fails, we retry.
+        if !self
+            .inner
+            .buffer
+            .load(Ordering::Acquire, guard)
+            .ptr_eq(buffer)
+            || self
+                .inner
+                .front
+                .compare_exchange(f, f.wrapping_add(1), Ordering::SeqCst, Ordering::Relaxed)
+                .is_err()
+        {
+            // We didn't steal this task, forget it.
+            return Steal::Retry;
+        }
+
+        // Return the stolen task.
+        Steal::Success(unsafe { task.assume_init() })
+    }
+
+    /// Steals a batch of tasks from the front of the deque and pushes them into `dest`, returning
+    /// one more task directly.
+    ///
+    /// At most half of the tasks, and no more than a fixed limit, are stolen at once.
+    pub fn steal_batch_and_pop(&self, dest: &Worker<T>) -> Steal<T> {
+        let task = match self.steal() {
+            Steal::Success(task) => task,
+            Steal::Empty => return Steal::Empty,
+            Steal::Retry => return Steal::Retry,
+        };
+
+        let batch = (self.len() / 2).min(MAX_BATCH);
+        dest.reserve(batch);
+        for _ in 0..batch {
+            match self.steal() {
+                Steal::Success(t) => dest.push(t),
+                Steal::Empty | Steal::Retry => break,
+            }
+        }
+        Steal::Success(task)
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Stealer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Stealer { .. }")
+    }
+}
+
+/// The result of a steal operation.
+#[must_use]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Steal<T> {
+    /// The deque was empty at the time of stealing.
+    Empty,
+
+    /// At least one task was successfully stolen.
+    Success(T),
+
+    /// The steal operation needs to be retried.
+    Retry,
+}
+
+impl<T> Steal<T> {
+    /// Returns `true` if the deque was empty at the time of stealing.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Steal::Empty)
+    }
+
+    /// Returns `true` if at least one task was stolen.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Steal::Success(_))
+    }
+
+    /// Returns `true` if the steal operation needs to be retried.
+    pub fn is_retry(&self) -> bool {
+        matches!(self, Steal::Retry)
+    }
+
+    /// Returns the result of the operation, if successful.
+    pub fn success(self) -> Option<T> {
+        match self {
+            Steal::Success(res) => Some(res),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! A concurrent least-recently-used (LRU) cache.
+//!
+//! The cache is split into shards. Each shard combines a hash map from keys to slots and a doubly
+//! linked list of the slots, ordered from the most recently used to the least recently used one.
+//! Values are kept in [`Rc`]s, so a value evicted by one thread stays valid for the other threads
+//! that have looked it up in their critical sections.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::mem::size_of_val;
+use std::sync::{Mutex, MutexGuard};
+
+use crossbeam_utils::CachePadded;
+
+use crate::heap_size::{HeapSize, Visited};
+use crate::{Guard, Rc, RcObject};
+
+/// The default maximum number of shards of a cache.
+const DEFAULT_SHARDS: usize = 16;
+
+/// A sentinel index representing the end of a list.
+const NIL: usize = usize::MAX;
+
+/// A reference-counted cell holding a cached value.
+struct Entry<V> {
+    value: V,
+}
+
+unsafe impl<V> RcObject for Entry<V> {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl<V: HeapSize> HeapSize for Entry<V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.value.heap_size(visited)
+    }
+}
+
+/// A slot in the doubly linked list of a shard.
+struct Slot<K, V> {
+    key: K,
+    entry: Rc<Entry<V>>,
+    prev: usize,
+    next: usize,
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for Slot<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.key.heap_size(visited) + self.entry.heap_size(visited)
+    }
+}
+
+/// A part of the cache protected by a single lock.
+struct Shard<K, V> {
+    map: HashMap<K, usize>,
+    slots: Vec<Option<Slot<K, V>>>,
+    free: Vec<usize>,
+    /// The most recently used slot.
+    head: usize,
+    /// The least recently used slot.
+    tail: usize,
+    capacity: usize,
+}
+
+/// A shard with its lock, on a cache line of its own.
+type PaddedShard<K, V> = CachePadded<Mutex<Shard<K, V>>>;
+
+impl<K: Hash + Eq + Clone, V> Shard<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            map: HashMap::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+        }
+    }
+
+    fn slot(&self, index: usize) -> &Slot<K, V> {
+        self.slots[index].as_ref().unwrap()
+    }
+
+    fn slot_mut(&mut self, index: usize) -> &mut Slot<K, V> {
+        self.slots[index].as_mut().unwrap()
+    }
+
+    /// Unlinks the slot from the list.
+    fn detach(&mut self, index: usize) {
+        let (prev, next) = {
+            let slot = self.slot(index);
+            (slot.prev, slot.next)
+        };
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.slot_mut(prev).next = next;
+        }
+        if next == NIL {
+            self.tail = prev;
+        } else {
+            self.slot_mut(next).prev = prev;
+        }
+    }
+
+    /// Links the slot at the front of the list.
+    fn attach_front(&mut self, index: usize) {
+        let head = self.head;
+        {
+            let slot = self.slot_mut(index);
+            slot.prev = NIL;
+            slot.next = head;
+        }
+        if head == NIL {
+            self.tail = index;
+        } else {
+            self.slot_mut(head).prev = index;
+        }
+        self.head = index;
+    }
+
+    /// Marks the slot as the most recently used one.
+    fn touch(&mut self, index: usize) {
+        if self.head != index {
+            self.detach(index);
+            self.attach_front(index);
+        }
+    }
+
+    /// Finds the entry for `key` and marks it as the most recently used one.
+    fn get<Q>(&mut self, key: &Q) -> Option<&Rc<Entry<V>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = *self.map.get(key)?;
+        self.touch(index);
+        Some(&self.slot(index).entry)
+    }
+
+    /// Inserts an entry, returning the replaced and evicted entries.
+    fn put(&mut self, key: K, entry: Rc<Entry<V>>, out: &mut Vec<Rc<Entry<V>>>) {
+        if let Some(&index) = self.map.get(&key) {
+            let old = std::mem::replace(&mut self.slot_mut(index).entry, entry);
+            out.push(old);
+            self.touch(index);
+            return;
+        }
+
+        while self.map.len() >= self.capacity {
+            match self.pop_back() {
+                Some(evicted) => out.push(evicted),
+                None => break,
+            }
+        }
+
+        let slot = Slot {
+            key: key.clone(),
+            entry,
+            prev: NIL,
+            next: NIL,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Some(slot);
+                index
+            }
+            None => {
+                self.slots.push(Some(slot));
+                self.slots.len() - 1
+            }
+        };
+        self.map.insert(key, index);
+        self.attach_front(index);
+    }
+
+    /// Removes the slot at `index`, returning its entry.
+    fn remove_at(&mut self, index: usize) -> Rc<Entry<V>> {
+        self.detach(index);
+        let slot = self.slots[index].take().unwrap();
+        self.map.remove(&slot.key);
+        self.free.push(index);
+        slot.entry
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<Rc<Entry<V>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = *self.map.get(key)?;
+        Some(self.remove_at(index))
+    }
+
+    /// Removes the least recently used entry.
+    fn pop_back(&mut self) -> Option<Rc<Entry<V>>> {
+        if self.tail == NIL {
+            return None;
+        }
+        Some(self.remove_at(self.tail))
+    }
+}
+
+/// A concurrent cache that evicts the least recently used entries when it is full.
+///
+/// The cache is divided into shards by the hashes of the keys, and the recency order is
+/// maintained in each shard. Therefore, the evicted entry is the least recently used one among the
+/// entries of the same shard.
+///
+/// Looking up an entry returns a reference that is valid during the critical section of the given
+/// guard. Even if the entry is evicted or replaced by another thread in the meantime, the value is
+/// not destructed until the critical section ends.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::LruCache};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let cache = LruCache::with_shards(2, 1);
+/// let guard = &cs();
+///
+/// cache.put(1, "one", guard);
+/// cache.put(2, "two", guard);
+/// assert_eq!(cache.get(&1, guard), Some(&"one"));
+///
+/// // Inserting a new entry evicts the least recently used one.
+/// cache.put(3, "three", guard);
+/// assert_eq!(cache.get(&2, guard), None);
+/// assert_eq!(cache.get(&1, guard), Some(&"one"));
+/// ```
+pub struct LruCache<K, V, S = RandomState> {
+    shards: Box<[PaddedShard<K, V>]>,
+    hasher: S,
+    capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// Creates a new cache that holds at most `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_shards(capacity, DEFAULT_SHARDS)
+    }
+
+    /// Creates a new cache that holds at most `capacity` entries, divided into at most `shards`
+    /// shards.
+    ///
+    /// The number of shards is clamped so that every shard can hold at least one entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `shards` is zero.
+    pub fn with_shards(capacity: usize, shards: usize) -> Self {
+        Self::with_shards_and_hasher(capacity, shards, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, S: BuildHasher> LruCache<K, V, S> {
+    /// Creates a new cache that holds at most `capacity` entries, divided into at most `shards`
+    /// shards, and uses the given hash builder to distribute the keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `shards` is zero.
+    pub fn with_shards_and_hasher(capacity: usize, shards: usize, hasher: S) -> Self {
+        assert!(capacity > 0, "the capacity of a cache must be positive");
+        assert!(shards > 0, "the number of shards must be positive");
+        let count = shards.min(capacity);
+        let shards = (0..count)
+            .map(|i| {
+                // Distribute the capacity so that the total is exactly `capacity`.
+                let cap = capacity / count + usize::from(i < capacity % count);
+                CachePadded::new(Mutex::new(Shard::new(cap)))
+            })
+            .collect();
+        Self {
+            shards,
+            hasher,
+            capacity,
+        }
+    }
+
+    /// Returns the maximum number of entries in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of entries in the cache.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).map.len()).sum()
+    }
+
+    /// Returns `true` if the cache contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> MutexGuard<'_, Shard<K, V>> {
+        let hash = self.hasher.hash_one(key) as usize;
+        lock(&self.shards[hash % self.shards.len()])
+    }
+
+    /// Returns a reference to the value for `key`, marking it as the most recently used one.
+    pub fn get<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let snapshot = self.shard(key).get(key)?.snapshot(guard);
+        // The entry may be evicted as soon as the lock is released, but its destruction is
+        // deferred until the critical section of `guard` ends.
+        snapshot.as_ref().map(|entry| &entry.value)
+    }
+
+    /// Returns `true` if the cache contains `key`, without updating the recency order.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).map.contains_key(key)
+    }
+
+    /// Inserts a value for `key`, marking it as the most recently used one.
+    ///
+    /// If the shard of `key` is full, the least recently used entry of the shard is evicted.
+    pub fn put(&self, key: K, value: V, guard: &Guard) {
+        let mut out = Vec::new();
+        self.shard(&key)
+            .put(key, Rc::new(Entry { value }), &mut out);
+        for entry in out {
+            entry.finalize(guard);
+        }
+    }
+
+    /// Removes the entry for `key`, returning a reference to the removed value.
+    pub fn remove<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let entry = self.shard(key).remove(key)?;
+        let snapshot = entry.snapshot(guard);
+        entry.finalize(guard);
+        snapshot.as_ref().map(|entry| &entry.value)
+    }
+
+    /// Evicts the least recently used entry of each shard, returning the number of evicted
+    /// entries.
+    pub fn evict(&self, guard: &Guard) -> usize {
+        let mut count = 0;
+        for shard in self.shards.iter() {
+            let evicted = lock(shard).pop_back();
+            if let Some(entry) = evicted {
+                entry.finalize(guard);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&self, guard: &Guard) {
+        for shard in self.shards.iter() {
+            let entries: Vec<_> = {
+                let mut shard = lock(shard);
+                std::iter::from_fn(|| shard.pop_back()).collect()
+            };
+            for entry in entries {
+                entry.finalize(guard);
+            }
+        }
+    }
+}
+
+impl<K: HeapSize, V: HeapSize, S> HeapSize for LruCache<K, V, S> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let shards: usize = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let shard = lock(shard);
+                shard.map.heap_size(visited)
+                    + shard.slots.heap_size(visited)
+                    + shard.free.heap_size(visited)
+            })
+            .sum();
+        size_of_val(&*self.shards) + shards
+    }
+}
+
+/// Locks the shard, ignoring poisoning since the shards are always left in a consistent state.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,25 @@
+//! Concurrent data structures built on top of CIRC.
+//!
+//! The structures in this module manage their nodes with [`Rc`](crate::Rc) and
+//! [`AtomicRc`](crate::AtomicRc), so that removed elements are reclaimed safely even when other
+//! threads are still reading them inside their critical sections.
+
+mod append_log;
+mod array_queue;
+mod bst;
+mod deque;
+mod lru;
+mod persistent_map;
+mod priority_queue;
+mod stack;
+mod trie;
+
+pub use append_log::{AppendLog, Iter as AppendLogIter};
+pub use array_queue::ArrayQueue;
+pub use bst::{BstMap, Range as BstRange};
+pub use deque::{Steal, Stealer, Worker};
+pub use lru::LruCache;
+pub use persistent_map::{Iter as PersistentMapIter, MapSnapshot, PersistentMap};
+pub use priority_queue::PriorityQueue;
+pub use stack::{EliminationStack, Stack};
+pub use trie::{Iter as TrieIter, Trie};
@@ -0,0 +1,463 @@
+//! A persistent balanced tree map with cheap consistent snapshots.
+//!
+//! The map is an immutable AVL tree whose nodes are shared through [`Rc`]s. An update never
+//! modifies an existing node. Instead, it copies the path from the root to the updated node and
+//! installs the new root into an [`AtomicRc`] with a compare-and-swap. A snapshot is just a
+//! counted reference to a root, so taking it is wait-free and the snapshot stays consistent no
+//! matter how the map is updated afterwards.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering::{Equal, Greater, Less};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::Ordering;
+
+use crate::heap_size::{HeapSize, Visited};
+use crate::{cs, AtomicRc, Guard, Rc, RcObject};
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    height: u8,
+    size: usize,
+    left: Rc<Node<K, V>>,
+    right: Rc<Node<K, V>>,
+}
+
+unsafe impl<K, V> RcObject for Node<K, V> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(std::mem::take(&mut self.left));
+        out.push(std::mem::take(&mut self.right));
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for Node<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        // The recursion is bounded by the height of the tree, which is logarithmic.
+        self.key.heap_size(visited)
+            + self.value.heap_size(visited)
+            + self.left.heap_size(visited)
+            + self.right.heap_size(visited)
+    }
+}
+
+fn height<K, V>(node: &Rc<Node<K, V>>) -> u8 {
+    node.as_ref().map_or(0, |n| n.height)
+}
+
+fn size<K, V>(node: &Rc<Node<K, V>>) -> usize {
+    node.as_ref().map_or(0, |n| n.size)
+}
+
+/// Creates a new node without rebalancing.
+fn make<K, V>(key: K, value: V, left: Rc<Node<K, V>>, right: Rc<Node<K, V>>) -> Rc<Node<K, V>> {
+    Rc::new(Node {
+        height: height(&left).max(height(&right)) + 1,
+        size: size(&left) + size(&right) + 1,
+        key,
+        value,
+        left,
+        right,
+    })
+}
+
+/// Creates a new node, rotating the subtrees if their heights differ by more than one.
+fn balance<K: Clone, V: Clone>(
+    key: K,
+    value: V,
+    left: Rc<Node<K, V>>,
+    right: Rc<Node<K, V>>,
+) -> Rc<Node<K, V>> {
+    let (hl, hr) = (height(&left), height(&right));
+    if hl > hr + 1 {
+        let l = left.as_ref().unwrap();
+        if height(&l.left) >= height(&l.right) {
+            make(
+                l.key.clone(),
+                l.value.clone(),
+                l.left.clone(),
+                make(key, value, l.right.clone(), right),
+            )
+        } else {
+            let lr = l.right.as_ref().unwrap();
+            make(
+                lr.key.clone(),
+                lr.value.clone(),
+                make(
+                    l.key.clone(),
+                    l.value.clone(),
+                    l.left.clone(),
+                    lr.left.clone(),
+                ),
+                make(key, value, lr.right.clone(), right),
+            )
+        }
+    } else if hr > hl + 1 {
+        let r = right.as_ref().unwrap();
+        if height(&r.right) >= height(&r.left) {
+            make(
+                r.key.clone(),
+                r.value.clone(),
+                make(key, value, left, r.left.clone()),
+                r.right.clone(),
+            )
+        } else {
+            let rl = r.left.as_ref().unwrap();
+            make(
+                rl.key.clone(),
+                rl.value.clone(),
+                make(key, value, left, rl.left.clone()),
+                make(
+                    r.key.clone(),
+                    r.value.clone(),
+                    rl.right.clone(),
+                    r.right.clone(),
+                ),
+            )
+        }
+    } else {
+        make(key, value, left, right)
+    }
+}
+
+/// Returns a new tree with `key` mapped to `value`.
+fn insert<K: Ord + Clone, V: Clone>(node: &Rc<Node<K, V>>, key: K, value: V) -> Rc<Node<K, V>> {
+    let Some(n) = node.as_ref() else {
+        return make(key, value, Rc::null(), Rc::null());
+    };
+    match key.cmp(&n.key) {
+        Less => balance(
+            n.key.clone(),
+            n.value.clone(),
+            insert(&n.left, key, value),
+            n.right.clone(),
+        ),
+        Greater => balance(
+            n.key.clone(),
+            n.value.clone(),
+            n.left.clone(),
+            insert(&n.right, key, value),
+        ),
+        Equal => make(key, value, n.left.clone(), n.right.clone()),
+    }
+}
+
+/// Returns a new tree without `key`, or `None` if `key` is not in the tree.
+fn remove<K, V, Q>(node: &Rc<Node<K, V>>, key: &Q) -> Option<Rc<Node<K, V>>>
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    Q: Ord + ?Sized,
+{
+    let n = node.as_ref()?;
+    match key.cmp(n.key.borrow()) {
+        Less => {
+            let left = remove(&n.left, key)?;
+            Some(balance(
+                n.key.clone(),
+                n.value.clone(),
+                left,
+                n.right.clone(),
+            ))
+        }
+        Greater => {
+            let right = remove(&n.right, key)?;
+            Some(balance(
+                n.key.clone(),
+                n.value.clone(),
+                n.left.clone(),
+                right,
+            ))
+        }
+        Equal => Some(if n.left.is_null() {
+            n.right.clone()
+        } else if n.right.is_null() {
+            n.left.clone()
+        } else {
+            let (key, value, right) = remove_min(&n.right);
+            balance(key, value, n.left.clone(), right)
+        }),
+    }
+}
+
+/// Returns the minimum entry and a new tree without it.
+fn remove_min<K: Clone, V: Clone>(node: &Rc<Node<K, V>>) -> (K, V, Rc<Node<K, V>>) {
+    let n = node.as_ref().unwrap();
+    if n.left.is_null() {
+        (n.key.clone(), n.value.clone(), n.right.clone())
+    } else {
+        let (key, value, left) = remove_min(&n.left);
+        (
+            key,
+            value,
+            balance(n.key.clone(), n.value.clone(), left, n.right.clone()),
+        )
+    }
+}
+
+fn get<'a, K, V, Q>(mut node: &'a Rc<Node<K, V>>, key: &Q) -> Option<&'a V>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    while let Some(n) = node.as_ref() {
+        match key.cmp(n.key.borrow()) {
+            Less => node = &n.left,
+            Greater => node = &n.right,
+            Equal => return Some(&n.value),
+        }
+    }
+    None
+}
+
+/// A concurrent ordered map that supports wait-free consistent snapshots.
+///
+/// Every update copies the path from the root to the updated entry, so it costs `O(log n)`
+/// allocations and requires `K: Clone` and `V: Clone`. Unchanged subtrees are shared between
+/// versions. This trade-off fits read-mostly tables such as configurations and routing tables.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::PersistentMap};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let map = PersistentMap::new();
+/// let guard = &cs();
+/// map.insert(1, "one", guard);
+///
+/// let snapshot = map.snapshot();
+/// map.insert(2, "two", guard);
+///
+/// // The snapshot is not affected by the later insertion.
+/// assert_eq!(snapshot.len(), 1);
+/// assert_eq!(snapshot.get(&2), None);
+/// assert_eq!(map.get(&2, guard), Some(&"two"));
+/// ```
+pub struct PersistentMap<K, V> {
+    root: AtomicRc<Node<K, V>>,
+}
+
+impl<K, V> PersistentMap<K, V> {
+    /// Creates a new empty map.
+    pub fn new() -> Self {
+        Self {
+            root: AtomicRc::null(),
+        }
+    }
+
+    /// Takes a consistent snapshot of the map.
+    ///
+    /// This is wait-free and does not copy any entries.
+    pub fn snapshot(&self) -> MapSnapshot<K, V> {
+        MapSnapshot {
+            root: self.root.load(Ordering::Acquire, &cs()).counted(),
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        size(&self.snapshot().root)
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reference to the value for `key`.
+    pub fn get<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q> + 'g,
+        V: 'g,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self.root.load(Ordering::Acquire, guard).as_ref();
+        while let Some(n) = node {
+            match key.cmp(n.key.borrow()) {
+                Less => node = n.left.as_ref(),
+                Greater => node = n.right.as_ref(),
+                Equal => return Some(&n.value),
+            }
+        }
+        None
+    }
+
+    /// Returns `true` if the map contains `key`.
+    pub fn contains_key<Q>(&self, key: &Q, guard: &Guard) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key, guard).is_some()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentMap<K, V> {
+    /// Installs a new version of the tree computed by `f`, retrying until no other writer
+    /// interferes. If `f` returns `None`, the map is left unchanged.
+    fn update<F>(&self, mut f: F, guard: &Guard) -> bool
+    where
+        F: FnMut(&Rc<Node<K, V>>) -> Option<Rc<Node<K, V>>>,
+    {
+        let mut current = self.root.load(Ordering::Acquire, guard);
+        loop {
+            // Nodes are immutable, so a counted reference to the current root is enough to
+            // build the next version outside of any lock.
+            let root = current.counted();
+            let Some(new) = f(&root) else {
+                root.finalize(guard);
+                return false;
+            };
+            root.finalize(guard);
+            match self.root.compare_exchange(
+                current,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(old) => {
+                    old.finalize(guard);
+                    return true;
+                }
+                Err(e) => {
+                    e.desired.finalize(guard);
+                    current = e.current;
+                }
+            }
+        }
+    }
+
+    /// Inserts a value for `key`, replacing the previous one if any.
+    pub fn insert(&self, key: K, value: V, guard: &Guard) {
+        self.update(|root| Some(insert(root, key.clone(), value.clone())), guard);
+    }
+
+    /// Removes the entry for `key`, returning `true` if it was in the map.
+    pub fn remove<Q>(&self, key: &Q, guard: &Guard) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.update(|root| remove(root, key), guard)
+    }
+}
+
+impl<K, V> Default for PersistentMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for PersistentMap<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.root.heap_size(visited)
+    }
+}
+
+impl<K, V> Debug for PersistentMap<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("PersistentMap { .. }")
+    }
+}
+
+/// A consistent, immutable view of a [`PersistentMap`] at some point in time.
+///
+/// Unlike a [`Snapshot`](crate::Snapshot), it owns a reference count, so it can be kept outside
+/// of a critical section and sent to other threads.
+pub struct MapSnapshot<K, V> {
+    root: Rc<Node<K, V>>,
+}
+
+impl<K, V> MapSnapshot<K, V> {
+    /// Returns the number of entries in the snapshot.
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    /// Returns `true` if the snapshot contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.root.is_null()
+    }
+
+    /// Returns a reference to the value for `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        get(&self.root, key)
+    }
+
+    /// Returns `true` if the snapshot contains `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns an iterator over the entries in ascending order of the keys.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::with_capacity(height(&self.root) as usize),
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+impl<K, V> Clone for MapSnapshot<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for MapSnapshot<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.root.heap_size(visited)
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for MapSnapshot<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a MapSnapshot<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`MapSnapshot`].
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut node: &'a Rc<Node<K, V>>) {
+        while let Some(n) = node.as_ref() {
+            self.stack.push(n);
+            node = &n.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        Some((&node.key, &node.value))
+    }
+}
@@ -0,0 +1,315 @@
+//! A lock-free concurrent priority queue.
+//!
+//! This is a lock-free skiplist in the style of Fraser and of Herlihy and Shavit, used as a
+//! priority queue as proposed by Lotan and Shavit. Entries are ordered by their priority and then
+//! by a sequence number taken on insertion, so that every key is unique and entries of the same
+//! priority are popped in insertion order.
+//!
+//! An entry is deleted by setting the low tag bit of its `next` pointers, from the top level down
+//! to the bottom level. Setting the mark on the bottom level is the linearization point of the
+//! deletion and decides which thread has popped the entry. Marked nodes are physically unlinked
+//! by the following searches.
+
+use std::fmt::{Debug, Formatter};
+use std::mem::size_of_val;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::heap_size::{rc_allocation, HeapSize, Visited};
+use crate::{cs, AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// The maximum height of a tower.
+const MAX_HEIGHT: usize = 16;
+
+struct Entry<K, V> {
+    priority: K,
+    seq: u64,
+    value: V,
+}
+
+struct Node<K, V> {
+    /// The entry of the node, or `None` for the head.
+    entry: Option<Entry<K, V>>,
+    next: Box<[AtomicRc<Node<K, V>>]>,
+}
+
+unsafe impl<K, V> RcObject for Node<K, V> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.extend(self.next.iter_mut().map(AtomicRc::take));
+    }
+}
+
+impl<K: Ord, V> Node<K, V> {
+    fn new(entry: Option<Entry<K, V>>, height: usize) -> Self {
+        Self {
+            entry,
+            next: (0..height).map(|_| AtomicRc::null()).collect(),
+        }
+    }
+
+    /// Returns `true` if the entry of the node precedes the given key.
+    fn precedes(&self, priority: &K, seq: u64) -> bool {
+        let entry = self.entry.as_ref().unwrap();
+        (&entry.priority, entry.seq) < (priority, seq)
+    }
+}
+
+/// The nodes at each level of the skiplist.
+type Tower<'g, K, V> = [Snapshot<'g, Node<K, V>>; MAX_HEIGHT];
+
+/// Returns a pseudo-random tower height for the node with the sequence number `seq`.
+///
+/// The height is `h` with probability `2^-h`, which is obtained by mixing the bits of `seq` with
+/// the finalizer of SplitMix64.
+fn height(seq: u64) -> usize {
+    let mut z = seq.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z.trailing_ones() as usize + 1).min(MAX_HEIGHT)
+}
+
+/// A lock-free concurrent priority queue.
+///
+/// Entries with smaller priorities are popped first, and entries with the same priority are popped
+/// in the order they were pushed. Popped entries stay valid until the end of the critical section
+/// of the thread that popped them, and are reclaimed afterwards.
+///
+/// The minimum is determined by scanning the bottom level of the skiplist, so an entry pushed
+/// concurrently with a [`PriorityQueue::pop_min`] may be overtaken by an entry with a larger
+/// priority. Operations that do not overlap are ordered strictly.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::PriorityQueue};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let queue = PriorityQueue::new();
+/// let guard = &cs();
+/// queue.push(3, "c", guard);
+/// queue.push(1, "a", guard);
+/// queue.push(2, "b", guard);
+///
+/// assert_eq!(queue.pop_min(guard), Some((&1, &"a")));
+/// assert_eq!(queue.pop_min(guard), Some((&2, &"b")));
+/// assert_eq!(queue.pop_min(guard), Some((&3, &"c")));
+/// assert_eq!(queue.pop_min(guard), None);
+/// ```
+pub struct PriorityQueue<K, V> {
+    head: Rc<Node<K, V>>,
+    /// The sequence number of the next entry.
+    seq: AtomicU64,
+}
+
+impl<K: Ord, V> PriorityQueue<K, V> {
+    /// Creates a new empty queue.
+    pub fn new() -> Self {
+        Self {
+            head: Rc::new(Node::new(None, MAX_HEIGHT)),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Finds the predecessors and the successors of the given key at each level, unlinking the
+    /// deleted nodes on the way.
+    fn find<'g>(
+        &self,
+        priority: &K,
+        seq: u64,
+        guard: &'g Guard,
+    ) -> (Tower<'g, K, V>, Tower<'g, K, V>) {
+        'retry: loop {
+            let mut preds = [Snapshot::null(); MAX_HEIGHT];
+            let mut succs = [Snapshot::null(); MAX_HEIGHT];
+            let mut pred = self.head.snapshot(guard);
+            for level in (0..MAX_HEIGHT).rev() {
+                let mut curr = pred.as_ref().unwrap().next[level].load(Ordering::Acquire, guard);
+                if curr.is_marked() {
+                    continue 'retry;
+                }
+                while let Some(node) = curr.as_ref() {
+                    let succ = node.next[level].load(Ordering::Acquire, guard);
+                    if succ.is_marked() {
+                        // `curr` is deleted. Unlink it at this level.
+                        let next = &pred.as_ref().unwrap().next[level];
+                        match next.compare_exchange(
+                            curr,
+                            succ.with_tag(0).counted(),
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                            guard,
+                        ) {
+                            Ok(_) => curr = succ.with_tag(0),
+                            Err(e) => {
+                                e.desired.finalize(guard);
+                                continue 'retry;
+                            }
+                        }
+                    } else if node.precedes(priority, seq) {
+                        pred = curr;
+                        curr = succ;
+                    } else {
+                        break;
+                    }
+                }
+                preds[level] = pred;
+                succs[level] = curr;
+            }
+            return (preds, succs);
+        }
+    }
+
+    /// Pushes `value` with `priority` into the queue.
+    pub fn push(&self, priority: K, value: V, guard: &Guard) {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let height = height(seq);
+        let entry = Entry {
+            priority,
+            seq,
+            value,
+        };
+        let node = Rc::new(Node::new(Some(entry), height));
+        let node_ref = node.as_ref().unwrap();
+        let priority = &node_ref.entry.as_ref().unwrap().priority;
+
+        // Link the node at the bottom level, which makes it visible.
+        let (mut preds, mut succs) = loop {
+            let (preds, succs) = self.find(priority, seq, guard);
+            for (next, succ) in node_ref.next.iter().zip(&succs).take(height) {
+                next.store(succ.counted(), Ordering::Relaxed, guard);
+            }
+            match preds[0].as_ref().unwrap().next[0].compare_exchange(
+                succs[0],
+                node.clone(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => break (preds, succs),
+                Err(e) => e.desired.finalize(guard),
+            }
+        };
+
+        // Link the node at the upper levels, unless it is popped in the meantime.
+        for level in 1..height {
+            loop {
+                let next = &node_ref.next[level];
+                let current = next.load(Ordering::Acquire, guard);
+                if current.is_marked() {
+                    return;
+                }
+                if !current.ptr_eq(succs[level]) {
+                    // Only a deletion can interfere, since the node is not linked at this level.
+                    if let Err(e) = next.compare_exchange(
+                        current,
+                        succs[level].counted(),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        guard,
+                    ) {
+                        e.desired.finalize(guard);
+                        return;
+                    }
+                }
+                match preds[level].as_ref().unwrap().next[level].compare_exchange(
+                    succs[level],
+                    node.clone(),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                ) {
+                    Ok(_) => break,
+                    Err(e) => {
+                        e.desired.finalize(guard);
+                        (preds, succs) = self.find(priority, seq, guard);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pops the entry with the smallest priority.
+    ///
+    /// The returned references stay valid until `guard` is dropped.
+    pub fn pop_min<'g>(&self, guard: &'g Guard) -> Option<(&'g K, &'g V)> {
+        let mut curr = self.head.as_ref().unwrap().next[0].load(Ordering::Acquire, guard);
+        while let Some(node) = curr.as_ref() {
+            let succ = node.next[0].load(Ordering::Acquire, guard);
+            if succ.is_marked() {
+                curr = succ.with_tag(0);
+                continue;
+            }
+
+            // Mark the upper levels first, so that a concurrent push does not link the node at a
+            // new level after it has been popped.
+            for next in node.next[1..].iter().rev() {
+                mark(next, guard);
+            }
+            if mark(&node.next[0], guard) {
+                let entry = node.entry.as_ref().unwrap();
+                self.find(&entry.priority, entry.seq, guard);
+                return Some((&entry.priority, &entry.value));
+            }
+            // Another thread has popped the node.
+            curr = node.next[0].load(Ordering::Acquire, guard).with_tag(0);
+        }
+        None
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        let mut curr = self.head.as_ref().unwrap().next[0].load(Ordering::Acquire, guard);
+        while let Some(node) = curr.as_ref() {
+            let succ = node.next[0].load(Ordering::Acquire, guard);
+            if !succ.is_marked() {
+                return false;
+            }
+            curr = succ.with_tag(0);
+        }
+        true
+    }
+}
+
+/// Marks `next` as deleted, returning `true` if it was marked by this call.
+fn mark<K, V>(next: &AtomicRc<Node<K, V>>, guard: &Guard) -> bool {
+    let mut current = next.load(Ordering::Acquire, guard);
+    while !current.is_marked() {
+        match next.try_mark(current, guard) {
+            Ok(_) => return true,
+            Err(actual) => current = actual,
+        }
+    }
+    false
+}
+
+impl<K: Ord, V> Default for PriorityQueue<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for PriorityQueue<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        // Every node, including a deleted one that is not unlinked yet, is on the bottom level.
+        // Walking it in a loop avoids recursing once per node.
+        let guard = &cs();
+        let mut bytes = 0;
+        let mut node = self.head.snapshot(guard);
+        while let Some(allocation) = rc_allocation(node.ptr, visited) {
+            let current = node.as_ref().unwrap();
+            bytes += allocation + size_of_val(&*current.next);
+            if let Some(entry) = &current.entry {
+                bytes += entry.priority.heap_size(visited) + entry.value.heap_size(visited);
+            }
+            node = current.next[0].load(Ordering::Acquire, guard);
+        }
+        bytes
+    }
+}
+
+impl<K, V> Debug for PriorityQueue<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("PriorityQueue { .. }")
+    }
+}
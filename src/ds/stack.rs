@@ -0,0 +1,360 @@
+//! Lock-free stacks.
+//!
+//! [`Stack`] is Treiber's stack. [`EliminationStack`] augments it with an elimination array
+//! (Hendler, Shavit, and Yerushalmi, "A scalable lock-free stack algorithm", SPAA 2004): an
+//! operation that fails to update the top of the stack due to contention tries to meet an
+//! operation of the opposite kind at an exchange slot instead. A pusher offers its node in a slot
+//! and waits for a while, and a popper that finds the node takes it. The pair cancels out without
+//! touching the top of the stack.
+
+use std::fmt::{Debug, Formatter};
+use std::mem::size_of_val;
+use std::sync::atomic::Ordering;
+
+use crossbeam_utils::{Backoff, CachePadded};
+
+use crate::heap_size::{rc_allocation, HeapSize, Visited};
+use crate::{cs, AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// The default number of exchange slots of an elimination stack.
+const DEFAULT_SLOTS: usize = 8;
+
+/// The number of times a pusher checks whether its offer has been taken before withdrawing it.
+const EXCHANGE_SPINS: usize = 32;
+
+struct Node<T: RcObject> {
+    item: Rc<T>,
+    next: AtomicRc<Node<T>>,
+}
+
+unsafe impl<T: RcObject> RcObject for Node<T> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.next.take());
+    }
+}
+
+impl<T: RcObject> Node<T> {
+    /// Returns the heap bytes retained by the nodes from `node` to the bottom of the stack.
+    ///
+    /// The nodes are walked in a loop rather than recursively, so that a long stack does not
+    /// overflow the call stack.
+    fn chain_size<'g>(
+        mut node: Snapshot<'g, Self>,
+        visited: &mut Visited,
+        guard: &'g Guard,
+    ) -> usize
+    where
+        T: HeapSize,
+    {
+        let mut bytes = 0;
+        while let Some(allocation) = rc_allocation(node.ptr, visited) {
+            let current = node.as_ref().unwrap();
+            bytes += allocation + current.item.heap_size(visited);
+            node = current.next.load(Ordering::Acquire, guard);
+        }
+        bytes
+    }
+
+    fn new(item: Rc<T>) -> Rc<Self> {
+        Rc::new(Self {
+            item,
+            next: AtomicRc::null(),
+        })
+    }
+}
+
+/// Treiber's lock-free stack of [`Rc`] pointers.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::Stack, RcObject, Rc};
+///
+/// struct Item(usize);
+///
+/// unsafe impl RcObject for Item {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let stack = Stack::new();
+/// let guard = &cs();
+/// stack.push(Rc::new(Item(1)), guard);
+/// stack.push(Rc::new(Item(2)), guard);
+///
+/// assert_eq!(stack.pop(guard).unwrap().as_ref().unwrap().0, 2);
+/// assert_eq!(stack.pop(guard).unwrap().as_ref().unwrap().0, 1);
+/// assert!(stack.pop(guard).is_none());
+/// ```
+pub struct Stack<T: RcObject> {
+    head: AtomicRc<Node<T>>,
+}
+
+impl<T: RcObject> Stack<T> {
+    /// Creates a new empty stack.
+    pub fn new() -> Self {
+        Self {
+            head: AtomicRc::null(),
+        }
+    }
+
+    /// Attempts to push `node` with a single compare-and-swap, giving it back on failure.
+    fn try_push(&self, node: Rc<Node<T>>, guard: &Guard) -> Result<(), Rc<Node<T>>> {
+        let head = self.head.load(Ordering::Relaxed, guard);
+        // `node` is not shared yet, so its link can be overwritten freely.
+        node.as_ref()
+            .unwrap()
+            .next
+            .store(head.counted(), Ordering::Relaxed, guard);
+        self.head
+            .compare_exchange(head, node, Ordering::Release, Ordering::Relaxed, guard)
+            .map(|_| ())
+            .map_err(|e| e.desired)
+    }
+
+    /// Attempts to pop the top of the stack with a single compare-and-swap.
+    ///
+    /// Returns `Err(())` if the compare-and-swap has failed.
+    fn try_pop(&self, guard: &Guard) -> Result<Option<Rc<T>>, ()> {
+        let head = self.head.load(Ordering::Acquire, guard);
+        let Some(node) = head.as_ref() else {
+            return Ok(None);
+        };
+        let next = node.next.load(Ordering::Relaxed, guard);
+        match self.head.compare_exchange(
+            head,
+            next.counted(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            Ok(_) => Ok(Some(node.item.clone())),
+            Err(e) => {
+                e.desired.finalize(guard);
+                Err(())
+            }
+        }
+    }
+
+    /// Pushes `item` onto the top of the stack.
+    pub fn push(&self, item: Rc<T>, guard: &Guard) {
+        let backoff = Backoff::new();
+        let mut node = Node::new(item);
+        while let Err(rejected) = self.try_push(node, guard) {
+            node = rejected;
+            backoff.spin();
+        }
+    }
+
+    /// Pops the top of the stack, or returns `None` if the stack is empty.
+    pub fn pop(&self, guard: &Guard) -> Option<Rc<T>> {
+        let backoff = Backoff::new();
+        loop {
+            if let Ok(item) = self.try_pop(guard) {
+                return item;
+            }
+            backoff.spin();
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        self.head.load(Ordering::Acquire, guard).is_null()
+    }
+}
+
+impl<T: RcObject> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for Stack<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let guard = &cs();
+        Node::chain_size(self.head.load(Ordering::Acquire, guard), visited, guard)
+    }
+}
+
+impl<T: RcObject> Debug for Stack<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Stack { .. }")
+    }
+}
+
+/// A lock-free stack with an elimination array, which scales under heavy contention.
+///
+/// It behaves the same as [`Stack`]. When many threads push and pop at the same time, most of
+/// the operations are paired up at the exchange slots instead of contending on the top of the
+/// stack.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::EliminationStack, RcObject, Rc};
+///
+/// struct Item(usize);
+///
+/// unsafe impl RcObject for Item {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let stack = EliminationStack::with_slots(4);
+/// let guard = &cs();
+/// stack.push(Rc::new(Item(1)), guard);
+/// assert_eq!(stack.pop(guard).unwrap().as_ref().unwrap().0, 1);
+/// assert!(stack.pop(guard).is_none());
+/// ```
+pub struct EliminationStack<T: RcObject> {
+    stack: Stack<T>,
+    /// The exchange slots, each holding a node offered by a pusher or null.
+    slots: Box<[CachePadded<AtomicRc<Node<T>>>]>,
+}
+
+impl<T: RcObject> EliminationStack<T> {
+    /// Creates a new empty stack with the default number of exchange slots.
+    pub fn new() -> Self {
+        Self::with_slots(DEFAULT_SLOTS)
+    }
+
+    /// Creates a new empty stack with `slots` exchange slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slots` is zero.
+    pub fn with_slots(slots: usize) -> Self {
+        assert!(slots > 0, "the number of exchange slots must be positive");
+        Self {
+            stack: Stack::new(),
+            slots: (0..slots)
+                .map(|_| CachePadded::new(AtomicRc::null()))
+                .collect(),
+        }
+    }
+
+    /// Picks the exchange slot for the `attempt`-th retry of the current thread.
+    fn slot(&self, attempt: usize, guard: &Guard) -> &AtomicRc<Node<T>> {
+        // Spread the threads over the slots by the addresses of their participants.
+        let hash = (guard.local as usize >> 6).wrapping_mul(0x9e37_79b9);
+        &self.slots[hash.wrapping_add(attempt) % self.slots.len()]
+    }
+
+    /// Offers `node` to a popper, giving it back if no popper has taken it in time.
+    fn exchange_push(
+        &self,
+        node: Rc<Node<T>>,
+        attempt: usize,
+        guard: &Guard,
+    ) -> Result<(), Rc<Node<T>>> {
+        let slot = self.slot(attempt, guard);
+        let offered = node.snapshot(guard);
+        slot.compare_exchange(
+            Snapshot::null(),
+            node,
+            Ordering::Release,
+            Ordering::Relaxed,
+            guard,
+        )
+        .map_err(|e| e.desired)?;
+
+        let backoff = Backoff::new();
+        for _ in 0..EXCHANGE_SPINS {
+            if !slot.load(Ordering::Acquire, guard).ptr_eq(offered) {
+                return Ok(());
+            }
+            backoff.spin();
+        }
+        // `offered` is protected by `guard`, so it cannot be offered again by another pusher.
+        match slot.compare_exchange(
+            offered,
+            Rc::null(),
+            Ordering::Acquire,
+            Ordering::Acquire,
+            guard,
+        ) {
+            Ok(node) => Err(node),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Takes a node offered by a pusher, if any.
+    fn exchange_pop(&self, attempt: usize, guard: &Guard) -> Option<Rc<T>> {
+        let slot = self.slot(attempt, guard);
+        let offered = slot.load(Ordering::Acquire, guard);
+        if offered.is_null() {
+            return None;
+        }
+        let node = slot
+            .compare_exchange(
+                offered,
+                Rc::null(),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+                guard,
+            )
+            .ok()?;
+        Some(node.as_ref().unwrap().item.clone())
+    }
+
+    /// Pushes `item` onto the top of the stack.
+    pub fn push(&self, item: Rc<T>, guard: &Guard) {
+        let mut node = Node::new(item);
+        for attempt in 0.. {
+            node = match self.stack.try_push(node, guard) {
+                Ok(()) => return,
+                Err(node) => node,
+            };
+            node = match self.exchange_push(node, attempt, guard) {
+                Ok(()) => return,
+                Err(node) => node,
+            };
+        }
+    }
+
+    /// Pops the top of the stack, or returns `None` if the stack is empty.
+    pub fn pop(&self, guard: &Guard) -> Option<Rc<T>> {
+        for attempt in 0.. {
+            if let Ok(item) = self.stack.try_pop(guard) {
+                return item;
+            }
+            if let Some(item) = self.exchange_pop(attempt, guard) {
+                return Some(item);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        self.stack.is_empty(guard)
+    }
+}
+
+impl<T: RcObject> Default for EliminationStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for EliminationStack<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let guard = &cs();
+        let offers: usize = self
+            .slots
+            .iter()
+            .map(|slot| Node::chain_size(slot.load(Ordering::Acquire, guard), visited, guard))
+            .sum();
+        self.stack.heap_size(visited) + size_of_val(&*self.slots) + offers
+    }
+}
+
+impl<T: RcObject> Debug for EliminationStack<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EliminationStack")
+            .field("slots", &self.slots.len())
+            .finish_non_exhaustive()
+    }
+}
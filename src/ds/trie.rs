@@ -0,0 +1,384 @@
+//! A concurrent ordered map keyed by byte strings.
+//!
+//! The map is a trie with a fan-out of 16 which consumes one nibble of the key per level, similar
+//! to a qp-trie. Each inner node has an [`AtomicRc`] slot per nibble and an extra slot holding the
+//! leaf of the key that ends at the node. Since the children of a node are ordered by nibble and
+//! the leaf comes first, a depth-first traversal visits the keys in lexicographic order.
+//!
+//! Writers update a single slot with a compare-and-swap. Removing the last entry below an inner
+//! node unlinks the node: the remover first *freezes* the node by setting the low tag bit of every
+//! slot, so that no writer can modify it anymore, and then replaces the node in its parent with
+//! null (or with an unfrozen copy if an entry was inserted before the node was frozen). A writer
+//! that runs into a frozen slot helps to replace the node and restarts from the root. Readers
+//! ignore the tag bits, because the contents of a frozen node are still valid.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::Ordering;
+
+use crate::heap_size::{HeapSize, Visited};
+use crate::{AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// The number of children of an inner node.
+const FANOUT: usize = 16;
+
+/// The index of the slot holding the leaf of the key that ends at an inner node.
+const LEAF: usize = FANOUT;
+
+/// The number of slots of an inner node.
+const SLOTS: usize = FANOUT + 1;
+
+/// The tag of a slot in a node that is being unlinked.
+const FROZEN: usize = 1;
+
+enum Node<V> {
+    Inner([AtomicRc<Node<V>>; SLOTS]),
+    Leaf { key: Box<[u8]>, value: V },
+}
+
+unsafe impl<V> RcObject for Node<V> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        if let Node::Inner(slots) = self {
+            out.extend(slots.iter_mut().map(AtomicRc::take));
+        }
+    }
+}
+
+impl<V: HeapSize> HeapSize for Node<V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        // The recursion is bounded by the number of nibbles of the longest key.
+        match self {
+            Node::Inner(slots) => slots.heap_size(visited),
+            Node::Leaf { key, value } => key.len() + value.heap_size(visited),
+        }
+    }
+}
+
+impl<V> Node<V> {
+    fn inner() -> Self {
+        Node::Inner(Default::default())
+    }
+
+    fn slots(&self) -> &[AtomicRc<Node<V>>; SLOTS] {
+        match self {
+            Node::Inner(slots) => slots,
+            Node::Leaf { .. } => unreachable!("a leaf has no slots"),
+        }
+    }
+
+    fn value(&self) -> &V {
+        match self {
+            Node::Leaf { value, .. } => value,
+            Node::Inner(_) => unreachable!("an inner node has no value"),
+        }
+    }
+}
+
+/// A link to an inner node and the node itself, as seen while descending the trie.
+type Link<'g, V> = (&'g AtomicRc<Node<V>>, Snapshot<'g, Node<V>>);
+
+fn nibbles(key: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    key.iter()
+        .flat_map(|b| [(b >> 4) as usize, (b & 0xf) as usize])
+}
+
+/// Freezes the node and replaces it in its parent, returning `true` if it was removed from the
+/// trie by this call.
+fn unlink<V>((link, node): Link<'_, V>, guard: &Guard) -> bool {
+    let slots = node.as_ref().unwrap().slots();
+    for slot in slots {
+        let mut current = slot.load(Ordering::Acquire, guard);
+        while current.tag() != FROZEN {
+            match slot.compare_exchange_tag(
+                current,
+                FROZEN,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => break,
+                Err(e) => current = e.current,
+            }
+        }
+    }
+
+    // The node is immutable from now on.
+    let replacement = if slots
+        .iter()
+        .all(|slot| slot.load(Ordering::Acquire, guard).is_null())
+    {
+        Rc::null()
+    } else {
+        Rc::new(Node::Inner(std::array::from_fn(|i| {
+            AtomicRc::from(
+                slots[i]
+                    .load(Ordering::Acquire, guard)
+                    .with_tag(0)
+                    .counted(),
+            )
+        })))
+    };
+    let removed = replacement.is_null();
+    match link.compare_exchange(
+        node,
+        replacement,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        guard,
+    ) {
+        Ok(_) => removed,
+        Err(e) => {
+            e.desired.finalize(guard);
+            false
+        }
+    }
+}
+
+/// A concurrent ordered map from byte strings to values.
+///
+/// Lookups are wait-free and take `O(k)` steps for a key of `k` bytes, regardless of the number
+/// of entries. Entries can be iterated in lexicographic order of their keys, optionally restricted
+/// to the keys starting with a given prefix. Iterators are weakly consistent: they observe every
+/// entry that is present during the whole iteration and may or may not observe concurrent updates.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, ds::Trie};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let trie = Trie::new();
+/// let guard = &cs();
+/// trie.insert(b"apple", 1, guard);
+/// trie.insert(b"apricot", 2, guard);
+/// trie.insert(b"banana", 3, guard);
+///
+/// assert_eq!(trie.get(b"apple", guard), Some(&1));
+///
+/// let keys: Vec<_> = trie.prefix_iter(b"ap", guard).map(|(k, _)| k).collect();
+/// assert_eq!(keys, [&b"apple"[..], &b"apricot"[..]]);
+/// ```
+pub struct Trie<V> {
+    root: Rc<Node<V>>,
+}
+
+impl<V> Trie<V> {
+    /// Creates a new empty trie.
+    pub fn new() -> Self {
+        Self {
+            root: Rc::new(Node::inner()),
+        }
+    }
+
+    /// Returns the inner node at the end of the path of `key`, if any.
+    fn find<'g>(&self, key: &[u8], guard: &'g Guard) -> Option<&'g Node<V>> {
+        let mut node = self.root.snapshot(guard).as_ref().unwrap();
+        for nibble in nibbles(key) {
+            node = node.slots()[nibble]
+                .load(Ordering::Acquire, guard)
+                .as_ref()?;
+        }
+        Some(node)
+    }
+
+    /// Returns a reference to the value for `key`.
+    pub fn get<'g>(&self, key: &[u8], guard: &'g Guard) -> Option<&'g V> {
+        let node = self.find(key, guard)?;
+        let leaf = node.slots()[LEAF].load(Ordering::Acquire, guard);
+        leaf.as_ref().map(Node::value)
+    }
+
+    /// Returns `true` if the trie contains `key`.
+    pub fn contains_key(&self, key: &[u8], guard: &Guard) -> bool {
+        self.get(key, guard).is_some()
+    }
+
+    /// Inserts `value` for `key`, returning the previous value if any.
+    pub fn insert<'g>(&self, key: &[u8], value: V, guard: &'g Guard) -> Option<&'g V> {
+        let mut leaf = Rc::new(Node::Leaf {
+            key: key.into(),
+            value,
+        });
+        loop {
+            match self.try_insert(key, leaf, guard) {
+                Ok(old) => return old,
+                Err(rejected) => leaf = rejected,
+            }
+        }
+    }
+
+    fn try_insert<'g>(
+        &self,
+        key: &[u8],
+        leaf: Rc<Node<V>>,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g V>, Rc<Node<V>>> {
+        let mut parent: Option<Link<'g, V>> = None;
+        let mut node = self.root.snapshot(guard);
+        for nibble in nibbles(key) {
+            let slot = &node.as_ref().unwrap().slots()[nibble];
+            let mut child = slot.load(Ordering::Acquire, guard);
+            if child.is_null() && child.tag() != FROZEN {
+                let new = Rc::new(Node::inner());
+                let snapshot = new.snapshot(guard);
+                match slot.compare_exchange(child, new, Ordering::AcqRel, Ordering::Acquire, guard)
+                {
+                    Ok(_) => child = snapshot,
+                    Err(e) => {
+                        e.desired.finalize(guard);
+                        child = e.current;
+                    }
+                }
+            }
+            if child.tag() == FROZEN {
+                // The root is never frozen, so `node` has a parent.
+                unlink(parent.unwrap(), guard);
+                return Err(leaf);
+            }
+            parent = Some((slot, child));
+            node = child;
+        }
+
+        let slot = &node.as_ref().unwrap().slots()[LEAF];
+        let current = slot.load(Ordering::Acquire, guard);
+        if current.tag() == FROZEN {
+            unlink(parent.unwrap(), guard);
+            return Err(leaf);
+        }
+        match slot.compare_exchange(current, leaf, Ordering::AcqRel, Ordering::Acquire, guard) {
+            Ok(_) => Ok(current.as_ref().map(Node::value)),
+            Err(e) => Err(e.desired),
+        }
+    }
+
+    /// Removes `key` from the trie, returning its value if it was present.
+    pub fn remove<'g>(&self, key: &[u8], guard: &'g Guard) -> Option<&'g V> {
+        loop {
+            if let Ok(old) = self.try_remove(key, guard) {
+                return old;
+            }
+        }
+    }
+
+    fn try_remove<'g>(&self, key: &[u8], guard: &'g Guard) -> Result<Option<&'g V>, ()> {
+        let mut path: Vec<Link<'g, V>> = Vec::with_capacity(key.len() * 2);
+        let mut node = self.root.snapshot(guard);
+        for nibble in nibbles(key) {
+            let slot = &node.as_ref().unwrap().slots()[nibble];
+            let child = slot.load(Ordering::Acquire, guard);
+            if child.tag() == FROZEN {
+                unlink(*path.last().unwrap(), guard);
+                return Err(());
+            }
+            if child.is_null() {
+                return Ok(None);
+            }
+            path.push((slot, child));
+            node = child;
+        }
+
+        let slot = &node.as_ref().unwrap().slots()[LEAF];
+        let mut current = slot.load(Ordering::Acquire, guard);
+        loop {
+            if current.tag() == FROZEN {
+                unlink(*path.last().unwrap(), guard);
+                return Err(());
+            }
+            if current.is_null() {
+                return Ok(None);
+            }
+            match slot.compare_exchange(
+                current,
+                Rc::null(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => break,
+                Err(e) => current = e.current,
+            }
+        }
+
+        // Unlink the nodes that have become empty, from the bottom up.
+        for &link in path.iter().rev() {
+            let slots = link.1.as_ref().unwrap().slots();
+            if slots
+                .iter()
+                .any(|slot| !slot.load(Ordering::Acquire, guard).is_null())
+                || !unlink(link, guard)
+            {
+                break;
+            }
+        }
+        Ok(current.as_ref().map(Node::value))
+    }
+
+    /// Returns an iterator over the entries of the trie in lexicographic order of their keys.
+    pub fn iter<'g>(&self, guard: &'g Guard) -> Iter<'g, V> {
+        self.prefix_iter(&[], guard)
+    }
+
+    /// Returns an iterator over the entries whose keys start with `prefix`, in lexicographic
+    /// order of their keys.
+    pub fn prefix_iter<'g>(&self, prefix: &[u8], guard: &'g Guard) -> Iter<'g, V> {
+        Iter {
+            stack: self
+                .find(prefix, guard)
+                .map(|node| (node.slots(), 0))
+                .into_iter()
+                .collect(),
+            guard,
+        }
+    }
+}
+
+impl<V> Default for Trie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: HeapSize> HeapSize for Trie<V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.root.heap_size(visited)
+    }
+}
+
+impl<V> Debug for Trie<V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Trie { .. }")
+    }
+}
+
+/// An iterator over the entries of a [`Trie`].
+///
+/// This is created by [`Trie::iter`] and [`Trie::prefix_iter`].
+pub struct Iter<'g, V> {
+    /// The inner nodes being traversed and the position of the next slot to visit in each.
+    stack: Vec<(&'g [AtomicRc<Node<V>>; SLOTS], usize)>,
+    guard: &'g Guard,
+}
+
+impl<'g, V> Iterator for Iter<'g, V> {
+    type Item = (&'g [u8], &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (slots, pos) = self.stack.last_mut()?;
+            let slots = *slots;
+            if *pos == SLOTS {
+                self.stack.pop();
+                continue;
+            }
+            // The leaf of a node precedes its children.
+            let index = if *pos == 0 { LEAF } else { *pos - 1 };
+            *pos += 1;
+
+            match slots[index].load(Ordering::Acquire, self.guard).as_ref() {
+                Some(Node::Leaf { key, value }) => return Some((&**key, value)),
+                Some(Node::Inner(slots)) => self.stack.push((slots, 0)),
+                None => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! The process-wide hook that decides what happens when an internal allocation fails.
+//!
+//! The collector allocates bags for the retired objects and boxes the deferred functions that do
+//! not fit inline. When such an allocation fails even after the garbage has been reclaimed, the
+//! hook installed with [`set_alloc_failure_hook`] is consulted instead of aborting right away, so
+//! that a long-running service can log the failure, shed load, or free memory of its own before
+//! the allocation is retried.
+
+use core::alloc::Layout;
+use std::sync::{Mutex, MutexGuard};
+
+/// What to do after an internal allocation of the collector has failed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AllocFailureAction {
+    /// Retries the allocation, after reclaiming the expired garbage again where possible.
+    Retry,
+    /// Aborts with [`std::alloc::handle_alloc_error`].
+    Abort,
+}
+
+type Hook = fn(Layout) -> AllocFailureAction;
+
+dyntls::lazy_static! {
+    static ref HOOK: Mutex<Option<Hook>> = Mutex::new(None);
+}
+
+fn lock_hook() -> MutexGuard<'static, Option<Hook>> {
+    // The slot is never left inconsistent, so poisoning is harmless.
+    HOOK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Installs the hook that is consulted whenever an internal allocation of any collector fails,
+/// with the layout of the failed allocation, replacing the previous hook.
+///
+/// The hook runs after the hook of the collector installed with
+/// [`Collector::set_alloc_failure_hook`](crate::Collector::set_alloc_failure_hook) and the
+/// reclamation of the expired garbage have not made the allocation succeed. If it returns
+/// [`AllocFailureAction::Retry`], the allocation is retried and the hook is consulted again if
+/// it fails again, so the hook should eventually return [`AllocFailureAction::Abort`]. Without a
+/// hook, a failed allocation aborts.
+///
+/// The hook may be called in a critical section, so it must not wait for the garbage of the
+/// current thread to be reclaimed.
+///
+/// # Examples
+///
+/// ```
+/// use circ::AllocFailureAction;
+/// use std::alloc::Layout;
+///
+/// fn on_alloc_failure(layout: Layout) -> AllocFailureAction {
+///     eprintln!("failed to allocate {} bytes for the collector", layout.size());
+///     AllocFailureAction::Abort
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// circ::set_alloc_failure_hook(on_alloc_failure);
+/// # circ::remove_alloc_failure_hook();
+/// ```
+pub fn set_alloc_failure_hook(hook: fn(Layout) -> AllocFailureAction) {
+    *lock_hook() = Some(hook);
+}
+
+/// Removes the hook installed by [`set_alloc_failure_hook`], so that a failed internal allocation
+/// aborts.
+pub fn remove_alloc_failure_hook() {
+    *lock_hook() = None;
+}
+
+/// Consults the hook after an internal allocation of `layout` has failed. Returns if the
+/// allocation should be retried, and aborts otherwise.
+#[cold]
+pub(crate) fn alloc_failed(layout: Layout) {
+    let hook = *lock_hook();
+    match hook.map(|hook| hook(layout)) {
+        Some(AllocFailureAction::Retry) => {}
+        Some(AllocFailureAction::Abort) | None => std::alloc::handle_alloc_error(layout),
+    }
+}
+
+/// Moves `value` into a new box, consulting the hook if the allocation fails.
+pub(crate) fn boxed<T>(value: T) -> Box<T> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        return Box::new(value);
+    }
+    loop {
+        let ptr = unsafe { std::alloc::alloc(layout) }.cast::<T>();
+        if !ptr.is_null() {
+            unsafe {
+                ptr.write(value);
+                return Box::from_raw(ptr);
+            }
+        }
+        alloc_failed(layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn retry() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+        fn hook(layout: Layout) -> AllocFailureAction {
+            assert_eq!(layout, Layout::new::<u64>());
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            AllocFailureAction::Retry
+        }
+
+        set_alloc_failure_hook(hook);
+        alloc_failed(Layout::new::<u64>());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        remove_alloc_failure_hook();
+        assert!(lock_hook().is_none());
+
+        assert_eq!(*boxed(7u64), 7);
+    }
+}
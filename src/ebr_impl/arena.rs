@@ -0,0 +1,261 @@
+//! Slabs from which the reference-counted objects of a collector are carved.
+//!
+//! A collector built with [`CollectorBuilder::rc_arena`](super::CollectorBuilder::rc_arena)
+//! allocates small objects from chunks of its own instead of the global allocator. Objects of
+//! the same size class share the slots of the chunks of the class, so that a structure that
+//! churns nodes at a high rate reuses the same memory and does not contend on the allocator.
+//!
+//! Each chunk is aligned to its size and starts with a header, so the class of a slot is found by
+//! masking its address. The addresses of the chunks of all arenas are registered, so that an
+//! object can be told to live in a slot without spending a bit of its reference counts on it. The
+//! free slots of a class are kept in lists sharded by thread. The chunks are freed with the
+//! collector, unless some of their slots are still in use, in which case the class is leaked.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::alloc::{alloc, dealloc};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use crossbeam_utils::CachePadded;
+
+use super::internal::current_thread_hash;
+use crate::sanitizer;
+
+/// The size and alignment of a chunk.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The granularity of the sizes of the slots, which is also their alignment.
+const SLOT_ALIGN: usize = 16;
+
+/// The size of the largest slot. Larger objects are allocated with the global allocator.
+const MAX_SLOT: usize = 1024;
+
+/// The number of shards of the free lists of a class.
+const SHARDS: usize = 8;
+
+dyntls::thread_local! {
+    static SHARD: usize = current_thread_hash() % SHARDS;
+}
+
+/// The addresses of the chunks of all the arenas.
+#[derive(Default)]
+struct Chunks {
+    /// The number of addresses in `set`, so that programs without arenas skip the lock.
+    count: AtomicUsize,
+    set: RwLock<HashSet<usize>>,
+}
+
+/// The chunks of all the arenas of the process.
+dyntls::lazy_static! {
+    static ref CHUNKS: Chunks = Chunks::default();
+}
+
+impl Chunks {
+    fn insert(&self, chunk: *mut ChunkHeader) {
+        let mut set = self.set.write().unwrap_or_else(|e| e.into_inner());
+        set.insert(chunk as usize);
+        self.count.fetch_add(1, Ordering::Release);
+    }
+
+    fn remove(&self, chunk: *mut ChunkHeader) {
+        let mut set = self.set.write().unwrap_or_else(|e| e.into_inner());
+        set.remove(&(chunk as usize));
+        self.count.fetch_sub(1, Ordering::Release);
+    }
+
+    fn contains(&self, ptr: *const u8) -> bool {
+        if self.count.load(Ordering::Acquire) == 0 {
+            return false;
+        }
+        let chunk = ptr as usize & !(CHUNK_SIZE - 1);
+        self.set
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&chunk)
+    }
+}
+
+/// Returns the shard of the free lists for the current thread.
+#[inline]
+fn shard() -> usize {
+    // Objects may be freed while the thread-local storage is destroyed.
+    SHARD.try_with(|shard| *shard).unwrap_or(0)
+}
+
+/// The header at the start of each chunk.
+#[repr(C, align(16))]
+struct ChunkHeader {
+    /// The class that owns the chunk.
+    class: *const SlabClass,
+    /// The number of slots of the chunk that are in use.
+    live: AtomicUsize,
+}
+
+impl ChunkHeader {
+    /// Returns the header of the chunk that contains `slot`.
+    #[inline]
+    fn of(slot: *mut u8) -> *const Self {
+        (slot as usize & !(CHUNK_SIZE - 1)) as *const Self
+    }
+}
+
+/// The chunks of slots of one size.
+struct SlabClass {
+    slot_size: usize,
+    /// The free slots, sharded by thread.
+    free: Box<[CachePadded<Mutex<Vec<*mut u8>>>]>,
+    /// All the chunks that have been allocated.
+    chunks: Mutex<Vec<*mut ChunkHeader>>,
+}
+
+impl SlabClass {
+    fn new(slot_size: usize) -> Self {
+        Self {
+            slot_size,
+            free: (0..SHARDS)
+                .map(|_| CachePadded::new(Mutex::new(Vec::new())))
+                .collect(),
+            chunks: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn chunk_layout() -> Layout {
+        Layout::from_size_align(CHUNK_SIZE, CHUNK_SIZE).unwrap()
+    }
+
+    /// Allocates a new chunk and adds its slots to `free`, returning `false` if the allocation
+    /// fails.
+    fn grow(&self, free: &mut Vec<*mut u8>) -> bool {
+        let chunk = unsafe { alloc(Self::chunk_layout()) }.cast::<ChunkHeader>();
+        if chunk.is_null() {
+            return false;
+        }
+        unsafe {
+            chunk.write(ChunkHeader {
+                class: self,
+                live: AtomicUsize::new(0),
+            })
+        };
+        let start = core::mem::size_of::<ChunkHeader>();
+        let slots = (CHUNK_SIZE - start) / self.slot_size;
+        unsafe { sanitizer::poison(chunk.cast::<u8>().add(start), CHUNK_SIZE - start) };
+        free.extend(
+            (0..slots)
+                .rev()
+                .map(|i| unsafe { chunk.cast::<u8>().add(start + i * self.slot_size) }),
+        );
+        CHUNKS.insert(chunk);
+        self.chunks.lock().unwrap().push(chunk);
+        true
+    }
+
+    fn alloc(&self) -> Option<NonNull<u8>> {
+        let shard = shard();
+        let slot = {
+            let mut free = self.free[shard].lock().unwrap();
+            if free.is_empty() && !self.grow(&mut free) {
+                return None;
+            }
+            free.pop().unwrap()
+        };
+        unsafe {
+            sanitizer::unpoison(slot, self.slot_size);
+            (*ChunkHeader::of(slot))
+                .live
+                .fetch_add(1, Ordering::Relaxed)
+        };
+        NonNull::new(slot)
+    }
+
+    /// # Safety
+    ///
+    /// `slot` must have been allocated from this class and not be used afterwards.
+    unsafe fn dealloc(&self, slot: *mut u8) {
+        sanitizer::poison(slot, self.slot_size);
+        let shard = shard();
+        self.free[shard].lock().unwrap().push(slot);
+        (*ChunkHeader::of(slot))
+            .live
+            .fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl Drop for SlabClass {
+    fn drop(&mut self) {
+        for &chunk in self.chunks.get_mut().unwrap().iter() {
+            CHUNKS.remove(chunk);
+            unsafe {
+                sanitizer::unpoison(chunk, CHUNK_SIZE);
+                dealloc(chunk.cast(), Self::chunk_layout());
+            }
+        }
+    }
+}
+
+/// The slabs of a collector, one for each size class.
+pub(crate) struct RcArena {
+    classes: Box<[OnceLock<Box<SlabClass>>]>,
+}
+
+// The raw pointers of the slabs are only accessed under their locks.
+unsafe impl Send for RcArena {}
+unsafe impl Sync for RcArena {}
+
+impl RcArena {
+    pub(crate) fn new() -> Self {
+        Self {
+            classes: (0..MAX_SLOT / SLOT_ALIGN)
+                .map(|_| OnceLock::new())
+                .collect(),
+        }
+    }
+
+    /// Allocates a slot for `layout`, or returns `None` if the layout does not fit into a slot or
+    /// the chunk cannot be allocated.
+    pub(crate) fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if layout.align() > SLOT_ALIGN || layout.size() > MAX_SLOT || layout.size() == 0 {
+            return None;
+        }
+        let index = (layout.size() - 1) / SLOT_ALIGN;
+        self.classes[index]
+            .get_or_init(|| Box::new(SlabClass::new((index + 1) * SLOT_ALIGN)))
+            .alloc()
+    }
+
+    /// Returns `true` if `ptr` points into a slot of an arena, rather than into memory of the
+    /// global allocator.
+    #[inline]
+    pub(crate) fn contains(ptr: *const u8) -> bool {
+        CHUNKS.contains(ptr)
+    }
+
+    /// Returns a slot to the class it was allocated from.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must have been returned by [`RcArena::alloc`] and not be used afterwards.
+    pub(crate) unsafe fn dealloc(slot: *mut u8) {
+        let class = (*ChunkHeader::of(slot)).class;
+        (*class).dealloc(slot);
+    }
+}
+
+impl Drop for RcArena {
+    fn drop(&mut self) {
+        for class in self.classes.iter_mut().filter_map(OnceLock::take) {
+            let live = class
+                .chunks
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|&chunk| unsafe { (*chunk).live.load(Ordering::Acquire) } != 0);
+            if live {
+                // The objects that outlive the collector keep using their slots, whose chunks
+                // point to the class.
+                Box::leak(class);
+            }
+        }
+    }
+}
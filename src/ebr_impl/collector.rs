@@ -2,9 +2,18 @@
 use core::fmt;
 use core::sync::atomic::Ordering;
 use std::sync::Arc;
-
-use super::guard::Guard;
-use super::internal::{Global, Local};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+use super::default::{cs, current_collector};
+use super::deferred_async::DeferredFuture;
+#[cfg(feature = "async")]
+use super::grace_period::GracePeriod;
+use super::guard::{Guard, PendingDeferred};
+use super::handoff::HandoffBuffer;
+use super::internal::{is_expired, Config, Global, Local};
+#[cfg(feature = "type-stats")]
+use super::type_stats::TypeStats;
 use super::Epoch;
 
 /// A garbage collector based on *epoch-based reclamation* (EBR).
@@ -16,12 +25,8 @@ unsafe impl Send for Collector {}
 unsafe impl Sync for Collector {}
 
 impl Default for Collector {
-    // https://github.com/rust-lang/rust-clippy/issues/11382
-    #[allow(clippy::arc_with_non_send_sync)]
     fn default() -> Self {
-        Self {
-            global: Arc::new(Global::new()),
-        }
+        CollectorBuilder::new().build()
     }
 }
 
@@ -31,16 +36,696 @@ impl Collector {
         Self::default()
     }
 
+    /// Returns a builder for a collector with a custom configuration.
+    pub fn builder() -> CollectorBuilder {
+        CollectorBuilder::new()
+    }
+
+    /// Ends a frame of a frame-based collector.
+    ///
+    /// The global epoch is advanced unless a thread is still pinned in the previous frame, and
+    /// all the garbage that has expired is reclaimed by the current thread. The garbage retired
+    /// by the current thread during a frame is reclaimed when the second frame after it ends.
+    /// Garbage retired by other threads is reclaimed in the same way once they have flushed it.
+    ///
+    /// This may also be called on a collector that is not frame-based, to force a collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::builder().frame_based(true).build();
+    /// for _frame in 0..3 {
+    ///     collector.enter(|| {
+    ///         // ... simulate the frame ...
+    ///     });
+    ///     collector.advance_frame();
+    /// }
+    /// ```
+    pub fn advance_frame(&self) {
+        self.with_guard(|guard| {
+            guard.flush();
+            self.global.advance_frame(guard);
+        });
+    }
+
+    /// Attempts to advance the global epoch in the critical section of `guard`, returning the new
+    /// epoch if it has advanced, or the current epoch if the advancement is blocked.
+    ///
+    /// The epoch cannot advance while a participant is pinned in the previous epoch. This includes
+    /// the participant of `guard`, so a guard advances the epoch at most once until it is
+    /// [reactivated](Guard::reactivate). A maintenance thread can drive the epoch with this, and
+    /// report the advancement as blocked when it keeps failing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `guard` is not a guard of this collector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::builder().deterministic(true).build();
+    /// let maintenance = collector.register();
+    /// let reader = collector.register();
+    ///
+    /// let guard = reader.pin();
+    /// let epoch = collector.try_advance(&maintenance.pin()).unwrap();
+    /// // The reader is pinned in the previous epoch.
+    /// assert_eq!(collector.try_advance(&maintenance.pin()), Err(epoch));
+    /// drop(guard);
+    /// assert!(collector.try_advance(&maintenance.pin()).is_ok());
+    /// ```
+    pub fn try_advance(&self, guard: &Guard) -> Result<Epoch, Epoch> {
+        assert!(
+            unsafe { guard.local.as_ref() }.is_some_and(|local| local.collector() == self),
+            "the guard is not a guard of this collector"
+        );
+        self.global.try_advance(guard)
+    }
+
+    /// Flushes the garbage of the current thread and reclaims the garbage that has expired, largest
+    /// objects first, until at least `bytes` bytes have been freed. Returns the number of bytes
+    /// freed.
+    ///
+    /// This is meant to be called under memory pressure, to get the most memory back from a
+    /// bounded amount of work. Only the memory of the retired objects themselves is counted, and
+    /// garbage of unknown size, such as deferred closures, is reclaimed last.
+    pub fn collect_bytes(&self, bytes: usize) -> usize {
+        self.with_guard(|guard| {
+            guard.flush();
+            self.global.steal_idle_bags(guard);
+            self.global.reclaim_budgeted(guard, usize::MAX, bytes)
+        })
+    }
+
+    /// Reacts to memory pressure by reclaiming as much garbage as possible right away.
+    ///
+    /// The garbage of the current thread and of the threads that are not in a critical section
+    /// is flushed, and the global epoch is advanced for a few rounds, reclaiming the expired
+    /// garbage after each, until the queue is empty or a pinned thread holds the epoch back.
+    /// A collector that only advances on explicit calls is not advanced.
+    ///
+    /// This is meant to be called when the application learns that memory is running low, so that
+    /// a containerized service is not killed for garbage that sits in bags. On Linux, the
+    /// `memory-pressure` feature provides `circ::pressure::Watcher`, which calls this on the
+    /// memory-pressure events of the kernel.
+    pub fn notify_pressure(&self) {
+        // Garbage sealed now expires after three advancements.
+        self.with_pin(|pin| {
+            for _ in 0..3 {
+                {
+                    let guard = &pin();
+                    guard.flush();
+                    self.global.steal_idle_bags(guard);
+                    if !self.global.config.is_explicit() {
+                        let _ = self.global.try_advance(guard);
+                    }
+                    self.global.reclaim(guard, usize::MAX);
+                }
+                if self.queued_garbage() == 0 {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Flushes the garbage of the current thread and reclaims all the garbage that has expired,
+    /// without advancing the global epoch.
+    ///
+    /// The garbage in the local caches of the threads that are not in a critical section is
+    /// flushed as well, so that a thread that retires a lot and then blocks does not hold on to
+    /// its garbage. This does not apply to real-time participants and participants with
+    /// [fixed bags](CollectorBuilder::fixed_bags), whose bags are never replaced by another
+    /// thread.
+    pub fn collect(&self) {
+        self.with_guard(|guard| {
+            guard.flush();
+            self.global.steal_idle_bags(guard);
+            self.global.reclaim(guard, usize::MAX);
+        });
+    }
+
+    /// Advances the global epoch and reclaims garbage until none is left or `timeout` has elapsed,
+    /// returning what has been reclaimed.
+    ///
+    /// The garbage of the current thread and of the threads that are not in a critical section is
+    /// flushed in every round. The garbage retired by a thread that stays in a critical section
+    /// cannot be reclaimed, and such a thread also keeps the epoch from advancing, in which case
+    /// this returns after `timeout` with [`CollectAllStats::remaining`] non-zero. This is meant
+    /// for a graceful shutdown.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::time::Duration;
+    ///
+    /// static CALLS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// collector.enter(|| circ::cs().defer(|| CALLS.fetch_add(1, Ordering::Relaxed)));
+    /// let stats = collector.collect_all(Duration::from_secs(1));
+    /// assert_eq!(stats.reclaimed, 1);
+    /// assert_eq!(stats.remaining, 0);
+    /// assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn collect_all(&self, timeout: Duration) -> CollectAllStats {
+        let start = Instant::now();
+        let reclaimed = self.global.reclaimed.load(Ordering::Relaxed);
+        let mut rounds = 0;
+        self.with_pin(|pin| loop {
+            rounds += 1;
+            {
+                let guard = &pin();
+                guard.flush();
+                self.global.steal_idle_bags(guard);
+                let _ = self.global.try_advance(guard);
+                self.global.reclaim(guard, usize::MAX);
+                // Reclaiming may retire more garbage, which is only counted in the global queues.
+                if let Some(local) = unsafe { guard.local.as_ref() } {
+                    local.push_to_global(guard);
+                }
+            }
+            let remaining = self.queued_garbage();
+            if remaining == 0 || start.elapsed() >= timeout {
+                return CollectAllStats {
+                    reclaimed: self.global.reclaimed.load(Ordering::Relaxed) - reclaimed,
+                    remaining,
+                    rounds,
+                };
+            }
+            std::thread::yield_now();
+        })
+    }
+
+    /// Runs `f` with a guard of this collector, pinning the participant of the current thread if
+    /// it is the collector of the thread.
+    pub(crate) fn with_guard<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Guard) -> R,
+    {
+        self.with_pin(|pin| f(&pin()))
+    }
+
+    /// Runs `f` with a function that pins a participant of this collector: the participant of the
+    /// current thread if this is the collector of the thread, or else a participant that is
+    /// registered for the whole call.
+    ///
+    /// A participant leaves garbage of its own behind when it exits, so a call that pins in rounds
+    /// keeps a single participant rather than registering one in every round, and the bags of the
+    /// registered participant are pushed into the global queues before it exits.
+    pub(crate) fn with_pin<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&dyn Fn() -> Guard) -> R,
+    {
+        if current_collector(|c| c == self) {
+            return f(&cs);
+        }
+        let handle = self.register();
+        let result = f(&|| handle.pin());
+        // Move what is left in the bags of the participant, such as the nodes of the queues freed
+        // by `f`, into the global queues, so that its exit does not orphan them.
+        let guard = &handle.pin();
+        unsafe { (*handle.local).push_to_global(guard) };
+        result
+    }
+
+    /// Installs a hook that is run when an allocation of the collector fails, replacing the
+    /// previous one.
+    ///
+    /// When a bag of garbage or an object created with [`Rc::try_new`](crate::Rc::try_new) cannot
+    /// be allocated, the hook is run so that the application can free its own caches, and then
+    /// the collector advances the epoch and reclaims all the expired garbage before retrying the
+    /// allocation once. The hook runs on the thread whose allocation failed, in a critical section
+    /// of the collector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// collector.set_alloc_failure_hook(|| {
+    ///     // ... drop the caches of the application ...
+    /// });
+    /// ```
+    pub fn set_alloc_failure_hook<F>(&self, hook: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        *self
+            .global
+            .alloc_failure_hook
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(hook));
+    }
+
+    /// Removes the hook installed by [`Collector::set_alloc_failure_hook`].
+    pub fn remove_alloc_failure_hook(&self) {
+        *self
+            .global
+            .alloc_failure_hook
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Installs an executor that runs the destructors of the expired garbage, replacing the
+    /// previous one.
+    ///
+    /// Without an executor, the garbage is destroyed by whichever thread reclaims it, typically
+    /// while it is pinned, so that a reader may stall on freeing a huge tree. With one, each
+    /// batch of expired garbage is handed to `executor` as a task instead, which should send it
+    /// to a designated thread pool. The task may be run on any thread, at any later time, and
+    /// enters the collector there, so the destructors retire more garbage to this collector.
+    ///
+    /// The garbage of participants with fixed bags, and the garbage left when the collector is
+    /// dropped, is still destroyed inline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// collector.set_drop_executor(|task| {
+    ///     std::thread::spawn(task);
+    /// });
+    /// ```
+    pub fn set_drop_executor(&self, executor: fn(Box<dyn FnOnce() + Send>)) {
+        self.global.set_drop_executor(Some(executor));
+    }
+
+    /// Removes the executor installed by [`Collector::set_drop_executor`], so that the garbage
+    /// is destroyed inline again.
+    pub fn remove_drop_executor(&self) {
+        self.global.set_drop_executor(None);
+    }
+
+    /// Registers the spawner of the futures deferred by [`Guard::defer_async`] in the critical
+    /// sections of the collector, replacing the previous one.
+    ///
+    /// After the grace period of a deferred future, the thread that reclaims it calls the
+    /// spawner with the future, which should hand it to an executor rather than run it.
+    /// Futures deferred before a spawner is registered, or after it is removed, are polled to
+    /// completion by the reclaiming thread instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{Collector, DeferredFuture};
+    /// use std::sync::mpsc::channel;
+    /// use std::sync::Mutex;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// // A stand-in for the queue of an executor.
+    /// let (sender, _receiver) = channel::<DeferredFuture>();
+    /// let sender = Mutex::new(sender);
+    /// let collector = Collector::new();
+    /// collector.set_async_spawner(move |future| {
+    ///     let _ = sender.lock().unwrap().send(future);
+    /// });
+    /// ```
+    pub fn set_async_spawner<F>(&self, spawner: F)
+    where
+        F: Fn(DeferredFuture) + Send + Sync + 'static,
+    {
+        *self
+            .global
+            .async_spawner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(spawner));
+    }
+
+    /// Removes the spawner registered by [`Collector::set_async_spawner`].
+    pub fn remove_async_spawner(&self) {
+        *self
+            .global
+            .async_spawner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Registers a hook that is run with the new global epoch whenever the epoch advances,
+    /// returning an id to remove it with.
+    ///
+    /// The hook is run by the thread that advanced the epoch, in a critical section of the
+    /// collector, so it should be short. It lets the application hook its own periodic work, such
+    /// as trimming caches or flushing statistics, onto the grace periods of the collector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::builder().deterministic(true).build();
+    /// let advances = Arc::new(AtomicUsize::new(0));
+    /// let hook = collector.on_epoch_advance({
+    ///     let advances = advances.clone();
+    ///     move |_| {
+    ///         advances.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// assert!(collector.try_advance(&collector.register().pin()).is_ok());
+    /// assert_eq!(advances.load(Ordering::Relaxed), 1);
+    /// assert!(collector.remove_epoch_advance_hook(hook));
+    /// ```
+    pub fn on_epoch_advance<F>(&self, hook: F) -> EpochAdvanceHookId
+    where
+        F: Fn(Epoch) + Send + Sync + 'static,
+    {
+        let id = self.global.next_hook_id.fetch_add(1, Ordering::Relaxed);
+        let mut hooks = self
+            .global
+            .epoch_advance_hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        hooks.push((id, Arc::new(hook)));
+        self.global
+            .has_epoch_advance_hooks
+            .store(true, Ordering::Release);
+        EpochAdvanceHookId(id)
+    }
+
+    /// Removes a hook registered with [`Collector::on_epoch_advance`], returning `false` if it
+    /// has already been removed.
+    pub fn remove_epoch_advance_hook(&self, id: EpochAdvanceHookId) -> bool {
+        let mut hooks = self
+            .global
+            .epoch_advance_hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let len = hooks.len();
+        hooks.retain(|(hook_id, _)| *hook_id != id.0);
+        self.global
+            .has_epoch_advance_hooks
+            .store(!hooks.is_empty(), Ordering::Release);
+        hooks.len() != len
+    }
+
     /// Registers a new handle for the collector.
     pub fn register(&self) -> LocalHandle {
         Local::register(self)
     }
 
+    /// Runs `f` with this collector as the collector of the current thread.
+    ///
+    /// During `f`, [`cs`](super::cs) enters critical sections of this collector, and the
+    /// reference-counted objects that are released are reclaimed by this collector. This allows
+    /// independent subsystems to have isolated reclamation domains, which are torn down with their
+    /// collectors.
+    ///
+    /// A collector may be entered while another one is entered, in which case the innermost one
+    /// is used. The objects of a data structure must always be accessed while the same collector
+    /// is entered: mixing collectors on the same objects leads to premature reclamation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Collector, RcObject, Rc};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Item(usize);
+    ///
+    /// unsafe impl RcObject for Item {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// collector.enter(|| {
+    ///     let item = AtomicRc::new(Item(1));
+    ///     let guard = &cs();
+    ///     item.store(Rc::new(Item(2)), Ordering::Release, guard);
+    ///     assert_eq!(item.load(Ordering::Acquire, guard).as_ref().unwrap().0, 2);
+    /// });
+    /// // Dropping the collector reclaims the garbage that it still holds.
+    /// drop(collector);
+    /// ```
+    pub fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        super::default::enter(self, f)
+    }
+
+    /// Returns an identifier of the collector, which is shared by all of its clones.
+    #[inline]
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.global) as usize
+    }
+
     /// Reads the global epoch, without issueing a fence.
     #[inline]
     pub fn global_epoch(&self) -> Epoch {
         self.global.epoch.load(Ordering::Relaxed)
     }
+
+    /// Returns the number of deferred functions that wait in the global queue of the collector.
+    ///
+    /// Functions that are still in the thread-local bags are not counted.
+    pub fn queued_garbage(&self) -> usize {
+        let reclaimed = self.global.reclaimed.load(Ordering::Relaxed);
+        let sealed = self.global.sealed.load(Ordering::Relaxed);
+        sealed.saturating_sub(reclaimed)
+    }
+
+    /// Returns the number of bytes that have been retired and not reclaimed yet.
+    ///
+    /// Only the garbage whose size is known is counted, which includes the objects of
+    /// [`Rc`](crate::Rc) pointers, but not the functions deferred by [`Guard::defer`]. The garbage
+    /// is counted once its bag is sealed, so the garbage that is still in the thread-local bags is
+    /// not counted yet.
+    pub fn unreclaimed_bytes(&self) -> usize {
+        self.global.unreclaimed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the peak of [`Collector::unreclaimed_bytes`] since the collector was created or
+    /// the peak was last reset.
+    ///
+    /// This is the extra memory that deferred reclamation has cost at worst, which is meant for
+    /// capacity planning.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{Collector, Rc, RcObject};
+    /// use std::time::Duration;
+    ///
+    /// struct Blob([u8; 4096]);
+    ///
+    /// unsafe impl RcObject for Blob {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::builder().deterministic(true).build();
+    /// collector.enter(|| {
+    ///     for _ in 0..16 {
+    ///         drop(Rc::new(Blob([0; 4096])));
+    ///     }
+    /// });
+    /// collector.collect_all(Duration::from_secs(1));
+    ///
+    /// assert_eq!(collector.unreclaimed_bytes(), 0);
+    /// assert!(collector.peak_unreclaimed_bytes() >= 16 * 4096);
+    /// collector.reset_peak_unreclaimed_bytes();
+    /// assert_eq!(collector.peak_unreclaimed_bytes(), 0);
+    /// ```
+    pub fn peak_unreclaimed_bytes(&self) -> usize {
+        self.global.peak_unreclaimed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Resets the peak of [`Collector::unreclaimed_bytes`] to the current value.
+    pub fn reset_peak_unreclaimed_bytes(&self) {
+        let now = self.global.unreclaimed_bytes.load(Ordering::Relaxed);
+        self.global
+            .peak_unreclaimed_bytes
+            .store(now, Ordering::Relaxed);
+    }
+
+    /// Returns the number of bags of garbage left by the threads that have exited and not
+    /// reclaimed yet.
+    ///
+    /// Orphaned bags are reclaimed before the other garbage, so that they do not linger behind
+    /// newer garbage in a service that spawns and exits threads at a high rate.
+    pub fn orphaned_bags(&self) -> usize {
+        self.global.orphaned.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of deferred functions that did not fit into the bags of the participants
+    /// of a collector with [fixed bags](CollectorBuilder::fixed_bags), and were pushed into the
+    /// global queue instead.
+    pub fn fixed_bag_overflows(&self) -> usize {
+        self.global.fixed_overflows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of shards of the global garbage queue.
+    pub fn shards(&self) -> usize {
+        self.global.shards()
+    }
+
+    /// Returns the number of empty bags in the pools of the participants of the collector.
+    ///
+    /// Each participant keeps the bags of the garbage it reclaims in its own pool and reuses them
+    /// when it seals its bags, so that retiring garbage does not allocate in the steady state. The
+    /// pool of a participant is freed when it exits.
+    pub fn pooled_bags(&self) -> usize {
+        self.with_guard(|guard| self.global.pooled_bags(guard))
+    }
+
+    /// Returns a token that expires once the grace period of the data unlinked before the call has
+    /// elapsed.
+    ///
+    /// This lets a structure that does not use the pointers of this crate, such as a custom arena,
+    /// reuse the grace periods of the collector: its readers access it in critical sections of
+    /// the collector, and after unlinking an entry, the writer frees it only once
+    /// [`Collector::is_expired`] returns `true` for a token taken after the unlinking. The token
+    /// expires as the global epoch advances, which happens as the participants pin and collect,
+    /// or with [`Collector::try_advance`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// // ... unlink an entry of the structure ...
+    /// let token = collector.pin_external();
+    /// while !collector.is_expired(token) {
+    ///     let _ = collector.try_advance(&collector.register().pin());
+    /// }
+    /// // ... free the entry ...
+    /// ```
+    pub fn pin_external(&self) -> EpochToken {
+        EpochToken {
+            epoch: self.global.seal_epoch(),
+            collector: self.id(),
+        }
+    }
+
+    /// Returns `true` if the grace period of `token` has elapsed, so that no critical section of
+    /// the collector can still access the data unlinked before the token was taken.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `token` was returned by another collector.
+    pub fn is_expired(&self, token: EpochToken) -> bool {
+        assert_eq!(
+            token.collector,
+            self.id(),
+            "the token was returned by another collector"
+        );
+        is_expired(token.epoch, self.global.epoch.load(Ordering::Acquire))
+    }
+
+    /// Returns a future that resolves once the grace period of the data unlinked before the call
+    /// has elapsed, which takes at least two advances of the global epoch.
+    ///
+    /// This lets async teardown code wait until the collector can no longer hand out references
+    /// to the data it has unlinked, without a blocking thread that spins on
+    /// [`Collector::collect`]. The future tries to advance the epoch whenever it is polled, and is
+    /// woken when another thread advances it. It must not be awaited in a critical section of
+    /// the collector, which would hold the epoch back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// async fn shutdown(collector: &Collector) {
+    ///     // ... unlink the shared state of the service ...
+    ///     collector.grace_period().await;
+    ///     // ... no reader can access the unlinked state anymore ...
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn grace_period(&self) -> GracePeriod {
+        GracePeriod::new(self)
+    }
+
+    /// Returns the number of live [`Lease`](crate::lease::Lease)s on the collector.
+    pub fn active_leases(&self) -> usize {
+        self.global.leases.load(Ordering::Relaxed)
+    }
+
+    /// Returns an iterator over the participants registered in the collector, reporting the epoch,
+    /// the pinned status and the pending garbage of each one.
+    ///
+    /// This is meant for operational tooling, such as a view of the participants printed during
+    /// an incident to find the thread that holds the global epoch back. The reports are taken
+    /// while the participants keep running, so they may be stale by the time they are read. If the
+    /// calling thread has no participant in the collector, a temporary one is registered for the
+    /// traversal and is not reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// let handle = collector.register();
+    /// let guard = handle.pin();
+    ///
+    /// let participant = collector.participants().next().unwrap();
+    /// assert!(participant.pinned);
+    /// assert_eq!(participant.thread, std::thread::current().id());
+    /// drop(guard);
+    /// ```
+    pub fn participants(&self) -> Participants {
+        let handle;
+        let (guard, skip_own) = if current_collector(|c| c == self) {
+            (cs(), false)
+        } else {
+            handle = self.register();
+            (handle.pin(), true)
+        };
+        Participants(self.global.participants(&guard, skip_own).into_iter())
+    }
+
+    /// Returns the numbers of retired and reclaimed reference-counted objects of each type, the
+    /// types with the most objects waiting for reclamation first.
+    ///
+    /// An object is counted when its destruction is deferred, and counted as reclaimed when the
+    /// deferred destruction runs. Only objects retired in a critical section of this collector
+    /// are counted.
+    #[cfg(feature = "type-stats")]
+    pub fn type_stats(&self) -> Vec<TypeStats> {
+        self.global.type_stats.snapshot()
+    }
+
+    /// Returns the number of guards of the collector that have been held for longer than the
+    /// threshold set with [`CollectorBuilder::long_guard_threshold`].
+    ///
+    /// Only the outermost guard of a participant is checked, when it is dropped.
+    #[cfg(feature = "guard-tracking")]
+    pub fn long_guards(&self) -> usize {
+        self.global.long_guards.load(Ordering::Relaxed)
+    }
 }
 
 impl Clone for Collector {
@@ -66,6 +751,293 @@ impl PartialEq for Collector {
 }
 impl Eq for Collector {}
 
+/// A builder for a [`Collector`] with a custom configuration.
+#[derive(Clone, Default, Debug)]
+pub struct CollectorBuilder {
+    config: Config,
+}
+
+impl CollectorBuilder {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the collector is frame-based.
+    ///
+    /// The global epoch of a frame-based collector never advances implicitly, and its garbage is
+    /// reclaimed only by [`Collector::advance_frame`], which is meant to be called once per frame
+    /// of a game loop. Defaults to `false`.
+    pub fn frame_based(mut self, frame_based: bool) -> Self {
+        self.config.frame_based = frame_based;
+        self
+    }
+
+    /// Sets whether the collector is deterministic.
+    ///
+    /// A deterministic collector never advances the global epoch or reclaims garbage by itself,
+    /// regardless of the number of threads or how often they pin. Both happen only when
+    /// [`Collector::try_advance`], [`Collector::collect`], or [`Collector::advance_frame`] is
+    /// called, so that the reclamation of a program can be replayed, for example with a
+    /// [`ReclamationSchedule`]. Defaults to `false`.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.config.deterministic = deterministic;
+        self
+    }
+
+    /// Sets the number of shards of the global garbage queue.
+    ///
+    /// Each thread pushes its garbage into the shard chosen by hashing its thread id, and steals
+    /// expired garbage from the other shards when it collects. More shards reduce the contention
+    /// on the queue when many threads retire garbage at the same time. Defaults to `1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn shards(mut self, shards: usize) -> Self {
+        assert!(shards > 0, "a collector must have at least one shard");
+        self.config.shards = shards;
+        self
+    }
+
+    /// Sets when the participants of the collector collect garbage on their own.
+    ///
+    /// This can be overridden for each participant with [`LocalHandle::set_pin_collection`].
+    /// Defaults to [`PinCollection::OnFlush`].
+    pub fn pin_collection(mut self, pin_collection: PinCollection) -> Self {
+        self.config.pin_collection = pin_collection;
+        self
+    }
+
+    /// Sets whether small reference-counted objects are allocated from the arena of the
+    /// collector.
+    ///
+    /// The objects of [`Rc`](crate::Rc)s whose sizes fall into the same size class are carved
+    /// from shared chunks of the collector instead of the global allocator, which improves their
+    /// locality and avoids contention on the allocator when nodes are allocated and retired at a
+    /// high rate. The chunks are freed when the collector is dropped. Defaults to `false`.
+    pub fn rc_arena(mut self, rc_arena: bool) -> Self {
+        self.config.rc_arena = rc_arena;
+        self
+    }
+
+    /// Makes each participant retire garbage into `bags` bags of its own, without allocating.
+    ///
+    /// The bags are allocated when a participant is registered. A participant keeps its full bags
+    /// instead of pushing them into the global queue, which allocates, and reclaims them by itself
+    /// once they have expired. [`Guard::try_defer`] returns the function back when all the bags
+    /// are full and none has expired, which happens when a participant retires more than the
+    /// bags hold during a single critical section. Other ways to retire garbage, such as dropping
+    /// an [`Rc`](crate::Rc), fall back to the global queue instead, and are counted by
+    /// [`Collector::fixed_bag_overflows`]. Functions that do not fit into the inline storage of
+    /// a deferred function are still boxed. Defaults to zero, which disables the mode.
+    pub fn fixed_bags(mut self, bags: usize) -> Self {
+        self.config.fixed_bags = bags;
+        self
+    }
+
+    /// Sets how long a guard may be held before it is reported.
+    ///
+    /// When the outermost guard of a participant is dropped after `threshold`, a warning with the
+    /// location where it was created is logged with `tracing`, and the guard is counted by
+    /// [`Collector::long_guards`]. This helps to find a critical section that blocks the
+    /// reclamation, such as one held across a blocking call. Defaults to one second.
+    #[cfg(feature = "guard-tracking")]
+    pub fn long_guard_threshold(mut self, threshold: Duration) -> Self {
+        self.config.long_guard_threshold = Some(threshold);
+        self
+    }
+
+    /// Builds the collector.
+    // https://github.com/rust-lang/rust-clippy/issues/11382
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn build(self) -> Collector {
+        Collector {
+            global: Arc::new(Global::new(self.config)),
+        }
+    }
+}
+
+/// When a participant collects garbage on its own.
+///
+/// A participant collects when its outermost guard is dropped, by attempting to advance the global
+/// epoch and reclaiming some of the expired garbage. Explicit calls such as
+/// [`Collector::collect`] are not affected, and neither are frame-based or deterministic
+/// collectors, which never collect on their own.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{Collector, PinCollection};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let collector = Collector::builder()
+///     .pin_collection(PinCollection::Never)
+///     .build();
+///
+/// // A dedicated maintenance thread collects every time it pins.
+/// let maintenance = collector.register();
+/// maintenance.set_pin_collection(PinCollection::Every(1));
+/// drop(maintenance.pin());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PinCollection {
+    /// Collects after the participant has pushed its garbage into the global queue, which happens
+    /// when its bag is full or flushed.
+    #[default]
+    OnFlush,
+    /// Collects as with `OnFlush`, and also after every `n` pins. `Every(0)` and `Every(1)` both
+    /// collect after every pin.
+    Every(usize),
+    /// Never collects, leaving the garbage of the participant to the others. This suits readers
+    /// whose critical sections must stay short.
+    Never,
+}
+
+/// A reproducible schedule of reclamation steps, for testing with a deterministic collector.
+///
+/// Each call to [`ReclamationSchedule::step`] makes a pseudo-random decision derived from the
+/// seed, so the same seed always results in the same sequence of epoch advancements and
+/// collections.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{Collector, ReclamationSchedule};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let collector = Collector::builder().deterministic(true).build();
+/// let mut schedule = ReclamationSchedule::new(42);
+/// let steps: Vec<_> = (0..8).map(|_| schedule.step(&collector)).collect();
+///
+/// let mut replay = ReclamationSchedule::new(42);
+/// assert!(steps.iter().all(|step| *step == replay.next_step()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReclamationSchedule {
+    state: u64,
+}
+
+/// A step of a [`ReclamationSchedule`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReclamationStep {
+    /// Does nothing.
+    Idle,
+    /// Attempts to advance the global epoch.
+    Advance,
+    /// Reclaims the expired garbage.
+    Collect,
+    /// Attempts to advance the global epoch, and then reclaims the expired garbage.
+    AdvanceAndCollect,
+}
+
+impl ReclamationSchedule {
+    /// Creates a schedule from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next step without performing it.
+    pub fn next_step(&mut self) -> ReclamationStep {
+        // SplitMix64.
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        match (z ^ (z >> 31)) % 4 {
+            0 => ReclamationStep::Idle,
+            1 => ReclamationStep::Advance,
+            2 => ReclamationStep::Collect,
+            _ => ReclamationStep::AdvanceAndCollect,
+        }
+    }
+
+    /// Performs the next step on `collector`, and returns it.
+    pub fn step(&mut self, collector: &Collector) -> ReclamationStep {
+        let step = self.next_step();
+        match step {
+            ReclamationStep::Idle => {}
+            ReclamationStep::Advance => {
+                collector.with_guard(|guard| {
+                    let _ = collector.try_advance(guard);
+                });
+            }
+            ReclamationStep::Collect => collector.collect(),
+            ReclamationStep::AdvanceAndCollect => collector.advance_frame(),
+        }
+        step
+    }
+}
+
+/// What [`Collector::collect_all`] has reclaimed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CollectAllStats {
+    /// The number of deferred functions that have been executed, including those reclaimed by
+    /// other threads in the meantime.
+    pub reclaimed: usize,
+    /// The number of deferred functions that are still waiting in the global queue.
+    pub remaining: usize,
+    /// The number of rounds of advancing and reclaiming.
+    pub rounds: usize,
+}
+
+/// A report of the state of a participant, returned by [`Collector::participants`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParticipantInfo {
+    /// The local epoch, which is the global epoch at the time the participant was pinned.
+    ///
+    /// It is only meaningful while the participant is pinned.
+    pub epoch: Epoch,
+    /// Whether the participant is pinned, that is, whether one of its guards is alive.
+    pub pinned: bool,
+    /// The number of deferred functions in the local bags of the participant, or `None` if the
+    /// bags are in use, which is the case while the participant is pinned.
+    pub pending: Option<usize>,
+    /// The id of the thread that registered the participant.
+    pub thread: ThreadId,
+    /// The name of the thread that registered the participant, if it has one.
+    pub thread_name: Option<String>,
+}
+
+/// An iterator over the participants of a collector, returned by [`Collector::participants`].
+#[derive(Debug)]
+pub struct Participants(std::vec::IntoIter<ParticipantInfo>);
+
+impl Iterator for Participants {
+    type Item = ParticipantInfo;
+
+    fn next(&mut self) -> Option<ParticipantInfo> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Participants {}
+
+/// The id of a hook registered with [`Collector::on_epoch_advance`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EpochAdvanceHookId(usize);
+
+/// A point in the epochs of a collector, returned by [`Collector::pin_external`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EpochToken {
+    epoch: Epoch,
+    collector: usize,
+}
+
+impl EpochToken {
+    /// Returns the epoch in which the token was taken.
+    #[inline]
+    pub fn epoch(self) -> Epoch {
+        self.epoch
+    }
+}
+
 /// A handle to a garbage collector.
 pub struct LocalHandle {
     pub(crate) local: *const Local,
@@ -74,14 +1046,48 @@ pub struct LocalHandle {
 impl LocalHandle {
     /// Pins the handle.
     #[inline]
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
     pub fn pin(&self) -> Guard {
         unsafe { (*self.local).pin() }
     }
 
-    /// Returns `true` if the handle is pinned.
-    #[cfg(test)]
+    /// Makes the participant of the handle real-time.
+    ///
+    /// A real-time participant pins and unpins in a bounded number of steps: it never advances the
+    /// global epoch, collects garbage, or allocates. The functions it defers, including the
+    /// destruction of the objects it releases, are pushed into a pre-allocated buffer of
+    /// `capacity` entries, and other participants reclaim them on their next collection. If the
+    /// buffer is full, the participant falls back to the regular path, which may allocate. Such
+    /// overflows are counted by [`LocalHandle::realtime_overflows`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn set_realtime(&self, capacity: usize) {
+        unsafe { (*self.local).set_realtime(capacity) }
+    }
+
+    /// Sets when the participant of the handle collects garbage on its own, overriding the
+    /// configuration of the collector.
+    pub fn set_pin_collection(&self, pin_collection: PinCollection) {
+        unsafe { (*self.local).set_pin_collection(pin_collection) }
+    }
+
+    /// Returns the number of deferred functions that did not fit into the buffer of a real-time
+    /// participant.
+    pub fn realtime_overflows(&self) -> usize {
+        unsafe { (*self.local).handoff() }.map_or(0, HandoffBuffer::overflows)
+    }
+
+    /// Returns the deferred functions in the local cache of the participant that have not been
+    /// flushed yet. See [`Guard::pending_deferred`].
+    pub fn pending_deferred(&self) -> PendingDeferred {
+        self.pin().pending_deferred()
+    }
+
+    /// Returns `true` if the handle is pinned, that is, if a guard of its participant is alive.
     #[inline]
-    pub(crate) fn is_pinned(&self) -> bool {
+    pub fn is_pinned(&self) -> bool {
         unsafe { (*self.local).is_pinned() }
     }
 }
@@ -105,10 +1111,13 @@ impl fmt::Debug for LocalHandle {
 mod tests {
     use std::mem::ManuallyDrop;
     use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
 
     use crossbeam_utils::thread;
 
-    use crate::ebr_impl::{collector::Collector, RawShared};
+    use crate::ebr_impl::{collector::Collector, cs, unprotected, RawShared};
+    use crate::{Rc, RcObject};
 
     const NUM_THREADS: usize = 8;
 
@@ -149,9 +1158,13 @@ mod tests {
             let guard = &handle.pin();
             unsafe {
                 let a = RawShared::from_owned(7);
-                guard.defer_destroy(a);
+                guard.defer_destroy_internal(a);
 
-                let is_empty = || (*(*guard.local).bag.get()).is_empty();
+                let is_empty = || {
+                    (*(*guard.local).bags.get())
+                        .iter()
+                        .all(|bag| bag.is_empty())
+                };
                 assert!(!is_empty());
 
                 while !is_empty() {
@@ -175,9 +1188,9 @@ mod tests {
         unsafe {
             for _ in 0..10 {
                 let a = RawShared::from_owned(7);
-                guard.defer_destroy(a);
+                guard.defer_destroy_internal(a);
             }
-            assert!(!(*(*guard.local).bag.get()).is_empty());
+            assert!(!(*(*guard.local).bags.get())[0].is_empty());
         }
     }
 
@@ -291,7 +1304,7 @@ mod tests {
 
             for _ in 0..COUNT {
                 let a = RawShared::from_owned(Elem(7));
-                guard.defer_destroy(a);
+                guard.defer_destroy_internal(a);
             }
             guard.flush();
         }
@@ -374,7 +1387,7 @@ mod tests {
         {
             let a = RawShared::from_owned(v);
             unsafe {
-                guard.defer_destroy(a);
+                guard.defer_destroy_internal(a);
             }
             guard.flush();
         }
@@ -466,7 +1479,7 @@ mod tests {
                         let guard = &handle.pin();
                         unsafe {
                             let a = RawShared::from_owned(Elem(7i32));
-                            guard.defer_destroy(a);
+                            guard.defer_destroy_internal(a);
                         }
                     }
                 });
@@ -481,4 +1494,334 @@ mod tests {
         }
         assert_eq!(DROPS_STRESS.load(Ordering::Relaxed), COUNT * THREADS);
     }
+
+    #[test]
+    fn recycle_reclaimed_bags() {
+        const ITEMS: usize = 1024;
+
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        struct Item;
+
+        unsafe impl RcObject for Item {
+            fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+        }
+
+        let retire = || {
+            let guard = &cs();
+            for _ in 0..ITEMS {
+                Rc::new(Item).finalize(guard);
+            }
+            guard.flush();
+        };
+
+        let collector = Collector::builder().frame_based(true).build();
+        // The bags are pooled by the participant that reclaims them, which stays registered here.
+        collector.enter(|| {
+            assert_eq!(collector.pooled_bags(), 0);
+
+            retire();
+            for _ in 0..3 {
+                collector.advance_frame();
+            }
+            let pooled = collector.pooled_bags();
+            assert!(pooled > 0);
+
+            // Sealing bags takes them from the pool instead of allocating.
+            retire();
+            assert!(collector.pooled_bags() < pooled);
+        });
+        // The pool is freed when the participant exits.
+        assert_eq!(collector.pooled_bags(), 0);
+    }
+
+    #[test]
+    fn reclaim_largest_first() {
+        dyntls::lazy_static! {
+            static ref DROPS_SMALL: AtomicUsize = AtomicUsize::new(0);
+        }
+        dyntls::lazy_static! {
+            static ref DROPS_LARGE: AtomicUsize = AtomicUsize::new(0);
+        }
+
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        struct Small;
+
+        unsafe impl RcObject for Small {
+            fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+        }
+
+        impl Drop for Small {
+            fn drop(&mut self) {
+                DROPS_SMALL.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        struct Large([u8; 8192]);
+
+        unsafe impl RcObject for Large {
+            fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+        }
+
+        impl Drop for Large {
+            fn drop(&mut self) {
+                DROPS_LARGE.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let collector = Collector::builder().frame_based(true).build();
+        collector.enter(|| {
+            let guard = &cs();
+            for _ in 0..256 {
+                Rc::new(Small).finalize(guard);
+            }
+            for _ in 0..4 {
+                let large = Rc::new(Large([1; 8192]));
+                assert_eq!(large.as_ref().unwrap().0[0], 1);
+                large.finalize(guard);
+            }
+            guard.flush();
+        });
+        let advancer = collector.register();
+        for _ in 0..3 {
+            assert!(collector.try_advance(&advancer.pin()).is_ok());
+        }
+
+        // A small budget is met by the large objects alone.
+        assert!(collector.collect_bytes(1) >= 8192);
+        assert_eq!(DROPS_LARGE.load(Ordering::Relaxed), 4);
+        assert_eq!(DROPS_SMALL.load(Ordering::Relaxed), 0);
+
+        collector.collect_bytes(usize::MAX);
+        assert_eq!(DROPS_SMALL.load(Ordering::Relaxed), 256);
+    }
+
+    #[test]
+    fn steal_garbage_of_blocked_thread() {
+        const ITEMS: usize = 10;
+
+        let context = &dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        // A deterministic collector never flushes the local garbage on its own.
+        let collector = Collector::builder().deterministic(true).build();
+        let (retired_tx, retired_rx) = mpsc::channel();
+        let (exit_tx, exit_rx) = mpsc::channel::<()>();
+
+        thread::scope(|scope| {
+            let (collector, drops) = (&collector, &drops);
+            scope.spawn(move |_| {
+                unsafe {
+                    context.initialize();
+                }
+                let handle = collector.register();
+                {
+                    let guard = &handle.pin();
+                    for _ in 0..ITEMS {
+                        let drops = drops.clone();
+                        guard.defer(move || drops.fetch_add(1, Ordering::Relaxed));
+                    }
+                }
+                assert_eq!(handle.pending_deferred().count, ITEMS);
+                retired_tx.send(()).unwrap();
+                // Block with the garbage in the local bags, without being pinned.
+                exit_rx.recv().unwrap();
+                drop(handle);
+            });
+
+            retired_rx.recv().unwrap();
+            let advancer = collector.register();
+            for _ in 0..8 {
+                collector.collect();
+                let _ = collector.try_advance(&advancer.pin());
+            }
+            assert_eq!(drops.load(Ordering::Relaxed), ITEMS);
+            exit_tx.send(()).unwrap();
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn keep_fixed_bags_of_idle_participant() {
+        const ITEMS: usize = 10;
+
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let collector = Collector::builder()
+            .deterministic(true)
+            .fixed_bags(2)
+            .build();
+        let handle = collector.register();
+        {
+            let guard = &handle.pin();
+            for _ in 0..ITEMS {
+                assert!(guard.try_defer(|| ()).is_ok());
+            }
+        }
+
+        // Stealing would replace the fixed bags with bags allocated by the collecting thread.
+        collector.collect();
+        assert_eq!(handle.pending_deferred().count, ITEMS);
+    }
+
+    #[test]
+    fn reclaim_orphans_first() {
+        const THREADS: usize = 4;
+        const ITEMS: usize = 10;
+
+        let context = &dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let collector = Collector::builder().deterministic(true).build();
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|_| {
+                    unsafe {
+                        context.initialize();
+                    }
+                    let handle = collector.register();
+                    let guard = &handle.pin();
+                    for _ in 0..ITEMS {
+                        let drops = drops.clone();
+                        guard.defer(move || drops.fetch_add(1, Ordering::Relaxed));
+                    }
+                    // The garbage is left behind when the handle is dropped on exit.
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(collector.orphaned_bags(), THREADS);
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+
+        let advancer = collector.register();
+        for _ in 0..3 {
+            let _ = collector.try_advance(&advancer.pin());
+        }
+        collector.collect();
+        assert_eq!(collector.orphaned_bags(), 0);
+        assert_eq!(drops.load(Ordering::Relaxed), THREADS * ITEMS);
+    }
+
+    #[test]
+    fn try_advance_reports_blocked_advancement() {
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let collector = Collector::builder().deterministic(true).build();
+        let maintenance = collector.register();
+        let reader = collector.register();
+
+        let start = collector.global_epoch();
+        let guard = reader.pin();
+        let epoch = collector.try_advance(&maintenance.pin()).unwrap();
+        assert_eq!(epoch.wrapping_sub(start), 1);
+        assert_eq!(collector.global_epoch(), epoch);
+
+        // The reader is pinned in the previous epoch.
+        for _ in 0..3 {
+            assert_eq!(collector.try_advance(&maintenance.pin()), Err(epoch));
+        }
+        drop(guard);
+
+        // A guard advances the epoch at most once, until it is reactivated.
+        let mut guard = maintenance.pin();
+        let next = collector.try_advance(&guard).unwrap();
+        assert_eq!(next.wrapping_sub(epoch), 1);
+        assert_eq!(collector.try_advance(&guard), Err(next));
+        guard.reactivate();
+        assert!(collector.try_advance(&guard).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "not a guard of this collector")]
+    fn try_advance_with_guard_of_another_collector() {
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let (first, second) = (Collector::new(), Collector::new());
+        let handle = first.register();
+        let _ = second.try_advance(&handle.pin());
+    }
+
+    #[test]
+    #[should_panic(expected = "not a guard of this collector")]
+    fn try_advance_with_unprotected_guard() {
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let collector = Collector::new();
+        let _ = collector.try_advance(&unsafe { unprotected() });
+    }
+
+    #[test]
+    fn collect_all_drains_everything() {
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let collector = Collector::builder().deterministic(true).build();
+        let reclaimed = Arc::new(AtomicUsize::new(0));
+        collector.enter(|| {
+            let guard = &cs();
+            for _ in 0..100 {
+                let reclaimed = reclaimed.clone();
+                guard.defer(move || reclaimed.fetch_add(1, Ordering::Relaxed));
+            }
+        });
+        assert_eq!(reclaimed.load(Ordering::Relaxed), 0);
+
+        let stats = collector.collect_all(Duration::from_secs(10));
+        assert_eq!(stats.reclaimed, 100);
+        assert_eq!(stats.remaining, 0);
+        assert!(stats.rounds >= 1);
+        assert_eq!(reclaimed.load(Ordering::Relaxed), 100);
+        assert_eq!(collector.queued_garbage(), 0);
+    }
+
+    #[test]
+    fn collect_all_times_out_behind_pinned_participant() {
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+
+        let collector = Collector::builder().deterministic(true).build();
+        let handle = collector.register();
+        let guard = handle.pin();
+        guard.defer(|| ());
+        guard.flush();
+
+        // The pinned participant keeps the epoch from advancing far enough.
+        let stats = collector.collect_all(Duration::from_millis(50));
+        assert_eq!(stats.reclaimed, 0);
+        assert_eq!(stats.remaining, 1);
+
+        drop(guard);
+        let stats = collector.collect_all(Duration::from_secs(10));
+        assert_eq!(stats.reclaimed, 1);
+        assert_eq!(stats.remaining, 0);
+    }
 }
@@ -3,16 +3,39 @@
 //! For each thread, a participant is lazily initialized on its first use, when the current thread
 //! is registered in the default collector.  If initialized, the thread's participant will get
 //! destructed on thread exit, which in turn unregisters the thread.
+//!
+//! A thread may temporarily switch to another collector with [`Collector::enter`], in which case
+//! the participant of the innermost entered collector is used instead of the default participant.
+
+use core::alloc::Layout;
+use core::cell::{Cell, OnceCell};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering;
+use std::sync::{Mutex, MutexGuard};
 
-use super::collector::{Collector, LocalHandle};
+use scopeguard::defer;
+
+use super::collector::{Collector, LocalHandle, PinCollection};
 use super::guard::Guard;
+use super::internal::Local;
+use super::pin_site::PinSite;
 use super::sync::once_lock::OnceLock;
 
+/// A collector adopted from another dynamic library, which becomes the default collector if it is
+/// installed before the default collector is first used.
+dyntls::lazy_static! {
+    static ref ADOPTED: Mutex<Option<Collector>> = Mutex::new(None);
+}
+
 /// The global data for the default garbage collector.
 dyntls::lazy_static! {
-    static ref COLLECTOR: Collector = Collector::new();
+    static ref COLLECTOR: Collector = lock_adopted().take().unwrap_or_default();
 }
 
+fn lock_adopted() -> MutexGuard<'static, Option<Collector>> {
+    // The slot is never left inconsistent, so poisoning is harmless.
+    ADOPTED.lock().unwrap_or_else(|e| e.into_inner())
+}
 
 fn collector() -> &'static Collector {
     // /// The global data for the default garbage collector.
@@ -20,15 +43,125 @@ fn collector() -> &'static Collector {
     &COLLECTOR
 }
 
+/// The participants of a thread.
+struct ThreadHandle {
+    /// The participant that is used instead of the default one, or null: the participant that is
+    /// executing the deferred functions of its collector, or that of the innermost entered
+    /// collector, whichever has been set last.
+    current: Cell<*const Local>,
+    /// The participant of the default collector, registered on its first use.
+    default: OnceCell<LocalHandle>,
+}
+
 dyntls::thread_local! {
-    /// The per-thread participant for the default garbage collector.
-    static HANDLE: LocalHandle = collector().register();
+    /// The participants of the current thread, in a single slot so that pinning takes one
+    /// thread-local access.
+    static HANDLE: ThreadHandle = ThreadHandle {
+        current: Cell::new(ptr::null()),
+        default: OnceCell::new(),
+    };
 }
 
 /// Enters EBR critical section.
+///
+/// The critical section belongs to the innermost collector entered by the current thread with
+/// [`Collector::enter`], or to the default collector if there is none.
 #[inline]
+#[cfg_attr(feature = "guard-tracking", track_caller)]
 pub fn cs() -> Guard {
-    with_handle(|handle| handle.pin())
+    let site = PinSite::here();
+    with_local(|local| unsafe { (*local).pin_at(site) })
+}
+
+/// Runs `f` in an EBR critical section, which ends as soon as `f` returns.
+///
+/// The guard cannot escape the closure, which keeps critical sections short and scoped.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{with_cs, AtomicRc, Rc, RcObject};
+/// use std::sync::atomic::Ordering;
+///
+/// struct Node(usize);
+///
+/// unsafe impl RcObject for Node {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let node = AtomicRc::new(Node(7));
+/// let value = with_cs(|cs| node.load(Ordering::Acquire, cs).as_ref().unwrap().0);
+/// assert_eq!(value, 7);
+/// ```
+#[inline]
+#[cfg_attr(feature = "guard-tracking", track_caller)]
+pub fn with_cs<F, R>(f: F) -> R
+where
+    F: FnOnce(&Guard) -> R,
+{
+    f(&cs())
+}
+
+/// Runs the fallible `f` in an EBR critical section, which ends as soon as `f` returns, and
+/// returns its result.
+///
+/// This is [`with_cs`] for closures that use the `?` operator.
+#[inline]
+#[cfg_attr(feature = "guard-tracking", track_caller)]
+pub fn try_with_cs<F, T, E>(f: F) -> Result<T, E>
+where
+    F: FnOnce(&Guard) -> Result<T, E>,
+{
+    f(&cs())
+}
+
+/// Makes the participant of the current thread real-time, with a buffer of `capacity` deferred
+/// functions.
+///
+/// This applies to the participant that [`cs`] uses, and lasts until the thread exits or the
+/// entered collector is exited. See [`LocalHandle::set_realtime`] for details.
+pub fn set_realtime(capacity: usize) {
+    with_local(|local| unsafe { (*local).set_realtime(capacity) });
+}
+
+/// Sets when the participant of the current thread collects garbage on its own.
+///
+/// This applies to the participant that [`cs`] uses, and lasts until the thread exits or the
+/// entered collector is exited. See [`PinCollection`] for details.
+pub fn set_pin_collection(pin_collection: PinCollection) {
+    with_local(|local| unsafe { (*local).set_pin_collection(pin_collection) });
+}
+
+/// Runs `f` with the handle of the participant of the current thread, which is the participant
+/// that [`cs`] pins.
+///
+/// This gives access to the per-thread options and state of the participant, without
+/// registering another one.
+///
+/// # Examples
+///
+/// ```
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let guard = circ::cs();
+/// assert!(circ::with_local_handle(|handle| handle.is_pinned()));
+/// drop(guard);
+/// circ::with_local_handle(|handle| handle.pin().flush());
+/// ```
+pub fn with_local_handle<F, R>(f: F) -> R
+where
+    F: FnOnce(&LocalHandle) -> R,
+{
+    with_local(|local| {
+        // SAFETY: The temporary handle accounts for itself.
+        let handle = unsafe {
+            (*local).acquire_handle();
+            LocalHandle { local }
+        };
+        f(&handle)
+    })
 }
 
 /// Returns the default global collector.
@@ -36,19 +169,137 @@ pub fn default_collector() -> &'static Collector {
     collector()
 }
 
+/// Runs `f` with the participant of the current thread, which is the participant that [`cs`]
+/// pins: the one that is executing deferred functions or that of the innermost entered
+/// collector, or else the participant of the default collector.
+///
+/// The participant is kept alive until `f` returns.
 #[inline]
-fn with_handle<F, R>(mut f: F) -> R
+fn with_local<F, R>(f: F) -> R
+where
+    F: FnOnce(*const Local) -> R,
+{
+    let mut f = Some(f);
+    HANDLE
+        .try_with(|handle| {
+            let mut local = handle.current.get();
+            if local.is_null() {
+                local = handle.default.get_or_init(|| collector().register()).local;
+            }
+            (f.take().unwrap())(local)
+        })
+        .unwrap_or_else(|_| {
+            // The thread is exiting, so pin a temporary participant.
+            let handle = collector().register();
+            (f.take().unwrap())(handle.local)
+        })
+}
+
+/// Makes `collector` the default collector, unless the default collector has already been used.
+pub(crate) fn install_default(collector: Collector) -> Result<(), Collector> {
+    *lock_adopted() = Some(collector.clone());
+    if *default_collector() == collector {
+        Ok(())
+    } else {
+        lock_adopted().take();
+        Err(collector)
+    }
+}
+
+/// Returns the collector of the current thread: the innermost entered collector, or the default
+/// collector.
+pub(crate) fn current_collector<F, R>(f: F) -> R
 where
-    F: FnMut(&LocalHandle) -> R,
+    F: FnOnce(&Collector) -> R,
 {
+    match scoped_local() {
+        // SAFETY: The participant is kept alive until the scope is exited.
+        Some(local) => f(unsafe { (*local).collector() }),
+        None => f(default_collector()),
+    }
+}
+
+/// Allocates `layout` from the arena of the collector of the current thread, if the collector
+/// was built with [`CollectorBuilder::rc_arena`](super::CollectorBuilder::rc_arena) and the
+/// layout fits into a slot.
+#[inline]
+pub(crate) fn alloc_from_rc_arena(layout: Layout) -> Option<NonNull<u8>> {
+    current_collector(|collector| {
+        let global = &collector.global;
+        if global.config.rc_arena {
+            global.rc_arena.alloc(layout)
+        } else {
+            None
+        }
+    })
+}
+
+/// Frees as much memory as possible in the collector of the current thread after an allocation
+/// has failed.
+#[cold]
+pub(crate) fn reclaim_on_alloc_failure() {
+    let guard = cs();
+    if let Some(local) = unsafe { guard.local.as_ref() } {
+        local.reclaim_on_alloc_failure(&guard);
+    }
+}
+
+/// Returns the participant that is reclaiming garbage on the current thread or that of the
+/// innermost collector entered by the current thread, if any.
+#[inline]
+fn scoped_local() -> Option<*const Local> {
     HANDLE
-        .try_with(|h| f(h))
-        .unwrap_or_else(|_| f(&collector().register()))
+        .try_with(|handle| handle.current.get())
+        .ok()
+        .filter(|local| !local.is_null())
+}
+
+/// Runs `f` with `local` as the participant of the current thread.
+fn with_current<F, R>(local: *const Local, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let Ok(prev) = HANDLE.try_with(|handle| handle.current.replace(local)) else {
+        return f();
+    };
+    defer! {
+        let _ = HANDLE.try_with(|handle| handle.current.set(prev));
+    }
+    f()
+}
+
+/// Runs `f`, which executes deferred functions of the collector of `guard`, with that collector
+/// as the collector of the current thread.
+///
+/// The destructors of the objects then stamp epochs of, and retire more garbage to, the collector
+/// that the objects belong to, rather than the collector that happens to be current.
+pub(crate) fn reclaiming<F, R>(guard: &Guard, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    if guard.local.is_null() {
+        return f();
+    }
+    with_current(guard.local, f)
+}
+
+/// Runs `f` with `collector` as the collector of the current thread.
+pub(crate) fn enter<F, R>(collector: &Collector, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    // The handle outlives the scope, in which its participant is the one of the thread.
+    let handle = collector.register();
+    with_current(handle.local, f)
 }
 
 #[inline]
 pub(crate) fn global_epoch() -> usize {
-    default_collector().global_epoch().value()
+    match scoped_local() {
+        // SAFETY: The participant is kept alive until the scope is exited.
+        Some(local) => unsafe { (*local).global().epoch.load(Ordering::Relaxed).value() },
+        None => default_collector().global_epoch().value(),
+    }
 }
 
 #[cfg(test)]
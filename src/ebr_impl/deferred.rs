@@ -2,12 +2,42 @@ use core::fmt;
 use core::marker::PhantomData;
 use core::mem::{self, MaybeUninit};
 use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::alloc_failure::boxed;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "deferred-inline-16")] {
+        /// Number of words a piece of `Data` can hold.
+        const DATA_WORDS: usize = 16;
+    } else if #[cfg(feature = "deferred-inline-8")] {
+        /// Number of words a piece of `Data` can hold.
+        const DATA_WORDS: usize = 8;
+    } else {
+        /// Number of words a piece of `Data` can hold.
+        ///
+        /// Three words should be enough for the majority of cases. For example, you can fit inside
+        /// it the function pointer together with a fat pointer representing an object that needs
+        /// to be destroyed.
+        const DATA_WORDS: usize = 3;
+    }
+}
+
+/// The number of deferred functions that have been boxed because they did not fit inline.
+static BOXED: AtomicUsize = AtomicUsize::new(0);
 
-/// Number of words a piece of `Data` can hold.
+/// Returns the number of deferred functions that have been boxed on the heap because they did not
+/// fit into the inline storage of a deferred function.
 ///
-/// Three words should be enough for the majority of cases. For example, you can fit inside it the
-/// function pointer together with a fat pointer representing an object that needs to be destroyed.
-const DATA_WORDS: usize = 3;
+/// The inline storage holds three words by default, and can be enlarged with the
+/// `deferred-inline-8` and `deferred-inline-16` features.
+pub fn boxed_deferreds() -> usize {
+    BOXED.load(Ordering::Relaxed)
+}
+
+/// The bit of `Deferred::bytes` that marks a function deferred by the collector for its own
+/// bookkeeping.
+const INTERNAL: usize = 1 << (usize::BITS - 1);
 
 /// Some space to keep a `FnOnce()` object on the stack.
 type Data = [usize; DATA_WORDS];
@@ -18,6 +48,8 @@ type Data = [usize; DATA_WORDS];
 pub(crate) struct Deferred {
     call: unsafe fn(*mut u8),
     data: MaybeUninit<Data>,
+    /// The number of bytes that the function frees, or zero if unknown.
+    bytes: usize,
     _marker: PhantomData<*mut ()>, // !Send + !Sync
 }
 
@@ -46,10 +78,12 @@ impl Deferred {
                 Deferred {
                     call: call::<F>,
                     data,
+                    bytes: 0,
                     _marker: PhantomData,
                 }
             } else {
-                let b: Box<F> = Box::new(f);
+                BOXED.fetch_add(1, Ordering::Relaxed);
+                let b: Box<F> = boxed(f);
                 let mut data = MaybeUninit::<Data>::uninit();
                 ptr::write(data.as_mut_ptr().cast::<Box<F>>(), b);
 
@@ -63,12 +97,40 @@ impl Deferred {
                 Deferred {
                     call: call::<F>,
                     data,
+                    bytes: 0,
                     _marker: PhantomData,
                 }
             }
         }
     }
 
+    /// Records that the function frees `bytes` bytes.
+    #[inline]
+    pub(crate) fn with_bytes(mut self, bytes: usize) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Marks the function as freeing memory of the collector itself, such as the nodes of its
+    /// queues, which is not counted as garbage.
+    #[inline]
+    pub(crate) fn internal(mut self) -> Self {
+        self.bytes = INTERNAL;
+        self
+    }
+
+    /// Returns the number of bytes that the function frees, or zero if unknown.
+    #[inline]
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes & !INTERNAL
+    }
+
+    /// Returns `true` if the function frees memory of the collector itself.
+    #[inline]
+    pub(crate) fn is_internal(&self) -> bool {
+        self.bytes & INTERNAL != 0
+    }
+
     /// Calls the function.
     #[inline]
     pub(crate) fn call(mut self) {
@@ -81,7 +143,7 @@ impl Deferred {
 mod tests {
     #![allow(dropping_copy_types)]
 
-    use super::Deferred;
+    use super::{boxed_deferreds, Deferred};
     use core::hint::black_box;
     use std::cell::Cell;
 
@@ -103,12 +165,14 @@ mod tests {
     #[test]
     fn on_heap() {
         let fired = &Cell::new(false);
-        let a = [0usize; 10];
+        let a = [0usize; 32];
 
+        let boxed = boxed_deferreds();
         let d = Deferred::new(move || {
             black_box(a);
             fired.set(true);
         });
+        assert!(boxed_deferreds() > boxed);
 
         assert!(!fired.get());
         d.call();
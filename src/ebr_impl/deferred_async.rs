@@ -0,0 +1,52 @@
+//! Cleanup futures that are handed to an executor after a grace period.
+//!
+//! [`Guard::defer_async`](super::Guard::defer_async) defers a future like any other function.
+//! When the deferred function runs, the future is handed to the spawner registered with
+//! [`Collector::set_async_spawner`](super::Collector::set_async_spawner) at the time it was
+//! deferred, so that the reclaiming thread does not wait for it. Without a spawner, the future is
+//! polled to completion on the reclaiming thread instead.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Waker};
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, Thread};
+
+/// A future deferred by [`Guard::defer_async`](super::Guard::defer_async), which is handed to
+/// the spawner of the collector after a grace period.
+pub type DeferredFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A spawner that runs deferred futures on an executor of the application.
+pub(crate) type AsyncSpawner = Arc<dyn Fn(DeferredFuture) + Send + Sync>;
+
+/// Hands `future` to `spawner`, or runs it to completion on the current thread if there is no
+/// spawner.
+pub(crate) fn spawn(future: DeferredFuture, spawner: Option<AsyncSpawner>) {
+    match spawner {
+        Some(spawner) => spawner(future),
+        None => block_on(future),
+    }
+}
+
+/// Wakes the thread that polls a future in [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` to completion, parking the current thread while it is pending.
+fn block_on(mut future: DeferredFuture) {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    while future.as_mut().poll(&mut cx).is_pending() {
+        thread::park();
+    }
+}
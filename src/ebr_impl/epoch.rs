@@ -74,6 +74,54 @@ impl Epoch {
     pub fn value(self) -> usize {
         self.unpinned().data >> 1
     }
+
+    /// Returns the unpinned epoch of the given value.
+    ///
+    /// The most significant bit of `value` is discarded, as epochs wrap around at
+    /// `usize::MAX / 2`.
+    #[inline]
+    pub fn from_value(value: usize) -> Self {
+        Epoch { data: value << 1 }
+    }
+
+    /// Returns `true` if `self` is later than `other`, taking the wrap-around into account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Epoch;
+    ///
+    /// let first = Epoch::from_value(usize::MAX / 2);
+    /// let second = Epoch::from_value(0);
+    /// assert!(second.is_after(first));
+    /// assert_eq!(second.wrapping_sub(first), 1);
+    /// ```
+    #[inline]
+    pub fn is_after(self, other: Self) -> bool {
+        self.wrapping_sub(other) > 0
+    }
+
+    /// Returns `true` if `self` is earlier than `other`, taking the wrap-around into account.
+    #[inline]
+    pub fn is_before(self, other: Self) -> bool {
+        self.wrapping_sub(other) < 0
+    }
+}
+
+impl From<usize> for Epoch {
+    /// Returns the unpinned epoch of the given value. See [`Epoch::from_value`].
+    #[inline]
+    fn from(value: usize) -> Self {
+        Self::from_value(value)
+    }
+}
+
+impl From<Epoch> for usize {
+    /// Returns the value of the epoch. See [`Epoch::value`].
+    #[inline]
+    fn from(epoch: Epoch) -> Self {
+        epoch.value()
+    }
 }
 
 /// An atomic value that holds an `Epoch`.
@@ -0,0 +1,88 @@
+//! A future that resolves after a grace period of a collector.
+//!
+//! [`GracePeriod`] takes an [`EpochToken`] when it is created and resolves once the token has
+//! expired. Whenever it is polled, it tries to advance the global epoch itself. If a participant
+//! holds the epoch back, the future registers an epoch advance hook that wakes it once another
+//! thread advances the epoch, so that no thread has to block or spin on its behalf.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::{Arc, Mutex};
+
+use super::collector::{Collector, EpochAdvanceHookId, EpochToken};
+
+/// A future that resolves once the grace period of the data unlinked before its creation has
+/// elapsed.
+///
+/// This is created by [`Collector::grace_period`].
+#[must_use = "futures do nothing unless polled"]
+pub struct GracePeriod {
+    collector: Collector,
+    token: EpochToken,
+    /// The waker of the last poll, which the hook wakes when the global epoch advances.
+    waker: Arc<Mutex<Option<Waker>>>,
+    hook: Option<EpochAdvanceHookId>,
+}
+
+impl GracePeriod {
+    pub(crate) fn new(collector: &Collector) -> Self {
+        Self {
+            collector: collector.clone(),
+            token: collector.pin_external(),
+            waker: Arc::new(Mutex::new(None)),
+            hook: None,
+        }
+    }
+
+    fn remove_hook(&mut self) {
+        if let Some(hook) = self.hook.take() {
+            self.collector.remove_epoch_advance_hook(hook);
+        }
+    }
+}
+
+impl Future for GracePeriod {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        *this.waker.lock().unwrap_or_else(|e| e.into_inner()) = Some(cx.waker().clone());
+        // The hook is installed before the epoch is checked, so that an advance in between is
+        // not missed.
+        if this.hook.is_none() {
+            let waker = this.waker.clone();
+            this.hook = Some(this.collector.on_epoch_advance(move |_| {
+                if let Some(waker) = &*waker.lock().unwrap_or_else(|e| e.into_inner()) {
+                    waker.wake_by_ref();
+                }
+            }));
+        }
+        let collector = &this.collector;
+        while !collector.is_expired(this.token) {
+            if collector
+                .with_guard(|guard| collector.try_advance(guard))
+                .is_err()
+            {
+                return Poll::Pending;
+            }
+        }
+        this.remove_hook();
+        Poll::Ready(())
+    }
+}
+
+impl Drop for GracePeriod {
+    fn drop(&mut self) {
+        self.remove_hook();
+    }
+}
+
+impl fmt::Debug for GracePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GracePeriod")
+            .field("token", &self.token)
+            .finish_non_exhaustive()
+    }
+}
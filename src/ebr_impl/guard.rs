@@ -1,10 +1,14 @@
 use core::fmt;
+use core::future::Future;
 use core::mem;
+use core::sync::atomic::Ordering;
 
 use scopeguard::defer;
 
 use super::deferred::Deferred;
+use super::deferred_async::{self, DeferredFuture};
 use super::internal::Local;
+use super::pin_site::PinSite;
 use super::RawShared;
 
 /// A RAII-style guard that keeps the current thread in an EBR critical section.
@@ -13,6 +17,125 @@ pub struct Guard {
 }
 
 impl Guard {
+    /// Stores a function so that it can be executed at some point after all currently pinned
+    /// threads get unpinned.
+    ///
+    /// This is the safe counterpart of [`Guard::defer_unchecked`]: since `f` is `Send` and
+    /// `'static`, it cannot borrow from the stack and may run on any thread. It is useful to delay
+    /// arbitrary cleanup, such as closing a channel, until a grace period has elapsed.
+    ///
+    /// If this method is called from an [`unprotected`] guard, the function will simply be
+    /// executed immediately.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc::channel;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let (sender, receiver) = channel();
+    /// let guard = circ::cs();
+    /// guard.defer(move || sender.send(1).unwrap());
+    /// drop(guard);
+    ///
+    /// while receiver.try_recv().is_err() {
+    ///     circ::cs().flush();
+    /// }
+    /// ```
+    pub fn defer<F, R>(&self, f: F)
+    where
+        F: FnOnce() -> R,
+        F: Send + 'static,
+    {
+        unsafe { self.defer_unchecked(f) }
+    }
+
+    /// Stores a cleanup future that is handed to the spawner of the collector after all
+    /// currently pinned threads get unpinned.
+    ///
+    /// This suits destructors that need asynchronous work, such as flushing to disk or notifying
+    /// a remote peer, which must not block the thread that reclaims the garbage. The future is
+    /// handed to the spawner registered with
+    /// [`Collector::set_async_spawner`](crate::Collector::set_async_spawner) when this method is
+    /// called, or polled to completion by the reclaiming thread if there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let spawned = Arc::new(Mutex::new(Vec::new()));
+    /// let collector = Collector::builder().deterministic(true).build();
+    /// collector.set_async_spawner({
+    ///     let spawned = spawned.clone();
+    ///     move |future| spawned.lock().unwrap().push(future)
+    /// });
+    ///
+    /// collector.enter(|| {
+    ///     circ::cs().defer_async(async {
+    ///         // ... flush the object to disk ...
+    ///     });
+    /// });
+    /// collector.collect_all(Duration::from_secs(1));
+    /// assert_eq!(spawned.lock().unwrap().len(), 1);
+    /// ```
+    pub fn defer_async<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let future: DeferredFuture = Box::pin(future);
+        let spawner = unsafe { self.local.as_ref() }.and_then(|local| {
+            local
+                .global()
+                .async_spawner
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        });
+        self.defer(move || deferred_async::spawn(future, spawner));
+    }
+
+    /// Stores a function like [`Guard::defer`], but returns it back instead of allocating if the
+    /// participant has no room left in its [fixed bags](crate::CollectorBuilder::fixed_bags).
+    ///
+    /// The participants of other collectors always have room, so the function is always stored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::Collector;
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::builder().fixed_bags(4).build();
+    /// let handle = collector.register();
+    /// let guard = handle.pin();
+    /// let mut retired = 0;
+    /// while guard.try_defer(|| ()).is_ok() {
+    ///     retired += 1;
+    /// }
+    /// // The bags are full until the critical section ends.
+    /// assert!(retired > 0);
+    /// ```
+    pub fn try_defer<F, R>(&self, f: F) -> Result<(), F>
+    where
+        F: FnOnce() -> R,
+        F: Send + 'static,
+    {
+        if let Some(local) = unsafe { self.local.as_ref() } {
+            if local.handoff().is_none() && local.is_fixed() && !local.reserve_fixed(self) {
+                return Err(f);
+            }
+        }
+        self.defer(f);
+        Ok(())
+    }
+
     /// Stores a function so that it can be executed at some point after all currently pinned
     /// threads get unpinned.
     ///
@@ -38,28 +161,39 @@ impl Guard {
     /// Apart from that, keep in mind that another thread may execute `f`, so anything accessed by
     /// the closure must be `Send`.
     pub(crate) unsafe fn defer_unchecked<F, R>(&self, f: F)
+    where
+        F: FnOnce() -> R,
+    {
+        self.defer_sized_unchecked(f, 0);
+    }
+
+    /// Stores a function that frees an object of `bytes` bytes so that it can be executed at some
+    /// point after all currently pinned threads get unpinned.
+    ///
+    /// The size is used to reclaim larger objects first. See [`Guard::defer_unchecked`] for
+    /// details.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`Guard::defer_unchecked`].
+    pub(crate) unsafe fn defer_sized_unchecked<F, R>(&self, f: F, bytes: usize)
     where
         F: FnOnce() -> R,
     {
         if let Some(local) = self.local.as_ref() {
-            local.defer(Deferred::new(move || drop(f())), self);
+            let deferred = Deferred::new(move || drop(f())).with_bytes(bytes);
+            local.defer(deferred, self);
         } else {
             drop(f());
         }
     }
 
-    /// Stores a destructor for an object so that it can be deallocated and dropped at some point
-    /// after all currently pinned threads get unpinned.
-    ///
-    /// This method first stores the destructor into the thread-local (or handle-local) cache. If
-    /// this cache becomes full, some destructors are moved into the global cache. At the same
-    /// time, some destructors from both local and global caches may get executed in order to
-    /// incrementally clean up the caches as they fill up.
+    /// Stores a destructor for an object of the collector itself, such as a node of its queues or
+    /// a participant that has exited, so that it is dropped after all currently pinned threads get
+    /// unpinned.
     ///
-    /// There is no guarantee when exactly the destructor will be executed. The only guarantee is
-    /// that it won't be executed until all currently pinned threads get unpinned. In theory, the
-    /// destructor might never run, but the epoch-based garbage collection will make an effort to
-    /// execute it reasonably soon.
+    /// The destructor is not counted as garbage, so that reclaiming garbage, which frees the nodes
+    /// of the queues, does not leave garbage behind in the counters.
     ///
     /// If this method is called from an [`unprotected`] guard, the destructor will simply be
     /// executed immediately.
@@ -71,8 +205,13 @@ impl Guard {
     ///
     /// Apart from that, keep in mind that another thread may execute the destructor, so the object
     /// must be sendable to other threads.
-    pub(crate) unsafe fn defer_destroy<T>(&self, ptr: RawShared<T>) {
-        self.defer_unchecked(move || unsafe { ptr.drop() });
+    pub(crate) unsafe fn defer_destroy_internal<T>(&self, ptr: RawShared<T>) {
+        if let Some(local) = self.local.as_ref() {
+            let deferred = Deferred::new(move || unsafe { ptr.drop() }).internal();
+            local.defer(deferred, self);
+        } else {
+            ptr.drop();
+        }
     }
 
     /// Clears up the thread-local cache of deferred functions by executing them or moving into the
@@ -87,12 +226,44 @@ impl Guard {
         }
     }
 
+    /// Returns the deferred functions in the local cache that have not been flushed yet.
+    ///
+    /// A maintenance loop may use it to decide when to call [`Guard::flush`]. An [`unprotected`]
+    /// guard has no cache, so nothing is pending on it.
+    pub fn pending_deferred(&self) -> PendingDeferred {
+        unsafe { self.local.as_ref() }
+            .map_or_else(PendingDeferred::default, Local::pending_deferred)
+    }
+
+    /// Returns `true` if the global epoch has not advanced since the critical section began.
+    ///
+    /// A critical section holds the global epoch back, so it advances at most one step while the
+    /// guard is alive. Once it has, the view of an optimistic reader may be stale, and the reader
+    /// can restart early, for example after [`Guard::reactivate`], instead of failing a later
+    /// compare-and-swap. An [`unprotected`] guard is always valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let mut guard = circ::cs();
+    /// if !guard.validate() {
+    ///     guard.reactivate();
+    /// }
+    /// ```
+    #[inline]
+    pub fn validate(&self) -> bool {
+        unsafe { self.local.as_ref() }.is_none_or(Local::is_epoch_current)
+    }
+
     /// Deactivate and reactivate the critical section.
     ///
     /// This method is useful when you don't want delay the advancement of the global epoch by
     /// holding an old epoch. For safety, you should not maintain any guard-based reference across
     /// the call (the latter is enforced by `&mut self`). The thread will only be repinned if this
     /// is the only active guard for the current thread.
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
     pub fn reactivate(&mut self) {
         if let Some(local) = unsafe { self.local.as_ref() } {
             local.repin();
@@ -106,10 +277,12 @@ impl Guard {
     /// and don't need to maintain any guard-based reference across the call (the latter is enforced
     /// by `&mut self`). The thread will only be unpinned if this is the only active guard for the
     /// current thread.
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
     pub fn reactivate_after<F, R>(&mut self, f: F) -> R
     where
         F: FnOnce() -> R,
     {
+        let site = PinSite::here();
         if let Some(local) = unsafe { self.local.as_ref() } {
             // We need to acquire a handle here to ensure the Local doesn't
             // disappear from under us.
@@ -120,7 +293,7 @@ impl Guard {
         // Ensure the Guard is re-pinned even if the function panics
         defer! {
             if let Some(local) = unsafe { self.local.as_ref() } {
-                mem::forget(local.pin());
+                mem::forget(local.pin_at(site));
                 local.release_handle();
             }
         }
@@ -128,6 +301,16 @@ impl Guard {
         f()
     }
 
+    /// Returns the global epoch of the collector of this guard, or of the collector of the current
+    /// thread if this guard is unprotected.
+    #[inline]
+    pub(crate) fn global_epoch(&self) -> usize {
+        match unsafe { self.local.as_ref() } {
+            Some(local) => local.global().epoch.load(Ordering::Relaxed).value(),
+            None => super::global_epoch(),
+        }
+    }
+
     /// Increases the manual collection counter, and perform collection if the counter reaches
     /// the threshold which is set by `set_manual_collection_interval`.
     pub(crate) fn incr_manual_collection(&self) {
@@ -137,6 +320,22 @@ impl Guard {
     }
 }
 
+/// Cloning a guard pins the participant once more, so that the critical section lasts until all
+/// of the clones are dropped. This lets the components of a thread each hold their own guard for
+/// the same critical section. A clone of an [`unprotected`] guard is unprotected as well.
+impl Clone for Guard {
+    #[inline]
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
+    fn clone(&self) -> Self {
+        match unsafe { self.local.as_ref() } {
+            Some(local) => local.pin(),
+            None => Guard {
+                local: core::ptr::null(),
+            },
+        }
+    }
+}
+
 impl Drop for Guard {
     #[inline]
     fn drop(&mut self) {
@@ -152,15 +351,66 @@ impl fmt::Debug for Guard {
     }
 }
 
+/// The deferred functions in the local cache of a participant, as returned by
+/// [`Guard::pending_deferred`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PendingDeferred {
+    /// The number of deferred functions.
+    pub count: usize,
+    /// The number of bytes the deferred functions free. This is approximate, as only the
+    /// destructors of objects record their size.
+    pub bytes: usize,
+}
+
 /// Returns a reference to a dummy guard that allows unprotected access to atomic pointers.
 ///
 /// This guard should be used in special occasions only. Note that it doesn't actually keep any
 /// thread pinned - it's just a fake guard that allows loading from [`crate::AtomicRc`]s unsafely.
 ///
+/// The dummy guard is a [`Guard`] like the ones returned by [`cs`](crate::cs), so the code that
+/// takes a `&Guard` runs unchanged both in critical sections and in single-threaded teardown,
+/// without a separate unprotected path.
+///
 /// # Safety
 ///
 /// Loading and dereferencing data from atomic shared pointers using this guard is safe only if
 /// the pointers are not being concurrently modified by other threads.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, unprotected_cs, AtomicRc, Guard, Rc, RcObject};
+/// use std::sync::atomic::Ordering;
+///
+/// struct Node {
+///     value: usize,
+///     next: AtomicRc<Node>,
+/// }
+///
+/// unsafe impl RcObject for Node {
+///     fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+///         out.push(self.next.take());
+///     }
+/// }
+///
+/// fn sum(head: &AtomicRc<Node>, guard: &Guard) -> usize {
+///     let mut sum = 0;
+///     let mut curr = head.load(Ordering::Acquire, guard);
+///     while let Some(node) = curr.as_ref() {
+///         sum += node.value;
+///         curr = node.next.load(Ordering::Acquire, guard);
+///     }
+///     sum
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let tail = AtomicRc::new(Node { value: 2, next: AtomicRc::null() });
+/// let head = AtomicRc::new(Node { value: 1, next: tail });
+/// assert_eq!(sum(&head, &cs()), 3);
+/// // No other thread accesses the list during teardown.
+/// assert_eq!(sum(&head, &unsafe { unprotected_cs() }), 3);
+/// ```
 #[inline]
 pub unsafe fn unprotected() -> Guard {
     Guard {
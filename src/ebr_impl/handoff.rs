@@ -0,0 +1,102 @@
+//! Pre-allocated buffers through which real-time participants hand off their garbage.
+//!
+//! A real-time participant never allocates or reclaims. Instead of its bag, it pushes deferred
+//! functions into a fixed ring buffer, which is drained by the other participants when they
+//! collect. The real-time thread is the only producer, and the draining participants exclude
+//! each other with a lock that the producer never takes.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, TryLockError};
+
+use super::deferred::Deferred;
+
+pub(crate) struct HandoffBuffer {
+    slots: Box<[UnsafeCell<MaybeUninit<Deferred>>]>,
+    /// The number of deferred functions that have been drained.
+    head: AtomicUsize,
+    /// The number of deferred functions that have been pushed.
+    tail: AtomicUsize,
+    /// Held by the participant that is draining the buffer.
+    drain: Mutex<()>,
+    /// The number of deferred functions that did not fit into the buffer.
+    overflows: AtomicUsize,
+}
+
+// The slots are written only by the producer and read only by the drainer holding `drain`, and
+// `head` and `tail` order the accesses.
+unsafe impl Send for HandoffBuffer {}
+unsafe impl Sync for HandoffBuffer {}
+
+impl HandoffBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "the capacity of a handoff buffer must be positive"
+        );
+        Self {
+            slots: (0..capacity)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            drain: Mutex::new(()),
+            overflows: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes `deferred` without allocating, or gives it back if the buffer is full.
+    ///
+    /// # Safety
+    ///
+    /// Only a single thread may push into the buffer.
+    pub(crate) unsafe fn push(&self, deferred: Deferred) -> Result<(), Deferred> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == self.slots.len() {
+            self.overflows.fetch_add(1, Ordering::Relaxed);
+            return Err(deferred);
+        }
+        (*self.slots[tail % self.slots.len()].get()).write(deferred);
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Moves the pushed deferred functions out of the buffer into `f`, unless another thread is
+    /// draining it.
+    pub(crate) fn drain<F: FnMut(Deferred)>(&self, mut f: F) {
+        let _lock = match self.drain.try_lock() {
+            Ok(lock) => lock,
+            // The lock protects no data, so poisoning is harmless.
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        let tail = self.tail.load(Ordering::Acquire);
+        let mut head = self.head.load(Ordering::Relaxed);
+        while head != tail {
+            let slot = &self.slots[head % self.slots.len()];
+            let deferred = unsafe { (*slot.get()).assume_init_read() };
+            head = head.wrapping_add(1);
+            self.head.store(head, Ordering::Release);
+            f(deferred);
+        }
+    }
+
+    /// Returns `true` if there is nothing to drain.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of deferred functions that did not fit into the buffer.
+    pub(crate) fn overflows(&self) -> usize {
+        self.overflows.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for HandoffBuffer {
+    fn drop(&mut self) {
+        // Run the functions that have never been drained.
+        self.drain(Deferred::call);
+    }
+}
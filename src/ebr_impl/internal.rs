@@ -5,7 +5,9 @@
 //! In order to track all participants in one place, we need some form of participant
 //! registration. When a participant is created, it is registered to a global lock-free
 //! singly-linked list of registries; and when a participant is leaving, it is unregistered from the
-//! list.
+//! list. The registry is split into several lists, chosen by hashing the id of the registering
+//! thread, so that the churn of short-lived threads is spread over the lists and a traversal that
+//! races with the unlinking of a leaving participant only restarts within one list.
 //!
 //! # Pinning
 //!
@@ -28,28 +30,57 @@
 //!
 //! Whenever a bag is pushed into a queue, the objects in some bags in the queue are collected and
 //! destroyed along the way. This design reduces contention on data structures. The global queue
-//! cannot be explicitly accessed: the only way to interact with it is by calling functions
-//! `defer()` that adds an object to the thread-local bag, or `collect()` that manually triggers
-//! garbage collection.
+//! may be split into shards, each participant pushing into the shard of its thread and stealing
+//! from the other shards once its own has no expired bags. Each shard keeps a queue per size class
+//! of the retired objects, and the queues of larger objects are drained first, so that a budgeted
+//! collection reclaims as much memory as possible. The global queue cannot be explicitly
+//! accessed: the only way to interact with it is by calling functions `defer()` that adds an
+//! object to the thread-local bag, or `collect()` that manually triggers garbage collection.
 //!
 //! Ideally each instance of concurrent data structure may have its own queue that gets fully
 //! destroyed as soon as the data structure gets dropped.
+//!
+//! # Allocation failure
+//!
+//! When a bag or a reference-counted object cannot be allocated, the collector runs the hook
+//! installed by the application, and then advances the epoch and reclaims all the expired garbage
+//! before retrying the allocation once. If a bag still cannot be allocated, the process-wide hook
+//! of [`set_alloc_failure_hook`](super::set_alloc_failure_hook) decides whether to retry again or
+//! to abort.
 
 use super::RawShared;
+use core::alloc::Layout;
 use core::cell::{Cell, UnsafeCell};
+use core::hash::{Hash, Hasher};
 use core::mem::{forget, replace, ManuallyDrop};
-use core::sync::atomic::{compiler_fence, Ordering};
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 use core::{fmt, ptr};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread::Thread;
+#[cfg(feature = "guard-tracking")]
+use std::time::Duration;
 
 use crossbeam_utils::CachePadded;
 use memoffset::offset_of;
 
-use super::collector::{Collector, LocalHandle};
+use super::alloc_failure::alloc_failed;
+use super::arena::RcArena;
+use super::collector::{Collector, LocalHandle, ParticipantInfo, PinCollection};
+use super::default::reclaiming;
 use super::deferred::Deferred;
+use super::deferred_async::AsyncSpawner;
 use super::epoch::{AtomicEpoch, Epoch};
-use super::guard::{unprotected, Guard};
+use super::guard::{unprotected, Guard, PendingDeferred};
+use super::handoff::HandoffBuffer;
+use super::pin_site::PinSite;
+#[cfg(feature = "guard-tracking")]
+use super::pin_site::DEFAULT_LONG_GUARD_THRESHOLD;
 use super::sync::list::{Entry, IsElement, IterError, List};
 use super::sync::queue::Queue;
+#[cfg(feature = "type-stats")]
+use super::type_stats::TypeStatsTable;
 
 // /// Maximum number of objects a bag can contain.
 // static mut MAX_OBJECTS: usize = 64;
@@ -64,6 +95,21 @@ dyntls::lazy_static! {
     static ref MANUAL_EVENTS_BETWEEN_COLLECT: usize = 64;
 }
 
+/// The number of size classes of retired objects.
+pub(crate) const SIZE_CLASSES: usize = 4;
+
+/// Returns the size class of a deferred function that frees `bytes` bytes. Larger objects have
+/// higher classes, and functions of unknown size are in the lowest class.
+#[inline]
+fn size_class(bytes: usize) -> usize {
+    match bytes {
+        0..=255 => 0,
+        256..=4095 => 1,
+        4096..=65535 => 2,
+        _ => 3,
+    }
+}
+
 /// A bag of deferred functions.
 pub(crate) struct Bag(Vec<Deferred>);
 
@@ -76,11 +122,52 @@ impl Bag {
         Self::default()
     }
 
+    /// Returns a new, empty bag, or `None` if it cannot be allocated.
+    fn try_new() -> Option<Self> {
+        let mut deferreds = Vec::new();
+        deferreds.try_reserve_exact(unsafe { *MAX_OBJECTS }).ok()?;
+        Some(Bag(deferreds))
+    }
+
+    /// Returns a bag without capacity, which does not allocate.
+    fn unallocated() -> Self {
+        Bag(Vec::new())
+    }
+
+    /// Returns the layout of the allocation of a bag.
+    fn layout() -> Layout {
+        Layout::array::<Deferred>(unsafe { *MAX_OBJECTS }).unwrap()
+    }
+
     /// Returns `true` if the bag is empty.
     pub(crate) fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
+    /// Returns the number of deferred functions in the bag.
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns the number of deferred functions in the bag that are counted as garbage, leaving
+    /// out those of the bookkeeping of the collector.
+    pub(crate) fn counted(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|deferred| !deferred.is_internal())
+            .count()
+    }
+
+    /// Returns `true` if another deferred function fits into the bag.
+    fn has_room(&self) -> bool {
+        self.0.len() < self.0.capacity()
+    }
+
+    /// Returns the number of bytes that the deferred functions in the bag free.
+    pub(crate) fn bytes(&self) -> usize {
+        self.0.iter().map(Deferred::bytes).sum()
+    }
+
     /// Attempts to insert a deferred function into the bag.
     ///
     /// Returns `Ok(())` if successful, and `Err(deferred)` for the given `deferred` if the bag is
@@ -90,7 +177,7 @@ impl Bag {
     ///
     /// It should be safe for another thread to execute the given function.
     pub(crate) unsafe fn try_push(&mut self, deferred: Deferred) -> Result<(), Deferred> {
-        if self.0.len() < self.0.capacity() {
+        if self.has_room() {
             self.0.push(deferred);
             Ok(())
         } else {
@@ -98,9 +185,16 @@ impl Bag {
         }
     }
 
+    /// Calls all deferred functions in the bag, leaving it empty.
+    fn call_all(&mut self) {
+        for deferred in self.0.drain(..) {
+            deferred.call();
+        }
+    }
+
     /// Seals the bag with the given epoch.
     fn seal(self, epoch: Epoch) -> SealedBag {
-        SealedBag { epoch, _bag: self }
+        SealedBag { epoch, bag: self }
     }
 }
 
@@ -113,9 +207,7 @@ impl Default for Bag {
 impl Drop for Bag {
     fn drop(&mut self) {
         // Call all deferred functions.
-        for deferred in self.0.drain(..) {
-            deferred.call();
-        }
+        self.call_all();
     }
 }
 
@@ -130,7 +222,7 @@ impl fmt::Debug for Bag {
 #[derive(Default, Debug)]
 struct SealedBag {
     epoch: Epoch,
-    _bag: Bag,
+    bag: Bag,
 }
 
 /// It is safe to share `SealedBag` because `is_expired` only inspects the epoch.
@@ -139,47 +231,472 @@ unsafe impl Sync for SealedBag {}
 impl SealedBag {
     /// Checks if it is safe to drop the bag w.r.t. the given global epoch.
     fn is_expired(&self, global_epoch: Epoch) -> bool {
-        // A pinned participant can witness at most one epoch advancement. Therefore, any bag that
-        // is within one epoch of the current one cannot be destroyed yet.
-        // NOTE: This version of EBR maintain sepoch skew of threads ≤ 1 and reclaim garbages
-        // three epochs ago.
-        global_epoch.wrapping_sub(self.epoch) >= 3
+        is_expired(self.epoch, global_epoch)
+    }
+}
+
+/// Checks if it is safe to reclaim the garbage sealed with `epoch` w.r.t. the given global epoch.
+#[inline]
+pub(crate) fn is_expired(epoch: Epoch, global_epoch: Epoch) -> bool {
+    // A pinned participant can witness at most one epoch advancement. Therefore, any bag that
+    // is within one epoch of the current one cannot be destroyed yet.
+    // NOTE: This version of EBR maintain sepoch skew of threads ≤ 1 and reclaim garbages
+    // three epochs ago.
+    global_epoch.wrapping_sub(epoch) >= 3
+}
+
+/// The configuration of a garbage collector.
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct Config {
+    /// Whether the epoch is advanced only by `Collector::advance_frame`.
+    pub(crate) frame_based: bool,
+
+    /// Whether the epoch is advanced and the garbage is reclaimed only by explicit calls.
+    pub(crate) deterministic: bool,
+
+    /// The number of shards of the global queue, where zero means one.
+    pub(crate) shards: usize,
+
+    /// When the participants collect on their own, unless overridden by their handles.
+    pub(crate) pin_collection: PinCollection,
+
+    /// Whether small reference-counted objects are allocated from the arena of the collector.
+    pub(crate) rc_arena: bool,
+
+    /// The number of bags that each participant seals and reclaims by itself instead of pushing
+    /// them into the global queue, where zero disables the mode.
+    pub(crate) fixed_bags: usize,
+
+    /// How long a guard may be held before it is reported, where `None` means the default.
+    #[cfg(feature = "guard-tracking")]
+    pub(crate) long_guard_threshold: Option<Duration>,
+}
+
+impl Config {
+    /// Returns `true` if the collector never advances the epoch or collects by itself.
+    #[inline]
+    pub(crate) fn is_explicit(&self) -> bool {
+        self.frame_based || self.deterministic
+    }
+
+    /// Returns how long a guard may be held before it is reported.
+    #[cfg(feature = "guard-tracking")]
+    #[inline]
+    pub(crate) fn long_guard_threshold(&self) -> Duration {
+        self.long_guard_threshold
+            .unwrap_or(DEFAULT_LONG_GUARD_THRESHOLD)
     }
 }
 
 /// The global data for a garbage collector.
 pub(crate) struct Global {
-    /// The intrusive linked list of `Local`s.
-    locals: List<Local>,
+    /// The shards of the intrusive linked list of `Local`s.
+    locals: Box<[CachePadded<List<Local>>]>,
 
-    /// The global queue of bags of deferred functions.
-    queue: Queue<SealedBag>,
+    /// The shards of the global queue of bags of deferred functions, each with a queue per size
+    /// class.
+    queues: Box<[CachePadded<[Queue<SealedBag>; SIZE_CLASSES]>]>,
+
+    /// The bags left by the participants that have exited, which are drained before `queues`.
+    orphans: Queue<SealedBag>,
+
+    /// The number of bags in `orphans`.
+    pub(crate) orphaned: AtomicUsize,
+
+    /// The number of deferred functions that did not fit into the fixed bags of the participants.
+    pub(crate) fixed_overflows: AtomicUsize,
 
     /// The global epoch.
     pub(crate) epoch: CachePadded<AtomicEpoch>,
+
+    /// The number of deferred functions that have ever been pushed into the queue.
+    pub(crate) sealed: AtomicUsize,
+
+    /// The number of deferred functions that have been executed by `collect`.
+    pub(crate) reclaimed: AtomicUsize,
+
+    /// The number of bytes that have been retired and not reclaimed yet, counting only the
+    /// deferred functions whose sizes are known.
+    pub(crate) unreclaimed_bytes: AtomicUsize,
+
+    /// The peak of `unreclaimed_bytes` since the collector was created or the peak was reset.
+    pub(crate) peak_unreclaimed_bytes: AtomicUsize,
+
+    /// The number of live [`Lease`](crate::lease::Lease)s.
+    pub(crate) leases: AtomicUsize,
+
+    /// The hook that is run when an allocation fails, before the garbage is reclaimed.
+    pub(crate) alloc_failure_hook: Mutex<Option<AllocFailureHook>>,
+
+    /// The executor that runs the expired bags popped from the queues as a [`DropExecutor`], or
+    /// null if there is none.
+    ///
+    /// It is read for every recycled bag, so it is kept in an atomic rather than behind a lock.
+    pub(crate) drop_executor: AtomicPtr<()>,
+
+    /// The spawner of the futures deferred by `Guard::defer_async`.
+    pub(crate) async_spawner: Mutex<Option<AsyncSpawner>>,
+
+    /// The hooks that are run whenever the global epoch advances, with their ids.
+    pub(crate) epoch_advance_hooks: Mutex<Vec<(usize, EpochAdvanceHook)>>,
+
+    /// Whether `epoch_advance_hooks` is non-empty, so that advancing does not lock it otherwise.
+    pub(crate) has_epoch_advance_hooks: AtomicBool,
+
+    /// The id of the next hook in `epoch_advance_hooks`.
+    pub(crate) next_hook_id: AtomicUsize,
+
+    /// The handoff buffers of the real-time participants.
+    pub(crate) handoffs: Mutex<Vec<Arc<HandoffBuffer>>>,
+
+    /// The configuration given by the `CollectorBuilder`.
+    pub(crate) config: Config,
+
+    /// The slabs of the reference-counted objects. It is dropped after the queues, whose garbage
+    /// may free its slots.
+    pub(crate) rc_arena: RcArena,
+
+    /// The counters of the retired objects of each type.
+    #[cfg(feature = "type-stats")]
+    pub(crate) type_stats: TypeStatsTable,
+
+    /// The number of guards that have been held for longer than the threshold.
+    #[cfg(feature = "guard-tracking")]
+    pub(crate) long_guards: AtomicUsize,
+}
+
+/// A hook that lets the application free memory when an allocation fails.
+pub(crate) type AllocFailureHook = Arc<dyn Fn() + Send + Sync>;
+
+/// An executor that runs the deferred functions of an expired bag, typically on a thread pool.
+pub(crate) type DropExecutor = fn(Box<dyn FnOnce() + Send>);
+
+/// A hook that is run with the new global epoch whenever it advances.
+pub(crate) type EpochAdvanceHook = Arc<dyn Fn(Epoch) + Send + Sync>;
+
+/// Returns a hash of the id of the current thread.
+pub(crate) fn current_thread_hash() -> usize {
+    let mut hasher = DefaultHasher::new();
+    std::thread::current().id().hash(&mut hasher);
+    hasher.finish() as usize
 }
 
 impl Global {
     const COLLECTS_TRIALS: usize = 16;
 
+    /// The number of shards of the list of `Local`s.
+    const LOCALS_SHARDS: usize = 8;
+
     /// Creates a new global data for garbage collection.
     #[inline]
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(config: Config) -> Self {
         Self {
-            locals: List::new(),
-            queue: Queue::new(),
+            locals: (0..Self::LOCALS_SHARDS)
+                .map(|_| CachePadded::new(List::new()))
+                .collect(),
+            queues: (0..config.shards.max(1))
+                .map(|_| CachePadded::new(core::array::from_fn(|_| Queue::new())))
+                .collect(),
+            orphans: Queue::new(),
+            orphaned: AtomicUsize::new(0),
+            fixed_overflows: AtomicUsize::new(0),
             epoch: CachePadded::new(AtomicEpoch::new(Epoch::starting())),
+            sealed: AtomicUsize::new(0),
+            reclaimed: AtomicUsize::new(0),
+            unreclaimed_bytes: AtomicUsize::new(0),
+            peak_unreclaimed_bytes: AtomicUsize::new(0),
+            leases: AtomicUsize::new(0),
+            alloc_failure_hook: Mutex::new(None),
+            drop_executor: AtomicPtr::new(ptr::null_mut()),
+            async_spawner: Mutex::new(None),
+            epoch_advance_hooks: Mutex::new(Vec::new()),
+            has_epoch_advance_hooks: AtomicBool::new(false),
+            next_hook_id: AtomicUsize::new(0),
+            handoffs: Mutex::new(Vec::new()),
+            config,
+            rc_arena: RcArena::new(),
+            #[cfg(feature = "type-stats")]
+            type_stats: TypeStatsTable::default(),
+            #[cfg(feature = "guard-tracking")]
+            long_guards: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of shards of the global queue.
+    #[inline]
+    pub(crate) fn shards(&self) -> usize {
+        self.queues.len()
+    }
+
+    /// Returns the shard of the global queue for the current thread.
+    fn shard_of_current_thread(&self) -> usize {
+        if self.shards() == 1 {
+            return 0;
+        }
+        current_thread_hash() % self.shards()
+    }
+
+    /// Returns the shard of the global queue that the participant of `guard` pushes into.
+    #[inline]
+    fn home_shard(&self, guard: &Guard) -> usize {
+        unsafe { guard.local.as_ref() }.map_or(0, |local| local.shard)
+    }
+
+    /// Returns an empty bag from the pool of the participant of `guard`, or allocates one if the
+    /// pool is empty. Returns `None` if the allocation fails.
+    fn take_bag(guard: &Guard) -> Option<Bag> {
+        unsafe { guard.local.as_ref() }
+            .and_then(Local::take_pooled_bag)
+            .or_else(Bag::try_new)
+    }
+
+    /// Records that a bag of deferred functions freeing `bytes` bytes in total has been sealed,
+    /// raising the peak if needed.
+    ///
+    /// The bytes are counted by bag rather than by deferred function, so that retiring does not
+    /// update the counters shared by all the participants.
+    #[inline]
+    fn retired_bytes(&self, bytes: usize) {
+        if bytes > 0 {
+            let now = self.unreclaimed_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+            self.peak_unreclaimed_bytes
+                .fetch_max(now, Ordering::Relaxed);
         }
     }
 
-    /// Pushes the bag into the global queue and replaces the bag with a new empty bag.
-    pub(crate) fn push_bag(&self, bag: &mut Bag, guard: &Guard) {
-        let bag = replace(bag, Bag::new());
+    /// Records that deferred functions freeing `bytes` bytes in total have been reclaimed.
+    #[inline]
+    fn reclaimed_bytes(&self, bytes: usize) {
+        if bytes > 0 {
+            self.unreclaimed_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Installs the executor of the expired bags, or removes it if `None`.
+    pub(crate) fn set_drop_executor(&self, executor: Option<DropExecutor>) {
+        let ptr = executor.map_or(ptr::null_mut(), |executor| executor as *mut ());
+        self.drop_executor.store(ptr, Ordering::Release);
+    }
+
+    /// Returns the executor of the expired bags, if any.
+    #[inline]
+    fn drop_executor(&self) -> Option<DropExecutor> {
+        let ptr = self.drop_executor.load(Ordering::Acquire);
+        // SAFETY: A non-null pointer is a `DropExecutor` stored by `set_drop_executor`.
+        (!ptr.is_null()).then(|| unsafe { core::mem::transmute::<*mut (), DropExecutor>(ptr) })
+    }
+
+    /// Executes the deferred functions in `bag`, which has been popped in the critical section of
+    /// `guard`, and returns it to the pool of the participant of `guard`, unless the pool is full.
+    fn recycle_bag(&self, mut bag: Bag, guard: &Guard) {
+        self.reclaimed_bytes(bag.bytes());
+        if !bag.is_empty() {
+            if let Some(executor) = self.drop_executor() {
+                // The bag calls its deferred functions when dropped, and is not recycled. The
+                // executor may run it on any thread, so it enters the collector of the garbage
+                // there, as `reclaiming` does on this thread.
+                let collector =
+                    unsafe { guard.local.as_ref() }.map(|local| local.collector().clone());
+                executor(Box::new(move || match collector {
+                    Some(collector) => collector.enter(|| drop(bag)),
+                    None => drop(bag),
+                }));
+                return;
+            }
+        }
+        // The deferred functions may themselves defer functions, so they are called before the
+        // pool is borrowed.
+        reclaiming(guard, || bag.call_all());
+        if let Some(local) = unsafe { guard.local.as_ref() } {
+            local.pool_bag(bag);
+        }
+    }
+
+    /// Pushes the bag of size class `class` into the global queue and replaces the bag with an
+    /// empty bag from the pool.
+    ///
+    /// Returns `false` if a new bag cannot be allocated, in which case the bag is left without
+    /// capacity.
+    pub(crate) fn push_bag(&self, bag: &mut Bag, class: usize, guard: &Guard) -> bool {
+        let full = replace(bag, Bag::unallocated());
+
+        if !full.is_empty() {
+            let epoch = self.seal_epoch();
+            self.sealed.fetch_add(full.counted(), Ordering::Relaxed);
+            self.retired_bytes(full.bytes());
+            self.queues[self.home_shard(guard)][class].push(full.seal(epoch), guard);
+        }
+
+        match Self::take_bag(guard) {
+            Some(empty) => {
+                *bag = empty;
+                true
+            }
+            None => false,
+        }
+    }
 
+    /// Returns the epoch to seal the garbage that has been unlinked before the call with.
+    #[inline]
+    pub(crate) fn seal_epoch(&self) -> Epoch {
         atomic::fence(Ordering::SeqCst);
+        self.epoch.load(Ordering::Relaxed)
+    }
 
-        let epoch = self.epoch.load(Ordering::Relaxed);
-        self.queue.push(bag.seal(epoch), guard);
+    /// Pushes the bag of a participant that is exiting into the queue of orphaned bags, leaving
+    /// the bag without capacity.
+    fn push_orphan(&self, bag: &mut Bag, guard: &Guard) {
+        let full = replace(bag, Bag::unallocated());
+        if !full.is_empty() {
+            let epoch = self.seal_epoch();
+            self.sealed.fetch_add(full.counted(), Ordering::Relaxed);
+            self.retired_bytes(full.bytes());
+            self.orphaned.fetch_add(1, Ordering::Relaxed);
+            self.orphans.push(full.seal(epoch), guard);
+        }
+    }
+
+    /// Checks the invariants of the counters, the epochs of the participants and the epochs of
+    /// the sealed bags, returning a description of the first one that is broken.
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_invariants(&self, guard: &Guard) -> Result<(), String> {
+        let sealed = self.sealed.load(Ordering::Acquire);
+        let reclaimed = self.reclaimed.load(Ordering::Acquire);
+        if reclaimed > sealed {
+            return Err(format!(
+                "{reclaimed} deferred functions have been reclaimed, but only {sealed} sealed"
+            ));
+        }
+
+        let global_epoch = self.epoch.load(Ordering::Acquire);
+        if global_epoch.is_pinned() {
+            return Err("the global epoch is pinned".into());
+        }
+        for local in self.locals.iter().flat_map(|locals| locals.iter(guard)) {
+            let local = match local {
+                // A concurrent thread stalled the iteration.
+                Err(IterError::Stalled) => break,
+                Ok(local) => local,
+            };
+            let epoch = local.epoch.load(Ordering::Acquire);
+            if epoch.is_pinned() {
+                let lag = global_epoch.wrapping_sub(epoch.unpinned());
+                if !(0..=1).contains(&lag) {
+                    return Err(format!(
+                        "a participant is pinned in epoch {}, but the global epoch is {}",
+                        epoch.unpinned().value(),
+                        global_epoch.value()
+                    ));
+                }
+            }
+        }
+
+        let mut result = Ok(());
+        let mut orphans = 0;
+        let mut check_bag = |bag: &SealedBag| {
+            if result.is_ok() && bag.epoch.is_after(global_epoch) {
+                result = Err(format!(
+                    "a bag is sealed in epoch {}, after the global epoch {}",
+                    bag.epoch.value(),
+                    global_epoch.value()
+                ));
+            }
+        };
+        for queues in self.queues.iter() {
+            for queue in queues.iter() {
+                queue.for_each(&mut check_bag, guard);
+            }
+        }
+        self.orphans.for_each(
+            |bag| {
+                orphans += 1;
+                check_bag(bag);
+            },
+            guard,
+        );
+        result?;
+
+        let orphaned = self.orphaned.load(Ordering::Acquire);
+        if orphans != orphaned {
+            return Err(format!(
+                "{orphans} orphaned bags are queued, but {orphaned} are counted"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Frees as much memory as possible after an allocation has failed.
+    ///
+    /// The hook of the application is run first, and then the global epoch is advanced if
+    /// possible and all the expired garbage is reclaimed.
+    #[cold]
+    pub(crate) fn reclaim_on_alloc_failure(&self, guard: &Guard) {
+        let hook = self
+            .alloc_failure_hook
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(hook) = hook {
+            hook();
+        }
+        if guard.local.is_null() {
+            return;
+        }
+        self.steal_idle_bags(guard);
+        if !self.config.is_explicit() {
+            let _ = self.try_advance(guard);
+        }
+        self.reclaim(guard, usize::MAX);
+    }
+
+    /// Moves the bags of the participants that are not pinned into the global queue, so that the
+    /// garbage of idle threads is reclaimed too. Returns the number of deferred functions moved.
+    pub(crate) fn steal_idle_bags(&self, guard: &Guard) -> usize {
+        let mut stolen = 0;
+        for local in self.locals.iter().flat_map(|locals| locals.iter(guard)) {
+            match local {
+                // A concurrent thread stalled the iteration. The rest is left for later.
+                Err(IterError::Stalled) => break,
+                Ok(local) => stolen += local.steal_bags(self, guard),
+            }
+        }
+        stolen
+    }
+
+    /// Returns the number of empty bags in the pools of the registered participants.
+    pub(crate) fn pooled_bags(&self, guard: &Guard) -> usize {
+        let mut pooled = 0;
+        for locals in self.locals.iter() {
+            let mut shard = 0;
+            for local in locals.iter(guard) {
+                match local {
+                    // The iteration restarts from the head of the list.
+                    Err(IterError::Stalled) => shard = 0,
+                    Ok(local) => shard += local.pooled_bags(),
+                }
+            }
+            pooled += shard;
+        }
+        pooled
+    }
+
+    /// Returns reports of the registered participants, skipping the one of `guard` if `skip_own`
+    /// is `true`.
+    pub(crate) fn participants(&self, guard: &Guard, skip_own: bool) -> Vec<ParticipantInfo> {
+        let mut participants = Vec::new();
+        for locals in self.locals.iter() {
+            let mut shard = Vec::new();
+            for local in locals.iter(guard) {
+                match local {
+                    // The iteration restarts from the head of the list.
+                    Err(IterError::Stalled) => shard.clear(),
+                    Ok(local) if skip_own && ptr::eq(local, guard.local) => {}
+                    Ok(local) => shard.push(local.info()),
+                }
+            }
+            participants.append(&mut shard);
+        }
+        participants
     }
 
     /// Collects several bags from the global queue and executes deferred functions in them.
@@ -195,24 +712,107 @@ impl Global {
             local.manual_count.set(0);
             local.pin_count.set(0);
         }
-        self.try_advance(guard);
+        if self.config.is_explicit() {
+            // The epoch is advanced and the garbage is reclaimed only by explicit calls.
+            return;
+        }
+        let _ = self.try_advance(guard);
 
         debug_assert!(
             !guard.local.is_null(),
             "An unprotected guard cannot be used to collect global garbages."
         );
+        self.reclaim(guard, Self::COLLECTS_TRIALS);
+    }
+
+    /// Advances the global epoch if possible, and then executes all the deferred functions in the
+    /// expired bags.
+    pub(crate) fn advance_frame(&self, guard: &Guard) {
+        debug_assert!(
+            !guard.local.is_null(),
+            "An unprotected guard cannot be used to advance a frame."
+        );
+        let _ = self.try_advance(guard);
+        self.reclaim(guard, usize::MAX);
+    }
 
-        for _ in 0..Self::COLLECTS_TRIALS {
-            match self.queue.try_pop_if(
+    /// Moves the garbage of the real-time participants into the bag of the participant of
+    /// `guard`, forgetting the buffers of the participants that have left.
+    fn drain_handoffs(&self, guard: &Guard) {
+        let Some(local) = (unsafe { guard.local.as_ref() }) else {
+            return;
+        };
+        let mut handoffs = self.handoffs.lock().unwrap_or_else(|e| e.into_inner());
+        if handoffs.is_empty() {
+            return;
+        }
+        handoffs.retain(|buffer| Arc::strong_count(buffer) > 1 || !buffer.is_empty());
+        let buffers = handoffs.clone();
+        drop(handoffs);
+        for buffer in buffers {
+            // Sealing the garbage later than it was retired is conservative.
+            buffer.drain(|deferred| unsafe { local.defer(deferred, guard) });
+        }
+    }
+
+    /// Pops at most `trials` expired bags from the global queue and executes them.
+    pub(crate) fn reclaim(&self, guard: &Guard, trials: usize) {
+        self.reclaim_budgeted(guard, trials, usize::MAX);
+    }
+
+    /// Pops expired bags from the global queue and executes them, until `trials` bags have been
+    /// popped or at least `bytes` bytes have been freed. Returns the number of bytes freed.
+    ///
+    /// The orphaned bags of the participants that have exited are popped first, and then the bags
+    /// of larger objects. Within a size class, the bags are popped from the shard of the
+    /// participant of `guard` first, and then stolen from the other shards in order.
+    pub(crate) fn reclaim_budgeted(&self, guard: &Guard, trials: usize, bytes: usize) -> usize {
+        self.drain_handoffs(guard);
+        let home = self.home_shard(guard);
+        let mut trials = trials;
+        let mut freed = 0;
+        while trials > 0 && freed < bytes {
+            match self.orphans.try_pop_if(
                 |sealed_bag: &SealedBag| sealed_bag.is_expired(self.epoch.load(Ordering::Relaxed)),
                 guard,
             ) {
                 None => break,
                 Some(sealed_bag) => {
-                    drop(sealed_bag);
+                    trials -= 1;
+                    self.orphaned.fetch_sub(1, Ordering::Relaxed);
+                    freed += sealed_bag.bag.bytes();
+                    self.reclaimed
+                        .fetch_add(sealed_bag.bag.counted(), Ordering::Relaxed);
+                    self.recycle_bag(sealed_bag.bag, guard);
                 }
             }
         }
+        for class in (0..SIZE_CLASSES).rev() {
+            for i in 0..self.shards() {
+                let queue = &self.queues[(home + i) % self.shards()][class];
+                while trials > 0 && freed < bytes {
+                    match queue.try_pop_if(
+                        |sealed_bag: &SealedBag| {
+                            sealed_bag.is_expired(self.epoch.load(Ordering::Relaxed))
+                        },
+                        guard,
+                    ) {
+                        None => break,
+                        Some(sealed_bag) => {
+                            trials -= 1;
+                            freed += sealed_bag.bag.bytes();
+                            self.reclaimed
+                                .fetch_add(sealed_bag.bag.counted(), Ordering::Relaxed);
+                            self.recycle_bag(sealed_bag.bag, guard);
+                        }
+                    }
+                }
+                if trials == 0 || freed >= bytes {
+                    return freed;
+                }
+            }
+        }
+        freed
     }
 
     /// Attempts to advance the global epoch.
@@ -220,24 +820,25 @@ impl Global {
     /// The global epoch can advance only if all currently pinned participants have been pinned in
     /// the current epoch.
     ///
-    /// Returns the current global epoch.
+    /// Returns the new global epoch if it has advanced, or the current one if a participant blocks
+    /// the advancement.
     ///
     /// `try_advance()` is annotated `#[cold]` because it is rarely called.
     #[cold]
-    pub(crate) fn try_advance(&self, guard: &Guard) -> Epoch {
+    pub(crate) fn try_advance(&self, guard: &Guard) -> Result<Epoch, Epoch> {
         let global_epoch = self.epoch.load(Ordering::Relaxed);
         atomic::fence(Ordering::SeqCst);
 
-        // `Local`s are stored in a linked list because linked lists are fairly
+        // `Local`s are stored in linked lists because linked lists are fairly
         // easy to implement in a lock-free manner. However, traversal can be slow due to cache
         // misses and data dependencies. We should experiment with other data structures as well.
-        for local in self.locals.iter(guard) {
+        for local in self.locals.iter().flat_map(|locals| locals.iter(guard)) {
             match local {
                 Err(IterError::Stalled) => {
                     // A concurrent thread stalled this iteration. That thread might also try to
                     // advance the epoch, in which case we leave the job to it. Otherwise, the
                     // epoch will not be advanced.
-                    return global_epoch;
+                    return Err(global_epoch);
                 }
                 Ok(local) => {
                     let local_epoch = local.epoch.load(Ordering::Relaxed);
@@ -245,7 +846,7 @@ impl Global {
                     // If the participant was pinned in a different epoch, we cannot advance the
                     // global epoch just yet.
                     if local_epoch.is_pinned() && local_epoch.unpinned() != global_epoch {
-                        return global_epoch;
+                        return Err(global_epoch);
                     }
                 }
             }
@@ -255,13 +856,39 @@ impl Global {
         // All pinned participants were pinned in the current global epoch.
         // Now let's advance the global epoch...
         //
-        // Note that if another thread already advanced it before us, this store will simply
-        // overwrite the global epoch with the same value. This is true because `try_advance` was
-        // called from a thread that was pinned in `global_epoch`, and the global epoch cannot be
-        // advanced two steps ahead of it.
+        // Note that if another thread already advanced it before us, the global epoch is left as
+        // it is. This is true because `try_advance` was called from a thread that was pinned in
+        // `global_epoch`, and the global epoch cannot be advanced two steps ahead of it.
         let new_epoch = global_epoch.successor();
-        self.epoch.store(new_epoch, Ordering::Release);
-        new_epoch
+        let advanced = self
+            .epoch
+            .compare_exchange(
+                global_epoch,
+                new_epoch,
+                Ordering::Release,
+                Ordering::Relaxed,
+            )
+            .is_ok();
+        if advanced && self.has_epoch_advance_hooks.load(Ordering::Acquire) {
+            self.run_epoch_advance_hooks(new_epoch);
+        }
+        Ok(new_epoch)
+    }
+
+    /// Runs the hooks registered with `Collector::on_epoch_advance`.
+    #[cold]
+    fn run_epoch_advance_hooks(&self, new_epoch: Epoch) {
+        // The hooks are run outside of the lock, so that they may register or remove hooks.
+        let hooks: Vec<_> = self
+            .epoch_advance_hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(_, hook)| hook.clone())
+            .collect();
+        for hook in hooks {
+            hook(new_epoch);
+        }
     }
 }
 
@@ -275,11 +902,15 @@ pub(crate) struct Local {
     /// When all guards and handles get dropped, this reference is destroyed.
     collector: UnsafeCell<ManuallyDrop<Collector>>,
 
-    /// The local bag of deferred functions.
+    /// The local bags of deferred functions, one per size class.
     ///
     /// Note that removing the global garbage queue and using only the thread local bags
     /// will increase the memory consumption in a queue workload.
-    pub(crate) bag: UnsafeCell<Bag>,
+    pub(crate) bags: UnsafeCell<[Bag; SIZE_CLASSES]>,
+
+    /// Whether another participant is stealing `bags`, which it only does while this participant
+    /// is not pinned.
+    bags_locked: AtomicBool,
 
     /// The number of guards keeping this participant pinned.
     guard_count: Cell<usize>,
@@ -293,9 +924,45 @@ pub(crate) struct Local {
     pin_count: Cell<usize>,
     manual_count: Cell<usize>,
 
+    /// When this participant collects on its own.
+    pin_collection: Cell<PinCollection>,
+
     must_collect: Cell<bool>,
     collecting: Cell<bool>,
 
+    /// Whether the participant is reclaiming garbage because an allocation has failed.
+    reclaiming_on_alloc_failure: Cell<bool>,
+
+    /// The buffer for the garbage of a real-time participant, which neither allocates nor
+    /// reclaims.
+    handoff: UnsafeCell<Option<Arc<HandoffBuffer>>>,
+
+    /// Whether `handoff` has been set, read by the other threads.
+    realtime: AtomicBool,
+
+    /// The full bags of a participant of a collector with fixed bags, oldest first.
+    fixed_sealed: UnsafeCell<VecDeque<SealedBag>>,
+
+    /// The empty bags of a participant of a collector with fixed bags.
+    fixed_spare: UnsafeCell<Vec<Bag>>,
+
+    /// The empty bags of reclaimed garbage, which are reused when the bags of the participant are
+    /// sealed, so that retiring garbage does not allocate in the steady state.
+    bag_pool: UnsafeCell<Vec<Bag>>,
+
+    /// The number of bags in `bag_pool`, read by the other threads.
+    pooled_bags: AtomicUsize,
+
+    /// The shard of the global queue that this participant pushes its bags into.
+    shard: usize,
+
+    /// The thread that registered the participant.
+    thread: Thread,
+
+    /// Where and when the outermost guard has been created.
+    #[cfg(feature = "guard-tracking")]
+    pin_site: Cell<Option<PinSite>>,
+
     /// The local epoch.
     epoch: CachePadded<AtomicEpoch>,
 }
@@ -303,6 +970,9 @@ pub(crate) struct Local {
 impl Local {
     const COUNTS_BETWEEN_ADVANCE: usize = 64;
 
+    /// The maximum number of empty bags kept in the pool of a participant.
+    const POOLED_BAGS: usize = 16;
+
     /// Registers a new `Local` in the provided `Global`.
     pub(crate) fn register(collector: &Collector) -> LocalHandle {
         unsafe {
@@ -311,24 +981,71 @@ impl Local {
             let local = RawShared::from_owned(Local {
                 entry: Entry::default(),
                 collector: UnsafeCell::new(ManuallyDrop::new(collector.clone())),
-                bag: UnsafeCell::new(Bag::new()),
+                bags: UnsafeCell::new(core::array::from_fn(|_| Bag::new())),
+                bags_locked: AtomicBool::new(false),
+                realtime: AtomicBool::new(false),
                 guard_count: Cell::new(0),
                 handle_count: Cell::new(1),
                 advance_count: Cell::new(0),
                 prev_epoch: Cell::new(Epoch::starting()),
                 pin_count: Cell::new(0),
                 manual_count: Cell::new(0),
+                pin_collection: Cell::new(collector.global.config.pin_collection),
                 must_collect: Cell::new(false),
                 collecting: Cell::new(false),
+                reclaiming_on_alloc_failure: Cell::new(false),
+                handoff: UnsafeCell::new(None),
+                fixed_sealed: UnsafeCell::new(VecDeque::with_capacity(
+                    collector.global.config.fixed_bags,
+                )),
+                fixed_spare: UnsafeCell::new(
+                    (0..collector.global.config.fixed_bags)
+                        .map(|_| Bag::new())
+                        .collect(),
+                ),
+                bag_pool: UnsafeCell::new(Vec::with_capacity(Self::POOLED_BAGS)),
+                pooled_bags: AtomicUsize::new(0),
+                shard: collector.global.shard_of_current_thread(),
+                thread: std::thread::current(),
+                #[cfg(feature = "guard-tracking")]
+                pin_site: Cell::new(None),
                 epoch: CachePadded::new(AtomicEpoch::new(Epoch::starting())),
             });
-            collector.global.locals.insert(local, &unprotected());
+            let locals = &collector.global.locals;
+            locals[current_thread_hash() % locals.len()].insert(local, &unprotected());
             LocalHandle {
                 local: local.as_raw(),
             }
         }
     }
 
+    /// Takes an empty bag from the pool of the participant, if any.
+    ///
+    /// Must be called by the thread of the participant.
+    fn take_pooled_bag(&self) -> Option<Bag> {
+        let pool = unsafe { &mut *self.bag_pool.get() };
+        let bag = pool.pop();
+        self.pooled_bags.store(pool.len(), Ordering::Relaxed);
+        bag
+    }
+
+    /// Puts an empty bag into the pool of the participant, or drops it if the pool is full.
+    ///
+    /// Must be called by the thread of the participant.
+    fn pool_bag(&self, bag: Bag) {
+        let pool = unsafe { &mut *self.bag_pool.get() };
+        if pool.len() < Self::POOLED_BAGS {
+            pool.push(bag);
+            self.pooled_bags.store(pool.len(), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of empty bags in the pool of the participant.
+    #[inline]
+    pub(crate) fn pooled_bags(&self) -> usize {
+        self.pooled_bags.load(Ordering::Relaxed)
+    }
+
     /// Returns a reference to the `Global` in which this `Local` resides.
     #[inline]
     pub(crate) fn global(&self) -> &Global {
@@ -343,37 +1060,259 @@ impl Local {
 
     /// Returns `true` if the current participant is pinned.
     #[inline]
-    #[cfg(test)]
     pub(crate) fn is_pinned(&self) -> bool {
         self.guard_count.get() > 0
     }
 
-    /// Adds `deferred` to the thread-local bag.
+    /// Returns `true` if the global epoch is still the one the participant is pinned in.
+    #[inline]
+    pub(crate) fn is_epoch_current(&self) -> bool {
+        let global_epoch = self.global().epoch.load(Ordering::Acquire);
+        self.epoch.load(Ordering::Relaxed).unpinned() == global_epoch
+    }
+
+    /// Returns the handoff buffer if the participant is real-time.
+    #[inline]
+    pub(crate) fn handoff(&self) -> Option<&HandoffBuffer> {
+        unsafe { (*self.handoff.get()).as_deref() }
+    }
+
+    /// Makes the participant real-time, with a handoff buffer of `capacity` deferred functions.
+    pub(crate) fn set_realtime(&self, capacity: usize) {
+        let buffer = Arc::new(HandoffBuffer::new(capacity));
+        self.global()
+            .handoffs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(buffer.clone());
+        // The previous buffer, if any, stays registered until it is drained.
+        unsafe { *self.handoff.get() = Some(buffer) };
+        self.realtime.store(true, Ordering::Relaxed);
+    }
+
+    /// Sets when the participant collects on its own.
+    #[inline]
+    pub(crate) fn set_pin_collection(&self, pin_collection: PinCollection) {
+        self.pin_collection.set(pin_collection);
+        self.pin_count.set(0);
+    }
+
+    /// Adds `deferred` to the thread-local bag, or to the handoff buffer of a real-time
+    /// participant.
     ///
     /// # Safety
     ///
     /// It should be safe for another thread to execute the given function.
     pub(crate) unsafe fn defer(&self, mut deferred: Deferred, guard: &Guard) {
-        let bag = &mut *self.bag.get();
+        if let Some(handoff) = self.handoff() {
+            match handoff.push(deferred) {
+                Ok(()) => return,
+                // The buffer overflowed, so fall back to the bag, which may allocate.
+                Err(d) => deferred = d,
+            }
+        }
+        if self.is_fixed() {
+            if self.reserve_fixed(guard) {
+                let pushed = (*self.bags.get())[0].try_push(deferred);
+                debug_assert!(pushed.is_ok());
+                self.incr_advance(guard);
+                return;
+            }
+            // All the fixed bags are full, so fall back to the global queue, which allocates.
+            self.global()
+                .fixed_overflows
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        let class = size_class(deferred.bytes());
+        let mut retried = false;
 
-        while let Err(d) = bag.try_push(deferred) {
-            self.global().push_bag(bag, guard);
+        while let Err(d) = (*self.bags.get())[class].try_push(deferred) {
             deferred = d;
+            if !self
+                .global()
+                .push_bag(&mut (*self.bags.get())[class], class, guard)
+            {
+                // No bag could be allocated. Free some memory and retry, during which the bag of
+                // this participant has no capacity. If that does not help, the hook of the
+                // process decides whether to retry again or to abort.
+                if retried || self.reclaiming_on_alloc_failure.get() {
+                    alloc_failed(Bag::layout());
+                }
+                if !self.reclaiming_on_alloc_failure.get() {
+                    retried = true;
+                    self.reclaim_on_alloc_failure(guard);
+                }
+            }
             self.schedule_collection();
         }
         self.incr_advance(guard);
     }
 
+    /// Returns `true` if the participant seals and reclaims its bags by itself.
+    #[inline]
+    pub(crate) fn is_fixed(&self) -> bool {
+        self.global().config.fixed_bags > 0
+    }
+
+    /// Makes room in the current bag of a participant with fixed bags, by sealing it and taking
+    /// an empty bag, without allocating. Returns `false` if all the bags are full and none of the
+    /// sealed ones has expired.
+    pub(crate) fn reserve_fixed(&self, guard: &Guard) -> bool {
+        if unsafe { (*self.bags.get())[0].has_room() } {
+            return true;
+        }
+        if unsafe { (*self.fixed_spare.get()).is_empty() } {
+            self.reclaim_fixed(guard);
+        }
+
+        // The bags are borrowed only now, as the reclaimed functions may have deferred more.
+        let bag = unsafe { &mut (*self.bags.get())[0] };
+        if bag.has_room() {
+            return true;
+        }
+        let empty = match unsafe { (*self.fixed_spare.get()).pop() } {
+            Some(empty) => empty,
+            None => return false,
+        };
+        let full = replace(bag, empty);
+        self.global().retired_bytes(full.bytes());
+        let epoch = self.global().seal_epoch();
+        // There are as many fixed bags as the capacity, so this does not allocate.
+        unsafe { (*self.fixed_sealed.get()).push_back(full.seal(epoch)) };
+        true
+    }
+
+    /// Executes the deferred functions in the expired fixed bags and makes them empty bags,
+    /// advancing the global epoch first unless the collector only advances on explicit calls.
+    fn reclaim_fixed(&self, guard: &Guard) {
+        let global = self.global();
+        let oldest = unsafe { (*self.fixed_sealed.get()).front().map(|bag| bag.epoch) };
+        match oldest {
+            None => return,
+            Some(epoch)
+                if !global.config.is_explicit()
+                    && !is_expired(epoch, global.epoch.load(Ordering::Relaxed)) =>
+            {
+                let _ = global.try_advance(guard);
+            }
+            Some(_) => {}
+        }
+
+        let global_epoch = global.epoch.load(Ordering::Relaxed);
+        loop {
+            let sealed = unsafe { &mut *self.fixed_sealed.get() };
+            if !sealed
+                .front()
+                .is_some_and(|bag| bag.is_expired(global_epoch))
+            {
+                break;
+            }
+            let mut bag = sealed.pop_front().unwrap().bag;
+            global.reclaimed_bytes(bag.bytes());
+            // The deferred functions may defer more, so no reference to the bags is held.
+            reclaiming(guard, || bag.call_all());
+            unsafe { (*self.fixed_spare.get()).push(bag) };
+        }
+    }
+
+    /// Frees as much memory as possible after an allocation has failed.
+    #[cold]
+    pub(crate) fn reclaim_on_alloc_failure(&self, guard: &Guard) {
+        self.reclaiming_on_alloc_failure.set(true);
+        self.global().reclaim_on_alloc_failure(guard);
+        self.reclaiming_on_alloc_failure.set(false);
+    }
+
+    /// Returns a report of the state of the participant, which may be owned by another thread.
+    fn info(&self) -> ParticipantInfo {
+        let epoch = self.epoch.load(Ordering::Acquire);
+        // The bags can only be read while the participant is not using them.
+        let pending = if self.try_lock_bags() {
+            // SAFETY: The lock keeps the participant from touching its bags.
+            let count = self.pending_deferred().count;
+            self.bags_locked.store(false, Ordering::Release);
+            Some(count)
+        } else {
+            None
+        };
+        ParticipantInfo {
+            epoch: epoch.unpinned(),
+            pinned: epoch.is_pinned(),
+            pending,
+            thread: self.thread.id(),
+            thread_name: self.thread.name().map(str::to_owned),
+        }
+    }
+
+    /// Locks the bags of the participant, which may be owned by another thread, if it is not
+    /// pinned. Returns `false` if the participant is pinned or the bags are already locked.
+    ///
+    /// The participant checks the lock only after publishing its pinned epoch, so that pinning
+    /// does not write to the lock. Either the participant waits for the lock to be released, or
+    /// the epoch loaded here is pinned.
+    fn try_lock_bags(&self) -> bool {
+        if self
+            .bags_locked
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        if self.epoch.load(Ordering::SeqCst).is_pinned() {
+            self.bags_locked.store(false, Ordering::Release);
+            return false;
+        }
+        true
+    }
+
+    /// Moves the bags into the global queue if the participant is not pinned, returning the number
+    /// of deferred functions moved.
+    ///
+    /// The bags of the participants with fixed bags and of the real-time participants are left
+    /// alone, as they must not be replaced by bags allocated by another thread.
+    fn steal_bags(&self, global: &Global, guard: &Guard) -> usize {
+        if self.is_fixed() || self.realtime.load(Ordering::Relaxed) || !self.try_lock_bags() {
+            return 0;
+        }
+        let mut stolen = 0;
+        // SAFETY: The lock keeps the participant from touching its bags.
+        let bags = unsafe { &mut *self.bags.get() };
+        for (class, bag) in bags.iter_mut().enumerate() {
+            if !bag.is_empty() {
+                stolen += bag.len();
+                // Sealing the garbage later than it was retired is conservative.
+                global.push_bag(bag, class, guard);
+            }
+        }
+        self.bags_locked.store(false, Ordering::Release);
+        stolen
+    }
+
+    /// Returns the deferred functions in the local bags.
+    ///
+    /// The participant must be pinned, so that its bags are not stolen in the meantime.
+    pub(crate) fn pending_deferred(&self) -> PendingDeferred {
+        let bags = unsafe { &*self.bags.get() };
+        let sealed = unsafe { &*self.fixed_sealed.get() };
+        let bags = || bags.iter().chain(sealed.iter().map(|sealed| &sealed.bag));
+        PendingDeferred {
+            count: bags().map(Bag::len).sum(),
+            bytes: bags().map(Bag::bytes).sum(),
+        }
+    }
+
     pub(crate) fn flush(&self, guard: &Guard) {
         self.push_to_global(guard);
         self.schedule_collection();
     }
 
     pub(crate) fn push_to_global(&self, guard: &Guard) {
-        let bag = unsafe { &mut *self.bag.get() };
+        let bags = unsafe { &mut *self.bags.get() };
 
-        if !bag.is_empty() {
-            self.global().push_bag(bag, guard);
+        for (class, bag) in bags.iter_mut().enumerate() {
+            if !bag.is_empty() {
+                self.global().push_bag(bag, class, guard);
+            }
         }
     }
 
@@ -385,23 +1324,37 @@ impl Local {
     }
 
     pub(crate) fn incr_advance(&self, guard: &Guard) {
+        if self.global().config.is_explicit() || self.handoff().is_some() {
+            return;
+        }
         let advance_count = self.advance_count.get().wrapping_add(1);
         self.advance_count.set(advance_count);
 
         if advance_count % Self::COUNTS_BETWEEN_ADVANCE == 0 {
-            self.global().try_advance(guard);
+            let _ = self.global().try_advance(guard);
         }
     }
 
     /// Pins the `Local`.
     #[inline]
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
     pub(crate) fn pin(&self) -> Guard {
+        self.pin_at(PinSite::here())
+    }
+
+    /// Pins the `Local`, recording `site` as the creation site of the guard if it is the
+    /// outermost one.
+    #[inline]
+    pub(crate) fn pin_at(&self, site: PinSite) -> Guard {
         let guard = Guard { local: self };
 
         let guard_count = self.guard_count.get();
         self.guard_count.set(guard_count.checked_add(1).unwrap());
 
         if guard_count == 0 {
+            #[cfg(feature = "guard-tracking")]
+            self.pin_site.set(Some(site));
+
             let new_epoch = loop {
                 let global_epoch = self.global().epoch.load(Ordering::Relaxed);
                 let new_epoch = global_epoch.pinned();
@@ -449,13 +1402,31 @@ impl Local {
                 self.epoch.store(Epoch::starting(), Ordering::Release);
             };
 
+            // Wait for another participant that is stealing the bags, which is brief. It checks
+            // that this participant is not pinned after locking them, so it has either seen the
+            // pinned epoch and backed off, or locked them before this load.
+            while self.bags_locked.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+
             // Reset the advance couter if epoch has advanced.
             if new_epoch != self.prev_epoch.get() {
                 self.prev_epoch.set(new_epoch);
                 self.advance_count.set(0);
             }
+
+            // Collect when this guard is dropped if it is the `n`-th pin since the last collection.
+            if let PinCollection::Every(n) = self.pin_collection.get() {
+                let pin_count = self.pin_count.get().wrapping_add(1);
+                self.pin_count.set(pin_count);
+                if pin_count >= n {
+                    self.must_collect.set(true);
+                }
+            }
         }
 
+        #[cfg(not(feature = "guard-tracking"))]
+        let _ = site;
         guard
     }
 
@@ -463,7 +1434,11 @@ impl Local {
     #[inline]
     pub(crate) fn unpin(&self) {
         let guard_count = self.guard_count.get();
-        if guard_count == 1 && !self.collecting.get() {
+        if guard_count == 1
+            && !self.collecting.get()
+            && self.handoff().is_none()
+            && self.pin_collection.get() != PinCollection::Never
+        {
             self.collecting.set(true);
             while self.must_collect.get() {
                 self.must_collect.set(false);
@@ -477,6 +1452,10 @@ impl Local {
 
         self.guard_count.set(guard_count - 1);
         if guard_count == 1 {
+            #[cfg(feature = "guard-tracking")]
+            if let Some(site) = self.pin_site.take() {
+                site.check(self.global());
+            }
             self.epoch.store(Epoch::starting(), Ordering::Release);
 
             if self.handle_count.get() == 0 {
@@ -487,11 +1466,13 @@ impl Local {
 
     /// Unpins and then pins the `Local`.
     #[inline]
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
     pub(crate) fn repin(&self) {
+        let site = PinSite::here();
         self.acquire_handle();
         self.unpin();
         compiler_fence(Ordering::SeqCst);
-        forget(self.pin());
+        forget(self.pin_at(site));
         self.release_handle();
     }
 
@@ -541,14 +1522,28 @@ impl Local {
         // doesn't call `finalize` again.
         self.handle_count.set(1);
         {
-            // Pin and move the local bag into the global queue. It's important that `push_bag`
-            // doesn't defer destruction on any new garbage.
+            // Pin and move the local bags into the queue of orphaned bags. It's important that
+            // `push_orphan` doesn't defer destruction on any new garbage.
             let guard = &self.pin();
-            self.push_to_global(guard);
+            let bags = unsafe { &mut *self.bags.get() };
+            for bag in bags.iter_mut() {
+                self.global().push_orphan(bag, guard);
+            }
+            let sealed = unsafe { &mut *self.fixed_sealed.get() };
+            for mut sealed in sealed.drain(..) {
+                // Sealing the garbage again later is conservative. Its bytes are counted again
+                // when it is sealed again.
+                self.global().reclaimed_bytes(sealed.bag.bytes());
+                self.global().push_orphan(&mut sealed.bag, guard);
+            }
         }
         // Revert the handle count back to zero.
         self.handle_count.set(0);
 
+        // The pooled bags are not reused once the participant has exited.
+        unsafe { (*self.bag_pool.get()).clear() };
+        self.pooled_bags.store(0, Ordering::Relaxed);
+
         unsafe {
             // Take the reference to the `Global` out of this `Local`. Since we're not protected
             // by a guard at this time, it's crucial that the reference is read before marking the
@@ -566,6 +1561,9 @@ impl Local {
     }
 
     pub(crate) fn incr_manual_collection(&self, guard: &Guard) {
+        if self.handoff().is_some() {
+            return;
+        }
         let manual_count = self.manual_count.get().wrapping_add(1);
         self.manual_count.set(manual_count);
 
@@ -589,7 +1587,7 @@ impl IsElement<Local> for Local {
     }
 
     unsafe fn finalize(entry: &Entry, guard: &Guard) {
-        guard.defer_destroy(RawShared::from(Self::element_of(entry) as *const Local));
+        guard.defer_destroy_internal(RawShared::from(Self::element_of(entry) as *const Local));
     }
 }
 
@@ -618,6 +1616,29 @@ mod tests {
         assert_eq!(FLAG_CHECK_DEFER.load(Ordering::Relaxed), 42);
     }
 
+    #[test]
+    fn alloc_failure_hook() {
+        let context = dyntls_host::get();
+        unsafe {
+            context.initialize();
+        }
+        let collector = Collector::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let hook_calls = calls.clone();
+        collector.set_alloc_failure_hook(move || {
+            hook_calls.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let handle = collector.register();
+        let guard = &handle.pin();
+        unsafe { (*guard.local).reclaim_on_alloc_failure(guard) };
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        collector.remove_alloc_failure_hook();
+        unsafe { (*guard.local).reclaim_on_alloc_failure(guard) };
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
     #[test]
     fn check_bag() {
         dyntls::lazy_static! {
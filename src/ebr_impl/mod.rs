@@ -48,16 +48,38 @@
 //! For majority of use cases, just use the default garbage collector by invoking [`pin`]. If you
 //! want to create your own garbage collector, use the [`Collector`] API.
 
+mod alloc_failure;
+mod arena;
 mod collector;
 mod default;
 mod deferred;
+mod deferred_async;
 mod epoch;
+#[cfg(feature = "async")]
+mod grace_period;
 mod guard;
+mod handoff;
 mod internal;
+mod pin_site;
 mod pointers;
 mod sync;
+mod type_stats;
 
+pub use alloc_failure::{remove_alloc_failure_hook, set_alloc_failure_hook, AllocFailureAction};
+pub(crate) use arena::RcArena;
+pub use collector::{
+    CollectAllStats, Collector, CollectorBuilder, EpochAdvanceHookId, EpochToken, LocalHandle,
+    ParticipantInfo, Participants, PinCollection, ReclamationSchedule, ReclamationStep,
+};
 pub use default::*;
+pub use deferred::boxed_deferreds;
+pub use deferred_async::DeferredFuture;
 pub use epoch::*;
+#[cfg(feature = "async")]
+pub use grace_period::GracePeriod;
 pub use guard::*;
+pub(crate) use internal::current_thread_hash;
 pub use pointers::*;
+pub(crate) use type_stats::RetireRecord;
+#[cfg(feature = "type-stats")]
+pub use type_stats::TypeStats;
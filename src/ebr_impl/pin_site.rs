@@ -0,0 +1,63 @@
+//! Detection of long-held guards.
+//!
+//! With the `guard-tracking` feature, the outermost guard of each participant records when and
+//! where it was created. If it is dropped after the threshold set with
+//! [`CollectorBuilder::long_guard_threshold`](super::CollectorBuilder::long_guard_threshold), a
+//! warning with its creation location is logged with `tracing`, and the guard is counted by
+//! [`Collector::long_guards`](super::Collector::long_guards). Without the feature, the sites are
+//! empty and cost nothing.
+
+#[cfg(feature = "guard-tracking")]
+use core::panic::Location;
+#[cfg(feature = "guard-tracking")]
+use core::sync::atomic::Ordering;
+#[cfg(feature = "guard-tracking")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "guard-tracking")]
+use super::internal::Global;
+
+/// How long a guard may be held before it is reported, unless configured otherwise.
+#[cfg(feature = "guard-tracking")]
+pub(crate) const DEFAULT_LONG_GUARD_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Where and when a participant has been pinned.
+#[derive(Clone, Copy)]
+pub(crate) struct PinSite {
+    #[cfg(feature = "guard-tracking")]
+    location: &'static Location<'static>,
+    #[cfg(feature = "guard-tracking")]
+    since: Instant,
+}
+
+impl PinSite {
+    /// Records the current time and the location of the caller, which is the first caller that
+    /// does not track its own caller.
+    #[inline]
+    #[cfg_attr(feature = "guard-tracking", track_caller)]
+    pub(crate) fn here() -> Self {
+        Self {
+            #[cfg(feature = "guard-tracking")]
+            location: Location::caller(),
+            #[cfg(feature = "guard-tracking")]
+            since: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "guard-tracking")]
+impl PinSite {
+    /// Reports the guard created at this site if it has been held for longer than the threshold
+    /// of `global`.
+    pub(crate) fn check(&self, global: &Global) {
+        let held = self.since.elapsed();
+        if held >= global.config.long_guard_threshold() {
+            global.long_guards.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                location = %self.location,
+                ?held,
+                "a guard has been held for longer than the threshold"
+            );
+        }
+    }
+}
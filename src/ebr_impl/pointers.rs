@@ -9,6 +9,7 @@ use atomic::{Atomic, Ordering};
 
 use super::Guard;
 
+#[repr(transparent)]
 pub struct Tagged<T: ?Sized> {
     ptr: *mut T,
 }
@@ -94,6 +95,11 @@ impl<T> Tagged<T> {
         (ptr & !low_bits::<T>() & !Self::high_bits()) as *mut T
     }
 
+    /// Converts the pointer to a raw pointer, keeping all the tags.
+    pub fn as_tagged_raw(&self) -> *mut T {
+        self.ptr
+    }
+
     pub fn with_tag(&self, tag: usize) -> Self {
         Self::from(with_tag(self.ptr, tag))
     }
@@ -44,7 +44,7 @@ pub(crate) struct Entry {
 ///     }
 ///
 ///     unsafe fn finalize(entry: &Entry, guard: &Guard) {
-///         guard.defer_destroy(Shared::from(Self::element_of(entry) as *const _));
+///         guard.defer_destroy_internal(Shared::from(Self::element_of(entry) as *const _));
 ///     }
 /// }
 /// ```
@@ -312,7 +312,7 @@ mod tests {
         }
 
         unsafe fn finalize(entry: &Entry, guard: &Guard) {
-            guard.defer_destroy(RawShared::from(Self::element_of(entry) as *const _));
+            guard.defer_destroy_internal(RawShared::from(Self::element_of(entry) as *const _));
         }
     }
 
@@ -127,7 +127,7 @@ impl<T> Queue<T> {
                                 .tail
                                 .compare_exchange(tail, next, Release, Relaxed, guard);
                         }
-                        guard.defer_destroy(head);
+                        guard.defer_destroy_internal(head);
                         Some(n.data.assume_init_read())
                     })
                     .map_err(|_| ())
@@ -159,7 +159,7 @@ impl<T> Queue<T> {
                                 .tail
                                 .compare_exchange(tail, next, Release, Relaxed, guard);
                         }
-                        guard.defer_destroy(head);
+                        guard.defer_destroy_internal(head);
                         Some(n.data.assume_init_read())
                     })
                     .map_err(|_| ())
@@ -196,6 +196,23 @@ impl<T> Queue<T> {
             backoff.spin();
         }
     }
+
+    /// Calls `f` on every item in the queue, from the front.
+    ///
+    /// Items that are pushed or popped concurrently may or may not be visited.
+    #[cfg(debug_assertions)]
+    pub(crate) fn for_each<F>(&self, mut f: F, guard: &Guard)
+    where
+        T: Sync,
+        F: FnMut(&T),
+    {
+        let head = self.head.load(Acquire, guard);
+        let mut next = unsafe { head.deref() }.next.load(Acquire, guard);
+        while let Some(n) = unsafe { next.as_ref() } {
+            f(unsafe { &*n.data.as_ptr() });
+            next = n.next.load(Acquire, guard);
+        }
+    }
 }
 
 impl<T> Drop for Queue<T> {
@@ -0,0 +1,126 @@
+//! Per-type counters of the retired reference-counted objects.
+//!
+//! With the `type-stats` feature, each collector counts the objects that have been retired and
+//! reclaimed for every type, so that the type responsible for a growth of unreclaimed memory can
+//! be found with [`Collector::type_stats`](super::Collector::type_stats). Without the feature,
+//! the records are empty and cost nothing.
+
+#[cfg(feature = "type-stats")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "type-stats")]
+use std::collections::HashMap;
+#[cfg(feature = "type-stats")]
+use std::sync::{Arc, Mutex};
+
+use super::guard::Guard;
+
+/// The numbers of retired and reclaimed objects of a type, as returned by
+/// [`Collector::type_stats`](super::Collector::type_stats).
+#[cfg(feature = "type-stats")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeStats {
+    /// The name of the type, as returned by [`core::any::type_name`].
+    pub type_name: &'static str,
+    /// The number of objects whose destruction has been deferred.
+    pub retired: usize,
+    /// The number of objects whose deferred destruction has run.
+    pub reclaimed: usize,
+}
+
+#[cfg(feature = "type-stats")]
+impl TypeStats {
+    /// Returns the number of retired objects that have not been reclaimed yet.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.retired.saturating_sub(self.reclaimed)
+    }
+}
+
+/// The counters of a type.
+#[cfg(feature = "type-stats")]
+#[derive(Default)]
+struct TypeCounters {
+    retired: AtomicUsize,
+    reclaimed: AtomicUsize,
+}
+
+/// The counters of all the types retired in a collector.
+#[cfg(feature = "type-stats")]
+#[derive(Default)]
+pub(crate) struct TypeStatsTable {
+    types: Mutex<HashMap<&'static str, Arc<TypeCounters>>>,
+}
+
+#[cfg(feature = "type-stats")]
+impl TypeStatsTable {
+    fn counters(&self, type_name: &'static str) -> Arc<TypeCounters> {
+        self.types
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(type_name)
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the statistics of all the types, the ones with the most pending objects first.
+    pub(crate) fn snapshot(&self) -> Vec<TypeStats> {
+        let mut stats: Vec<_> = self
+            .types
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(&type_name, counters)| TypeStats {
+                type_name,
+                retired: counters.retired.load(Ordering::Relaxed),
+                reclaimed: counters.reclaimed.load(Ordering::Relaxed),
+            })
+            .collect();
+        stats.sort_by(|a, b| {
+            b.pending()
+                .cmp(&a.pending())
+                .then(a.type_name.cmp(b.type_name))
+        });
+        stats
+    }
+}
+
+/// Records the retirement of objects of a type, and their reclamation when it is dropped.
+///
+/// It is moved into the deferred function that destructs the objects.
+pub(crate) struct RetireRecord {
+    #[cfg(feature = "type-stats")]
+    counters: Option<(Arc<TypeCounters>, usize)>,
+}
+
+impl RetireRecord {
+    /// Records the retirement of `count` objects of type `T` in the collector of `guard`.
+    #[inline]
+    pub(crate) fn new<T>(guard: &Guard, count: usize) -> Self {
+        #[cfg(feature = "type-stats")]
+        {
+            let counters = unsafe { guard.local.as_ref() }.map(|local| {
+                let counters = local
+                    .global()
+                    .type_stats
+                    .counters(core::any::type_name::<T>());
+                counters.retired.fetch_add(count, Ordering::Relaxed);
+                (counters, count)
+            });
+            Self { counters }
+        }
+        #[cfg(not(feature = "type-stats"))]
+        {
+            let _ = (guard, count);
+            Self {}
+        }
+    }
+}
+
+#[cfg(feature = "type-stats")]
+impl Drop for RetireRecord {
+    fn drop(&mut self) {
+        if let Some((counters, count)) = &self.counters {
+            counters.reclaimed.fetch_add(*count, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! A C API for sharing CIRC-managed objects with foreign code.
+//!
+//! Foreign objects are opaque `void *` payloads with an optional destructor, wrapped in a
+//! [`ForeignObject`] and managed by [`Rc`] and [`AtomicRc`] pointers. The handle types are
+//! `#[repr(C)]`, so the header can be generated with cbindgen.
+//!
+//! * A [`CircRc`] owns a strong reference. It must be released exactly once with
+//!   [`circ_rc_drop`], or moved into a function that takes it by value.
+//! * A [`CircSnapshot`] is a reference that is valid until the critical section it was loaded in
+//!   is exited with [`circ_cs_exit`].
+//! * A [`CircAtomicRc`] and a [`CircGuard`] are opaque and only handled by pointers.
+//!
+//! Like the rest of the crate, the functions expect the dyntls context of the calling thread to
+//! be initialized.
+
+use std::ffi::c_void;
+use std::fmt::{Debug, Formatter};
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::Ordering;
+
+use crate::utils::{Raw, RcInner};
+use crate::{cs, AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// The destructor of the payload of a [`ForeignObject`].
+pub type CircDropFn = Option<unsafe extern "C" fn(data: *mut c_void)>;
+
+/// An object owned by foreign code, which is dropped by calling its destructor on the payload.
+pub struct ForeignObject {
+    data: *mut c_void,
+    drop: CircDropFn,
+}
+
+// The foreign code is responsible for making the payload shareable between threads.
+unsafe impl Send for ForeignObject {}
+unsafe impl Sync for ForeignObject {}
+
+unsafe impl RcObject for ForeignObject {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {
+        // The references held by the payload are released by its destructor.
+    }
+}
+
+impl ForeignObject {
+    /// Returns the payload of the object.
+    #[inline]
+    pub fn data(&self) -> *mut c_void {
+        self.data
+    }
+}
+
+impl Drop for ForeignObject {
+    fn drop(&mut self) {
+        if let Some(drop) = self.drop {
+            unsafe { drop(self.data) };
+        }
+    }
+}
+
+impl Debug for ForeignObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ForeignObject")
+            .field("data", &self.data)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A strong reference to a [`ForeignObject`], which may be null.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CircRc {
+    ptr: *mut c_void,
+}
+
+impl From<Rc<ForeignObject>> for CircRc {
+    #[inline]
+    fn from(rc: Rc<ForeignObject>) -> Self {
+        Self {
+            ptr: rc.into_raw().as_tagged_raw().cast(),
+        }
+    }
+}
+
+impl From<CircRc> for Rc<ForeignObject> {
+    #[inline]
+    fn from(handle: CircRc) -> Self {
+        Rc::from_raw(raw(handle.ptr))
+    }
+}
+
+/// A reference to a [`ForeignObject`] loaded in a critical section, which may be null.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CircSnapshot {
+    ptr: *mut c_void,
+}
+
+impl CircSnapshot {
+    fn new(snapshot: Snapshot<'_, ForeignObject>) -> Self {
+        Self {
+            ptr: snapshot.ptr.as_tagged_raw().cast(),
+        }
+    }
+
+    fn get<'g>(self, guard: &'g Guard) -> Snapshot<'g, ForeignObject> {
+        Snapshot::from_raw(raw(self.ptr), guard)
+    }
+}
+
+/// An atomic reference to a [`ForeignObject`].
+#[derive(Debug)]
+pub struct CircAtomicRc(AtomicRc<ForeignObject>);
+
+/// A critical section of the calling thread.
+#[derive(Debug)]
+pub struct CircGuard(Guard);
+
+#[inline]
+fn raw(ptr: *mut c_void) -> Raw<ForeignObject> {
+    Raw::from(ptr.cast::<RcInner<ForeignObject>>())
+}
+
+/// Views `handle` as an [`Rc`] pointer, without taking over its strong reference.
+#[inline]
+fn borrow(handle: &CircRc) -> ManuallyDrop<Rc<ForeignObject>> {
+    ManuallyDrop::new(Rc::from_raw(raw(handle.ptr)))
+}
+
+/// Allocates a new object with the payload `data` and returns a strong reference to it.
+///
+/// `drop` is called on `data` when the object is reclaimed, possibly on another thread.
+#[no_mangle]
+pub extern "C" fn circ_rc_new(data: *mut c_void, drop: CircDropFn) -> CircRc {
+    CircRc::from(Rc::new(ForeignObject { data, drop }))
+}
+
+/// Returns a null reference.
+#[no_mangle]
+pub extern "C" fn circ_rc_null() -> CircRc {
+    CircRc::from(Rc::null())
+}
+
+/// Creates another strong reference to the object of `rc`.
+///
+/// # Safety
+///
+/// `rc` must point to a live reference.
+#[no_mangle]
+pub unsafe extern "C" fn circ_rc_clone(rc: *const CircRc) -> CircRc {
+    CircRc::from((*borrow(&*rc)).clone())
+}
+
+/// Releases the strong reference `rc`.
+///
+/// # Safety
+///
+/// `rc` must be a live reference, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn circ_rc_drop(rc: CircRc) {
+    drop(Rc::from(rc));
+}
+
+/// Returns the payload of the object of `rc`, or null if `rc` is null.
+///
+/// # Safety
+///
+/// `rc` must point to a live reference.
+#[no_mangle]
+pub unsafe extern "C" fn circ_rc_get(rc: *const CircRc) -> *mut c_void {
+    borrow(&*rc)
+        .as_ref()
+        .map_or(ptr::null_mut(), ForeignObject::data)
+}
+
+/// Enters a critical section, which lasts until the returned guard is passed to
+/// [`circ_cs_exit`].
+#[no_mangle]
+pub extern "C" fn circ_cs_enter() -> *mut CircGuard {
+    Box::into_raw(Box::new(CircGuard(cs())))
+}
+
+/// Exits the critical section of `guard`.
+///
+/// # Safety
+///
+/// `guard` must have been returned by [`circ_cs_enter`] on the current thread, and must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn circ_cs_exit(guard: *mut CircGuard) {
+    drop(Box::from_raw(guard));
+}
+
+/// Allocates a new atomic reference initialized with `init`, taking over its strong reference.
+#[no_mangle]
+pub extern "C" fn circ_atomic_new(init: CircRc) -> *mut CircAtomicRc {
+    Box::into_raw(Box::new(CircAtomicRc(AtomicRc::from(Rc::from(init)))))
+}
+
+/// Deallocates an atomic reference, releasing the strong reference it holds.
+///
+/// # Safety
+///
+/// `atomic` must have been returned by [`circ_atomic_new`], and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn circ_atomic_free(atomic: *mut CircAtomicRc) {
+    drop(Box::from_raw(atomic));
+}
+
+/// Loads the reference stored in `atomic`.
+///
+/// The returned snapshot is valid until `guard` is passed to [`circ_cs_exit`].
+///
+/// # Safety
+///
+/// `atomic` and `guard` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn circ_atomic_load(
+    atomic: *const CircAtomicRc,
+    guard: *const CircGuard,
+) -> CircSnapshot {
+    CircSnapshot::new((*atomic).0.load(Ordering::Acquire, &(*guard).0))
+}
+
+/// Stores `new` into `atomic`, taking over its strong reference.
+///
+/// # Safety
+///
+/// `atomic`, `new`, and `guard` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn circ_atomic_store(
+    atomic: *const CircAtomicRc,
+    new: CircRc,
+    guard: *const CircGuard,
+) {
+    (*atomic)
+        .0
+        .store(Rc::from(new), Ordering::Release, &(*guard).0);
+}
+
+/// Stores `new` into `atomic`, taking over its strong reference, and returns the previous
+/// reference.
+///
+/// # Safety
+///
+/// `atomic` and `new` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn circ_atomic_swap(atomic: *const CircAtomicRc, new: CircRc) -> CircRc {
+    CircRc::from((*atomic).0.swap(Rc::from(new), Ordering::AcqRel))
+}
+
+/// Stores `desired` into `atomic` if it holds `expected`, returning `true` on success.
+///
+/// The strong reference of `desired` is taken over on success and released on failure.
+///
+/// # Safety
+///
+/// `atomic`, `desired`, and `guard` must be live, and `expected` must have been loaded during the
+/// critical section of `guard`.
+#[no_mangle]
+pub unsafe extern "C" fn circ_atomic_compare_exchange(
+    atomic: *const CircAtomicRc,
+    expected: CircSnapshot,
+    desired: CircRc,
+    guard: *const CircGuard,
+) -> bool {
+    let guard = &(*guard).0;
+    match (*atomic).0.compare_exchange(
+        expected.get(guard),
+        Rc::from(desired),
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        guard,
+    ) {
+        Ok(old) => {
+            old.finalize(guard);
+            true
+        }
+        Err(e) => {
+            e.desired.finalize(guard);
+            false
+        }
+    }
+}
+
+/// Returns the payload of the object of `snapshot`, or null if `snapshot` is null.
+///
+/// # Safety
+///
+/// The critical section `snapshot` was loaded in must not have been exited.
+#[no_mangle]
+pub unsafe extern "C" fn circ_snapshot_get(snapshot: CircSnapshot) -> *mut c_void {
+    raw(snapshot.ptr)
+        .as_ref()
+        .map_or(ptr::null_mut(), |inner| inner.data().data())
+}
+
+/// Creates a strong reference to the object of `snapshot`.
+///
+/// # Safety
+///
+/// `snapshot` must have been loaded during the critical section of `guard`, which must be live.
+#[no_mangle]
+pub unsafe extern "C" fn circ_snapshot_counted(
+    snapshot: CircSnapshot,
+    guard: *const CircGuard,
+) -> CircRc {
+    CircRc::from(snapshot.get(&(*guard).0).counted())
+}
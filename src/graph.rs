@@ -0,0 +1,346 @@
+//! A concurrent directed graph whose edges may form cycles.
+//!
+//! Each node holds its outgoing edges in an [`AtomicRc`] to an immutable adjacency list, which is
+//! replaced as a whole on every update. An edge holds a strong [`Rc`] to its target, so the nodes
+//! stay alive as long as they are reachable, and traversals only need a guard.
+//!
+//! The graph keeps a registry of the nodes that have not been removed. Removing a node *closes*
+//! it: its adjacency list is dropped and replaced with a tagged null pointer, which prevents new
+//! edges from being added. Closing the nodes breaks every cycle that passes through them, so a
+//! removed node is reclaimed as soon as the edges pointing to it are gone, and dropping the graph
+//! reclaims all of its nodes.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{cs, AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// The tag of the adjacency list of a removed node.
+const CLOSED: usize = 1;
+
+/// The identifier of a node in a [`Graph`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct NodeId(usize);
+
+struct Node<N, E> {
+    id: NodeId,
+    value: N,
+    removed: AtomicBool,
+    adjacency: AtomicRc<Adjacency<N, E>>,
+}
+
+unsafe impl<N, E> RcObject for Node<N, E> {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {
+        // The edges are reached through the adjacency list, whose destructor releases them.
+    }
+}
+
+struct Edge<N, E> {
+    target: Rc<Node<N, E>>,
+    weight: E,
+}
+
+struct Adjacency<N, E> {
+    edges: Vec<Edge<N, E>>,
+}
+
+unsafe impl<N, E> RcObject for Adjacency<N, E> {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl<N, E> Node<N, E> {
+    fn is_removed(&self) -> bool {
+        self.removed.load(Ordering::Acquire)
+    }
+
+    /// Replaces the adjacency list with the one computed by `f` from the current edges, unless
+    /// the node is closed. Edges to removed nodes are dropped on the way.
+    ///
+    /// Returns the result of `f`, or `None` if the node is closed or `f` has declined to update.
+    fn update<R, F>(&self, mut f: F, guard: &Guard) -> Option<R>
+    where
+        F: FnMut(&mut Vec<Edge<N, E>>) -> Option<R>,
+        E: Clone,
+    {
+        let mut current = self.adjacency.load(Ordering::Acquire, guard);
+        loop {
+            if current.tag() == CLOSED {
+                return None;
+            }
+            let mut edges = current.as_ref().map_or_else(Vec::new, |adjacency| {
+                adjacency
+                    .edges
+                    .iter()
+                    .filter(|edge| !edge.target.as_ref().unwrap().is_removed())
+                    .map(|edge| Edge {
+                        target: edge.target.clone(),
+                        weight: edge.weight.clone(),
+                    })
+                    .collect()
+            });
+            let result = f(&mut edges)?;
+            let new = if edges.is_empty() {
+                Rc::null()
+            } else {
+                Rc::new(Adjacency { edges })
+            };
+            match self.adjacency.compare_exchange(
+                current,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => return Some(result),
+                Err(e) => {
+                    e.desired.finalize(guard);
+                    current = e.current;
+                }
+            }
+        }
+    }
+
+    /// Drops the adjacency list and prevents new edges from being added.
+    fn close(&self, guard: &Guard) {
+        self.removed.store(true, Ordering::Release);
+        let closed = Rc::null().with_tag(CLOSED);
+        let old = self.adjacency.swap(closed, Ordering::AcqRel);
+        old.finalize(guard);
+    }
+}
+
+/// A reference to a node of a [`Graph`], valid during a critical section.
+pub struct NodeRef<'g, N, E> {
+    node: &'g Node<N, E>,
+    guard: &'g Guard,
+}
+
+impl<'g, N, E> NodeRef<'g, N, E> {
+    /// Returns the identifier of the node.
+    #[inline]
+    pub fn id(&self) -> NodeId {
+        self.node.id
+    }
+
+    /// Returns the value of the node.
+    #[inline]
+    pub fn value(&self) -> &'g N {
+        &self.node.value
+    }
+
+    /// Returns `true` if the node has been removed from the graph.
+    #[inline]
+    pub fn is_removed(&self) -> bool {
+        self.node.is_removed()
+    }
+
+    /// Returns an iterator over the outgoing edges of the node, skipping those to removed nodes.
+    ///
+    /// The edges are read from a single version of the adjacency list.
+    pub fn edges(&self) -> impl Iterator<Item = (NodeRef<'g, N, E>, &'g E)> + 'g {
+        let guard = self.guard;
+        let adjacency = self.node.adjacency.load(Ordering::Acquire, guard);
+        adjacency
+            .as_ref()
+            .map_or(&[][..], |adjacency| adjacency.edges.as_slice())
+            .iter()
+            .filter_map(move |edge| {
+                let node = edge.target.as_ref().unwrap();
+                (!node.is_removed()).then_some((NodeRef { node, guard }, &edge.weight))
+            })
+    }
+}
+
+impl<'g, N, E> Clone for NodeRef<'g, N, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'g, N, E> Copy for NodeRef<'g, N, E> {}
+
+impl<'g, N: Debug, E> Debug for NodeRef<'g, N, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("id", &self.node.id)
+            .field("value", &self.node.value)
+            .finish()
+    }
+}
+
+/// A concurrent directed graph with weighted edges, which may contain cycles.
+///
+/// Nodes and edges can be added and removed concurrently, and the graph can be traversed from
+/// any node during a critical section. Updating the edges of a node copies its adjacency list,
+/// so the graph is best suited to read-mostly workloads.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, graph::Graph};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let graph = Graph::new();
+/// let guard = &cs();
+/// let a = graph.add_node("a");
+/// let b = graph.add_node("b");
+/// graph.add_edge(a, b, 1, guard);
+/// graph.add_edge(b, a, 2, guard);
+///
+/// let node = graph.node(a, guard).unwrap();
+/// let (next, weight) = node.edges().next().unwrap();
+/// assert_eq!((*next.value(), *weight), ("b", 1));
+///
+/// // Removing a node breaks the cycle, so both nodes are reclaimed eventually.
+/// assert!(graph.remove_node(b, guard));
+/// assert_eq!(node.edges().count(), 0);
+/// ```
+pub struct Graph<N, E> {
+    nodes: Mutex<HashMap<NodeId, Rc<Node<N, E>>>>,
+    next_id: AtomicUsize,
+}
+
+impl<N, E> Graph<N, E> {
+    /// Creates a new empty graph.
+    pub fn new() -> Self {
+        Self {
+            nodes: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<NodeId, Rc<Node<N, E>>>> {
+        // The registry is never left inconsistent, so poisoning is harmless.
+        self.nodes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn find<'g>(&self, id: NodeId, guard: &'g Guard) -> Option<&'g Node<N, E>> {
+        let snapshot: Snapshot<'g, _> = self.lock().get(&id)?.snapshot(guard);
+        // The node may be removed as soon as the lock is released, but its destruction is
+        // deferred until the critical section of `guard` ends.
+        snapshot.as_ref()
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if the graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the identifiers of the nodes in the graph, in no particular order.
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        self.lock().keys().copied().collect()
+    }
+
+    /// Adds a node with `value`, returning its identifier.
+    pub fn add_node(&self, value: N) -> NodeId {
+        let id = NodeId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let node = Rc::new(Node {
+            id,
+            value,
+            removed: AtomicBool::new(false),
+            adjacency: AtomicRc::null(),
+        });
+        self.lock().insert(id, node);
+        id
+    }
+
+    /// Returns a reference to the node `id`.
+    pub fn node<'g>(&self, id: NodeId, guard: &'g Guard) -> Option<NodeRef<'g, N, E>> {
+        let node = self.find(id, guard)?;
+        Some(NodeRef { node, guard })
+    }
+
+    /// Removes the node `id` and its outgoing edges, returning `true` if the node was present.
+    ///
+    /// The edges from other nodes to the removed node are skipped by traversals and dropped
+    /// lazily when the adjacency lists of those nodes are updated.
+    pub fn remove_node(&self, id: NodeId, guard: &Guard) -> bool {
+        let Some(node) = self.lock().remove(&id) else {
+            return false;
+        };
+        node.as_ref().unwrap().close(guard);
+        node.finalize(guard);
+        true
+    }
+
+    /// Adds an edge from `from` to `to` with `weight`, replacing the existing one if any.
+    ///
+    /// Returns `false` if either node is not in the graph.
+    pub fn add_edge(&self, from: NodeId, to: NodeId, weight: E, guard: &Guard) -> bool
+    where
+        E: Clone,
+    {
+        let Some(source) = self.find(from, guard) else {
+            return false;
+        };
+        let Some(target) = self.lock().get(&to).cloned() else {
+            return false;
+        };
+        source
+            .update(
+                |edges| {
+                    edges.retain(|edge| edge.target.as_ref().unwrap().id != to);
+                    edges.push(Edge {
+                        target: target.clone(),
+                        weight: weight.clone(),
+                    });
+                    Some(())
+                },
+                guard,
+            )
+            .is_some()
+    }
+
+    /// Removes the edge from `from` to `to`, returning `true` if it was present.
+    pub fn remove_edge(&self, from: NodeId, to: NodeId, guard: &Guard) -> bool
+    where
+        E: Clone,
+    {
+        let Some(source) = self.find(from, guard) else {
+            return false;
+        };
+        source
+            .update(
+                |edges| {
+                    let len = edges.len();
+                    edges.retain(|edge| edge.target.as_ref().unwrap().id != to);
+                    (edges.len() != len).then_some(())
+                },
+                guard,
+            )
+            .is_some()
+    }
+}
+
+impl<N, E> Default for Graph<N, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N, E> Drop for Graph<N, E> {
+    fn drop(&mut self) {
+        let guard = &cs();
+        let nodes = std::mem::take(self.nodes.get_mut().unwrap_or_else(|e| e.into_inner()));
+        for node in nodes.into_values() {
+            node.as_ref().unwrap().close(guard);
+            node.finalize(guard);
+        }
+    }
+}
+
+impl<N, E> Debug for Graph<N, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Graph")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,230 @@
+//! Measurement of the memory retained by object graphs.
+//!
+//! [`HeapSize`] reports the number of heap bytes owned by a value, following [`Rc`],
+//! [`AtomicRc`] and [`Snapshot`] edges into the objects they point to. A [`Visited`] set records
+//! the objects counted so far, so an object shared by several pointers, or reached again through
+//! a cycle, is counted once. This makes the total suitable for enforcing a memory budget, such as
+//! in a caching layer that evicts entries until the retained size of the cache fits.
+//!
+//! The sizes are estimates: the allocator may round requests up, and the containers of the
+//! standard library are counted by their capacity.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::heap_size::{retained_size, HeapSize, Visited};
+//! use circ::{Rc, RcObject};
+//!
+//! struct Blob(Vec<u8>);
+//!
+//! unsafe impl RcObject for Blob {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! impl HeapSize for Blob {
+//!     fn heap_size(&self, visited: &mut Visited) -> usize {
+//!         self.0.heap_size(visited)
+//!     }
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let blob = Rc::new(Blob(vec![0; 1024]));
+//! let one = retained_size(&blob);
+//! assert!(one >= 1024);
+//!
+//! // The shared blob is counted once.
+//! let both = vec![blob.clone(), blob];
+//! assert_eq!(retained_size(&both), one + 2 * std::mem::size_of::<Rc<Blob>>());
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::mem::{size_of, size_of_val};
+use std::sync::atomic::Ordering;
+
+use crate::utils::{Raw, RcInner};
+use crate::{cs, AtomicRc, Rc, RcObject, Snapshot};
+
+/// The set of objects that have been counted in a measurement.
+#[derive(Debug, Default)]
+pub struct Visited {
+    seen: HashSet<usize>,
+}
+
+impl Visited {
+    /// Creates a new empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the allocation at `ptr` as counted, returning `true` if it had not been counted
+    /// before.
+    ///
+    /// Implementations of [`HeapSize`] for types with shared ownership other than [`Rc`] call
+    /// this to count each shared allocation once.
+    pub fn first_visit<T: ?Sized>(&mut self, ptr: *const T) -> bool {
+        self.seen.insert(ptr as *const () as usize)
+    }
+
+    /// Returns the number of allocations counted so far.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Returns `true` if no allocation has been counted yet.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
+
+/// A value whose retained heap memory can be measured.
+pub trait HeapSize {
+    /// Returns the number of heap bytes owned by `self`, not counting `size_of_val(self)`.
+    ///
+    /// Allocations that have already been recorded in `visited` are not counted again.
+    fn heap_size(&self, visited: &mut Visited) -> usize;
+}
+
+/// Returns the number of heap bytes retained by `value`, counting each shared allocation once.
+pub fn retained_size<T: HeapSize + ?Sized>(value: &T) -> usize {
+    value.heap_size(&mut Visited::new())
+}
+
+/// Returns the size of the allocation of the object at `ptr`, or `None` if `ptr` is null or the
+/// object has been counted already.
+pub(crate) fn rc_allocation<T>(ptr: Raw<T>, visited: &mut Visited) -> Option<usize> {
+    let ptr = ptr.as_raw();
+    if ptr.is_null() || !visited.first_visit(ptr) {
+        return None;
+    }
+    Some(size_of::<RcInner<T>>())
+}
+
+impl<T: RcObject + HeapSize> HeapSize for Rc<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        match rc_allocation(self.as_raw(), visited) {
+            Some(bytes) => bytes + self.as_ref().unwrap().heap_size(visited),
+            None => 0,
+        }
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for Snapshot<'_, T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        match rc_allocation(self.ptr, visited) {
+            Some(bytes) => bytes + self.as_ref().unwrap().heap_size(visited),
+            None => 0,
+        }
+    }
+}
+
+impl<T: RcObject + HeapSize> HeapSize for AtomicRc<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let guard = &cs();
+        self.load(Ordering::Acquire, guard).heap_size(visited)
+    }
+}
+
+macro_rules! impl_without_heap {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl HeapSize for $t {
+                #[inline]
+                fn heap_size(&self, _: &mut Visited) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_without_heap!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    &str,
+);
+
+impl HeapSize for String {
+    fn heap_size(&self, _: &mut Visited) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.as_ref().map_or(0, |value| value.heap_size(visited))
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.iter().map(|value| value.heap_size(visited)).sum()
+    }
+}
+
+impl<T: HeapSize, const N: usize> HeapSize for [T; N] {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self[..].heap_size(visited)
+    }
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for Box<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        size_of_val(&**self) + (**self).heap_size(visited)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.capacity() * size_of::<T>() + self[..].heap_size(visited)
+    }
+}
+
+impl<T: HeapSize> HeapSize for VecDeque<T> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let items: usize = self.iter().map(|value| value.heap_size(visited)).sum();
+        self.capacity() * size_of::<T>() + items
+    }
+}
+
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(key, value)| key.heap_size(visited) + value.heap_size(visited))
+            .sum();
+        // The table also keeps a control byte per bucket.
+        self.capacity() * (size_of::<(K, V)>() + 1) + entries
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.iter()
+            .map(|(key, value)| {
+                size_of::<(K, V)>() + key.heap_size(visited) + value.heap_size(visited)
+            })
+            .sum()
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.0.heap_size(visited) + self.1.heap_size(visited)
+    }
+}
@@ -0,0 +1,348 @@
+//! Reference-counted pointers to objects that embed their own counter.
+//!
+//! [`Rc`](crate::Rc) allocates its objects inside a header of CIRC, whose counter also records the
+//! epochs that cycle-aware reclamation relies on. Objects whose layout is fixed, for example to
+//! match a C struct with a reference count in its header, can instead implement
+//! [`IntrusiveRcObject`] and be shared with [`IntrusiveRc`] and [`AtomicIntrusiveRc`]. These
+//! pointers count through the embedded counter and defer the destruction of an object to the
+//! collector, so that an object loaded from an [`AtomicIntrusiveRc`] stays valid until the end
+//! of the critical section, like a [`Snapshot`](crate::Snapshot).
+//!
+//! Unlike [`RcObject`](crate::RcObject)s, intrusive objects are not traced, so cycles among them
+//! are never reclaimed.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::cs;
+//! use circ::intrusive::{AtomicIntrusiveRc, IntrusiveRc, IntrusiveRcObject};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! #[repr(C)]
+//! struct Buffer {
+//!     refcount: AtomicUsize,
+//!     len: usize,
+//! }
+//!
+//! unsafe impl IntrusiveRcObject for Buffer {
+//!     fn counter(&self) -> &AtomicUsize {
+//!         &self.refcount
+//!     }
+//!
+//!     unsafe fn destroy(this: *mut Self) {
+//!         drop(Box::from_raw(this));
+//!     }
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let buffer = Box::new(Buffer { refcount: AtomicUsize::new(1), len: 4 });
+//! let buffer = unsafe { IntrusiveRc::from_raw(Box::into_raw(buffer)) };
+//! let shared = AtomicIntrusiveRc::new(buffer);
+//!
+//! let guard = &cs();
+//! assert_eq!(shared.load(Ordering::Acquire, guard).unwrap().len, 4);
+//! let counted = shared.load_rc(Ordering::Acquire, guard);
+//! assert_eq!(counted.as_ref().unwrap().refcount.load(Ordering::Relaxed), 2);
+//! ```
+
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::mem::forget;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+
+use crate::{cs, Guard};
+
+/// An object that embeds its own strong reference counter.
+///
+/// # Safety
+///
+/// [`IntrusiveRcObject::counter`] must always return the same counter, which must not be modified
+/// other than by [`IntrusiveRc`] and [`AtomicIntrusiveRc`] while they point to the object, and
+/// [`IntrusiveRcObject::destroy`] must be safe to call on another thread.
+pub unsafe trait IntrusiveRcObject {
+    /// Returns the strong reference counter embedded in the object.
+    fn counter(&self) -> &AtomicUsize;
+
+    /// Destroys the object and frees its memory, after its counter has dropped to zero and no
+    /// thread can read it anymore.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be the pointer to the object that was given to [`IntrusiveRc::from_raw`], and
+    /// the object must not be used afterwards.
+    unsafe fn destroy(this: *mut Self);
+}
+
+/// Increments the counter of `obj` unless it has dropped to zero, returning `true` on success.
+fn try_increment<T: IntrusiveRcObject>(obj: &T) -> bool {
+    let counter = obj.counter();
+    let mut curr = counter.load(Ordering::Relaxed);
+    while curr != 0 {
+        match counter.compare_exchange_weak(curr, curr + 1, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return true,
+            Err(actual) => curr = actual,
+        }
+    }
+    false
+}
+
+/// Releases a strong reference to `ptr`, deferring its destruction to the collector if it was the
+/// last one.
+///
+/// # Safety
+///
+/// `ptr` must be a valid pointer whose strong reference is owned by the caller.
+unsafe fn release<T: IntrusiveRcObject>(ptr: *mut T, guard: &Guard) {
+    if (*ptr).counter().fetch_sub(1, Ordering::Release) == 1 {
+        fence(Ordering::Acquire);
+        // Readers of an `AtomicIntrusiveRc` may still be reading the object in their critical
+        // sections.
+        let ptr = ptr as usize;
+        guard.defer_sized_unchecked(move || T::destroy(ptr as *mut T), std::mem::size_of::<T>());
+    }
+}
+
+/// A reference-counted pointer to an [`IntrusiveRcObject`].
+pub struct IntrusiveRc<T: IntrusiveRcObject> {
+    ptr: *mut T,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: IntrusiveRcObject + Send + Sync> Send for IntrusiveRc<T> {}
+unsafe impl<T: IntrusiveRcObject + Send + Sync> Sync for IntrusiveRc<T> {}
+
+impl<T: IntrusiveRcObject> IntrusiveRc<T> {
+    /// Constructs a null `IntrusiveRc`.
+    #[inline]
+    pub fn null() -> Self {
+        Self {
+            ptr: ptr::null_mut(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constructs an `IntrusiveRc` that takes over one strong reference of `ptr`, which is
+    /// already included in its counter. A freshly created object should start with a count of
+    /// one.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or point to a valid object that stays valid until
+    /// [`IntrusiveRcObject::destroy`] is called on it.
+    #[inline]
+    pub unsafe fn from_raw(ptr: *mut T) -> Self {
+        Self {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Consumes the pointer without releasing its strong reference, and returns the raw pointer.
+    #[inline]
+    pub fn into_raw(self) -> *mut T {
+        let ptr = self.ptr;
+        forget(self);
+        ptr
+    }
+
+    /// Returns the raw pointer.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Returns `true` if the pointer is null.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(&self) -> Option<&T> {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Returns `true` if the two pointers are identical.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.ptr, other.ptr)
+    }
+
+    /// Consumes this pointer and releases its strong reference in the critical section of
+    /// `guard`.
+    #[inline]
+    pub fn finalize(self, guard: &Guard) {
+        let ptr = self.into_raw();
+        if !ptr.is_null() {
+            unsafe { release(ptr, guard) };
+        }
+    }
+}
+
+impl<T: IntrusiveRcObject> Clone for IntrusiveRc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        if let Some(obj) = self.as_ref() {
+            obj.counter().fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: IntrusiveRcObject> Drop for IntrusiveRc<T> {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { release(self.ptr, &cs()) };
+        }
+    }
+}
+
+impl<T: IntrusiveRcObject> Default for IntrusiveRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: IntrusiveRcObject + Debug> Debug for IntrusiveRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.as_ref() {
+            Some(obj) => f.debug_tuple("IntrusiveRc").field(obj).finish(),
+            None => f.write_str("Null"),
+        }
+    }
+}
+
+impl<T: IntrusiveRcObject> Pointer for IntrusiveRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.ptr, f)
+    }
+}
+
+/// A thread-safe (atomic) mutable memory location that contains an [`IntrusiveRc<T>`].
+pub struct AtomicIntrusiveRc<T: IntrusiveRcObject> {
+    link: AtomicPtr<T>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: IntrusiveRcObject + Send + Sync> Send for AtomicIntrusiveRc<T> {}
+unsafe impl<T: IntrusiveRcObject + Send + Sync> Sync for AtomicIntrusiveRc<T> {}
+
+impl<T: IntrusiveRcObject> AtomicIntrusiveRc<T> {
+    /// Constructs a new `AtomicIntrusiveRc` that takes over the strong reference of `ptr`.
+    #[inline]
+    pub fn new(ptr: IntrusiveRc<T>) -> Self {
+        Self {
+            link: AtomicPtr::new(ptr.into_raw()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constructs a new `AtomicIntrusiveRc` containing a null pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self::new(IntrusiveRc::null())
+    }
+
+    /// Loads the pointer, and returns a reference to the object that is valid until the end of
+    /// the critical section of `guard`.
+    #[inline]
+    pub fn load<'g>(&self, order: Ordering, _guard: &'g Guard) -> Option<&'g T> {
+        // SAFETY: The destruction of an object is deferred until the critical section ends.
+        unsafe { self.link.load(order).as_ref() }
+    }
+
+    /// Loads the pointer and returns a new strong reference to the object.
+    #[inline]
+    pub fn load_rc(&self, order: Ordering, _guard: &Guard) -> IntrusiveRc<T> {
+        loop {
+            let ptr = self.link.load(order);
+            // SAFETY: The destruction of an object is deferred until the critical section ends.
+            match unsafe { ptr.as_ref() } {
+                None => return IntrusiveRc::null(),
+                // The object may have been replaced and released after it was loaded, in which
+                // case the next load sees the replacement.
+                Some(obj) if try_increment(obj) => return unsafe { IntrusiveRc::from_raw(ptr) },
+                Some(_) => continue,
+            }
+        }
+    }
+
+    /// Stores `ptr` into the atomic pointer, releasing the previous one in the critical section
+    /// of `guard`.
+    #[inline]
+    pub fn store(&self, ptr: IntrusiveRc<T>, order: Ordering, guard: &Guard) {
+        self.swap(ptr, order).finalize(guard);
+    }
+
+    /// Stores `ptr` into the atomic pointer, returning the previous one.
+    #[inline]
+    pub fn swap(&self, ptr: IntrusiveRc<T>, order: Ordering) -> IntrusiveRc<T> {
+        let old = self.link.swap(ptr.into_raw(), order);
+        unsafe { IntrusiveRc::from_raw(old) }
+    }
+
+    /// Stores `new` into the atomic pointer if the current value is `current`.
+    ///
+    /// On success, returns the previous pointer. On failure, returns `new` back.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: *const T,
+        new: IntrusiveRc<T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<IntrusiveRc<T>, IntrusiveRc<T>> {
+        match self
+            .link
+            .compare_exchange(current as *mut T, new.as_ptr(), success, failure)
+        {
+            Ok(old) => {
+                forget(new);
+                Ok(unsafe { IntrusiveRc::from_raw(old) })
+            }
+            Err(_) => Err(new),
+        }
+    }
+
+    /// Takes the pointer out, leaving a null pointer.
+    #[inline]
+    pub fn take(&mut self) -> IntrusiveRc<T> {
+        let ptr = std::mem::replace(self.link.get_mut(), ptr::null_mut());
+        unsafe { IntrusiveRc::from_raw(ptr) }
+    }
+}
+
+impl<T: IntrusiveRcObject> Drop for AtomicIntrusiveRc<T> {
+    #[inline]
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+impl<T: IntrusiveRcObject> Default for AtomicIntrusiveRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: IntrusiveRcObject> From<IntrusiveRc<T>> for AtomicIntrusiveRc<T> {
+    #[inline]
+    fn from(ptr: IntrusiveRc<T>) -> Self {
+        Self::new(ptr)
+    }
+}
+
+impl<T: IntrusiveRcObject> Debug for AtomicIntrusiveRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.link.load(Ordering::Relaxed), f)
+    }
+}
@@ -0,0 +1,113 @@
+//! Checkers of the internal invariants, for property-based tests.
+//!
+//! The checkers are only available with debug assertions. A property-based test, with `proptest`
+//! or `quickcheck` for example, can call them between its operations, so that a broken invariant
+//! is caught right after the operation that broke it instead of as a crash or a leak much later.
+//!
+//! The checks are exact only while no other thread operates on the checked collector or objects.
+//! An operation that runs concurrently may be observed halfway and reported as a violation.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::invariants::{check_atomic_rc, check_collector, check_rc};
+//! use circ::{cs, AtomicRc, Rc, RcObject};
+//! use std::sync::atomic::Ordering;
+//!
+//! struct Node(usize);
+//!
+//! unsafe impl RcObject for Node {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let atomic = AtomicRc::new(Node(0));
+//! for i in 1..10 {
+//!     let rc = Rc::new(Node(i));
+//!     atomic.store(rc.clone(), Ordering::Release, &cs());
+//!
+//!     check_rc(&rc).unwrap();
+//!     check_atomic_rc(&atomic, &cs()).unwrap();
+//!     check_collector(circ::default_collector()).unwrap();
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+
+use crate::{AtomicRc, Collector, Guard, Rc, RcObject, Snapshot};
+
+/// The error returned by a checker when an invariant is broken.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InvariantViolation {
+    message: String,
+}
+
+impl InvariantViolation {
+    /// Returns the description of the broken invariant.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<String> for InvariantViolation {
+    fn from(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invariant violated: {}", self.message)
+    }
+}
+
+impl Error for InvariantViolation {}
+
+/// Checks the invariants of a collector.
+///
+/// No more deferred functions may have been reclaimed than sealed, every pinned participant must
+/// lag at most one epoch behind the global epoch, and no bag may be sealed in an epoch after the
+/// global epoch.
+pub fn check_collector(collector: &Collector) -> Result<(), InvariantViolation> {
+    collector
+        .with_guard(|guard| collector.global.check_invariants(guard))
+        .map_err(InvariantViolation::from)
+}
+
+/// Checks the reference counts of the object of `rc`, which must not have been destructed.
+///
+/// A null pointer is always valid.
+pub fn check_rc<T: RcObject>(rc: &Rc<T>) -> Result<(), InvariantViolation> {
+    match unsafe { rc.as_raw().as_raw().as_ref() } {
+        Some(inner) => inner.check_counts(true).map_err(InvariantViolation::from),
+        None => Ok(()),
+    }
+}
+
+/// Checks the reference counts of the object of `snapshot`, which is reachable from the critical
+/// section of the snapshot and thus must not have been destructed.
+///
+/// Its strong count may be zero, as the destruction of an object is deferred until the critical
+/// sections that may have loaded it have ended.
+pub fn check_snapshot<T: RcObject>(snapshot: &Snapshot<'_, T>) -> Result<(), InvariantViolation> {
+    match unsafe { snapshot.ptr.as_raw().as_ref() } {
+        Some(inner) => inner.check_counts(false).map_err(InvariantViolation::from),
+        None => Ok(()),
+    }
+}
+
+/// Checks the reference counts of the object that `atomic` currently points to, like
+/// [`check_rc`], as the atomic pointer holds a strong reference to it.
+pub fn check_atomic_rc<T: RcObject>(
+    atomic: &AtomicRc<T>,
+    guard: &Guard,
+) -> Result<(), InvariantViolation> {
+    let snapshot = atomic.load(Ordering::Acquire, guard);
+    match unsafe { snapshot.ptr.as_raw().as_ref() } {
+        Some(inner) => inner.check_counts(true).map_err(InvariantViolation::from),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,130 @@
+//! The layout of reference-counted objects.
+//!
+//! An object of an [`Rc<T>`] is allocated as a `#[repr(C)]` struct of a header of
+//! [`HEADER_SIZE`] bytes, which holds the reference counts, followed by the payload of type `T`
+//! at [`payload_offset::<T>()`](payload_offset). The header is at the start of the allocation, so
+//! the payload of an object is always [`payload_offset::<T>()`](payload_offset) bytes after its
+//! header, whatever the build profile, and only padding follows the payload.
+//!
+//! This lets intrusive collections and FFI code that only keep a `*const T` or a `&T` to the
+//! payload get back to the counted object, with [`from_raw_payload`] and [`rc_from_ref`]. The
+//! layout is checked by static assertions, and does not change without a major version bump.
+//!
+//! This module requires the `unsafe-layout` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::layout::{from_raw_payload, into_raw_payload, rc_from_ref};
+//! use circ::{Rc, RcObject};
+//!
+//! struct Node(usize);
+//!
+//! unsafe impl RcObject for Node {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! // Pass the object through FFI as a pointer to the payload.
+//! let payload: *const Node = into_raw_payload(Rc::new(Node(7)));
+//! let rc = unsafe { from_raw_payload(payload) };
+//!
+//! // Get a new strong reference from a plain reference to the payload.
+//! let node: &Node = rc.as_ref().unwrap();
+//! let other = unsafe { rc_from_ref(node) };
+//! assert!(other.ptr_eq(&rc));
+//! ```
+
+use core::mem::{align_of, size_of};
+use core::sync::atomic::AtomicU64;
+
+use static_assertions::const_assert_eq;
+
+use crate::utils::RcInner;
+use crate::{Rc, RcObject};
+
+/// The size of the header of a reference-counted object, in bytes.
+pub const HEADER_SIZE: usize = size_of::<AtomicU64>();
+
+/// The alignment of the header of a reference-counted object, in bytes.
+pub const HEADER_ALIGN: usize = align_of::<AtomicU64>();
+
+/// A payload with a larger alignment than the header.
+#[allow(dead_code)]
+#[repr(align(32))]
+struct Aligned;
+
+// The payload follows the header as closely as its alignment allows.
+const_assert_eq!(HEADER_SIZE, 8);
+const_assert_eq!(payload_offset::<u8>(), HEADER_SIZE);
+const_assert_eq!(payload_offset::<u64>(), HEADER_SIZE);
+const_assert_eq!(payload_offset::<[u8; 3]>(), HEADER_SIZE);
+const_assert_eq!(payload_offset::<()>(), HEADER_SIZE);
+const_assert_eq!(payload_offset::<Aligned>(), 32);
+
+/// Returns the offset of the payload of an object of type `T` from the start of its header, in
+/// bytes.
+///
+/// This is [`HEADER_SIZE`] rounded up to the alignment of `T`.
+#[inline]
+pub const fn payload_offset<T>() -> usize {
+    RcInner::<T>::DATA_OFFSET
+}
+
+/// Returns the address of the header of the object whose payload is at `payload`.
+///
+/// This does not dereference `payload`, so it is safe to call, but the result is only meaningful
+/// for the payload of a reference-counted object.
+#[inline]
+pub fn header_of<T>(payload: *const T) -> *const u8 {
+    payload.cast::<u8>().wrapping_sub(payload_offset::<T>())
+}
+
+/// Returns the address of the payload of the object whose header is at `header`.
+///
+/// This does not dereference `header`, so it is safe to call, but the result is only meaningful
+/// for the header of a reference-counted object of type `T`.
+#[inline]
+pub fn payload_of<T>(header: *const u8) -> *const T {
+    header.wrapping_add(payload_offset::<T>()).cast()
+}
+
+/// Consumes `rc` and returns a pointer to its payload, without releasing its strong reference.
+///
+/// The tag of `rc` is not kept. A null `rc` gives a null pointer. The strong reference is taken
+/// over again by [`from_raw_payload`].
+#[inline]
+pub fn into_raw_payload<T: RcObject>(rc: Rc<T>) -> *const T {
+    RcInner::data_ptr(rc.into_raw().as_raw())
+}
+
+/// Constructs an [`Rc`] from a pointer to the payload of an object, taking over a strong
+/// reference.
+///
+/// A null `payload` gives a null [`Rc`].
+///
+/// # Safety
+///
+/// `payload` must be null, or the payload of a reference-counted object of type `T` with a
+/// strong reference that the caller owns, such as one returned by [`into_raw_payload`].
+#[inline]
+pub unsafe fn from_raw_payload<T: RcObject>(payload: *const T) -> Rc<T> {
+    if payload.is_null() {
+        return Rc::null();
+    }
+    Rc::from_raw(header_of(payload).cast::<RcInner<T>>().into())
+}
+
+/// Returns a new strong reference to the object whose payload is `payload`.
+///
+/// # Safety
+///
+/// `payload` must be the payload of a reference-counted object of type `T`, borrowed from a
+/// strong reference or from a [`Snapshot`](crate::Snapshot) of a live critical section.
+#[inline]
+pub unsafe fn rc_from_ref<T: RcObject>(payload: &T) -> Rc<T> {
+    let ptr = header_of(payload).cast::<RcInner<T>>();
+    (*ptr).increment_strong();
+    Rc::from_raw(ptr.into())
+}
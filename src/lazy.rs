@@ -0,0 +1,88 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::OnceLock;
+
+use crate::{default_collector, Rc, RcObject};
+
+/// A reference-counted value that is initialized on its first access, for use in statics.
+///
+/// A `LazyRc` can be constructed in a `static`, and the first access allocates its object with
+/// the initializer. Afterwards, [`LazyRc::get`] hands out clones of the same [`Rc`]. The object is
+/// always allocated in the [default collector](default_collector), which is shared by all the
+/// modules through the `dyntls` context, even if it is first accessed inside
+/// [`Collector::enter`](crate::Collector::enter). Like the other statics of `dyntls`, it must be
+/// accessed after the context has been initialized.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{LazyRc, Rc, RcObject};
+///
+/// struct Config {
+///     threads: usize,
+/// }
+///
+/// unsafe impl RcObject for Config {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// static CONFIG: LazyRc<Config> = LazyRc::new(|| Config { threads: 4 });
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let config: Rc<Config> = CONFIG.get();
+/// assert_eq!(config.as_ref().unwrap().threads, 4);
+/// assert!(config.ptr_eq(&CONFIG.get()));
+/// ```
+pub struct LazyRc<T: RcObject, F = fn() -> T> {
+    cell: OnceLock<Rc<T>>,
+    init: F,
+}
+
+impl<T: RcObject, F> LazyRc<T, F> {
+    /// Constructs a new `LazyRc` that initializes its object with `init` on the first access.
+    #[inline]
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceLock::new(),
+            init,
+        }
+    }
+
+    /// Returns `true` if the object has been initialized.
+    #[inline]
+    pub fn is_initialized(&self) -> bool {
+        self.cell.get().is_some()
+    }
+}
+
+impl<T: RcObject, F: Fn() -> T> LazyRc<T, F> {
+    /// Returns the shared [`Rc`], initializing the object if it has not been accessed before.
+    #[inline]
+    pub fn as_rc(&self) -> &Rc<T> {
+        self.cell
+            .get_or_init(|| default_collector().enter(|| Rc::new((self.init)())))
+    }
+
+    /// Returns a new [`Rc`] to the object, initializing it if it has not been accessed before.
+    #[inline]
+    pub fn get(&self) -> Rc<T> {
+        self.as_rc().clone()
+    }
+}
+
+impl<T: RcObject, F: Fn() -> T> AsRef<T> for LazyRc<T, F> {
+    /// Returns a reference to the object, initializing it if it has not been accessed before.
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self.as_rc().as_ref().unwrap()
+    }
+}
+
+impl<T: RcObject + Debug, F> Debug for LazyRc<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.cell.get() {
+            Some(rc) => f.debug_tuple("LazyRc").field(rc).finish(),
+            None => f.write_str("LazyRc(<uninit>)"),
+        }
+    }
+}
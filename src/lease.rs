@@ -0,0 +1,204 @@
+//! Leases for long read transactions.
+//!
+//! A long scan that holds a single critical section keeps a consistent view of the data, but it
+//! also prevents the collector from reclaiming anything retired after the scan has started. A
+//! [`Lease`] is a critical section with a budget: it tracks how much garbage it is withholding
+//! and for how long, and expires when either exceeds the limits of its [`LeaseOptions`], or when
+//! it is expired by another thread through an [`ExpireHandle`].
+//!
+//! Expiry is cooperative. The reader calls [`Lease::checkpoint`] between the steps of the scan,
+//! which ends the critical section of an expired lease, so that the reader must restart or resume
+//! the scan from a key instead of from a reference.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::lease::{Lease, LeaseOptions};
+//! use std::time::Duration;
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let options = LeaseOptions::new()
+//!     .max_duration(Duration::from_secs(1))
+//!     .max_withheld(1 << 20);
+//! let mut lease = Lease::new(options);
+//! for step in 0..10 {
+//!     if lease.checkpoint().is_err() {
+//!         // The view has been released; restart or resume from `step`.
+//!         break;
+//!     }
+//!     let _guard = lease.guard();
+//!     // ... read with `_guard` ...
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{cs, Collector, Guard};
+
+/// The limits of a [`Lease`].
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LeaseOptions {
+    max_duration: Option<Duration>,
+    max_withheld: Option<usize>,
+}
+
+impl LeaseOptions {
+    /// Creates options without any limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expires the lease after it has been held for `duration`.
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Expires the lease once it withholds more than `count` deferred functions from
+    /// reclamation.
+    pub fn max_withheld(mut self, count: usize) -> Self {
+        self.max_withheld = Some(count);
+        self
+    }
+}
+
+/// The error returned by [`Lease::checkpoint`] when the lease has expired.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LeaseExpired;
+
+impl Display for LeaseExpired {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the lease has expired")
+    }
+}
+
+impl Error for LeaseExpired {}
+
+/// A handle that expires a [`Lease`] from another thread.
+#[derive(Clone, Debug)]
+pub struct ExpireHandle {
+    expired: Arc<AtomicBool>,
+}
+
+impl ExpireHandle {
+    /// Forces the lease to expire at its next checkpoint.
+    pub fn expire(&self) {
+        self.expired.store(true, Ordering::Release);
+    }
+}
+
+/// A critical section that withholds garbage within the limits of its [`LeaseOptions`].
+///
+/// See the [module-level documentation](self) for details.
+pub struct Lease {
+    guard: Guard,
+    options: LeaseOptions,
+    /// When the current view was taken.
+    started: Instant,
+    /// The number of deferred functions that had been sealed when the current view was taken.
+    sealed: usize,
+    expired: Arc<AtomicBool>,
+}
+
+impl Lease {
+    /// Starts a lease in the collector of the current thread.
+    pub fn new(options: LeaseOptions) -> Self {
+        let guard = cs();
+        if let Some(collector) = collector(&guard) {
+            collector.global.leases.fetch_add(1, Ordering::Relaxed);
+        }
+        let sealed = sealed(&guard);
+        Self {
+            guard,
+            options,
+            started: Instant::now(),
+            sealed,
+            expired: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns the guard of the lease, which is valid until the next checkpoint.
+    #[inline]
+    pub fn guard(&self) -> &Guard {
+        &self.guard
+    }
+
+    /// Returns how long the current view has been held.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns the number of deferred functions that have been retired since the current view
+    /// was taken, which cannot be reclaimed while it is held.
+    pub fn withheld(&self) -> usize {
+        sealed(&self.guard).wrapping_sub(self.sealed)
+    }
+
+    /// Returns a handle that can expire the lease from another thread.
+    pub fn expire_handle(&self) -> ExpireHandle {
+        ExpireHandle {
+            expired: self.expired.clone(),
+        }
+    }
+
+    /// Returns `true` if the lease has exceeded its limits or has been expired by a handle.
+    pub fn is_expired(&self) -> bool {
+        self.expired.load(Ordering::Acquire)
+            || self
+                .options
+                .max_duration
+                .is_some_and(|max| self.elapsed() > max)
+            || self
+                .options
+                .max_withheld
+                .is_some_and(|max| self.withheld() > max)
+    }
+
+    /// Checks whether the lease has expired.
+    ///
+    /// If it has, the current view is released and a new one is taken, and an error is
+    /// returned. The references obtained with the previous view must not be used anymore, which is
+    /// enforced by `&mut self`.
+    pub fn checkpoint(&mut self) -> Result<(), LeaseExpired> {
+        if !self.is_expired() {
+            return Ok(());
+        }
+        self.guard.reactivate();
+        self.started = Instant::now();
+        self.sealed = sealed(&self.guard);
+        self.expired.store(false, Ordering::Release);
+        Err(LeaseExpired)
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Some(collector) = collector(&self.guard) {
+            collector.global.leases.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Debug for Lease {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lease")
+            .field("options", &self.options)
+            .field("elapsed", &self.elapsed())
+            .field("withheld", &self.withheld())
+            .finish_non_exhaustive()
+    }
+}
+
+fn collector(guard: &Guard) -> Option<&Collector> {
+    unsafe { guard.local.as_ref() }.map(|local| local.collector())
+}
+
+/// Returns the number of deferred functions that have ever been sealed in the collector.
+fn sealed(guard: &Guard) -> usize {
+    collector(guard).map_or(0, |c| c.global.sealed.load(Ordering::Relaxed))
+}
@@ -1,10 +1,62 @@
 #![doc = include_str!("../README.md")]
 
+pub mod affinity;
+pub mod biased;
+pub mod compat;
+pub mod compressed;
+mod cow;
+pub mod ds;
 pub(crate) mod ebr_impl;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod graph;
+pub mod heap_size;
+pub mod intrusive;
+#[cfg(debug_assertions)]
+pub mod invariants;
+#[cfg(feature = "unsafe-layout")]
+pub mod layout;
+mod lazy;
+pub mod lease;
+#[cfg(feature = "lincheck")]
+pub mod lincheck;
+pub mod local;
+pub mod pool;
+#[cfg(all(feature = "memory-pressure", target_os = "linux"))]
+pub mod pressure;
+#[cfg(feature = "rayon")]
+pub mod rayon;
+mod rc_ref;
+mod rcu;
+pub mod runtime;
+mod sanitizer;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod sharded;
+#[cfg(feature = "abi_stable")]
+pub mod stable_abi;
 mod strong;
+pub mod thin;
 mod utils;
+pub mod versioned;
+pub mod watch;
 mod weak;
+pub mod weighted;
 
-pub use ebr_impl::{cs, Guard, unprotected as unprotected_cs};
+pub use cow::*;
+#[cfg(feature = "async")]
+pub use ebr_impl::GracePeriod;
+#[cfg(feature = "type-stats")]
+pub use ebr_impl::TypeStats;
+pub use ebr_impl::{
+    boxed_deferreds, cs, default_collector, remove_alloc_failure_hook, set_alloc_failure_hook,
+    set_pin_collection, set_realtime, try_with_cs, unprotected as unprotected_cs, with_cs,
+    with_local_handle, AllocFailureAction, CollectAllStats, Collector, CollectorBuilder,
+    DeferredFuture, Epoch, EpochAdvanceHookId, EpochToken, Guard, LocalHandle, ParticipantInfo,
+    Participants, PendingDeferred, PinCollection, ReclamationSchedule, ReclamationStep,
+};
+pub use lazy::*;
+pub use rc_ref::*;
+pub use rcu::*;
 pub use strong::*;
 pub use weak::*;
@@ -0,0 +1,387 @@
+//! A linearizability checker for concurrent structures.
+//!
+//! [`Checker`] runs short random histories of operations on a concurrent structure from several
+//! threads at once, recording when each operation was invoked and when it returned. It then
+//! searches for a linearization of each history: a sequential order of the operations that
+//! respects their real-time order and in which a [`Model`] of the structure returns the same
+//! results. A history without one is reported as a [`Violation`].
+//!
+//! The histories are kept short, since the search is exponential in their length. Running many
+//! of them is more effective at finding races than running a few long ones.
+//!
+//! This module requires the `lincheck` feature. It is meant for tests, of the structures in
+//! [`ds`](crate::ds) as well as of structures built on top of this crate.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::ds::Stack;
+//! use circ::lincheck::{Checker, Concurrent, Model, Rng};
+//! use circ::{cs, Rc, RcObject};
+//!
+//! struct Item(u8);
+//!
+//! unsafe impl RcObject for Item {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! #[derive(Clone, Debug)]
+//! enum Op {
+//!     Push(u8),
+//!     Pop,
+//! }
+//!
+//! #[derive(Clone, Default)]
+//! struct StackModel(Vec<u8>);
+//!
+//! impl Model for StackModel {
+//!     type Op = Op;
+//!     type Ret = Option<u8>;
+//!
+//!     fn generate(rng: &mut Rng) -> Op {
+//!         if rng.below(2) == 0 {
+//!             Op::Push(rng.below(100) as u8)
+//!         } else {
+//!             Op::Pop
+//!         }
+//!     }
+//!
+//!     fn apply(&mut self, op: &Op) -> Option<u8> {
+//!         match op {
+//!             Op::Push(item) => {
+//!                 self.0.push(*item);
+//!                 None
+//!             }
+//!             Op::Pop => self.0.pop(),
+//!         }
+//!     }
+//! }
+//!
+//! impl Concurrent<StackModel> for Stack<Item> {
+//!     fn apply(&self, op: &Op) -> Option<u8> {
+//!         let guard = &cs();
+//!         match op {
+//!             Op::Push(item) => {
+//!                 self.push(Rc::new(Item(*item)), guard);
+//!                 None
+//!             }
+//!             Op::Pop => self.pop(guard).map(|item| item.as_ref().unwrap().0),
+//!         }
+//!     }
+//! }
+//!
+//! let context = dyntls_host::get();
+//! unsafe { context.initialize() };
+//! Checker::new()
+//!     .iterations(20)
+//!     .check(Stack::<Item>::new, StackModel::default, move || unsafe { context.initialize() })
+//!     .unwrap();
+//! ```
+
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Barrier;
+use std::thread;
+
+/// A sequential specification of a concurrent structure.
+pub trait Model: Clone {
+    /// An operation on the structure.
+    type Op: Clone + Debug + Send + Sync;
+    /// The result of an operation.
+    type Ret: Clone + Debug + PartialEq + Send;
+
+    /// Returns a random operation.
+    fn generate(rng: &mut Rng) -> Self::Op;
+
+    /// Applies `op` to the model and returns its result.
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret;
+}
+
+/// A concurrent structure that is checked against the model `M`.
+pub trait Concurrent<M: Model>: Sync {
+    /// Applies `op` to the structure and returns its result.
+    ///
+    /// This is called from several threads at once, each of which enters its own critical
+    /// sections as needed.
+    fn apply(&self, op: &M::Op) -> M::Ret;
+}
+
+/// A small pseudo-random number generator for generating operations.
+///
+/// This is SplitMix64, which is good enough for tests and makes a failing history reproducible
+/// from its seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a new generator from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo-random number less than `bound`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bound` is zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "the bound must be positive");
+        self.next_u64() % bound
+    }
+}
+
+/// An operation of a history with its result and the interval in which it took place.
+pub struct Event<M: Model> {
+    /// The thread that performed the operation.
+    pub thread: usize,
+    /// The operation.
+    pub op: M::Op,
+    /// The result returned by the structure.
+    pub ret: M::Ret,
+    /// The logical time at which the operation was invoked.
+    pub invoked: u64,
+    /// The logical time at which the operation returned.
+    pub returned: u64,
+}
+
+impl<M: Model> Clone for Event<M> {
+    fn clone(&self) -> Self {
+        Self {
+            thread: self.thread,
+            op: self.op.clone(),
+            ret: self.ret.clone(),
+            invoked: self.invoked,
+            returned: self.returned,
+        }
+    }
+}
+
+impl<M: Model> Debug for Event<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}, {}] thread {}: {:?} -> {:?}",
+            self.invoked, self.returned, self.thread, self.op, self.ret
+        )
+    }
+}
+
+/// A history that has no linearization.
+pub struct Violation<M: Model> {
+    /// The seed of the generator of the history, for reproducing it with [`Checker::seed`] and
+    /// a single iteration.
+    pub seed: u64,
+    /// The events of the history, in the order of their invocation.
+    pub history: Vec<Event<M>>,
+}
+
+impl<M: Model> Debug for Violation<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Violation")
+            .field("seed", &self.seed)
+            .field("history", &self.history)
+            .finish()
+    }
+}
+
+impl<M: Model> Display for Violation<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the history of seed {} is not linearizable:", self.seed)?;
+        for event in &self.history {
+            write!(f, "\n  {event:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: Model> Error for Violation<M> {}
+
+/// Runs random concurrent histories and checks that they are linearizable.
+#[derive(Clone, Debug)]
+pub struct Checker {
+    threads: usize,
+    ops_per_thread: usize,
+    iterations: usize,
+    seed: u64,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checker {
+    /// Creates a new checker that runs 100 histories of 3 threads with 4 operations each.
+    pub fn new() -> Self {
+        Self {
+            threads: 3,
+            ops_per_thread: 4,
+            iterations: 100,
+            seed: 0,
+        }
+    }
+
+    /// Sets the number of threads of each history.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the number of operations each thread performs in a history.
+    pub fn ops_per_thread(mut self, ops: usize) -> Self {
+        self.ops_per_thread = ops;
+        self
+    }
+
+    /// Sets the number of histories to run.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets the seed of the first history. The following histories use the following seeds.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the histories on fresh structures made by `new` and checks them against fresh models
+    /// made by `model`, returning the first history that is not linearizable.
+    ///
+    /// `init` is called first on every spawned thread, and is expected to initialize its dyntls
+    /// context.
+    pub fn check<S, M>(
+        &self,
+        new: impl Fn() -> S,
+        model: impl Fn() -> M,
+        init: impl Fn() + Sync,
+    ) -> Result<(), Violation<M>>
+    where
+        S: Concurrent<M>,
+        M: Model,
+    {
+        for iteration in 0..self.iterations {
+            let seed = self.seed.wrapping_add(iteration as u64);
+            let history = self.run(&new(), seed, &init);
+            if !linearizable(&model(), &history) {
+                return Err(Violation { seed, history });
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a single history on `structure`.
+    fn run<S: Concurrent<M>, M: Model>(
+        &self,
+        structure: &S,
+        seed: u64,
+        init: &(impl Fn() + Sync),
+    ) -> Vec<Event<M>> {
+        let mut rng = Rng::new(seed);
+        let programs: Vec<Vec<M::Op>> = (0..self.threads)
+            .map(|_| {
+                (0..self.ops_per_thread)
+                    .map(|_| M::generate(&mut rng))
+                    .collect()
+            })
+            .collect();
+        let clock = AtomicU64::new(0);
+        let start = Barrier::new(self.threads);
+
+        let mut history: Vec<Event<M>> = thread::scope(|s| {
+            let handles: Vec<_> = programs
+                .iter()
+                .enumerate()
+                .map(|(thread, program)| {
+                    let (clock, start) = (&clock, &start);
+                    s.spawn(move || {
+                        init();
+                        start.wait();
+                        program
+                            .iter()
+                            .map(|op| {
+                                let invoked = clock.fetch_add(1, Ordering::SeqCst);
+                                let ret = structure.apply(op);
+                                let returned = clock.fetch_add(1, Ordering::SeqCst);
+                                Event {
+                                    thread,
+                                    op: op.clone(),
+                                    ret,
+                                    invoked,
+                                    returned,
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        history.sort_by_key(|event| event.invoked);
+        history
+    }
+}
+
+/// Returns `true` if `history` has a linearization starting from the state of `model`.
+///
+/// The events of `history` must be sorted by their invocation.
+pub fn linearizable<M: Model>(model: &M, history: &[Event<M>]) -> bool {
+    search(
+        model,
+        history,
+        &mut vec![false; history.len()],
+        history.len(),
+    )
+}
+
+/// Searches for a linearization of the events of `history` that are not `done` yet.
+///
+/// This is the algorithm of Wing and Gong: any event invoked before the earliest response among
+/// the remaining events may take effect first, and each of them is tried in turn.
+fn search<M: Model>(model: &M, history: &[Event<M>], done: &mut [bool], left: usize) -> bool {
+    if left == 0 {
+        return true;
+    }
+    let bound = history
+        .iter()
+        .zip(done.iter())
+        .filter(|(_, done)| !**done)
+        .map(|(event, _)| event.returned)
+        .min()
+        .unwrap();
+    for (i, event) in history.iter().enumerate() {
+        if done[i] {
+            continue;
+        }
+        if event.invoked > bound {
+            // The events are sorted by invocation, so the rest are invoked later.
+            break;
+        }
+        let mut next = model.clone();
+        if next.apply(&event.op) != event.ret {
+            continue;
+        }
+        done[i] = true;
+        if search(&next, history, done, left - 1) {
+            return true;
+        }
+        done[i] = false;
+    }
+    false
+}
@@ -0,0 +1,239 @@
+//! Reference-counted pointers to objects that are neither [`Send`] nor [`Sync`].
+//!
+//! A single-threaded subsystem embedded in a multithreaded application may hold payloads that
+//! cannot leave their thread, and still want the pointer model of the rest of the application.
+//! A [`LocalRc`] and a [`LocalAtomicRc`] are an [`Rc`] and an [`AtomicRc`] restricted to the
+//! thread that created their objects: they use the same collector and the same deferred
+//! reclamation, but they are neither `Send` nor `Sync`, and the objects do not need to be either.
+//!
+//! The collector may still reclaim an object on another thread. In that case the object is not
+//! dropped there but routed back to its owner thread through a [`ThreadBound`], and dropped when
+//! the owner calls [`affinity::drain`](crate::affinity::drain) or exits. The edges of such an
+//! object are released when it is dropped on the owner thread.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::local::{LocalAtomicRc, LocalRc, LocalRcObject};
+//! use std::cell::RefCell;
+//!
+//! struct Node {
+//!     // A `RefCell` is not `Sync`, so the node cannot be shared through an `Rc`.
+//!     value: RefCell<String>,
+//!     next: LocalRc<Node>,
+//! }
+//!
+//! unsafe impl LocalRcObject for Node {
+//!     fn pop_edges(&mut self, out: &mut Vec<LocalRc<Self>>) {
+//!         out.push(std::mem::take(&mut self.next));
+//!     }
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let head = LocalAtomicRc::null();
+//! for i in 0..3 {
+//!     let next = head.load();
+//!     head.store(LocalRc::new(Node {
+//!         value: RefCell::new(i.to_string()),
+//!         next,
+//!     }));
+//! }
+//! let node = head.load();
+//! node.as_ref().unwrap().value.borrow_mut().push('!');
+//! assert_eq!(*node.as_ref().unwrap().value.borrow(), "2!");
+//! ```
+
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::sync::atomic::Ordering;
+
+use crate::affinity::ThreadBound;
+use crate::{cs, AtomicRc, Rc, RcObject};
+
+/// A trait for the objects of [`LocalRc`]s, like [`RcObject`] for the objects of [`Rc`]s.
+///
+/// # Safety
+///
+/// The same requirements as for [`RcObject`] apply.
+pub unsafe trait LocalRcObject: Sized {
+    /// Takes all `LocalRc`s in the object and adds them to `out`.
+    ///
+    /// This method is called just before the object is destructed, and only on its owner thread.
+    fn pop_edges(&mut self, out: &mut Vec<LocalRc<Self>>);
+}
+
+/// The object of a [`LocalRc`], which is only accessed and dropped on its owner thread.
+struct Bound<T>(ThreadBound<T>);
+
+unsafe impl<T: LocalRcObject> RcObject for Bound<T> {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        // On another thread, the edges are released when the owner drops the object.
+        if let Some(obj) = self.0.get_mut() {
+            let mut edges = Vec::new();
+            obj.pop_edges(&mut edges);
+            out.extend(edges.into_iter().map(|edge| edge.rc));
+        }
+    }
+}
+
+/// A reference-counted pointer to an object of type `T` that stays on the thread that created
+/// the object.
+///
+/// See the [module documentation](self) for details.
+pub struct LocalRc<T: LocalRcObject> {
+    rc: Rc<Bound<T>>,
+    /// A `LocalRc` is `!Send` and `!Sync`, so it never leaves the owner thread of its object.
+    _marker: PhantomData<*const T>,
+}
+
+impl<T: LocalRcObject> LocalRc<T> {
+    #[inline]
+    fn from_rc(rc: Rc<Bound<T>>) -> Self {
+        Self {
+            rc,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constructs a null `LocalRc` pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self::from_rc(Rc::null())
+    }
+
+    /// Constructs a new `LocalRc` by allocating a new reference-counted object, owned by the
+    /// current thread.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self::from_rc(Rc::new(Bound(ThreadBound::new(obj))))
+    }
+
+    /// Returns `true` if the pointer is null.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.rc.is_null()
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(&self) -> Option<&T> {
+        // SAFETY: A `LocalRc` stays on the thread that created its object.
+        self.rc.as_ref().map(|obj| unsafe { obj.0.get_unchecked() })
+    }
+
+    /// Returns `true` if the two pointers point to the same object.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.rc.ptr_eq(&other.rc)
+    }
+}
+
+impl<T: LocalRcObject> Clone for LocalRc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::from_rc(self.rc.clone())
+    }
+}
+
+impl<T: LocalRcObject> Default for LocalRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: LocalRcObject + Debug> Debug for LocalRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(obj) = self.as_ref() {
+            f.debug_tuple("LocalRc").field(obj).finish()
+        } else {
+            f.write_str("Null")
+        }
+    }
+}
+
+impl<T: LocalRcObject> Pointer for LocalRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.rc, f)
+    }
+}
+
+/// A mutable slot holding a [`LocalRc`], like an [`AtomicRc`] that stays on one thread.
+///
+/// As a `LocalAtomicRc` is not shared between threads, its operations need neither a memory
+/// ordering nor a critical section from the caller.
+pub struct LocalAtomicRc<T: LocalRcObject> {
+    inner: AtomicRc<Bound<T>>,
+    /// A `LocalAtomicRc` is `!Send` and `!Sync`, so it never leaves the owner thread of its
+    /// objects.
+    _marker: PhantomData<*const T>,
+}
+
+impl<T: LocalRcObject> LocalAtomicRc<T> {
+    /// Constructs a new `LocalAtomicRc` by allocating a new reference-counted object, owned by
+    /// the current thread.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self::from(LocalRc::new(obj))
+    }
+
+    /// Constructs a new `LocalAtomicRc` containing a null pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self::from(LocalRc::null())
+    }
+
+    /// Returns a new strong reference to the current object.
+    #[inline]
+    pub fn load(&self) -> LocalRc<T> {
+        LocalRc::from_rc(self.inner.load(Ordering::Acquire, &cs()).counted())
+    }
+
+    /// Stores `ptr` into the slot, releasing the previous object.
+    #[inline]
+    pub fn store(&self, ptr: LocalRc<T>) {
+        self.inner.store(ptr.rc, Ordering::Release, &cs());
+    }
+
+    /// Stores `new` into the slot and returns the previous pointer.
+    #[inline]
+    pub fn swap(&self, new: LocalRc<T>) -> LocalRc<T> {
+        LocalRc::from_rc(self.inner.swap(new.rc, Ordering::AcqRel))
+    }
+
+    /// Takes the pointer out of the slot, leaving a null pointer.
+    #[inline]
+    pub fn take(&mut self) -> LocalRc<T> {
+        LocalRc::from_rc(self.inner.take())
+    }
+}
+
+impl<T: LocalRcObject> Default for LocalAtomicRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: LocalRcObject> From<LocalRc<T>> for LocalAtomicRc<T> {
+    #[inline]
+    fn from(ptr: LocalRc<T>) -> Self {
+        Self {
+            inner: AtomicRc::from(ptr.rc),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: LocalRcObject + Debug> Debug for LocalAtomicRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.load(), f)
+    }
+}
+
+impl<T: LocalRcObject> Pointer for LocalAtomicRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.inner, f)
+    }
+}
@@ -0,0 +1,289 @@
+//! Object pools whose recycled slots are protected by the collector.
+
+use std::cell::UnsafeCell;
+use std::fmt::{Debug, Formatter};
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::Guard;
+
+/// The log2 of the number of slots of the first bucket.
+pub(crate) const FIRST_BUCKET_BITS: u32 = 5;
+
+/// The number of buckets. The slab holds fewer than `2^32` slots in total.
+pub(crate) const BUCKETS: usize = (u32::BITS - FIRST_BUCKET_BITS) as usize;
+
+/// The mask for the index part of the head of the free list.
+pub(crate) const INDEX_MASK: u64 = u32::MAX as u64;
+
+/// A key of a value in a [`Slab`].
+///
+/// A key carries the generation of its slot, so a key of a removed value never refers to another
+/// value that reuses the slot later.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    index: usize,
+    generation: usize,
+}
+
+impl Key {
+    /// Returns the index of the slot of the key.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+struct Slot<T> {
+    /// `2 * generation + 1` if the slot is occupied, and `2 * generation` otherwise.
+    stamp: AtomicUsize,
+    /// The index of the next free slot plus one, or zero at the end of the free list.
+    next_free: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self {
+            stamp: AtomicUsize::new(0),
+            next_free: AtomicUsize::new(0),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+/// Returns the bucket and the offset in the bucket of the slot at `index`.
+#[inline]
+pub(crate) fn locate(index: usize) -> (usize, usize) {
+    let i = index + (1 << FIRST_BUCKET_BITS);
+    let bucket = (usize::BITS - 1 - i.leading_zeros() - FIRST_BUCKET_BITS) as usize;
+    (bucket, i - bucket_len(bucket))
+}
+
+#[inline]
+pub(crate) fn bucket_len(bucket: usize) -> usize {
+    1 << (bucket + FIRST_BUCKET_BITS as usize)
+}
+
+struct Inner<T> {
+    /// Buckets of doubling sizes, allocated on demand.
+    buckets: [AtomicPtr<Slot<T>>; BUCKETS],
+    /// The number of slots that have ever been handed out.
+    len: AtomicUsize,
+    /// The head of the free list. The low 32 bits hold the index of the head plus one, and the
+    /// high 32 bits hold a counter that is bumped on every update to prevent ABA.
+    free: AtomicU64,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send + Sync> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    fn slot(&self, index: usize) -> Option<&Slot<T>> {
+        let (bucket, offset) = locate(index);
+        let ptr = self.buckets.get(bucket)?.load(Ordering::Acquire);
+        // SAFETY: a bucket is never deallocated while the slab is alive.
+        unsafe { ptr.as_ref().map(|_| &*ptr.add(offset)) }
+    }
+
+    /// Hands out a slot that has never been used.
+    fn grow(&self) -> usize {
+        let index = self.len.fetch_add(1, Ordering::Relaxed);
+        let (bucket, _) = locate(index);
+        assert!(bucket < BUCKETS, "too many slots in a slab");
+
+        let atomic = &self.buckets[bucket];
+        if atomic.load(Ordering::Acquire).is_null() {
+            let new: Box<[Slot<T>]> = (0..bucket_len(bucket)).map(|_| Slot::new()).collect();
+            let new = Box::into_raw(new) as *mut Slot<T>;
+            if atomic
+                .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+            {
+                // SAFETY: `new` has not been shared.
+                let new = ptr::slice_from_raw_parts_mut(new, bucket_len(bucket));
+                drop(unsafe { Box::from_raw(new) });
+            }
+        }
+        index
+    }
+
+    fn push_free(&self, index: usize) {
+        let slot = self.slot(index).unwrap();
+        let mut head = self.free.load(Ordering::Relaxed);
+        loop {
+            slot.next_free
+                .store((head & INDEX_MASK) as usize, Ordering::Relaxed);
+            let new = (((head >> 32) + 1) << 32) | (index as u64 + 1);
+            match self
+                .free
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn pop_free(&self) -> Option<usize> {
+        let mut head = self.free.load(Ordering::Acquire);
+        loop {
+            let index = ((head & INDEX_MASK) as usize).checked_sub(1)?;
+            // The slot may be popped and pushed again concurrently, in which case the counter
+            // makes the compare-and-swap fail.
+            let next = self.slot(index).unwrap().next_free.load(Ordering::Relaxed) as u64;
+            let new = (((head >> 32) + 1) << 32) | next;
+            match self
+                .free
+                .compare_exchange_weak(head, new, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => return Some(index),
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Drops the value in a removed slot and makes the slot available again.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been removed, and no thread may be reading its value.
+    unsafe fn release(&self, index: usize) {
+        let slot = self.slot(index).unwrap();
+        (*slot.value.get()).assume_init_drop();
+        self.push_free(index);
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let len = *self.len.get_mut();
+        for (bucket, atomic) in self.buckets.iter_mut().enumerate() {
+            let ptr = *atomic.get_mut();
+            if ptr.is_null() {
+                continue;
+            }
+            let start = bucket_len(bucket) - (1 << FIRST_BUCKET_BITS);
+            let mut slots =
+                unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, bucket_len(bucket))) };
+            for slot in slots.iter_mut().take(len.saturating_sub(start)) {
+                if *slot.stamp.get_mut() & 1 == 1 {
+                    unsafe { slot.value.get_mut().assume_init_drop() };
+                }
+            }
+        }
+    }
+}
+
+/// A concurrent slab that recycles the storage of removed values.
+///
+/// Values are stored in slots which are allocated in chunks and never deallocated until the slab
+/// is dropped. A removed value is not dropped immediately. Instead, dropping it and recycling its
+/// slot are deferred to the collector, so that the value stays valid for the threads that are
+/// reading it in their critical sections, and a slot is reused only after a grace period.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, pool::Slab};
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let slab = Slab::new();
+/// let guard = &cs();
+/// let key = slab.insert("value");
+/// assert_eq!(slab.get(key, guard), Some(&"value"));
+///
+/// assert!(slab.remove(key, guard));
+/// assert_eq!(slab.get(key, guard), None);
+/// ```
+pub struct Slab<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Slab<T> {
+    /// Creates a new empty slab.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                buckets: Default::default(),
+                len: AtomicUsize::new(0),
+                free: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Inserts `value` into a free slot, returning its key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slab already has `2^32 - 32` slots.
+    pub fn insert(&self, value: T) -> Key {
+        let index = self.inner.pop_free().unwrap_or_else(|| self.inner.grow());
+        let slot = self.inner.slot(index).unwrap();
+        unsafe { (*slot.value.get()).write(value) };
+        let stamp = slot.stamp.load(Ordering::Relaxed) | 1;
+        slot.stamp.store(stamp, Ordering::Release);
+        Key {
+            index,
+            generation: stamp >> 1,
+        }
+    }
+
+    /// Returns a reference to the value for `key`.
+    ///
+    /// The reference is valid until the end of the critical section, even if the value is removed
+    /// in the meantime.
+    pub fn get<'g>(&'g self, key: Key, _guard: &'g Guard) -> Option<&'g T> {
+        let slot = self.inner.slot(key.index)?;
+        if slot.stamp.load(Ordering::Acquire) != (key.generation << 1) | 1 {
+            return None;
+        }
+        // SAFETY: The value is dropped only after the current critical section ends.
+        Some(unsafe { (*slot.value.get()).assume_init_ref() })
+    }
+
+    /// Returns `true` if the slab contains a value for `key`.
+    pub fn contains(&self, key: Key, guard: &Guard) -> bool {
+        self.get(key, guard).is_some()
+    }
+
+    /// Removes the value for `key`, returning `true` if it was present.
+    ///
+    /// The value is dropped and its slot becomes available after all threads currently in their
+    /// critical sections have left them.
+    pub fn remove(&self, key: Key, guard: &Guard) -> bool
+    where
+        T: Send + 'static,
+    {
+        let Some(slot) = self.inner.slot(key.index) else {
+            return false;
+        };
+        let occupied = (key.generation << 1) | 1;
+        if slot
+            .stamp
+            .compare_exchange(occupied, occupied + 1, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        let inner = self.inner.clone();
+        let index = key.index;
+        unsafe { guard.defer_unchecked(move || inner.release(index)) };
+        true
+    }
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for Slab<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Slab { .. }")
+    }
+}
@@ -0,0 +1,167 @@
+//! Collection triggered by the memory-pressure events of Linux.
+//!
+//! The kernel reports how long tasks stall on memory through pressure stall information (PSI),
+//! either for the whole system in `/proc/pressure/memory` or for a cgroup in its
+//! `memory.pressure` file. A [`Watcher`] registers a trigger on such a file and calls
+//! [`Collector::notify_pressure`] whenever it fires, so that the garbage is reclaimed before the
+//! service is killed for running out of memory.
+//!
+//! This module requires the `memory-pressure` feature and a kernel with PSI enabled.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use circ::pressure::{Trigger, Watcher};
+//!
+//! let context = dyntls_host::get();
+//! unsafe { context.initialize() };
+//!
+//! let watcher = Watcher::spawn(circ::default_collector(), Trigger::cgroup(), move || unsafe {
+//!     context.initialize()
+//! })
+//! .expect("PSI is not available");
+//! // ... run the service ...
+//! drop(watcher);
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::Collector;
+
+/// A PSI trigger, which fires when tasks stall on memory for `stall` in total within a window of
+/// `window`.
+#[derive(Clone, Debug)]
+pub struct Trigger {
+    /// The PSI file to register the trigger on.
+    pub path: PathBuf,
+    /// Whether to count only the time when all tasks stall (`full`), rather than when some do
+    /// (`some`).
+    pub full: bool,
+    /// The total stall time within a window that fires the trigger.
+    pub stall: Duration,
+    /// The length of the window, which the kernel requires to be between 500 ms and 10 s.
+    pub window: Duration,
+}
+
+impl Trigger {
+    /// Returns a trigger on the memory pressure of the whole system, which fires when some tasks
+    /// stall for 100 ms within a second.
+    pub fn system() -> Self {
+        Self::new("/proc/pressure/memory")
+    }
+
+    /// Returns a trigger on the memory pressure of the cgroup of the process, as mounted at
+    /// `/sys/fs/cgroup` in its namespace, which fires when some tasks stall for 100 ms within a
+    /// second.
+    pub fn cgroup() -> Self {
+        Self::new("/sys/fs/cgroup/memory.pressure")
+    }
+
+    fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            full: false,
+            stall: Duration::from_millis(100),
+            window: Duration::from_secs(1),
+        }
+    }
+
+    /// Opens the PSI file and registers the trigger on it.
+    fn register(&self) -> io::Result<File> {
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        let kind = if self.full { "full" } else { "some" };
+        // The kernel expects the whole trigger in one write, terminated by a NUL byte.
+        let trigger = format!(
+            "{kind} {} {}\0",
+            self.stall.as_micros(),
+            self.window.as_micros()
+        );
+        file.write_all(trigger.as_bytes())?;
+        Ok(file)
+    }
+}
+
+/// A background thread that calls [`Collector::notify_pressure`] whenever a PSI trigger fires.
+///
+/// The thread is stopped and joined when the watcher is dropped.
+#[derive(Debug)]
+pub struct Watcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// How long the thread waits for an event before checking whether it should stop.
+    const POLL_TIMEOUT_MS: i32 = 200;
+
+    /// Registers `trigger` and spawns a thread that calls [`Collector::notify_pressure`] on
+    /// `collector` whenever it fires.
+    ///
+    /// `init` is called first on the new thread, and is expected to initialize its dyntls
+    /// context. Returns an error if the trigger cannot be registered, such as when the kernel
+    /// does not support PSI or the process may not write to the file.
+    pub fn spawn<F>(collector: &Collector, trigger: Trigger, init: F) -> io::Result<Self>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let file = trigger.register()?;
+        let collector = collector.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new().name("circ-pressure".into()).spawn({
+            let stop = stop.clone();
+            move || {
+                init();
+                watch(&file, &collector, &stop);
+            }
+        })?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Waits for the events of the trigger registered on `file` until `stop` is set.
+fn watch(file: &File, collector: &Collector, stop: &AtomicBool) {
+    let mut pollfd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLPRI,
+        revents: 0,
+    };
+    while !stop.load(Ordering::Acquire) {
+        // SAFETY: `pollfd` is a valid array of one element, and `file` outlives the call.
+        let ready = unsafe { libc::poll(&mut pollfd, 1, Watcher::POLL_TIMEOUT_MS) };
+        if ready < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            tracing::warn!("polling the memory-pressure trigger failed: {error}");
+            return;
+        }
+        if pollfd.revents & libc::POLLERR != 0 {
+            // The file has gone away, such as when the cgroup has been removed.
+            tracing::warn!("the memory-pressure trigger is no longer available");
+            return;
+        }
+        if pollfd.revents & libc::POLLPRI != 0 {
+            collector.notify_pressure();
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Integration with [`rayon`] thread pools.
+//!
+//! Every thread that uses CIRC must initialize its dyntls context first, and is registered in the
+//! collector on its first critical section. [`register_threads`] does both as soon as each
+//! thread of a pool starts, so that the closures run by parallel iterators can call [`cs`]
+//! directly.
+
+use rayon::ThreadPoolBuilder;
+
+use crate::cs;
+
+/// Installs a start handler on `builder` which calls `init` and registers the thread in the
+/// default collector.
+///
+/// `init` is expected to initialize the dyntls context of the thread. This replaces the start
+/// handler previously set on `builder`, if any.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, AtomicRc, RcObject, Rc};
+/// use rayon::prelude::*;
+/// use std::sync::atomic::Ordering;
+///
+/// struct Item(usize);
+///
+/// unsafe impl RcObject for Item {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// let context = dyntls_host::get();
+/// unsafe { context.initialize() };
+///
+/// let builder = rayon::ThreadPoolBuilder::new().num_threads(4);
+/// let pool = circ::rayon::register_threads(builder, move || unsafe { context.initialize() })
+///     .build()
+///     .unwrap();
+///
+/// let item = AtomicRc::new(Item(1));
+/// let sum: usize = pool.install(|| {
+///     (0..100)
+///         .into_par_iter()
+///         .map(|_| item.load(Ordering::Acquire, &cs()).as_ref().unwrap().0)
+///         .sum()
+/// });
+/// assert_eq!(sum, 100);
+/// ```
+pub fn register_threads<S, F>(builder: ThreadPoolBuilder<S>, init: F) -> ThreadPoolBuilder<S>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    builder.start_handler(move |_| {
+        init();
+        // Pinning registers the thread in the collector.
+        drop(cs());
+    })
+}
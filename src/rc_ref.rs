@@ -0,0 +1,111 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+
+use crate::{Rc, RcObject};
+
+/// A reference to a part of a reference-counted object, which keeps the whole object alive.
+///
+/// An `RcRef` is created by [`Rc::project`], and dereferences to the projected part, so an API
+/// can hand out a sub-object of a node without exposing the node or copying the sub-object.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{Rc, RcObject, RcRef};
+///
+/// struct Entry {
+///     key: u64,
+///     name: String,
+/// }
+///
+/// unsafe impl RcObject for Entry {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// fn name_of(entry: Rc<Entry>) -> RcRef<Entry, str> {
+///     entry.project(|entry| entry.name.as_str())
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let name = name_of(Rc::new(Entry { key: 1, name: "one".into() }));
+/// assert_eq!(&*name, "one");
+/// assert_eq!(name.as_rc().as_ref().unwrap().key, 1);
+/// ```
+pub struct RcRef<T: RcObject, U: ?Sized> {
+    rc: Rc<T>,
+    /// Points into the object of `rc`, which it keeps alive.
+    part: *const U,
+}
+
+unsafe impl<T: RcObject + Send + Sync, U: ?Sized + Sync> Send for RcRef<T, U> {}
+unsafe impl<T: RcObject + Send + Sync, U: ?Sized + Sync> Sync for RcRef<T, U> {}
+
+impl<T: RcObject> Rc<T> {
+    /// Consumes this pointer and returns an [`RcRef`] that dereferences to the part of the object
+    /// selected by `f`, while keeping the whole object alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pointer is null.
+    #[inline]
+    pub fn project<U, F>(self, f: F) -> RcRef<T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U,
+    {
+        let part: *const U = f(self.as_ref().expect("`Rc::project` on a null pointer"));
+        RcRef { rc: self, part }
+    }
+}
+
+impl<T: RcObject, U: ?Sized> RcRef<T, U> {
+    /// Returns the pointer to the whole object.
+    #[inline]
+    pub fn as_rc(&self) -> &Rc<T> {
+        &self.rc
+    }
+
+    /// Consumes the projection and returns the pointer to the whole object.
+    #[inline]
+    pub fn into_rc(self) -> Rc<T> {
+        self.rc
+    }
+
+    /// Projects further into a part of the projected part.
+    #[inline]
+    pub fn map<V, F>(self, f: F) -> RcRef<T, V>
+    where
+        V: ?Sized,
+        F: FnOnce(&U) -> &V,
+    {
+        let part: *const V = f(&*self);
+        RcRef { rc: self.rc, part }
+    }
+}
+
+impl<T: RcObject, U: ?Sized> Deref for RcRef<T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: The part belongs to the object, which is kept alive by `rc`.
+        unsafe { &*self.part }
+    }
+}
+
+impl<T: RcObject, U: ?Sized> Clone for RcRef<T, U> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            rc: self.rc.clone(),
+            part: self.part,
+        }
+    }
+}
+
+impl<T: RcObject, U: ?Sized + Debug> Debug for RcRef<T, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RcRef").field(&&**self).finish()
+    }
+}
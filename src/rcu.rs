@@ -0,0 +1,295 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{cs, AtomicRc, Guard, Rc, RcObject, Snapshot};
+
+/// A read-mostly shared cell holding an [`Rc<T>`].
+///
+/// `RcuCell` is a thin wrapper around a single [`AtomicRc`], tailored for the *read-copy-update*
+/// pattern. Readers take a [`Snapshot`] of the current value without touching the reference
+/// counter, and writers replace the value as a whole with [`RcuCell::store`] or
+/// [`RcuCell::update`]. A replaced value is reclaimed once all readers that may have loaded it
+/// have left their critical sections.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, RcObject, Rc, RcuCell};
+///
+/// struct Config {
+///     threads: usize,
+/// }
+///
+/// unsafe impl RcObject for Config {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let config = RcuCell::new(Config { threads: 4 });
+///
+/// let guard = &cs();
+/// let old = config.read(guard);
+/// config.update(|c| Config { threads: c.threads * 2 }, guard);
+///
+/// assert_eq!(old.as_ref().unwrap().threads, 4);
+/// assert_eq!(config.read(guard).as_ref().unwrap().threads, 8);
+/// ```
+pub struct RcuCell<T: RcObject> {
+    inner: AtomicRc<T>,
+    /// Incremented after every replacement of `inner`, so that [`Cache`]s can tell whether their
+    /// copies are stale.
+    generation: AtomicUsize,
+}
+
+impl<T: RcObject> RcuCell<T> {
+    /// Constructs a new `RcuCell` by allocating a new reference-counted object.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self {
+            inner: AtomicRc::new(obj),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    /// Constructs a new `RcuCell` containing a null pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self {
+            inner: AtomicRc::null(),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reads the current value as a [`Snapshot`] pointer.
+    ///
+    /// This does not touch the reference counter, so it is as cheap as an atomic load.
+    #[inline]
+    pub fn read<'g>(&self, guard: &'g Guard) -> Snapshot<'g, T> {
+        self.inner.load(Ordering::Acquire, guard)
+    }
+
+    /// Reads the current value as an [`Rc`] pointer, which can outlive a critical section.
+    ///
+    /// It activates a critical section internally. If the current thread is already in a critical
+    /// section, consider `self.read(guard).counted()` instead.
+    #[inline]
+    pub fn load(&self) -> Rc<T> {
+        self.read(&cs()).counted()
+    }
+
+    /// Replaces the current value with `new`.
+    #[inline]
+    pub fn store(&self, new: Rc<T>, guard: &Guard) {
+        self.inner.store(new, Ordering::Release, guard);
+        self.bump_generation();
+    }
+
+    /// Replaces the current value with `new`, returning the previous value.
+    #[inline]
+    pub fn swap(&self, new: Rc<T>) -> Rc<T> {
+        let old = self.inner.swap(new, Ordering::AcqRel);
+        self.bump_generation();
+        old
+    }
+
+    /// Replaces the current value with `new` if the current value is `current`.
+    ///
+    /// On success the previous value is returned. On failure `new` is given back.
+    #[inline]
+    pub fn compare_and_swap(
+        &self,
+        current: Snapshot<'_, T>,
+        new: Rc<T>,
+        guard: &Guard,
+    ) -> Result<Rc<T>, Rc<T>> {
+        let old = self
+            .inner
+            .compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire, guard)
+            .map_err(|e| e.desired)?;
+        self.bump_generation();
+        Ok(old)
+    }
+
+    /// Replaces the current value with the one computed by `f`, retrying until no other writer
+    /// interferes, and returns the replaced value.
+    ///
+    /// `f` may be called multiple times, each time with the latest value. It must not have side
+    /// effects that cannot be repeated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell holds a null pointer.
+    pub fn update<F>(&self, mut f: F, guard: &Guard) -> Rc<T>
+    where
+        F: FnMut(&T) -> T,
+    {
+        let mut current = self.read(guard);
+        loop {
+            let value = current
+                .as_ref()
+                .expect("`RcuCell::update` on a null pointer");
+            let new = Rc::new(f(value));
+            match self.inner.compare_exchange(
+                current,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(old) => {
+                    self.bump_generation();
+                    return old;
+                }
+                Err(e) => {
+                    e.desired.finalize(guard);
+                    current = e.current;
+                }
+            }
+        }
+    }
+
+    /// Returns the number of times the value has been replaced.
+    ///
+    /// This is used by [`Cache`] to detect replacements without loading the value itself.
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    fn bump_generation(&self) {
+        // Published after the new value, so that observing a new generation implies observing
+        // the new value as well.
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Consumes the cell, returning the current value.
+    #[inline]
+    pub fn into_inner(mut self) -> Rc<T> {
+        self.inner.take()
+    }
+}
+
+impl<T: RcObject> From<Rc<T>> for RcuCell<T> {
+    #[inline]
+    fn from(value: Rc<T>) -> Self {
+        Self {
+            inner: AtomicRc::from(value),
+            generation: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: RcObject> Default for RcuCell<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: RcObject> Debug for RcuCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RcuCell").field(&self.inner).finish()
+    }
+}
+
+/// A per-thread cache of the value of an [`RcuCell`].
+///
+/// A `Cache` keeps a counted copy of the value together with the [generation](RcuCell::generation)
+/// it was loaded at. [`Cache::load`] only checks the generation, which is a single atomic load,
+/// and reloads the value from the cell when it has been replaced. As long as the value is not
+/// replaced, reading it requires neither activating a critical section nor touching the
+/// reference counter.
+///
+/// The flip side is that a cached copy keeps a replaced value alive until the cache is reloaded
+/// or dropped.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{Cache, RcObject, Rc, RcuCell};
+///
+/// struct Config {
+///     threads: usize,
+/// }
+///
+/// unsafe impl RcObject for Config {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let config = RcuCell::new(Config { threads: 4 });
+/// let mut cache = Cache::new(&config);
+/// assert_eq!(cache.load().as_ref().unwrap().threads, 4);
+///
+/// config.swap(Rc::new(Config { threads: 8 }));
+/// assert_eq!(cache.load().as_ref().unwrap().threads, 8);
+/// ```
+pub struct Cache<'c, T: RcObject> {
+    cell: &'c RcuCell<T>,
+    cached: Rc<T>,
+    generation: usize,
+}
+
+impl<'c, T: RcObject> Cache<'c, T> {
+    /// Creates a new cache for `cell`, loading its current value.
+    pub fn new(cell: &'c RcuCell<T>) -> Self {
+        let generation = cell.generation();
+        Self {
+            cell,
+            cached: cell.load(),
+            generation,
+        }
+    }
+
+    /// Returns the cell this cache is attached to.
+    #[inline]
+    pub fn cell(&self) -> &'c RcuCell<T> {
+        self.cell
+    }
+
+    /// Returns the cached value, reloading it first if the value in the cell has been replaced.
+    #[inline]
+    pub fn load(&mut self) -> &Rc<T> {
+        let generation = self.cell.generation();
+        if generation != self.generation {
+            self.reload(generation);
+        }
+        &self.cached
+    }
+
+    /// Returns the cached value without checking whether it is up to date.
+    #[inline]
+    pub fn peek(&self) -> &Rc<T> {
+        &self.cached
+    }
+
+    #[cold]
+    fn reload(&mut self, generation: usize) {
+        // The value may be replaced again after reading `generation`. Then the next `load`
+        // observes a newer generation and reloads once more.
+        self.cached = self.cell.load();
+        self.generation = generation;
+    }
+}
+
+impl<'c, T: RcObject> Clone for Cache<'c, T> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell,
+            cached: self.cached.clone(),
+            generation: self.generation,
+        }
+    }
+}
+
+impl<'c, T: RcObject> Debug for Cache<'c, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cache")
+            .field("cached", &format_args!("{:p}", self.cached))
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
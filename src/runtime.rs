@@ -0,0 +1,124 @@
+//! Sharing a collector between dynamic libraries.
+//!
+//! Libraries that share the dyntls context of the host already share the default collector. A
+//! library that does not, for example one that is loaded as a plugin with its own copy of this
+//! crate, can adopt the collector of the host instead: the host [`export`]s its collector as a
+//! C-compatible [`CollectorHandle`], and the plugin [`adopt`]s it as its default collector before
+//! using any pointer. Objects can then be exchanged freely between the two.
+//!
+//! Both sides must use the same build of this crate, which is checked when the handle is
+//! adopted. With debug assertions enabled, reading an object in a critical section of a collector
+//! other than the one it was allocated with panics.
+
+use std::error::Error;
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use crate::ebr_impl::{current_collector, install_default, Collector};
+
+/// An identifier of the layout of the collector, which must match between the libraries that
+/// share a collector.
+const ABI_VERSION: u64 = {
+    let bytes = env!("CARGO_PKG_VERSION").as_bytes();
+    // FNV-1a over the version, mixed with the build configuration.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = (hash ^ bytes[i] as u64).wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash ^ (cfg!(debug_assertions) as u64)
+};
+
+/// A C-compatible handle owning a reference to a collector.
+///
+/// A handle must be passed to [`adopt`] or [`release`] exactly once.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CollectorHandle {
+    global: *const c_void,
+    abi_version: u64,
+}
+
+/// The error returned when a [`CollectorHandle`] cannot be adopted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AdoptError {
+    /// The handle was exported by an incompatible build of this crate.
+    AbiMismatch,
+    /// The default collector of this library has already been used.
+    AlreadyInitialized,
+}
+
+impl Display for AdoptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AbiMismatch => f.write_str("the collector was exported by an incompatible build"),
+            Self::AlreadyInitialized => f.write_str("the default collector has already been used"),
+        }
+    }
+}
+
+impl Error for AdoptError {}
+
+/// Exports the collector of the current thread, which is the default collector unless another
+/// one has been entered with [`Collector::enter`].
+pub fn export() -> CollectorHandle {
+    let collector = current_collector(Collector::clone);
+    CollectorHandle {
+        global: Arc::into_raw(collector.global).cast(),
+        abi_version: ABI_VERSION,
+    }
+}
+
+/// Makes the collector of `handle` the default collector of this library.
+///
+/// This must be called before this library first uses the default collector, and fails with
+/// [`AdoptError::AlreadyInitialized`] otherwise. The handle is consumed even on failure.
+///
+/// # Safety
+///
+/// `handle` must have been returned by [`export`], and must not have been adopted or released.
+pub unsafe fn adopt(handle: CollectorHandle) -> Result<(), AdoptError> {
+    if handle.abi_version != ABI_VERSION {
+        // The layout of the collector is unknown, so the reference is leaked.
+        return Err(AdoptError::AbiMismatch);
+    }
+    let collector = Collector {
+        global: Arc::from_raw(handle.global.cast()),
+    };
+    install_default(collector).map_err(|_| AdoptError::AlreadyInitialized)
+}
+
+/// Releases the reference to the collector owned by `handle`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by [`export`] of the same build of this crate, and must not
+/// have been adopted or released.
+pub unsafe fn release(handle: CollectorHandle) {
+    debug_assert_eq!(handle.abi_version, ABI_VERSION);
+    drop(Collector {
+        global: Arc::from_raw(handle.global.cast()),
+    });
+}
+
+/// Exports the collector of the current thread. See [`export`].
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub extern "C" fn circ_collector_export() -> CollectorHandle {
+    export()
+}
+
+/// Makes the collector of `handle` the default collector, returning `true` on success. See
+/// [`adopt`].
+///
+/// # Safety
+///
+/// `handle` must have been returned by [`circ_collector_export`], and must not have been adopted
+/// or released.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub unsafe extern "C" fn circ_collector_adopt(handle: CollectorHandle) -> bool {
+    adopt(handle).is_ok()
+}
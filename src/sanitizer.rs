@@ -0,0 +1,45 @@
+//! Poisoning of dead memory for AddressSanitizer.
+//!
+//! With the `asan` feature, which requires the program to be built with
+//! `-Zsanitizer=address`, memory that must not be accessed anymore is poisoned, so that an access
+//! is reported by the sanitizer instead of reading stale data silently:
+//!
+//! * The object of an [`Rc`](crate::Rc) is poisoned as soon as it is destructed, while its
+//!   allocation is kept alive by weak references, until the allocation is freed.
+//! * The free slots of the arena of a collector are poisoned until they are allocated again.
+//!
+//! An object is not poisoned when its destruction is merely deferred: until the deferred function
+//! runs, the object may still be read through the snapshots of the critical sections that have
+//! loaded it, or even revived by them. Without the feature, the functions do nothing.
+
+#[cfg(feature = "asan")]
+extern "C" {
+    fn __asan_poison_memory_region(addr: *const u8, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const u8, size: usize);
+}
+
+/// Marks `size` bytes at `ptr` as inaccessible.
+///
+/// # Safety
+///
+/// The memory must not be accessed until it is unpoisoned.
+#[inline]
+pub(crate) unsafe fn poison<T>(ptr: *const T, size: usize) {
+    #[cfg(feature = "asan")]
+    __asan_poison_memory_region(ptr.cast(), size);
+    #[cfg(not(feature = "asan"))]
+    let _ = (ptr, size);
+}
+
+/// Marks `size` bytes at `ptr` as accessible again.
+///
+/// # Safety
+///
+/// The memory must be owned by the caller.
+#[inline]
+pub(crate) unsafe fn unpoison<T>(ptr: *const T, size: usize) {
+    #[cfg(feature = "asan")]
+    __asan_unpoison_memory_region(ptr.cast(), size);
+    #[cfg(not(feature = "asan"))]
+    let _ = (ptr, size);
+}
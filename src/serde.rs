@@ -0,0 +1,543 @@
+//! Serialization of graphs of [`Rc`]s that preserves shared nodes.
+//!
+//! A node that is reachable along several paths would be written once per path by a plain
+//! serialization, and read back as as many separate copies. Here each node is written in full
+//! only the first time it is reached, with an id, and every later [`Rc`] to it is written as the
+//! id alone. On the way back, the nodes are looked up by id in a [`Registry`], so that a DAG
+//! round-trips to the same shared structure, with one allocation per unique node.
+//!
+//! The node type implements [`SerializeShared`] and [`DeserializeShared`], which serialize its
+//! edges with [`Ids::rc`] and deserialize them with [`Registry::seed`], a [`DeserializeSeed`].
+//!
+//! Graphs with cycles can be checkpointed and restored too, as long as every cycle passes through
+//! an [`AtomicRc`]. Such edges are loaded under a guard and written with [`Ids::snapshot`], and
+//! read with [`Registry::atomic_seed`]. An edge back to a node that is still being read is left
+//! null at first, and stored once the node has been read, through
+//! [`DeserializeShared::atomic_edges`]. An [`Rc`] edge back to such a node is an error, as an
+//! object cannot be changed once its [`Rc`] is created.
+//!
+//! This module requires the `serde` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::serde::{DeserializeShared, Ids, Registry, SerializeShared};
+//! use circ::{Rc, RcObject};
+//! use serde::de::{DeserializeSeed, Deserializer, Error, SeqAccess, Visitor};
+//! use serde::ser::{SerializeTuple, Serializer};
+//! use std::fmt;
+//!
+//! struct Node {
+//!     value: u32,
+//!     children: Vec<Rc<Node>>,
+//! }
+//!
+//! unsafe impl RcObject for Node {
+//!     fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+//!         out.append(&mut self.children);
+//!     }
+//! }
+//!
+//! impl SerializeShared for Node {
+//!     fn serialize_shared<S: Serializer>(
+//!         &self,
+//!         serializer: S,
+//!         ids: &Ids<Self>,
+//!     ) -> Result<S::Ok, S::Error> {
+//!         let children: Vec<_> = self.children.iter().map(|child| ids.rc(child)).collect();
+//!         let mut tuple = serializer.serialize_tuple(2)?;
+//!         tuple.serialize_element(&self.value)?;
+//!         tuple.serialize_element(&children)?;
+//!         tuple.end()
+//!     }
+//! }
+//!
+//! impl<'de> DeserializeShared<'de> for Node {
+//!     fn deserialize_shared<D: Deserializer<'de>>(
+//!         deserializer: D,
+//!         registry: &Registry<Self>,
+//!     ) -> Result<Self, D::Error> {
+//!         struct NodeVisitor<'r>(&'r Registry<Node>);
+//!         struct Children<'r>(&'r Registry<Node>);
+//!
+//!         impl<'de> Visitor<'de> for NodeVisitor<'_> {
+//!             type Value = Node;
+//!
+//!             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+//!                 f.write_str("a node")
+//!             }
+//!
+//!             fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+//!                 let value = seq
+//!                     .next_element()?
+//!                     .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+//!                 let children = seq
+//!                     .next_element_seed(Children(self.0))?
+//!                     .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+//!                 Ok(Node { value, children })
+//!             }
+//!         }
+//!
+//!         impl<'de> DeserializeSeed<'de> for Children<'_> {
+//!             type Value = Vec<Rc<Node>>;
+//!
+//!             fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+//!             where
+//!                 D: Deserializer<'de>,
+//!             {
+//!                 deserializer.deserialize_seq(self)
+//!             }
+//!         }
+//!
+//!         impl<'de> Visitor<'de> for Children<'_> {
+//!             type Value = Vec<Rc<Node>>;
+//!
+//!             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+//!                 f.write_str("a list of nodes")
+//!             }
+//!
+//!             fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+//!             where
+//!                 A: SeqAccess<'de>,
+//!             {
+//!                 let mut children = Vec::new();
+//!                 while let Some(child) = seq.next_element_seed(self.0.seed())? {
+//!                     children.push(child);
+//!                 }
+//!                 Ok(children)
+//!             }
+//!         }
+//!
+//!         deserializer.deserialize_tuple(2, NodeVisitor(registry))
+//!     }
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! // A diamond: the leaf is shared by both children of the root.
+//! let leaf = Rc::new(Node { value: 3, children: Vec::new() });
+//! let left = Rc::new(Node { value: 1, children: vec![leaf.clone()] });
+//! let right = Rc::new(Node { value: 2, children: vec![leaf] });
+//! let root = Rc::new(Node { value: 0, children: vec![left, right] });
+//!
+//! let ids = Ids::new();
+//! let json = serde_json::to_string(&ids.rc(&root)).unwrap();
+//!
+//! let registry = Registry::new();
+//! let root = registry
+//!     .seed()
+//!     .deserialize(&mut serde_json::Deserializer::from_str(&json))
+//!     .unwrap();
+//! assert_eq!(registry.len(), 4);
+//!
+//! let children = &root.as_ref().unwrap().children;
+//! let left = &children[0].as_ref().unwrap().children[0];
+//! let right = &children[1].as_ref().unwrap().children[0];
+//! assert!(left.ptr_eq(right));
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::take;
+use std::sync::atomic::Ordering;
+
+use serde::de::{DeserializeSeed, Deserializer, Error as _, SeqAccess, Visitor};
+use serde::ser::Serializer;
+use serde::Serialize;
+
+use crate::{cs, AtomicRc, Rc, RcObject, Snapshot};
+
+/// A node type whose edges are serialized with [`Ids::rc`] and [`Ids::snapshot`], so that the
+/// nodes shared by several edges are written once.
+pub trait SerializeShared: RcObject {
+    /// Serializes the object, writing its edges with `ids`.
+    fn serialize_shared<S: Serializer>(
+        &self,
+        serializer: S,
+        ids: &Ids<Self>,
+    ) -> Result<S::Ok, S::Error>;
+}
+
+/// A node type whose edges are deserialized with [`Registry::seed`] and
+/// [`Registry::atomic_seed`], so that the nodes shared by several edges are read back as shared.
+pub trait DeserializeShared<'de>: RcObject {
+    /// Deserializes an object, reading its edges with `registry`.
+    fn deserialize_shared<D: Deserializer<'de>>(
+        deserializer: D,
+        registry: &Registry<Self>,
+    ) -> Result<Self, D::Error>;
+
+    /// Returns the [`AtomicRc`] edges of the object, in the order in which
+    /// [`deserialize_shared`](Self::deserialize_shared) reads them with
+    /// [`Registry::atomic_seed`].
+    ///
+    /// An edge that closes a cycle is read before its target exists, so it is left null and
+    /// stored through this method once the target has been read. The default returns no edges,
+    /// which suits the types without cycles.
+    fn atomic_edges(&self) -> Vec<&AtomicRc<Self>> {
+        Vec::new()
+    }
+}
+
+/// The ids of the nodes written so far in a serialization.
+pub struct Ids<T> {
+    /// The id of each node, by the address of its object.
+    ids: RefCell<HashMap<usize, u64>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: SerializeShared> Ids<T> {
+    /// Creates an empty set of ids, for a new serialization.
+    pub fn new() -> Self {
+        Self {
+            ids: RefCell::new(HashMap::new()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a serializable form of `rc`, which is the object with a new id the first time it
+    /// is written with these ids, and the id alone afterwards. A null pointer is written as
+    /// `None`.
+    pub fn rc<'a>(&'a self, rc: &'a Rc<T>) -> SharedRc<'a, T> {
+        SharedRc {
+            value: rc.as_ref(),
+            addr: rc.as_raw().as_raw() as usize,
+            ids: self,
+        }
+    }
+
+    /// Returns a serializable form of `snapshot`, such as an [`AtomicRc`] edge loaded under a
+    /// guard, in the same format as [`Ids::rc`].
+    ///
+    /// The ids are assigned before the edges of an object are written, so an edge back to an
+    /// object that is being written is written as its id, which lets a cyclic graph be written.
+    pub fn snapshot<'a>(&'a self, snapshot: Snapshot<'a, T>) -> SharedRc<'a, T> {
+        SharedRc {
+            value: snapshot.as_ref(),
+            addr: snapshot.ptr.as_raw() as usize,
+            ids: self,
+        }
+    }
+
+    /// Returns the id of the object at `addr`, and whether it is new.
+    fn id(&self, addr: usize) -> (u64, bool) {
+        let mut ids = self.ids.borrow_mut();
+        let next = ids.len() as u64;
+        match ids.get(&addr) {
+            Some(&id) => (id, false),
+            None => {
+                ids.insert(addr, next);
+                (next, true)
+            }
+        }
+    }
+}
+
+impl<T: SerializeShared> Default for Ids<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pointer that is serialized with the ids of a serialization. See [`Ids::rc`].
+pub struct SharedRc<'a, T> {
+    value: Option<&'a T>,
+    addr: usize,
+    ids: &'a Ids<T>,
+}
+
+impl<T: SerializeShared> Serialize for SharedRc<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(value) = self.value else {
+            return serializer.serialize_none();
+        };
+        let (id, new) = self.ids.id(self.addr);
+        let value = new.then_some(Object {
+            value,
+            ids: self.ids,
+        });
+        serializer.serialize_some(&(id, value))
+    }
+}
+
+/// An object that is serialized with the ids of a serialization.
+struct Object<'a, T> {
+    value: &'a T,
+    ids: &'a Ids<T>,
+}
+
+impl<T: SerializeShared> Serialize for Object<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize_shared(serializer, self.ids)
+    }
+}
+
+/// The nodes read so far in a deserialization, by their ids.
+///
+/// The registry holds a strong reference to each node until it is dropped.
+pub struct Registry<T: RcObject> {
+    nodes: RefCell<HashMap<u64, Rc<T>>>,
+    /// The [`AtomicRc`] edges read so far by each of the objects being read, innermost last.
+    frames: RefCell<Vec<Frame>>,
+    /// The [`AtomicRc`] edges to store once the nodes being read are done: the node, the index
+    /// of the edge and the id of its target.
+    fixups: RefCell<Vec<(Rc<T>, usize, u64)>>,
+}
+
+/// The [`AtomicRc`] edges read by an object that is being read.
+#[derive(Default)]
+struct Frame {
+    /// The number of edges read so far.
+    edges: usize,
+    /// The edges whose targets are being read, with the ids of the targets.
+    pending: Vec<(usize, u64)>,
+}
+
+impl<T: RcObject> Registry<T> {
+    /// Creates an empty registry, for a new deserialization.
+    pub fn new() -> Self {
+        Self {
+            nodes: RefCell::new(HashMap::new()),
+            frames: RefCell::new(Vec::new()),
+            fixups: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns a [`DeserializeSeed`] that reads an [`Rc`] written by [`Ids::rc`], and returns the
+    /// node that has already been read if the id has been seen.
+    pub fn seed(&self) -> RcSeed<'_, T> {
+        RcSeed { registry: self }
+    }
+
+    /// Returns a [`DeserializeSeed`] that reads an [`AtomicRc`] edge of an object, written by
+    /// [`Ids::snapshot`] or [`Ids::rc`].
+    ///
+    /// If the target is still being read, as when the edge closes a cycle, the edge is read as a
+    /// null pointer and stored through [`DeserializeShared::atomic_edges`] once the outermost
+    /// node has been read.
+    pub fn atomic_seed(&self) -> AtomicRcSeed<'_, T> {
+        AtomicRcSeed { registry: self }
+    }
+
+    /// Returns the number of unique nodes read so far.
+    pub fn len(&self) -> usize {
+        self.nodes.borrow().len()
+    }
+
+    /// Returns `true` if no node has been read yet.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.borrow().is_empty()
+    }
+}
+
+impl<'de, T: DeserializeShared<'de>> Registry<T> {
+    /// Stores the [`AtomicRc`] edges whose targets were being read.
+    fn resolve(&self) -> Result<(), String> {
+        let fixups = take(&mut *self.fixups.borrow_mut());
+        let nodes = self.nodes.borrow();
+        let guard = &cs();
+        for (node, index, target) in fixups {
+            let target = nodes
+                .get(&target)
+                .ok_or_else(|| format!("node {target} is never defined"))?;
+            let edges = node.as_ref().unwrap().atomic_edges();
+            let edge = edges.get(index).ok_or_else(|| {
+                format!(
+                    "`atomic_edges` returns {} edges, but edge {index} has been read",
+                    edges.len()
+                )
+            })?;
+            edge.store(target.clone(), Ordering::Release, guard);
+        }
+        Ok(())
+    }
+}
+
+impl<T: RcObject> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`DeserializeSeed`] of an [`Rc`] that deduplicates the nodes by id. See
+/// [`Registry::seed`].
+pub struct RcSeed<'r, T: RcObject> {
+    registry: &'r Registry<T>,
+}
+
+impl<T: RcObject> Clone for RcSeed<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: RcObject> Copy for RcSeed<'_, T> {}
+
+impl<'de, T: DeserializeShared<'de>> DeserializeSeed<'de> for RcSeed<'_, T> {
+    type Value = Rc<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Rc<T>, D::Error> {
+        match deserializer.deserialize_option(Entry(self.registry))? {
+            Lookup::Found(rc) => Ok(rc),
+            Lookup::Pending(id) => Err(D::Error::custom(format_args!(
+                "node {id} is referenced by an `Rc` before it is defined, which only an \
+                 `AtomicRc` edge may do"
+            ))),
+        }
+    }
+}
+
+/// A [`DeserializeSeed`] of an [`AtomicRc`] edge that deduplicates the nodes by id. See
+/// [`Registry::atomic_seed`].
+pub struct AtomicRcSeed<'r, T: RcObject> {
+    registry: &'r Registry<T>,
+}
+
+impl<T: RcObject> Clone for AtomicRcSeed<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: RcObject> Copy for AtomicRcSeed<'_, T> {}
+
+impl<'de, T: DeserializeShared<'de>> DeserializeSeed<'de> for AtomicRcSeed<'_, T> {
+    type Value = AtomicRc<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<AtomicRc<T>, D::Error> {
+        let lookup = deserializer.deserialize_option(Entry(self.registry))?;
+        let mut frames = self.registry.frames.borrow_mut();
+        let frame = frames.last_mut();
+        match lookup {
+            Lookup::Found(rc) => {
+                if let Some(frame) = frame {
+                    frame.edges += 1;
+                }
+                Ok(AtomicRc::from(rc))
+            }
+            Lookup::Pending(id) => match frame {
+                Some(frame) => {
+                    frame.pending.push((frame.edges, id));
+                    frame.edges += 1;
+                    Ok(AtomicRc::null())
+                }
+                None => Err(D::Error::custom(format_args!(
+                    "node {id} is referenced before it is defined"
+                ))),
+            },
+        }
+    }
+}
+
+/// The node an edge points to, or the id of a node that is still being read.
+enum Lookup<T: RcObject> {
+    Found(Rc<T>),
+    Pending(u64),
+}
+
+/// A visitor of an optional id of a node, followed by the node if it is written for the first
+/// time.
+struct Entry<'r, T: RcObject>(&'r Registry<T>);
+
+impl<'de, T: DeserializeShared<'de>> Visitor<'de> for Entry<'_, T> {
+    type Value = Lookup<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a shared node")
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Lookup<T>, E> {
+        Ok(Lookup::Found(Rc::null()))
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Lookup<T>, E> {
+        Ok(Lookup::Found(Rc::null()))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Lookup<T>, D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Lookup<T>, A::Error> {
+        let id: u64 = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let value = seq
+            .next_element_seed(OptionSeed(ObjectSeed(self.0)))?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let Some((value, pending)) = value else {
+            return Ok(match self.0.nodes.borrow().get(&id) {
+                Some(rc) => Lookup::Found(rc.clone()),
+                None => Lookup::Pending(id),
+            });
+        };
+
+        let rc = {
+            let mut nodes = self.0.nodes.borrow_mut();
+            if nodes.contains_key(&id) {
+                return Err(A::Error::custom(format_args!("node {id} is defined twice")));
+            }
+            let rc = Rc::new(value);
+            nodes.insert(id, rc.clone());
+            rc
+        };
+        self.0.fixups.borrow_mut().extend(
+            pending
+                .into_iter()
+                .map(|(index, target)| (rc.clone(), index, target)),
+        );
+        if self.0.frames.borrow().is_empty() {
+            // The outermost node is done, so every node that an edge may point back to is too.
+            self.0.resolve().map_err(A::Error::custom)?;
+        }
+        Ok(Lookup::Found(rc))
+    }
+}
+
+/// A seed of an object whose edges are read with a registry, which also returns the
+/// [`AtomicRc`] edges whose targets are still being read.
+struct ObjectSeed<'r, T: RcObject>(&'r Registry<T>);
+
+impl<'de, T: DeserializeShared<'de>> DeserializeSeed<'de> for ObjectSeed<'_, T> {
+    type Value = (T, Vec<(usize, u64)>);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0.frames.borrow_mut().push(Frame::default());
+        let result = T::deserialize_shared(deserializer, self.0);
+        let frame = self.0.frames.borrow_mut().pop().unwrap();
+        result.map(|value| (value, frame.pending))
+    }
+}
+
+/// A seed of an optional value, read with the inner seed if present.
+struct OptionSeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for OptionSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> Visitor<'de> for OptionSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an optional node")
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0.deserialize(deserializer).map(Some)
+    }
+}
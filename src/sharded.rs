@@ -0,0 +1,185 @@
+//! Reference counters split across shards for extremely hot shared objects.
+//!
+//! Every clone and drop of an [`Rc`] updates the same reference count, so the cache line of a
+//! handful of objects that all threads clone and drop, such as a global configuration or the root
+//! of a tree, bounces between the cores. A [`ShardedRc`], created with [`Rc::new_sharded`], splits
+//! its counter across cache-padded slots and counts each clone in the slot of the cloning thread.
+//! The shared counter only tracks how many slots are in use, so it is touched only when a slot
+//! becomes empty or stops being empty.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::{Rc, RcObject};
+//! use std::thread;
+//!
+//! struct Config {
+//!     threads: usize,
+//! }
+//!
+//! unsafe impl RcObject for Config {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let config = Rc::new_sharded(Config { threads: 4 }, 8);
+//! let handles: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let config = config.clone();
+//!         thread::spawn(move || {
+//!             # let context = dyntls_host::get();
+//!             # unsafe { context.initialize() };
+//!             for _ in 0..100 {
+//!                 let local = config.clone();
+//!                 assert_eq!(local.as_ref().unwrap().threads, 4);
+//!             }
+//!         })
+//!     })
+//!     .collect();
+//! for handle in handles {
+//!     handle.join().unwrap();
+//! }
+//! ```
+
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_utils::CachePadded;
+
+use crate::ebr_impl::current_thread_hash;
+use crate::{Rc, RcObject};
+
+/// The split counter of a `ShardedRc` and the strong reference that it shares.
+struct Shards<T: RcObject> {
+    /// The number of `ShardedRc`s counted in each slot.
+    slots: Box<[CachePadded<AtomicUsize>]>,
+    /// The number of slots that are not empty. The shards are freed when it reaches zero.
+    occupied: CachePadded<AtomicUsize>,
+    /// The strong reference that the shards hold.
+    rc: Rc<T>,
+}
+
+/// A reference-counted pointer whose counter is split across cache-padded slots.
+///
+/// See the [module documentation](self) for details.
+pub struct ShardedRc<T: RcObject> {
+    shards: NonNull<Shards<T>>,
+    /// The slot in which this pointer is counted.
+    slot: usize,
+    _marker: PhantomData<Shards<T>>,
+}
+
+unsafe impl<T: RcObject + Send + Sync> Send for ShardedRc<T> {}
+unsafe impl<T: RcObject + Send + Sync> Sync for ShardedRc<T> {}
+
+impl<T: RcObject> ShardedRc<T> {
+    /// Constructs a new `ShardedRc` that takes over the strong reference of `rc`, with a counter
+    /// split into `shards` slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn from_rc(rc: Rc<T>, shards: usize) -> Self {
+        assert!(shards > 0, "a sharded counter must have at least one shard");
+        let slot = current_thread_hash() % shards;
+        let shards = Box::new(Shards {
+            slots: (0..shards)
+                .map(|i| CachePadded::new(AtomicUsize::new(usize::from(i == slot))))
+                .collect(),
+            occupied: CachePadded::new(AtomicUsize::new(1)),
+            rc,
+        });
+        Self {
+            shards: NonNull::from(Box::leak(shards)),
+            slot,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn shards(&self) -> &Shards<T> {
+        // SAFETY: The shards are alive while any of their `ShardedRc`s is, as the slot of each
+        // one is counted as occupied.
+        unsafe { self.shards.as_ref() }
+    }
+
+    /// Returns the number of slots of the counter.
+    #[inline]
+    pub fn shard_count(&self) -> usize {
+        self.shards().slots.len()
+    }
+
+    /// Returns the strong reference shared by the clones of this `ShardedRc`.
+    #[inline]
+    pub fn as_rc(&self) -> &Rc<T> {
+        &self.shards().rc
+    }
+
+    /// Returns a new [`Rc`] to the object.
+    ///
+    /// This increments the shared reference count of the object.
+    #[inline]
+    pub fn to_rc(&self) -> Rc<T> {
+        self.shards().rc.clone()
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(&self) -> Option<&T> {
+        self.shards().rc.as_ref()
+    }
+
+    /// Returns `true` if the two pointers point to the same object.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.shards().rc.ptr_eq(&other.shards().rc)
+    }
+}
+
+impl<T: RcObject> Clone for ShardedRc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        let shards = self.shards();
+        let slot = current_thread_hash() % shards.slots.len();
+        if shards.slots[slot].fetch_add(1, Ordering::Relaxed) == 0 {
+            // `self` keeps its own slot occupied, so the shards cannot be freed meanwhile.
+            shards.occupied.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            shards: self.shards,
+            slot,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RcObject> Drop for ShardedRc<T> {
+    #[inline]
+    fn drop(&mut self) {
+        let shards = self.shards();
+        if shards.slots[self.slot].fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        if shards.occupied.fetch_sub(1, Ordering::Release) == 1 {
+            std::sync::atomic::fence(Ordering::Acquire);
+            // SAFETY: No slot is occupied, so this was the last `ShardedRc` of the shards, which
+            // were leaked from a `Box` in `from_rc`. Dropping them releases their strong reference.
+            drop(unsafe { Box::from_raw(self.shards.as_ptr()) });
+        }
+    }
+}
+
+impl<T: RcObject + Debug> Debug for ShardedRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.shards().rc, f)
+    }
+}
+
+impl<T: RcObject> Pointer for ShardedRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.shards().rc, f)
+    }
+}
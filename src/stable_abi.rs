@@ -0,0 +1,165 @@
+//! Pointer wrappers that can cross [`abi_stable`] plugin boundaries.
+//!
+//! [`StableRc`] and [`StableAtomicRc`] are `#[repr(C)]` wrappers around [`Rc`] and [`AtomicRc`]
+//! which implement [`StableAbi`], so they can appear in the interfaces of `abi_stable` modules.
+//! The pointers themselves are opaque to the layout checker: the host and the plugins must link
+//! against the same build of this crate and share its collector through dyntls, as they already
+//! do for the plain pointer types.
+//!
+//! Both wrappers dereference to the wrapped pointer, so the whole pointer API is available on
+//! either side of the boundary.
+
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+
+use abi_stable::StableAbi;
+
+use crate::{AtomicRc, Rc, RcObject};
+
+/// An [`Rc`] pointer with a stable ABI.
+#[repr(C)]
+#[derive(StableAbi)]
+pub struct StableRc<T: RcObject> {
+    #[sabi(unsafe_opaque_field)]
+    inner: Rc<T>,
+}
+
+impl<T: RcObject> StableRc<T> {
+    /// Constructs a new `StableRc` by allocating a new reference-counted object.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self::from(Rc::new(obj))
+    }
+
+    /// Constructs a null `StableRc` pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self::from(Rc::null())
+    }
+
+    /// Converts back into the wrapped [`Rc`] pointer.
+    #[inline]
+    pub fn into_rc(self) -> Rc<T> {
+        self.inner
+    }
+}
+
+impl<T: RcObject> From<Rc<T>> for StableRc<T> {
+    #[inline]
+    fn from(inner: Rc<T>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T: RcObject> From<StableRc<T>> for Rc<T> {
+    #[inline]
+    fn from(value: StableRc<T>) -> Self {
+        value.into_rc()
+    }
+}
+
+impl<T: RcObject> Deref for StableRc<T> {
+    type Target = Rc<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T: RcObject> DerefMut for StableRc<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T: RcObject> Clone for StableRc<T> {
+    fn clone(&self) -> Self {
+        Self::from(self.inner.clone())
+    }
+}
+
+impl<T: RcObject> Default for StableRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: RcObject + Debug> Debug for StableRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.inner, f)
+    }
+}
+
+/// An [`AtomicRc`] pointer with a stable ABI.
+#[repr(C)]
+#[derive(StableAbi)]
+pub struct StableAtomicRc<T: RcObject> {
+    #[sabi(unsafe_opaque_field)]
+    inner: AtomicRc<T>,
+}
+
+impl<T: RcObject> StableAtomicRc<T> {
+    /// Constructs a new `StableAtomicRc` by allocating a new reference-counted object.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self::from(AtomicRc::new(obj))
+    }
+
+    /// Constructs a new `StableAtomicRc` containing a null pointer.
+    #[inline]
+    pub fn null() -> Self {
+        Self::from(AtomicRc::null())
+    }
+
+    /// Converts back into the wrapped [`AtomicRc`] pointer.
+    #[inline]
+    pub fn into_atomic_rc(self) -> AtomicRc<T> {
+        self.inner
+    }
+}
+
+impl<T: RcObject> From<AtomicRc<T>> for StableAtomicRc<T> {
+    #[inline]
+    fn from(inner: AtomicRc<T>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T: RcObject> From<StableRc<T>> for StableAtomicRc<T> {
+    #[inline]
+    fn from(value: StableRc<T>) -> Self {
+        Self::from(AtomicRc::from(value.into_rc()))
+    }
+}
+
+impl<T: RcObject> Deref for StableAtomicRc<T> {
+    type Target = AtomicRc<T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T: RcObject> DerefMut for StableAtomicRc<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T: RcObject> Default for StableAtomicRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: RcObject> Debug for StableAtomicRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.inner, f)
+    }
+}
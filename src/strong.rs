@@ -3,14 +3,14 @@ use std::{
     fmt::{Debug, Formatter, Pointer},
     hash::{Hash, Hasher},
     marker::PhantomData,
-    mem::{forget, size_of},
+    mem::{forget, size_of, ManuallyDrop},
     sync::atomic::{AtomicUsize, Ordering},
 };
 
 use atomic::Atomic;
 use static_assertions::const_assert;
 
-use crate::ebr_impl::{global_epoch, Guard, Tagged};
+use crate::ebr_impl::{global_epoch, Collector, Guard, Tagged};
 use crate::utils::{Raw, RcInner};
 use crate::{Weak, WeakSnapshot};
 
@@ -73,15 +73,34 @@ pub unsafe trait RcObject: Sized {
 }
 
 impl<T> Tagged<RcInner<T>> {
-    fn with_timestamp(self) -> Self {
+    pub(crate) fn with_timestamp(self) -> Self {
         if self.is_null() {
             self
         } else {
             self.with_high_tag(global_epoch())
         }
     }
+
+    /// Checks that the object, if any, belongs to the collector of `guard`.
+    ///
+    /// The tags are stripped first, and a null pointer, tagged or not, has no object to check.
+    #[inline(always)]
+    pub(crate) fn check_domain(self, guard: &Guard) {
+        #[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+        {
+            let ptr = self.as_raw();
+            if !ptr.is_null() {
+                unsafe { (*ptr).check_domain(guard) };
+            }
+        }
+        #[cfg(not(all(debug_assertions, not(feature = "unsafe-layout"))))]
+        let _ = guard;
+    }
 }
 
+/// The tag bit that marks the object holding a pointer as logically deleted.
+const MARK: usize = 1;
+
 /// Result of a failed `compare_exchange` operation.
 ///
 /// It returns the ownership of the pointer which was given as a parameter `desired`.
@@ -97,6 +116,60 @@ pub struct CompareExchangeError<P, S> {
 /// The pointer must be properly aligned. Since it is aligned, a tag can be stored into the unused
 /// least significant bits of the address. For example, the tag for a pointer to a sized type `T`
 /// should be less than `(1 << align_of::<T>().trailing_zeros())`.
+///
+/// A null pointer may carry a tag too, which lets an empty slot hold state, such as a mark that
+/// keeps a deleted slot from being refilled. A tagged null is loaded, stored and compared like
+/// any other pointer: [`is_null`](Snapshot::is_null) ignores the tag, while `compare_exchange`
+/// takes it into account, so a compare-and-swap that expects an untagged null fails on a tagged
+/// one and vice versa.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{cs, AtomicRc, Rc, RcObject, Snapshot};
+/// use std::sync::atomic::Ordering;
+///
+/// struct Item(usize);
+///
+/// unsafe impl RcObject for Item {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// const CLOSED: usize = 1;
+///
+/// let slot = AtomicRc::null();
+/// let guard = &cs();
+///
+/// // Close the empty slot, so that nothing is inserted into it anymore.
+/// let empty = slot.load(Ordering::Acquire, guard);
+/// assert!(slot
+///     .compare_exchange_tag(empty, CLOSED, Ordering::AcqRel, Ordering::Acquire, guard)
+///     .is_ok());
+///
+/// let closed = slot.load(Ordering::Acquire, guard);
+/// assert!(closed.is_null());
+/// assert_eq!(closed.tag(), CLOSED);
+///
+/// // An insertion that expects an empty slot fails.
+/// let insert = slot.compare_exchange(
+///     Snapshot::null(),
+///     Rc::new(Item(1)),
+///     Ordering::AcqRel,
+///     Ordering::Acquire,
+///     guard,
+/// );
+/// assert!(insert.is_err());
+/// ```
+///
+/// # Dropping
+///
+/// An `AtomicRc` can be dropped anywhere, without a guard in scope: outside of any critical
+/// section, in the destructor of a container, or in the destructor of a thread-local variable
+/// while the thread exits. Its destructor enters a critical section of the collector of the
+/// current thread by itself to release the strong reference, and the object is destructed after
+/// a grace period as usual. With a guard at hand, [`AtomicRc::finalize`] skips the pinning.
 pub struct AtomicRc<T: RcObject> {
     link: Atomic<Raw<T>>,
     _marker: PhantomData<T>,
@@ -130,6 +203,16 @@ impl<T: RcObject> AtomicRc<T> {
         }
     }
 
+    /// Constructs a new `AtomicRc` containing a null pointer tagged with `tag`. `tag` is
+    /// truncated to be fit into the unused bits of the pointer to `T`.
+    #[inline(always)]
+    pub fn null_with_tag(tag: usize) -> Self {
+        Self {
+            link: Atomic::new(Tagged::null().with_tag(tag)),
+            _marker: PhantomData,
+        }
+    }
+
     /// Loads a [`Snapshot`] pointer from this `AtomicRc`.
     ///
     /// This method takes an [`Ordering`] argument which describes the memory ordering of this
@@ -143,6 +226,35 @@ impl<T: RcObject> AtomicRc<T> {
         Snapshot::from_raw(self.link.load(order), guard)
     }
 
+    /// Returns `true` if this `AtomicRc` holds a null pointer, ignoring the tag.
+    ///
+    /// Unlike [`AtomicRc::load`], this only reads the pointer word and does not need a critical
+    /// section, so it suits cheap emptiness checks on cold paths. The answer may be stale by the
+    /// time it is used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn is_null(&self, order: Ordering) -> bool {
+        self.link.load(order).is_null()
+    }
+
+    /// Returns the address of the object this `AtomicRc` points to, without the tag, or a null
+    /// pointer if it holds none.
+    ///
+    /// This reads the pointer word without entering a critical section, so the object may be
+    /// reclaimed at any moment and the returned pointer must never be dereferenced. It is only
+    /// good for comparing identities or for diagnostics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn peek_raw(&self, order: Ordering) -> *const T {
+        RcInner::data_ptr(self.link.load(order).as_raw())
+    }
+
     /// Stores an [`Rc`] pointer into this `AtomicRc`.
     ///
     /// This method takes an [`Ordering`] argument which describes the memory ordering of
@@ -150,6 +262,7 @@ impl<T: RcObject> AtomicRc<T> {
     #[inline]
     pub fn store(&self, ptr: Rc<T>, order: Ordering, guard: &Guard) {
         let new_ptr = ptr.ptr;
+        new_ptr.check_domain(guard);
         let old_ptr = self.link.swap(new_ptr.with_timestamp(), order);
         // Skip decrementing a strong count of the inserted pointer.
         forget(ptr);
@@ -198,6 +311,7 @@ impl<T: RcObject> AtomicRc<T> {
         failure: Ordering,
         guard: &'g Guard,
     ) -> Result<Rc<T>, CompareExchangeError<Rc<T>, Snapshot<'g, T>>> {
+        desired.ptr.check_domain(guard);
         let mut expected_raw = expected.ptr;
         let desired_raw = desired.ptr.with_timestamp();
         loop {
@@ -250,6 +364,7 @@ impl<T: RcObject> AtomicRc<T> {
         failure: Ordering,
         guard: &'g Guard,
     ) -> Result<Rc<T>, CompareExchangeError<Rc<T>, Snapshot<'g, T>>> {
+        desired.ptr.check_domain(guard);
         let mut expected_raw = expected.ptr;
         let desired_raw = desired.ptr.with_timestamp();
         loop {
@@ -329,6 +444,422 @@ impl<T: RcObject> AtomicRc<T> {
         }
     }
 
+    /// Replaces the current object with a modified copy built by `f`, retrying until no other
+    /// writer interferes, and returns a [`Snapshot`] of the installed copy.
+    ///
+    /// `f` is called with the current object, and may be called several times, each time with
+    /// the latest object, so it must not have side effects that cannot be repeated. The copies
+    /// that lose a race and the replaced object are retired in the critical section of `guard`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pointer is null.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    ///
+    /// #[derive(Clone)]
+    /// struct Config {
+    ///     hosts: Vec<String>,
+    /// }
+    ///
+    /// unsafe impl RcObject for Config {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let config = AtomicRc::new(Config { hosts: Vec::new() });
+    /// let guard = &cs();
+    /// let updated = config.rcu(guard, |old| {
+    ///     let mut new = old.clone();
+    ///     new.hosts.push("example.com".into());
+    ///     new
+    /// });
+    /// assert_eq!(updated.as_ref().unwrap().hosts, ["example.com"]);
+    /// ```
+    pub fn rcu<'g, F>(&self, guard: &'g Guard, mut f: F) -> Snapshot<'g, T>
+    where
+        F: FnMut(&T) -> T,
+    {
+        let mut current = self.load(Ordering::Acquire, guard);
+        loop {
+            let value = current.as_ref().expect("`AtomicRc::rcu` on a null pointer");
+            let new = Rc::new(f(value));
+            let snapshot = new.snapshot(guard);
+            match self.compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire, guard) {
+                Ok(old) => {
+                    old.finalize(guard);
+                    return snapshot;
+                }
+                Err(e) => {
+                    e.desired.finalize(guard);
+                    current = e.current;
+                }
+            }
+        }
+    }
+
+    /// Like [`AtomicRc::rcu`], but `f` may abort the update by returning an error, which is then
+    /// returned with the pointer left untouched.
+    ///
+    /// `f` is called with the latest object on every retry, so it may abort on any of them. No
+    /// copy is allocated for an attempt that `f` aborts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pointer is null.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    ///
+    /// struct Account {
+    ///     balance: u64,
+    /// }
+    ///
+    /// unsafe impl RcObject for Account {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let account = AtomicRc::new(Account { balance: 10 });
+    /// let guard = &cs();
+    /// let withdraw = |amount: u64| {
+    ///     account.try_rcu(guard, |old| match old.balance.checked_sub(amount) {
+    ///         Some(balance) => Ok(Account { balance }),
+    ///         None => Err("insufficient funds"),
+    ///     })
+    /// };
+    /// assert_eq!(withdraw(7).unwrap().as_ref().unwrap().balance, 3);
+    /// assert_eq!(withdraw(7).err(), Some("insufficient funds"));
+    /// ```
+    pub fn try_rcu<'g, E, F>(&self, guard: &'g Guard, mut f: F) -> Result<Snapshot<'g, T>, E>
+    where
+        F: FnMut(&T) -> Result<T, E>,
+    {
+        let mut current = self.load(Ordering::Acquire, guard);
+        loop {
+            let value = current
+                .as_ref()
+                .expect("`AtomicRc::try_rcu` on a null pointer");
+            let new = Rc::new(f(value)?);
+            let snapshot = new.snapshot(guard);
+            match self.compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire, guard) {
+                Ok(old) => {
+                    old.finalize(guard);
+                    return Ok(snapshot);
+                }
+                Err(e) => {
+                    e.desired.finalize(guard);
+                    current = e.current;
+                }
+            }
+        }
+    }
+
+    /// Sets the mark bit of the tag of the pointer, if the pointer is still `expected` and is not
+    /// marked yet. Returns the marked pointer on success, and the current pointer otherwise.
+    ///
+    /// The mark bit is the least significant bit of the tag, which is always available because
+    /// the objects of `Rc`s are aligned to at least 8 bytes. It follows the deletion protocol of
+    /// Harris's linked list:
+    ///
+    /// 1. A node is *logically* deleted by marking the pointer to its successor. Whoever marks
+    ///    it owns the deletion, and nothing may be inserted after a marked node anymore, since
+    ///    the compare-and-swap of an insertion expects an unmarked pointer.
+    /// 2. The node is then *physically* unlinked by replacing the pointer to it in its
+    ///    predecessor with its successor, unmarked, with [`AtomicRc::compare_exchange`]. Any
+    ///    thread that traverses a marked node may do this on behalf of the deleting thread.
+    /// 3. Readers that encounter a marked pointer treat the node holding it as absent, and
+    ///    [`Snapshot::unmarked`] gives the successor to continue the traversal with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// #[derive(Debug)]
+    /// struct Node {
+    ///     key: usize,
+    ///     next: AtomicRc<Node>,
+    /// }
+    ///
+    /// unsafe impl RcObject for Node {
+    ///     fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+    ///         out.push(self.next.take());
+    ///     }
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let tail = Rc::new(Node { key: 2, next: AtomicRc::null() });
+    /// let head = AtomicRc::new(Node { key: 1, next: AtomicRc::from(tail) });
+    ///
+    /// // Delete the first node.
+    /// let guard = &cs();
+    /// let first = head.load(Ordering::Acquire, guard);
+    /// let next = &first.as_ref().unwrap().next;
+    /// let succ = next.load(Ordering::Acquire, guard);
+    /// let marked = next.try_mark(succ, guard).unwrap();
+    /// assert!(marked.is_marked());
+    /// // Marking again fails, as the node has already been deleted.
+    /// assert!(next.try_mark(succ, guard).is_err());
+    ///
+    /// // Unlink it.
+    /// let unlinked = head.compare_exchange(
+    ///     first,
+    ///     marked.unmarked().counted(),
+    ///     Ordering::AcqRel,
+    ///     Ordering::Acquire,
+    ///     guard,
+    /// );
+    /// assert!(unlinked.is_ok());
+    /// assert_eq!(head.load(Ordering::Acquire, guard).as_ref().unwrap().key, 2);
+    /// ```
+    #[inline]
+    pub fn try_mark<'g>(
+        &self,
+        expected: Snapshot<'g, T>,
+        guard: &'g Guard,
+    ) -> Result<Snapshot<'g, T>, Snapshot<'g, T>> {
+        if expected.is_marked() {
+            return Err(self.load(Ordering::Acquire, guard));
+        }
+        match self.compare_exchange_tag(
+            expected,
+            expected.tag() | MARK,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            guard,
+        ) {
+            Ok(_) => Ok(expected.marked()),
+            Err(e) => Err(e.current),
+        }
+    }
+
+    /// Returns the current pointer, or stores the [`Rc`] pointer returned by `init` if the current
+    /// pointer is null.
+    ///
+    /// If several threads initialize the pointer at the same time, only one of the values is
+    /// stored, and the others are retired in the critical section of `guard`. Every thread gets a
+    /// [`Snapshot`] of the stored value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    ///
+    /// struct Node(usize);
+    ///
+    /// unsafe impl RcObject for Node {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let child = AtomicRc::null();
+    /// let guard = &cs();
+    /// let first = child.get_or_init(guard, || Rc::new(Node(1)));
+    /// let second = child.get_or_init(guard, || Rc::new(Node(2)));
+    /// assert!(first.ptr_eq(second));
+    /// assert_eq!(second.as_ref().unwrap().0, 1);
+    /// ```
+    pub fn get_or_init<'g, F>(&self, guard: &'g Guard, init: F) -> Snapshot<'g, T>
+    where
+        F: FnOnce() -> Rc<T>,
+    {
+        let mut current = self.load(Ordering::Acquire, guard);
+        if !current.is_null() {
+            return current;
+        }
+        let mut desired = init();
+        let snapshot = desired.snapshot(guard);
+        loop {
+            match self.compare_exchange(
+                current,
+                desired,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => return snapshot,
+                Err(err) if err.current.is_null() => {
+                    // Only the tag has changed.
+                    current = err.current;
+                    desired = err.desired;
+                }
+                Err(err) => {
+                    // Another thread has won the race.
+                    err.desired.finalize(guard);
+                    return err.current;
+                }
+            }
+        }
+    }
+
+    /// Stores the [`Rc`] pointer `desired` into the atomic pointer if `predicate` holds for the
+    /// object it currently points to.
+    ///
+    /// The current pointer is loaded and `predicate` is evaluated on its object, which is
+    /// protected by `guard`. If the pointer changes before `desired` is installed, the new
+    /// object is loaded and `predicate` is evaluated again, so `desired` only replaces an object
+    /// that satisfied `predicate` when it was replaced. `predicate` may thus be called several
+    /// times.
+    ///
+    /// On success the pointer that was in this `AtomicRc` is returned. If `predicate` does not
+    /// hold, or the current pointer is null, the current value and `desired` are returned. The
+    /// orderings are those of [`AtomicRc::compare_exchange`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Config {
+    ///     version: usize,
+    /// }
+    ///
+    /// unsafe impl RcObject for Config {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let config = AtomicRc::new(Config { version: 1 });
+    /// let guard = &cs();
+    ///
+    /// // Only upgrade from version 1.
+    /// let upgrade = |desired| {
+    ///     config.compare_value_exchange(
+    ///         |current| current.version == 1,
+    ///         desired,
+    ///         Ordering::AcqRel,
+    ///         Ordering::Acquire,
+    ///         guard,
+    ///     )
+    /// };
+    /// assert!(upgrade(Rc::new(Config { version: 2 })).is_ok());
+    /// assert!(upgrade(Rc::new(Config { version: 3 })).is_err());
+    /// let current = config.load(Ordering::Acquire, guard);
+    /// assert_eq!(current.as_ref().unwrap().version, 2);
+    /// ```
+    pub fn compare_value_exchange<'g, P>(
+        &self,
+        mut predicate: P,
+        desired: Rc<T>,
+        success: Ordering,
+        failure: Ordering,
+        guard: &'g Guard,
+    ) -> Result<Rc<T>, CompareExchangeError<Rc<T>, Snapshot<'g, T>>>
+    where
+        P: FnMut(&T) -> bool,
+    {
+        let mut current = self.load(failure, guard);
+        let mut desired = desired;
+        loop {
+            if !current.as_ref().is_some_and(&mut predicate) {
+                return Err(CompareExchangeError { desired, current });
+            }
+            match self.compare_exchange(current, desired, success, failure, guard) {
+                Ok(old) => return Ok(old),
+                Err(err) => {
+                    // The pointer has changed, so the predicate must be checked again.
+                    current = err.current;
+                    desired = err.desired;
+                }
+            }
+        }
+    }
+
+    /// Swaps the pointers stored in this `AtomicRc` and `other`, returning `false` if `other`
+    /// changed in the middle of the swap.
+    ///
+    /// This is a best-effort operation, not a linearizable one: the two locations are updated by
+    /// two compare-and-swaps, so between them a reader may see the pointer of `other` in both
+    /// locations. If this `AtomicRc` changes before the first step, the swap is retried. If
+    /// `other` changes before the second step, the first step is rolled back and `false` is
+    /// returned. If this `AtomicRc` has also changed by then, the rollback is abandoned and the
+    /// pointer that was in it is released, as if the concurrent writer had overwritten it.
+    ///
+    /// Swapping with itself always succeeds and does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Buffer(usize);
+    ///
+    /// unsafe impl RcObject for Buffer {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let front = AtomicRc::new(Buffer(0));
+    /// let back = AtomicRc::new(Buffer(1));
+    /// let guard = &cs();
+    /// assert!(front.swap_with(&back, guard));
+    /// assert_eq!(front.load(Ordering::Acquire, guard).as_ref().unwrap().0, 1);
+    /// assert_eq!(back.load(Ordering::Acquire, guard).as_ref().unwrap().0, 0);
+    /// ```
+    pub fn swap_with(&self, other: &Self, guard: &Guard) -> bool {
+        if core::ptr::eq(self, other) {
+            return true;
+        }
+        loop {
+            let mine = self.load(Ordering::Acquire, guard);
+            let theirs = other.load(Ordering::Acquire, guard);
+            let mine_rc = match self.compare_exchange(
+                mine,
+                theirs.counted(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(rc) => rc,
+                Err(err) => {
+                    err.desired.finalize(guard);
+                    continue;
+                }
+            };
+            match other.compare_exchange(
+                theirs,
+                mine_rc,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(theirs_rc) => {
+                    // `self` holds its own count of the object.
+                    theirs_rc.finalize(guard);
+                    return true;
+                }
+                Err(err) => {
+                    match self.compare_exchange(
+                        theirs,
+                        err.desired,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        guard,
+                    ) {
+                        Ok(theirs_rc) => theirs_rc.finalize(guard),
+                        Err(err) => err.desired.finalize(guard),
+                    }
+                    return false;
+                }
+            }
+        }
+    }
+
     // get_mut is unsound, because it allows writing ref without link epoch.
     // Consider the motivating 3-thread example where
     // * T1 @e+1 loads node1
@@ -351,6 +882,16 @@ impl<T: RcObject> AtomicRc<T> {
     pub fn take(&mut self) -> Rc<T> {
         Rc::from_raw(core::mem::take(self.link.get_mut()))
     }
+
+    /// Consumes this pointer and releases the strong reference count it was owning in the
+    /// critical section of `guard`.
+    ///
+    /// Dropping an `AtomicRc` enters a critical section by itself, so this only saves the pinning
+    /// when a guard is already at hand, as in [`Rc::finalize`].
+    #[inline]
+    pub fn finalize(mut self, guard: &Guard) {
+        self.take().finalize(guard);
+    }
 }
 
 impl<T: RcObject> Drop for AtomicRc<T> {
@@ -359,6 +900,8 @@ impl<T: RcObject> Drop for AtomicRc<T> {
         let ptr = (*self.link.get_mut()).as_raw();
         unsafe {
             if let Some(cnt) = ptr.as_mut() {
+                // Without a guard, this enters a critical section of the current thread, so the
+                // pointer can be dropped in any context.
                 RcInner::decrement_strong(cnt, 1, None);
             }
         }
@@ -409,6 +952,19 @@ impl<T: RcObject> Pointer for AtomicRc<T> {
 /// The pointer must be properly aligned. Since it is aligned, a tag can be stored into the unused
 /// least significant bits of the address. For example, the tag for a pointer to a sized type `T`
 /// should be less than `(1 << align_of::<T>().trailing_zeros())`.
+///
+/// # Layout
+///
+/// `Rc<T>` is `#[repr(transparent)]` over a single tagged pointer, so it has the size and the
+/// alignment of a pointer and can be passed through FFI as one. The null pointer, possibly
+/// tagged, is a valid `Rc<T>`, so there is no niche for `Option<Rc<T>>` to use and it takes two
+/// words: an absent pointer is better represented by [`Rc::null`] itself. The same holds for
+/// [`Snapshot`] and [`Weak`](crate::Weak).
+///
+/// These pointers do not hold a `NonNull`, and a pointer-sized `Option<Rc<T>>` is not
+/// guaranteed. The tagged null pointer is how [`AtomicRc`] and the data structures built on it
+/// represent an empty link, and taking the niche would make it an invalid value.
+#[repr(transparent)]
 pub struct Rc<T: RcObject> {
     ptr: Raw<T>,
     _marker: PhantomData<T>,
@@ -439,6 +995,13 @@ impl<T: RcObject> Rc<T> {
         Self::from_raw(Raw::null())
     }
 
+    /// Constructs a null `Rc` pointer tagged with `tag`. `tag` is truncated to be fit into the
+    /// unused bits of the pointer to `T`.
+    #[inline(always)]
+    pub fn null_with_tag(tag: usize) -> Self {
+        Self::from_raw(Raw::null().with_tag(tag))
+    }
+
     /// Returns `true` if the pointer is null ignoring the tag.
     #[inline(always)]
     pub fn is_null(&self) -> bool {
@@ -463,6 +1026,141 @@ impl<T: RcObject> Rc<T> {
         }
     }
 
+    /// Constructs a new `Rc` whose object is stored in the memory of an object of the same size
+    /// that has been reclaimed by `collector`, and allocates a new one only if there is none.
+    ///
+    /// This uses the arena of `collector` even if it was not built with
+    /// [`CollectorBuilder::rc_arena`](crate::CollectorBuilder::rc_arena), which suits queues and
+    /// stacks whose nodes have a uniform size and are allocated and retired at a high rate.
+    /// Objects that are too large for a slot of the arena are allocated with the global allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Collector, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Node(usize);
+    ///
+    /// unsafe impl RcObject for Node {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let collector = Collector::new();
+    /// collector.enter(|| {
+    ///     let slot = AtomicRc::null();
+    ///     for i in 0..100 {
+    ///         let node = Rc::recycle_or_new(&collector, || Node(i));
+    ///         slot.swap(node, Ordering::AcqRel).finalize(&cs());
+    ///     }
+    /// });
+    /// ```
+    #[inline]
+    pub fn recycle_or_new<F>(collector: &Collector, init: F) -> Self
+    where
+        F: FnOnce() -> T,
+    {
+        let ptr = RcInner::alloc_in(init(), 1, &collector.global.rc_arena);
+        Self {
+            ptr: Raw::from(ptr),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constructs a new reference-counted object whose counter is split into `shards` cache-padded
+    /// slots, for objects that are cloned and dropped by many threads at once.
+    ///
+    /// See [`ShardedRc`](crate::sharded::ShardedRc) for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    #[inline]
+    pub fn new_sharded(obj: T, shards: usize) -> crate::sharded::ShardedRc<T> {
+        crate::sharded::ShardedRc::from_rc(Self::new(obj), shards)
+    }
+
+    /// Constructs a new reference-counted object whose handles split a weight when they are
+    /// cloned, instead of updating the shared counter.
+    ///
+    /// See [`WeightedRc`](crate::weighted::WeightedRc) for details.
+    #[inline]
+    pub fn new_weighted(obj: T) -> crate::weighted::WeightedRc<T> {
+        crate::weighted::WeightedRc::from_rc(Self::new(obj))
+    }
+
+    /// Constructs a new `Rc` like [`Rc::new`], but returns the object back instead of aborting if
+    /// it cannot be allocated.
+    ///
+    /// If the first allocation fails, the hook installed with
+    /// [`Collector::set_alloc_failure_hook`](crate::Collector::set_alloc_failure_hook) is run and
+    /// the expired garbage of the collector is reclaimed before the allocation is retried.
+    #[inline]
+    pub fn try_new(obj: T) -> Result<Self, T> {
+        let ptr = RcInner::try_alloc(obj, 1)?;
+        Ok(Self {
+            ptr: Raw::from(ptr),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Constructs a new `Rc` of a zero-sized object without allocating, for tokens that only
+    /// need to be passed around and stored.
+    ///
+    /// All the `Rc`s made by this method point to a single object that is shared by all
+    /// zero-sized types and never destructed. Thus they are [`ptr_eq`](Rc::ptr_eq) to each other,
+    /// and a [`Weak`] of them can always be upgraded, so they cannot tell whether a particular
+    /// token is still alive; use [`Rc::new`] for that. As the counts of the shared object are
+    /// updated by every thread, cloning and dropping these `Rc`s may contend.
+    ///
+    /// The shared object is a static of this crate. If several copies of the crate are linked into
+    /// a process, as with dynamic libraries that each link their own, each copy has its own object,
+    /// and the `Rc`s made by different copies are not `ptr_eq`.
+    ///
+    /// The strong count of the shared object starts at half of its range, so that it never hits
+    /// zero. The count is not published by this crate, but code that reads it from the header
+    /// found with `circ::layout` sees this offset rather than the number of `Rc`s.
+    ///
+    /// As the other half of the range is shared by all zero-sized types, at most `2^28 - 1` of
+    /// these `Rc`s, including their clones and the strong references held by
+    /// [`AtomicRc`](crate::AtomicRc)s, may be alive at once. The process aborts if the count would
+    /// exceed that.
+    ///
+    /// This fails to compile unless `T` is zero-sized, aligned to at most 8 bytes, and has no
+    /// drop glue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{AtomicRc, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Token;
+    ///
+    /// unsafe impl RcObject for Token {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let token = Rc::new_zst(Token);
+    /// let slot = AtomicRc::from(token.clone());
+    /// assert!(token.ptr_eq(&Rc::new_zst(Token)));
+    /// assert!(!slot.is_null(Ordering::Acquire));
+    /// ```
+    #[inline]
+    pub fn new_zst(obj: T) -> Self {
+        let ptr = RcInner::shared_zst();
+        // The object has no drop glue, and the shared object stands for it.
+        let _ = obj;
+        Self {
+            ptr: Raw::from(ptr),
+            _marker: PhantomData,
+        }
+    }
+
     /// Constructs multiple [`Rc`]s that point to the same object,
     /// which is allocated as a new reference-counted object.
     ///
@@ -493,6 +1191,62 @@ impl<T: RcObject> Rc<T> {
         }
     }
 
+    /// Constructs `n` [`Rc`]s that point to the current object, for handing a reference to each
+    /// of `n` tasks.
+    ///
+    /// This method is more efficient than calling [`Rc::clone`] `n` times because the reference
+    /// counter is incremented with a single read-modify-write operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{Rc, RcObject};
+    ///
+    /// struct Job(usize);
+    ///
+    /// unsafe impl RcObject for Job {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let job = Rc::new(Job(7));
+    /// let handles = job.clone_n(4);
+    /// assert!(handles.iter().all(|handle| handle.ptr_eq(&job)));
+    /// ```
+    #[inline]
+    pub fn clone_n(&self, n: usize) -> Vec<Self> {
+        self.increment_strong_by(n);
+        (0..n)
+            .map(|_| Self {
+                ptr: self.ptr,
+                _marker: PhantomData,
+            })
+            .collect()
+    }
+
+    /// Constructs `N` [`Rc`]s that point to the current object.
+    ///
+    /// This is the same as [`Rc::clone_n`], but returns an array.
+    #[inline]
+    pub fn clone_many<const N: usize>(&self) -> [Self; N] {
+        self.increment_strong_by(N);
+        [(); N].map(|_| Self {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn increment_strong_by(&self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        if let Some(cnt) = unsafe { self.ptr.as_raw().as_ref() } {
+            cnt.increment_strong_by(u32::try_from(count).expect("too many clones at once"));
+        }
+    }
+
     /// Constructs multiple [`Weak`]s that point to the current object.
     ///
     /// This method is more efficient than calling [`Rc::downgrade`] multiple times
@@ -520,6 +1274,44 @@ impl<T: RcObject> Rc<T> {
         self
     }
 
+    /// Consumes a batch of pointers and releases the strong reference counts they were owning.
+    ///
+    /// This is more efficient than [`Rc::finalize`] on each pointer for teardown paths that
+    /// release many pointers at once: the global epoch is read once for the whole batch, and the
+    /// objects whose counts hit zero are handed to the collector as a single deferred function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, Rc, RcObject};
+    ///
+    /// struct Node(usize);
+    ///
+    /// unsafe impl RcObject for Node {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let nodes: Vec<_> = (0..1000).map(|i| Rc::new(Node(i))).collect();
+    /// Rc::drop_many(nodes, &cs());
+    /// ```
+    pub fn drop_many<I>(iter: I, guard: &Guard)
+    where
+        I: IntoIterator<Item = Self>,
+    {
+        let ptrs = iter
+            .into_iter()
+            .filter_map(|rc| unsafe { rc.into_raw().as_raw().as_mut() }.map(|cnt| cnt as *mut _));
+        unsafe { RcInner::decrement_strong_many(ptrs, guard) };
+    }
+
+    /// Returns the raw pointer without giving up the reference.
+    #[inline]
+    pub(crate) fn as_raw(&self) -> Raw<T> {
+        self.ptr
+    }
+
     #[inline]
     pub(crate) fn into_raw(self) -> Raw<T> {
         let new_ptr = self.ptr;
@@ -561,6 +1353,16 @@ impl<T: RcObject> Rc<T> {
         Snapshot::from_raw(self.ptr, guard)
     }
 
+    /// Creates an [`RcBorrow`] pointer to the same object. This does not touch the reference
+    /// counter.
+    #[inline(always)]
+    pub fn borrow_rc(&self) -> RcBorrow<'_, T> {
+        RcBorrow {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+
     /// Dereferences the pointer and returns an immutable reference.
     ///
     /// It does not check whether the pointer is null.
@@ -610,6 +1412,13 @@ impl<T: RcObject> Rc<T> {
         }
     }
 
+    /// Returns `true` if this is the only strong reference to the object, and the object has no
+    /// weak references.
+    #[inline]
+    pub(crate) fn is_unique(&self) -> bool {
+        unsafe { self.ptr.as_raw().as_ref() }.is_some_and(RcInner::is_unique)
+    }
+
     /// Returns `true` if the two pointer values, including the tag values set by `with_tag`,
     /// are identical.
     #[inline]
@@ -622,6 +1431,59 @@ impl<T: RcObject> Rc<T> {
     }
 }
 
+impl Guard {
+    /// Releases the strong reference of `rc` in this critical section.
+    ///
+    /// This is the safe way to get rid of a node that has just been unlinked from a structure.
+    /// Unlike [`Rc::finalize`], which releases the count right away, the count itself is only
+    /// released after the grace period of this critical section, so the node and the nodes it
+    /// points to stay alive for the threads that still read them through a [`Snapshot`], even
+    /// while other strong references to it come and go.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Node(usize);
+    ///
+    /// unsafe impl RcObject for Node {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let head = AtomicRc::new(Node(1));
+    /// let guard = &cs();
+    /// let snapshot = head.load(Ordering::Acquire, guard);
+    ///
+    /// let removed = head.swap(Rc::null(), Ordering::AcqRel);
+    /// guard.retire(removed);
+    /// // The node outlives the critical section that has read it.
+    /// assert_eq!(snapshot.as_ref().unwrap().0, 1);
+    /// ```
+    #[inline]
+    pub fn retire<T: RcObject>(&self, rc: Rc<T>) {
+        let ptr = rc.into_raw().as_raw();
+        if !ptr.is_null() {
+            unsafe { RcInner::defer_decrement_strong(ptr, self) };
+        }
+    }
+
+    /// Releases the strong references of a batch of pointers in this critical section.
+    ///
+    /// See [`Rc::drop_many`] for details.
+    #[inline]
+    pub fn retire_all<T, I>(&self, iter: I)
+    where
+        T: RcObject,
+        I: IntoIterator<Item = Rc<T>>,
+    {
+        Rc::drop_many(iter, self);
+    }
+}
+
 impl<'g, T: RcObject> From<Snapshot<'g, T>> for Rc<T> {
     fn from(value: Snapshot<'g, T>) -> Self {
         value.counted()
@@ -689,6 +1551,148 @@ impl<T: RcObject + Ord> Ord for Rc<T> {
     }
 }
 
+/// A borrowed [`Rc`] pointer, which stands for a strong reference owned by the caller.
+///
+/// It is a copyable pointer that is as cheap to pass around as `&Rc<T>`, but without the double
+/// indirection, and it can be upgraded back to an [`Rc`] pointer at any time. It does not need a
+/// guard because the borrowed [`Rc`] keeps the object alive for `'a`. This is useful to pass a
+/// pointer down a deep call stack without a pair of clone and drop at every level.
+///
+/// # Examples
+///
+/// ```
+/// use circ::{RcBorrow, RcObject, Rc};
+///
+/// struct Node(usize);
+///
+/// unsafe impl RcObject for Node {
+///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+/// }
+///
+/// fn depth(node: RcBorrow<'_, Node>, level: usize) -> usize {
+///     if level == 0 {
+///         node.as_ref().unwrap().0
+///     } else {
+///         depth(node, level - 1)
+///     }
+/// }
+///
+/// # let context = dyntls_host::get();
+/// # unsafe { context.initialize() };
+/// let rc = Rc::new(Node(42));
+/// assert_eq!(depth(rc.borrow_rc(), 16), 42);
+///
+/// // Upgrading increments the counter only once.
+/// let owned = rc.borrow_rc().to_rc();
+/// assert!(owned.ptr_eq(&rc));
+/// ```
+pub struct RcBorrow<'a, T: RcObject> {
+    ptr: Raw<T>,
+    _marker: PhantomData<&'a Rc<T>>,
+}
+
+unsafe impl<'a, T: RcObject + Send + Sync> Send for RcBorrow<'a, T> {}
+unsafe impl<'a, T: RcObject + Send + Sync> Sync for RcBorrow<'a, T> {}
+
+impl<'a, T: RcObject> Clone for RcBorrow<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: RcObject> Copy for RcBorrow<'a, T> {}
+
+impl<'a, T: RcObject> RcBorrow<'a, T> {
+    /// Returns `true` if the pointer is null ignoring the tag.
+    #[inline(always)]
+    pub fn is_null(self) -> bool {
+        self.ptr.is_null()
+    }
+
+    /// Returns the tag stored within the pointer.
+    #[inline(always)]
+    pub fn tag(self) -> usize {
+        self.ptr.tag()
+    }
+
+    /// Creates an [`Rc`] pointer by incrementing the strong reference counter.
+    #[inline]
+    pub fn to_rc(self) -> Rc<T> {
+        let rc = Rc::from_raw(self.ptr);
+        unsafe {
+            if let Some(cnt) = rc.ptr.as_raw().as_ref() {
+                cnt.increment_strong();
+            }
+        }
+        rc
+    }
+
+    /// Calls `f` with a temporary [`Rc`] pointer to the same object, without touching the
+    /// reference counter.
+    ///
+    /// This is handy for calling a function that takes `&Rc<T>`.
+    #[inline]
+    pub fn with_rc<R, F: FnOnce(&Rc<T>) -> R>(self, f: F) -> R {
+        // The temporary pointer must not release the strong reference, even if `f` panics.
+        let rc = ManuallyDrop::new(Rc::from_raw(self.ptr));
+        f(&rc)
+    }
+
+    /// Creates a [`Snapshot`] pointer to the same object.
+    #[inline]
+    pub fn snapshot<'g>(self, guard: &'g Guard) -> Snapshot<'g, T> {
+        Snapshot::from_raw(self.ptr, guard)
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(self) -> Option<&'a T> {
+        if self.ptr.is_null() {
+            None
+        } else {
+            // SAFETY: The borrowed `Rc` keeps the object alive for `'a`.
+            Some(unsafe { self.ptr.deref().data() })
+        }
+    }
+
+    /// Returns `true` if the two pointer values, including the tag values set by `with_tag`,
+    /// are identical.
+    #[inline]
+    pub fn ptr_eq(self, other: Self) -> bool {
+        self.ptr.ptr_eq(other.ptr)
+    }
+}
+
+impl<'a, T: RcObject> From<&'a Rc<T>> for RcBorrow<'a, T> {
+    #[inline]
+    fn from(value: &'a Rc<T>) -> Self {
+        value.borrow_rc()
+    }
+}
+
+impl<'a, T: RcObject> From<RcBorrow<'a, T>> for Rc<T> {
+    #[inline]
+    fn from(value: RcBorrow<'a, T>) -> Self {
+        value.to_rc()
+    }
+}
+
+impl<'a, T: RcObject + Debug> Debug for RcBorrow<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(cnt) = self.as_ref() {
+            f.debug_tuple("RcBorrow").field(cnt).finish()
+        } else {
+            f.write_str("Null")
+        }
+    }
+}
+
+impl<'a, T: RcObject> Pointer for RcBorrow<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.ptr, f)
+    }
+}
+
 /// An iterator generating [`Rc`] pointers to the same and newly allocated object.
 ///
 /// See [`Rc::new_many_iter`] for the purpose of this iterator.
@@ -745,6 +1749,10 @@ impl<T: RcObject> Drop for NewRcIter<T> {
 ///
 /// Unlike [`Rc`] pointer, this pointer does not own a strong reference count by itself.
 /// This pointer is valid for use only during the lifetime of EBR guard `'g`.
+///
+/// It has the layout of a pointer, and its null pointer is a valid value. See the
+/// [layout of `Rc`](Rc#layout).
+#[repr(transparent)]
 pub struct Snapshot<'g, T> {
     pub(crate) ptr: Raw<T>,
     pub(crate) _marker: PhantomData<&'g T>,
@@ -801,6 +1809,26 @@ impl<'g, T: RcObject> Snapshot<'g, T> {
         result
     }
 
+    /// Returns `true` if the mark bit of the tag is set, which means that the object holding
+    /// the pointer has been logically deleted. See [`AtomicRc::try_mark`].
+    #[inline(always)]
+    pub fn is_marked(self) -> bool {
+        self.tag() & MARK != 0
+    }
+
+    /// Returns the same pointer with the mark bit of the tag set, keeping the other tag bits.
+    #[inline]
+    pub fn marked(self) -> Self {
+        self.with_tag(self.tag() | MARK)
+    }
+
+    /// Returns the same pointer with the mark bit of the tag cleared, keeping the other tag
+    /// bits.
+    #[inline]
+    pub fn unmarked(self) -> Self {
+        self.with_tag(self.tag() & !MARK)
+    }
+
     /// Dereferences the pointer and returns an immutable reference.
     ///
     /// It does not check whether the pointer is null.
@@ -872,8 +1900,51 @@ impl<'g, T> Snapshot<'g, T> {
         }
     }
 
+    /// Constructs a new `Snapshot` representing a null pointer tagged with `tag`. `tag` is
+    /// truncated to be fit into the unused bits of the pointer to `T`.
+    #[inline(always)]
+    pub fn null_with_tag(tag: usize) -> Self {
+        Self {
+            ptr: Tagged::null().with_tag(tag),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the global epoch has not advanced since the critical section of `guard`,
+    /// in which the snapshot has been loaded, began. See [`Guard::validate`].
+    ///
+    /// A snapshot has the layout of a pointer and does not record the epoch it has been loaded
+    /// in, so the guard is passed instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, AtomicRc, Rc, RcObject};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// struct Node(usize);
+    ///
+    /// unsafe impl RcObject for Node {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let head = AtomicRc::new(Node(1));
+    /// let guard = &cs();
+    /// let snapshot = head.load(Ordering::Acquire, guard);
+    /// if snapshot.is_current_epoch(guard) {
+    ///     assert_eq!(snapshot.as_ref().unwrap().0, 1);
+    /// }
+    /// ```
+    #[inline]
+    pub fn is_current_epoch(&self, guard: &'g Guard) -> bool {
+        guard.validate()
+    }
+
     #[inline]
-    pub(crate) fn from_raw(acquired: Raw<T>, _: &'g Guard) -> Self {
+    pub(crate) fn from_raw(acquired: Raw<T>, guard: &'g Guard) -> Self {
+        acquired.check_domain(guard);
         Self {
             ptr: acquired,
             _marker: PhantomData,
@@ -0,0 +1,339 @@
+//! Counted slices behind a single-word pointer.
+//!
+//! A pointer to a slice is two words wide, so it cannot be stored in an [`AtomicRc`](crate::AtomicRc)
+//! and compared-and-swapped atomically. A [`ThinSlice`] stores its length in the header of its
+//! allocation next to its reference count instead, so [`ThinRc`] and [`AtomicThinRc`] are a
+//! single word wide. They are [`IntrusiveRc`] and [`AtomicIntrusiveRc`] over [`ThinSlice`], and
+//! likewise defer the destruction of a slice to the collector.
+//!
+//! Trait objects are not supported, as their metadata cannot be stored and restored on stable
+//! Rust. Neither are `Rc<str>`, `Rc<Path>`, `Rc<CStr>` and `Rc<OsStr>`: strings and paths are
+//! shared as a [`ThinRc<u8>`] of their bytes instead, which can be converted from all of them.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::cs;
+//! use circ::thin::{AtomicThinRc, ThinSlice};
+//! use std::sync::atomic::Ordering;
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let shared = AtomicThinRc::new(ThinSlice::from_vec(vec![1, 2, 3]));
+//! assert_eq!(std::mem::size_of_val(&shared), std::mem::size_of::<usize>());
+//!
+//! let guard = &cs();
+//! let old = shared.load(Ordering::Acquire, guard).unwrap();
+//! let new = ThinSlice::from_slice(&[4, 5]);
+//! assert!(shared
+//!     .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire)
+//!     .is_ok());
+//! assert_eq!(&shared.load(Ordering::Acquire, guard).unwrap()[..], &[4, 5]);
+//! ```
+
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::ffi::{CStr, OsStr};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::path::Path;
+use std::ptr;
+use std::slice::Iter;
+use std::sync::atomic::AtomicUsize;
+
+use crate::intrusive::{AtomicIntrusiveRc, IntrusiveRc, IntrusiveRcObject};
+
+/// A reference-counted slice behind a single-word pointer.
+pub type ThinRc<T> = IntrusiveRc<ThinSlice<T>>;
+
+/// A thread-safe (atomic) mutable memory location that contains a [`ThinRc<T>`].
+pub type AtomicThinRc<T> = AtomicIntrusiveRc<ThinSlice<T>>;
+
+/// The header of a counted slice, whose elements follow it in the same allocation.
+#[repr(C)]
+pub struct ThinSlice<T> {
+    refcount: AtomicUsize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> ThinSlice<T> {
+    /// Returns the layout of a slice of `len` elements and the offset of its first element.
+    fn layout(len: usize) -> (Layout, usize) {
+        let (layout, offset) = Layout::new::<Self>()
+            .extend(Layout::array::<T>(len).expect("slice is too large"))
+            .expect("slice is too large");
+        (layout.pad_to_align(), offset)
+    }
+
+    /// Returns the number of elements of the slice.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the slice has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the elements of the slice.
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        let (_, offset) = Self::layout(self.len);
+        unsafe {
+            let data = (self as *const Self as *const u8).add(offset) as *const T;
+            std::slice::from_raw_parts(data, self.len)
+        }
+    }
+}
+
+impl<T: Send> ThinSlice<T> {
+    /// Moves the elements of `vec` into a new counted slice.
+    pub fn from_vec(mut vec: Vec<T>) -> ThinRc<T> {
+        let len = vec.len();
+        let (layout, offset) = Self::layout(len);
+        unsafe {
+            let ptr = alloc(layout);
+            if ptr.is_null() {
+                handle_alloc_error(layout);
+            }
+            ptr::write(
+                ptr as *mut Self,
+                Self {
+                    refcount: AtomicUsize::new(1),
+                    len,
+                    _marker: PhantomData,
+                },
+            );
+            ptr::copy_nonoverlapping(vec.as_ptr(), ptr.add(offset) as *mut T, len);
+            // The elements are owned by the counted slice now.
+            vec.set_len(0);
+            IntrusiveRc::from_raw(ptr as *mut Self)
+        }
+    }
+
+    /// Clones the elements of `slice` into a new counted slice.
+    pub fn from_slice(slice: &[T]) -> ThinRc<T>
+    where
+        T: Clone,
+    {
+        Self::from_vec(slice.to_vec())
+    }
+
+    /// Collects the elements of `iter`, which claims to yield exactly `len` elements, directly
+    /// into the allocation of a new counted slice. If the claim is wrong, the elements are
+    /// collected into a vector first.
+    fn from_exact_iter<I: Iterator<Item = T>>(mut iter: I, len: usize) -> ThinRc<T> {
+        /// The elements written so far, which are dropped with the allocation if the iterator
+        /// panics.
+        struct Partial<T> {
+            ptr: *mut u8,
+            layout: Layout,
+            data: *mut T,
+            written: usize,
+        }
+
+        impl<T> Partial<T> {
+            /// Moves the elements written so far into a vector and frees the allocation.
+            fn into_vec(mut self) -> Vec<T> {
+                let mut vec = Vec::with_capacity(self.written);
+                unsafe {
+                    ptr::copy_nonoverlapping(self.data, vec.as_mut_ptr(), self.written);
+                    vec.set_len(self.written);
+                }
+                self.written = 0;
+                vec
+            }
+        }
+
+        impl<T> Drop for Partial<T> {
+            fn drop(&mut self) {
+                unsafe {
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.data, self.written));
+                    dealloc(self.ptr, self.layout);
+                }
+            }
+        }
+
+        let (layout, offset) = Self::layout(len);
+        let ptr = unsafe { alloc(layout) };
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+        let mut partial = Partial {
+            ptr,
+            layout,
+            data: unsafe { ptr.add(offset) } as *mut T,
+            written: 0,
+        };
+        while partial.written < len {
+            match iter.next() {
+                Some(elem) => {
+                    unsafe { partial.data.add(partial.written).write(elem) };
+                    partial.written += 1;
+                }
+                None => return Self::from_vec(partial.into_vec()),
+            }
+        }
+        if let Some(elem) = iter.next() {
+            let mut vec = partial.into_vec();
+            vec.push(elem);
+            vec.extend(iter);
+            return Self::from_vec(vec);
+        }
+
+        mem::forget(partial);
+        unsafe {
+            ptr::write(
+                ptr as *mut Self,
+                Self {
+                    refcount: AtomicUsize::new(1),
+                    len,
+                    _marker: PhantomData,
+                },
+            );
+            IntrusiveRc::from_raw(ptr as *mut Self)
+        }
+    }
+}
+
+/// Collects the elements of an iterator into a new counted slice.
+///
+/// If the iterator knows its exact length, as an [`ExactSizeIterator`] does, the elements are
+/// written directly into the allocation of the counted slice. Otherwise, they are collected into
+/// a vector first.
+impl<T: Send> FromIterator<T> for ThinRc<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        match iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => ThinSlice::from_exact_iter(iter, lower),
+            _ => ThinSlice::from_vec(iter.collect()),
+        }
+    }
+}
+
+/// Moves the elements of a vector into a new counted slice.
+///
+/// The header of the counted slice precedes its elements in the same allocation, so the elements
+/// are moved into a new allocation and the buffer of the vector is freed. They are never cloned.
+impl<T: Send> From<Vec<T>> for ThinRc<T> {
+    #[inline]
+    fn from(vec: Vec<T>) -> Self {
+        ThinSlice::from_vec(vec)
+    }
+}
+
+/// Moves the elements of a boxed slice into a new counted slice, like `From<Vec<T>>`.
+impl<T: Send> From<Box<[T]>> for ThinRc<T> {
+    #[inline]
+    fn from(slice: Box<[T]>) -> Self {
+        ThinSlice::from_vec(slice.into_vec())
+    }
+}
+
+/// Moves the UTF-8 bytes of a string into a new counted slice, like `From<Vec<T>>`.
+impl From<String> for ThinRc<u8> {
+    #[inline]
+    fn from(string: String) -> Self {
+        ThinSlice::from_vec(string.into_bytes())
+    }
+}
+
+/// Copies the UTF-8 bytes of a string into a new counted slice.
+impl From<&str> for ThinRc<u8> {
+    #[inline]
+    fn from(string: &str) -> Self {
+        ThinSlice::from_slice(string.as_bytes())
+    }
+}
+
+/// Copies the bytes of a C string, including its nul terminator, into a new counted slice.
+///
+/// The string is recovered with [`CStr::from_bytes_with_nul`].
+impl From<&CStr> for ThinRc<u8> {
+    #[inline]
+    fn from(string: &CStr) -> Self {
+        ThinSlice::from_slice(string.to_bytes_with_nul())
+    }
+}
+
+/// Copies the encoded bytes of an OS string into a new counted slice.
+///
+/// The string is recovered with [`OsStr::from_encoded_bytes_unchecked`], which is sound as long
+/// as the bytes are read by the same program on the same platform.
+impl From<&OsStr> for ThinRc<u8> {
+    #[inline]
+    fn from(string: &OsStr) -> Self {
+        ThinSlice::from_slice(string.as_encoded_bytes())
+    }
+}
+
+/// Copies the encoded bytes of a path into a new counted slice, like `From<&OsStr>`.
+impl From<&Path> for ThinRc<u8> {
+    #[inline]
+    fn from(path: &Path) -> Self {
+        Self::from(path.as_os_str())
+    }
+}
+
+/// Clones the elements of a slice into a new counted slice.
+impl<T: Send + Clone> From<&[T]> for ThinRc<T> {
+    #[inline]
+    fn from(slice: &[T]) -> Self {
+        ThinSlice::from_slice(slice)
+    }
+}
+
+unsafe impl<T: Send> IntrusiveRcObject for ThinSlice<T> {
+    #[inline]
+    fn counter(&self) -> &AtomicUsize {
+        &self.refcount
+    }
+
+    unsafe fn destroy(this: *mut Self) {
+        let len = (*this).len;
+        let (layout, offset) = Self::layout(len);
+        let data = (this as *mut u8).add(offset) as *mut T;
+        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(data, len));
+        dealloc(this as *mut u8, layout);
+    }
+}
+
+impl<T> Deref for ThinSlice<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ThinSlice<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, T> {
+        self.as_slice().iter()
+    }
+}
+
+/// Iterates over the elements of the counted slice, or over nothing if the pointer is null.
+impl<'a, T: Send> IntoIterator for &'a ThinRc<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, T> {
+        self.as_ref().map_or(&[][..], ThinSlice::as_slice).iter()
+    }
+}
+
+impl<T: Debug> Debug for ThinSlice<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_slice(), f)
+    }
+}
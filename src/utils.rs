@@ -1,9 +1,11 @@
+use std::alloc::{alloc, Layout};
 use std::cell::Cell;
-use std::sync::atomic::Ordering;
-use std::{mem::ManuallyDrop, sync::atomic::AtomicU64};
+use std::mem::{offset_of, ManuallyDrop};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::ebr_impl::{cs, global_epoch, Guard, Tagged, HIGH_TAG_WIDTH};
-use crate::RcObject;
+use crate::ebr_impl::{cs, Guard, RcArena, RetireRecord, Tagged, HIGH_TAG_WIDTH};
+use crate::{sanitizer, RcObject};
 
 /// Raw pointer to a reference counted object. Allows tagging.
 pub(crate) type Raw<T> = Tagged<RcInner<T>>;
@@ -20,7 +22,15 @@ impl Deferable for Guard {
         F: FnOnce(*mut RcInner<T>),
     {
         debug_assert!(!ptr.is_null());
-        self.defer_unchecked(move || f(ptr));
+        let record = RetireRecord::new::<T>(self, 1);
+        self.defer_sized_unchecked(
+            move || {
+                // The objects are counted as reclaimed once the function has run.
+                let _record = record;
+                f(ptr);
+            },
+            std::mem::size_of::<RcInner<T>>(),
+        );
     }
 }
 
@@ -149,26 +159,181 @@ impl<const WIDTH: u32> Modular<WIDTH> {
 }
 
 /// A reference-counted object of type `T` with an atomic reference counts.
+///
+/// The counts come first, followed by the object, so that the offset of the object only depends
+/// on the alignment of `T`. This layout is published in `circ::layout`, and must not change.
+#[repr(C)]
 pub(crate) struct RcInner<T> {
-    storage: ManuallyDrop<T>,
     state: AtomicU64,
+    storage: ManuallyDrop<T>,
+    /// The identifier of the collector of the thread that allocated the object.
+    ///
+    /// It comes after the object, so that it does not move the object in debug builds. It is left
+    /// out with the `unsafe-layout` feature, as the published layout has nothing after the object.
+    #[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+    domain: usize,
 }
 
+/// The strong count that the object of [`ZST_TOKEN`] starts with, so that it never hits zero.
+///
+/// This leaves room for `2^(STRONG_WIDTH - 1) - 1` more strong references, beyond which
+/// [`RcInner::increment_strong_by`] aborts.
+const ZST_TOKEN_STRONG: u64 = 1 << (STRONG_WIDTH - 1);
+
+/// The domain of an object that may be shared between collectors.
+#[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+const ANY_DOMAIN: usize = usize::MAX;
+
+/// The object shared by all the `Rc`s of zero-sized types that are made by
+/// [`RcInner::shared_zst`]. It is never destructed, as its strong count never hits zero.
+///
+/// Each copy of the crate that is linked into a process has its own object.
+static ZST_TOKEN: RcInner<()> = RcInner {
+    state: AtomicU64::new(ZST_TOKEN_STRONG * COUNT + WEAK_COUNT),
+    storage: ManuallyDrop::new(()),
+    #[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+    domain: ANY_DOMAIN,
+};
+
 impl<T> RcInner<T> {
+    /// The offset of the object from the start of the allocation.
+    pub(crate) const DATA_OFFSET: usize = {
+        assert!(offset_of!(Self, state) == 0);
+        offset_of!(Self, storage)
+    };
+
+    /// Checks that an object of `T` can be stood for by [`ZST_TOKEN`].
+    const FITS_ZST_TOKEN: () = assert!(
+        std::mem::size_of::<T>() == 0
+            && std::mem::align_of::<T>() <= std::mem::align_of::<AtomicU64>()
+            && !std::mem::needs_drop::<T>(),
+        "the type must be zero-sized, aligned to at most 8 bytes, and have no drop glue"
+    );
+
     #[inline(always)]
     pub(crate) fn alloc(obj: T, init_strong: u32) -> *mut Self {
-        let obj = Self {
+        let layout = Layout::new::<Self>();
+        if let Some(slot) = crate::ebr_impl::alloc_from_rc_arena(layout) {
+            // SAFETY: `slot` is a fresh slot that fits the layout of `Self`.
+            return unsafe { Self::init_slot(slot, obj, init_strong) };
+        }
+        Box::into_raw(Box::new(Self::new(obj, init_strong)))
+    }
+
+    /// Returns the object shared by all the zero-sized objects, taking a new strong reference to
+    /// it, without allocating.
+    ///
+    /// The layout of `Self` is the same as that of `RcInner<()>` for such `T`, and the object is
+    /// only ever updated through its atomic counts.
+    pub(crate) fn shared_zst() -> *mut Self {
+        let () = Self::FITS_ZST_TOKEN;
+        ZST_TOKEN.increment_strong();
+        std::ptr::addr_of!(ZST_TOKEN).cast_mut().cast()
+    }
+
+    /// Allocates a new object from a free slot of `arena`, falling back to the global allocator
+    /// if the object does not fit into a slot.
+    pub(crate) fn alloc_in(obj: T, init_strong: u32, arena: &RcArena) -> *mut Self {
+        match arena.alloc(Layout::new::<Self>()) {
+            // SAFETY: `slot` is a fresh slot that fits the layout of `Self`.
+            Some(slot) => unsafe { Self::init_slot(slot, obj, init_strong) },
+            None => Box::into_raw(Box::new(Self::new(obj, init_strong))),
+        }
+    }
+
+    /// Allocates a new object like [`RcInner::alloc`], but returns `obj` back if the allocation
+    /// fails even after reclaiming garbage.
+    pub(crate) fn try_alloc(obj: T, init_strong: u32) -> Result<*mut Self, T> {
+        let layout = Layout::new::<Self>();
+        if let Some(slot) = crate::ebr_impl::alloc_from_rc_arena(layout) {
+            // SAFETY: `slot` is a fresh slot that fits the layout of `Self`.
+            return Ok(unsafe { Self::init_slot(slot, obj, init_strong) });
+        }
+        let mut ptr = unsafe { alloc(layout) }.cast::<Self>();
+        if ptr.is_null() {
+            crate::ebr_impl::reclaim_on_alloc_failure();
+            ptr = unsafe { alloc(layout) }.cast::<Self>();
+            if ptr.is_null() {
+                return Err(obj);
+            }
+        }
+        // SAFETY: `ptr` is a fresh allocation with the layout of `Self`, which `Box` can free.
+        unsafe { ptr.write(Self::new(obj, init_strong)) };
+        Ok(ptr)
+    }
+
+    /// Writes a new object into a slot of an arena.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must be a free slot that fits the layout of `Self`.
+    #[inline(always)]
+    unsafe fn init_slot(slot: NonNull<u8>, obj: T, init_strong: u32) -> *mut Self {
+        let ptr = slot.as_ptr().cast::<Self>();
+        ptr.write(Self::new(obj, init_strong));
+        ptr
+    }
+
+    #[inline(always)]
+    fn new(obj: T, init_strong: u32) -> Self {
+        Self {
             storage: ManuallyDrop::new(obj),
             state: AtomicU64::new((init_strong as u64) * COUNT + WEAK_COUNT),
-        };
-        Box::into_raw(Box::new(obj))
+            #[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+            domain: crate::ebr_impl::current_collector(crate::ebr_impl::Collector::id),
+        }
+    }
+
+    /// Checks that the object is loaded or stored in a critical section of the collector it was
+    /// allocated with, as objects must not be shared between collectors.
+    #[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+    pub(crate) fn check_domain(&self, guard: &Guard) {
+        if self.domain == ANY_DOMAIN {
+            return;
+        }
+        if let Some(local) = unsafe { guard.local.as_ref() } {
+            assert_eq!(
+                self.domain,
+                local.collector().id(),
+                "an object of a collector is accessed in a critical section of another collector"
+            );
+        }
+    }
+
+    /// Checks that the counts of the object are consistent, returning a description of the first
+    /// inconsistency. If `owned`, the object is known to be held by a strong reference.
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_counts(&self, owned: bool) -> Result<(), String> {
+        let state = State::from_raw(self.state.load(Ordering::Acquire));
+        if state.destructed() {
+            return Err("a reachable object has been destructed".into());
+        }
+        if owned && state.strong() == 0 {
+            return Err("an object held by a strong reference has a strong count of zero".into());
+        }
+        if state.weak() == 0 {
+            return Err("a live object has a weak count of zero".into());
+        }
+        if !state.weaked() && state.weak() != 1 {
+            return Err(format!(
+                "an object that has never been downgraded has a weak count of {}",
+                state.weak()
+            ));
+        }
+        Ok(())
     }
 
     /// # Safety
     ///
     /// The given `ptr` must not be shared across more than one thread.
     pub(crate) unsafe fn dealloc(ptr: *mut Self) {
-        drop(Box::from_raw(ptr));
+        sanitizer::unpoison(std::ptr::addr_of!((*ptr).storage), std::mem::size_of::<T>());
+        if RcArena::contains(ptr.cast()) {
+            // The fields other than the already destructed object need no drop.
+            RcArena::dealloc(ptr.cast());
+        } else {
+            drop(Box::from_raw(ptr));
+        }
     }
 
     /// Returns an immutable reference to the object.
@@ -181,9 +346,30 @@ impl<T> RcInner<T> {
         &mut self.storage
     }
 
+    /// Returns the address of the object in the allocation at `ptr` without dereferencing it,
+    /// so that `ptr` may be null or even dangling.
+    #[inline]
+    pub(crate) fn data_ptr(ptr: *const Self) -> *const T {
+        if ptr.is_null() {
+            return std::ptr::null();
+        }
+        ptr.cast::<u8>().wrapping_add(Self::DATA_OFFSET).cast()
+    }
+
     #[inline]
     pub(crate) fn increment_strong(&self) -> bool {
-        let val = State::from_raw(self.state.fetch_add(COUNT, Ordering::SeqCst));
+        self.increment_strong_by(1)
+    }
+
+    /// Increments the strong count by `count` with a single read-modify-write.
+    #[inline]
+    pub(crate) fn increment_strong_by(&self, count: u32) -> bool {
+        let val = State::from_raw(self.state.fetch_add(count as u64 * COUNT, Ordering::SeqCst));
+        if u64::from(val.strong()) + u64::from(count) > STRONG {
+            // The count has carried into the weak count, which can free the object while it is
+            // still in use. As `std::sync::Arc` does, abort rather than go on.
+            std::process::abort();
+        }
         if val.destructed() {
             return false;
         }
@@ -240,6 +426,14 @@ impl<T> RcInner<T> {
         }
     }
 
+    /// Returns `true` if the object has a single strong reference and no weak references. The
+    /// decrements that are still deferred are counted as references.
+    #[inline]
+    pub(crate) fn is_unique(&self) -> bool {
+        let state = State::from_raw(self.state.load(Ordering::Acquire));
+        state.strong() == 1 && state.weak() == 1
+    }
+
     #[inline]
     pub(crate) fn is_not_destructed(&self) -> bool {
         let mut old = State::from_raw(self.state.load(Ordering::SeqCst));
@@ -259,11 +453,12 @@ impl<T> RcInner<T> {
 }
 
 impl<T: RcObject> RcInner<T> {
+    /// Subtracts `count` from the strong count and marks `epoch` on it, returning `true` if the
+    /// strong count has hit zero.
     #[inline]
-    pub(crate) unsafe fn decrement_strong(ptr: *mut Self, count: u32, guard: Option<&Guard>) {
-        let epoch = global_epoch();
+    unsafe fn sub_strong_in_epoch(ptr: *mut Self, count: u32, epoch: usize) -> bool {
         // Should mark the current epoch on the strong count with CAS.
-        let hit_zero = loop {
+        loop {
             let curr = State::from_raw((*ptr).state.load(Ordering::SeqCst));
             debug_assert!(curr.strong() >= count);
             if (*ptr)
@@ -276,12 +471,45 @@ impl<T: RcObject> RcInner<T> {
                 )
                 .is_ok()
             {
-                break curr.strong() == count;
+                return curr.strong() == count;
             }
-        };
+        }
+    }
 
+    /// Decrements the strong counts of a batch of objects.
+    ///
+    /// The global epoch is read once for the whole batch, and the objects whose strong counts hit
+    /// zero are destructed by a single deferred function.
+    pub(crate) unsafe fn decrement_strong_many<I>(ptrs: I, guard: &Guard)
+    where
+        I: IntoIterator<Item = *mut Self>,
+    {
+        let epoch = guard.global_epoch();
+        let dead: Vec<_> = ptrs
+            .into_iter()
+            .filter(|&ptr| Self::sub_strong_in_epoch(ptr, 1, epoch))
+            .collect();
+        if !dead.is_empty() {
+            let bytes = dead.len() * std::mem::size_of::<Self>();
+            let record = RetireRecord::new::<T>(guard, dead.len());
+            guard.defer_sized_unchecked(
+                move || {
+                    let _record = record;
+                    for ptr in dead {
+                        Self::try_destruct(ptr);
+                    }
+                },
+                bytes,
+            );
+        }
+        guard.incr_manual_collection();
+    }
+
+    #[inline]
+    pub(crate) unsafe fn decrement_strong(ptr: *mut Self, count: u32, guard: Option<&Guard>) {
         let trigger_recl = |guard: &Guard| {
-            if hit_zero {
+            // The epoch is read from the collector that the object is retired to.
+            if Self::sub_strong_in_epoch(ptr, count, guard.global_epoch()) {
                 guard.defer_with_inner(ptr, |inner| Self::try_destruct(inner));
             }
             // Periodically triggers a collection.
@@ -295,6 +523,16 @@ impl<T: RcObject> RcInner<T> {
         }
     }
 
+    /// Releases a strong reference after the grace period of `guard`, rather than right away
+    /// like `decrement_strong`.
+    ///
+    /// The deferred function frees nothing by itself, so it is not recorded as a retired object
+    /// and has no size. The object is recorded once the decrement destructs it.
+    #[inline]
+    pub(crate) unsafe fn defer_decrement_strong(ptr: *mut Self, guard: &Guard) {
+        guard.defer_unchecked(move || Self::decrement_strong(ptr, 1, None));
+    }
+
     #[inline]
     unsafe fn try_destruct(ptr: *mut Self) {
         let mut old = State::from_raw((*ptr).state.load(Ordering::SeqCst));
@@ -356,7 +594,7 @@ unsafe fn dispose_general_node<T: RcObject>(
     let node_epoch = state.epoch();
     debug_assert_eq!(state.strong(), 0);
 
-    let curr_epoch = global_epoch();
+    let curr_epoch = guard.global_epoch();
     let modu: Modular<EPOCH_WIDTH> = Modular::new(curr_epoch as isize + 1);
     let mut outgoings = Vec::new();
 
@@ -368,6 +606,8 @@ unsafe fn dispose_general_node<T: RcObject>(
         unsafe {
             ManuallyDrop::drop(&mut rc.storage);
             if State::from_raw(rc.state.load(Ordering::SeqCst)).weaked() {
+                // The weak references keep the allocation, but the object is dead.
+                sanitizer::poison(std::ptr::addr_of!(rc.storage), std::mem::size_of::<T>());
                 RcInner::decrement_weak(rc, Some(guard));
             } else {
                 RcInner::dealloc(rc);
@@ -413,3 +653,6 @@ unsafe fn dispose_general_node<T: RcObject>(
         guard.defer_with_inner(rc, |rc| RcInner::try_destruct(rc));
     }
 }
+
+#[cfg(kani)]
+mod verification;
@@ -0,0 +1,160 @@
+//! Bounded proofs of the transitions of the reference counts, checked with
+//! [Kani](https://github.com/model-checking/kani) by `cargo kani`.
+//!
+//! The counts, the epoch and the flags of an object are packed into a single word. The proofs
+//! check that every transition changes only the field it is meant to, and that a reader that
+//! revives an object whose strong count has hit zero never succeeds once the object has been
+//! destructed, for every interleaving with the destructing thread.
+
+use super::*;
+
+/// Returns an arbitrary state whose counts can be incremented once.
+fn any_state() -> State {
+    let state = State::from_raw(kani::any());
+    kani::assume(state.strong() < STRONG as u32);
+    kani::assume(state.weak() < (WEAK / WEAK_COUNT) as u32);
+    state
+}
+
+fn assert_same_flags(a: State, b: State) {
+    assert_eq!(a.destructed(), b.destructed());
+    assert_eq!(a.weaked(), b.weaked());
+}
+
+#[kani::proof]
+fn strong_transitions() {
+    let state = any_state();
+    let added = state.add_strong(1);
+    assert_eq!(added.strong(), state.strong() + 1);
+    assert_eq!(added.weak(), state.weak());
+    assert_eq!(added.epoch(), state.epoch());
+    assert_same_flags(added, state);
+    assert_eq!(added.sub_strong(1).as_raw(), state.as_raw());
+}
+
+#[kani::proof]
+fn weak_transitions() {
+    let state = any_state();
+    let added = state.add_weak(1);
+    assert_eq!(added.weak(), state.weak() + 1);
+    assert_eq!(added.strong(), state.strong());
+    assert_eq!(added.epoch(), state.epoch());
+    assert_same_flags(added, state);
+}
+
+#[kani::proof]
+fn epoch_transitions() {
+    let state = any_state();
+    let epoch: usize = kani::any();
+    let marked = state.with_epoch(epoch);
+    assert_eq!(marked.epoch() as usize, epoch & ((1 << EPOCH_WIDTH) - 1));
+    assert_eq!(marked.strong(), state.strong());
+    assert_eq!(marked.weak(), state.weak());
+    assert_same_flags(marked, state);
+}
+
+#[kani::proof]
+fn flag_transitions() {
+    let state = any_state();
+    let destructed = state.with_destructed(true);
+    assert!(destructed.destructed());
+    assert_eq!(destructed.weaked(), state.weaked());
+    let weaked = state.with_weaked(true);
+    assert!(weaked.weaked());
+    assert_eq!(weaked.destructed(), state.destructed());
+    for changed in [destructed, weaked] {
+        assert_eq!(changed.strong(), state.strong());
+        assert_eq!(changed.weak(), state.weak());
+        assert_eq!(changed.epoch(), state.epoch());
+    }
+}
+
+/// The destructing thread of `RcInner::try_destruct`, one atomic step at a time.
+enum Destructor {
+    Load,
+    Cas(State),
+    Destructed,
+    Revived,
+}
+
+impl Destructor {
+    fn step(&mut self, word: &mut u64) {
+        *self = match *self {
+            Destructor::Load => {
+                let old = State::from_raw(*word);
+                if old.strong() > 0 {
+                    Destructor::Revived
+                } else {
+                    Destructor::Cas(old)
+                }
+            }
+            Destructor::Cas(old) if *word == old.as_raw() => {
+                *word = old.with_destructed(true).as_raw();
+                Destructor::Destructed
+            }
+            Destructor::Cas(_) => Destructor::Load,
+            Destructor::Destructed => Destructor::Destructed,
+            Destructor::Revived => Destructor::Revived,
+        };
+    }
+}
+
+/// The reviving thread of `RcInner::increment_strong`, one atomic step at a time.
+enum Reviver {
+    Add,
+    AddAgain,
+    Done(bool),
+}
+
+impl Reviver {
+    fn step(&mut self, word: &mut u64) {
+        *self = match *self {
+            Reviver::Add => {
+                let val = State::from_raw(*word);
+                *word += COUNT;
+                if val.destructed() {
+                    Reviver::Done(false)
+                } else if val.strong() == 0 {
+                    Reviver::AddAgain
+                } else {
+                    Reviver::Done(true)
+                }
+            }
+            Reviver::AddAgain => {
+                *word += COUNT;
+                Reviver::Done(true)
+            }
+            Reviver::Done(revived) => Reviver::Done(revived),
+        };
+    }
+}
+
+#[kani::proof]
+#[kani::unwind(8)]
+fn destruction_race() {
+    let mut word = State::from_raw(kani::any()).with_destructed(false).as_raw();
+    kani::assume(State::from_raw(word).strong() == 0);
+
+    let mut destructor = Destructor::Load;
+    let mut reviver = Reviver::Add;
+    for _ in 0..6 {
+        if kani::any() {
+            destructor.step(&mut word);
+        } else {
+            reviver.step(&mut word);
+        }
+    }
+    // Let both threads finish.
+    for _ in 0..3 {
+        destructor.step(&mut word);
+        reviver.step(&mut word);
+    }
+
+    let destructed = matches!(destructor, Destructor::Destructed);
+    let revived = matches!(reviver, Reviver::Done(true));
+    // Exactly one of them wins.
+    assert!(destructed != revived);
+    if revived {
+        assert!(State::from_raw(word).strong() >= 1);
+    }
+}
@@ -0,0 +1,372 @@
+//! An atomic reference-counted pointer paired with a 64-bit version.
+//!
+//! The tag bits of an [`AtomicRc`](crate::AtomicRc) are too few to serve as a version counter
+//! that rules out ABA: a tag wraps around after a handful of updates. A [`VersionedAtomicRc`]
+//! stores the pointer next to a 64-bit version that is incremented by every update, and compares
+//! both in [`compare_exchange`](VersionedAtomicRc::compare_exchange), so that an update fails if
+//! the pointer has been replaced in the meantime, even if it has been replaced back since.
+//!
+//! The pointer and the version are updated together with a 128-bit compare-and-swap where the
+//! target supports one, which is `x86_64` with the `cmpxchg16b` target feature. Elsewhere they
+//! are protected by a sequence lock, whose readers never block writers, but whose writers may
+//! wait for each other. [`VersionedAtomicRc::is_lock_free`] tells which one is used.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::versioned::VersionedAtomicRc;
+//! use circ::{cs, Rc, RcObject};
+//!
+//! struct Node(usize);
+//!
+//! unsafe impl RcObject for Node {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let a = Rc::new(Node(1));
+//! let head = VersionedAtomicRc::from(a.clone());
+//! let guard = &cs();
+//! let (current, version) = head.load(guard);
+//!
+//! // Another thread replaces the node, and then puts it back.
+//! let b = head.swap(Rc::new(Node(2)));
+//! head.store(b, guard);
+//! assert!(head.load(guard).0.ptr_eq(current));
+//!
+//! // The pointer is the same, but the version is not.
+//! let result = head.compare_exchange(current, version, Rc::new(Node(3)), guard);
+//! assert!(result.is_err());
+//! ```
+
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::mem::forget;
+
+use crate::utils::{Raw, RcInner};
+use crate::{CompareExchangeError, Guard, Rc, RcObject, Snapshot};
+
+/// The error of [`VersionedAtomicRc::compare_exchange`], holding the current pointer and version.
+type VersionedError<'g, T> = CompareExchangeError<Rc<T>, (Snapshot<'g, T>, u64)>;
+
+cfg_if::cfg_if! {
+    if #[cfg(all(target_arch = "x86_64", target_feature = "cmpxchg16b"))] {
+        use std::cell::UnsafeCell;
+
+        /// A pair of words updated with `cmpxchg16b`.
+        #[repr(C, align(16))]
+        struct Pair {
+            value: UnsafeCell<[u64; 2]>,
+        }
+
+        impl Pair {
+            const LOCK_FREE: bool = true;
+
+            fn new(value: [u64; 2]) -> Self {
+                Self {
+                    value: UnsafeCell::new(value),
+                }
+            }
+
+            fn load(&self) -> [u64; 2] {
+                // Swapping zero with zero leaves the pair intact and returns its value either way.
+                match self.compare_exchange([0, 0], [0, 0]) {
+                    Ok(value) | Err(value) => value,
+                }
+            }
+
+            fn compare_exchange(
+                &self,
+                current: [u64; 2],
+                new: [u64; 2],
+            ) -> Result<[u64; 2], [u64; 2]> {
+                let (lo, hi, ok): (u64, u64, u8);
+                // SAFETY: The pair is aligned to 16 bytes and only accessed atomically while
+                // shared. `rbx` is reserved by LLVM, so it is swapped in and out around the
+                // instruction.
+                unsafe {
+                    std::arch::asm!(
+                        "xchg {rbx_tmp}, rbx",
+                        "lock cmpxchg16b xmmword ptr [{dst}]",
+                        "sete {ok}",
+                        "mov rbx, {rbx_tmp}",
+                        dst = in(reg) self.value.get(),
+                        rbx_tmp = inout(reg) new[0] => _,
+                        ok = out(reg_byte) ok,
+                        inout("rax") current[0] => lo,
+                        inout("rdx") current[1] => hi,
+                        in("rcx") new[1],
+                        options(nostack),
+                    );
+                }
+                if ok != 0 {
+                    Ok([lo, hi])
+                } else {
+                    Err([lo, hi])
+                }
+            }
+        }
+    } else {
+        use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
+
+        use crossbeam_utils::Backoff;
+
+        /// A pair of words protected by a sequence lock.
+        struct Pair {
+            /// Odd while a writer updates the words.
+            seq: AtomicUsize,
+            value: [AtomicU64; 2],
+        }
+
+        impl Pair {
+            const LOCK_FREE: bool = false;
+
+            fn new(value: [u64; 2]) -> Self {
+                Self {
+                    seq: AtomicUsize::new(0),
+                    value: value.map(AtomicU64::new),
+                }
+            }
+
+            fn load_relaxed(&self) -> [u64; 2] {
+                [
+                    self.value[0].load(Ordering::Relaxed),
+                    self.value[1].load(Ordering::Relaxed),
+                ]
+            }
+
+            fn load(&self) -> [u64; 2] {
+                let backoff = Backoff::new();
+                loop {
+                    let seq = self.seq.load(Ordering::Acquire);
+                    if seq & 1 == 0 {
+                        let value = self.load_relaxed();
+                        fence(Ordering::Acquire);
+                        if self.seq.load(Ordering::Relaxed) == seq {
+                            return value;
+                        }
+                    }
+                    backoff.snooze();
+                }
+            }
+
+            fn compare_exchange(
+                &self,
+                current: [u64; 2],
+                new: [u64; 2],
+            ) -> Result<[u64; 2], [u64; 2]> {
+                let backoff = Backoff::new();
+                let mut seq = self.seq.load(Ordering::Relaxed);
+                loop {
+                    if seq & 1 == 0 {
+                        match self.seq.compare_exchange_weak(
+                            seq,
+                            seq.wrapping_add(1),
+                            Ordering::Acquire,
+                            Ordering::Relaxed,
+                        ) {
+                            Ok(_) => break,
+                            Err(s) => seq = s,
+                        }
+                    } else {
+                        backoff.snooze();
+                        seq = self.seq.load(Ordering::Relaxed);
+                    }
+                }
+                // Readers must not see the new words with the old sequence number.
+                fence(Ordering::Release);
+
+                let value = self.load_relaxed();
+                let result = if value == current {
+                    for (w, new) in self.value.iter().zip(new) {
+                        w.store(new, Ordering::Relaxed);
+                    }
+                    Ok(value)
+                } else {
+                    Err(value)
+                };
+                self.seq.store(seq.wrapping_add(2), Ordering::Release);
+                result
+            }
+        }
+    }
+}
+
+/// An atomic reference-counted pointer paired with a 64-bit version, which is incremented by
+/// every update.
+///
+/// See the [module documentation](self) for details.
+pub struct VersionedAtomicRc<T: RcObject> {
+    /// The pointer and the version.
+    pair: Pair,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: RcObject + Send + Sync> Send for VersionedAtomicRc<T> {}
+unsafe impl<T: RcObject + Send + Sync> Sync for VersionedAtomicRc<T> {}
+
+impl<T: RcObject> VersionedAtomicRc<T> {
+    /// Returns `true` if the pointer and the version are updated with a 128-bit compare-and-swap
+    /// rather than under a sequence lock.
+    pub const fn is_lock_free() -> bool {
+        Pair::LOCK_FREE
+    }
+
+    /// Constructs a new `VersionedAtomicRc` by allocating a new reference-counted object, at
+    /// version zero.
+    #[inline]
+    pub fn new(obj: T) -> Self {
+        Self::from(Rc::new(obj))
+    }
+
+    /// Constructs a new `VersionedAtomicRc` containing a null pointer, at version zero.
+    #[inline]
+    pub fn null() -> Self {
+        Self::from(Rc::null())
+    }
+
+    #[inline]
+    fn unpack(value: [u64; 2]) -> (Raw<T>, u64) {
+        (Raw::from(value[0] as usize as *mut RcInner<T>), value[1])
+    }
+
+    #[inline]
+    fn pack(ptr: Raw<T>, version: u64) -> [u64; 2] {
+        [ptr.as_tagged_raw() as usize as u64, version]
+    }
+
+    /// Loads the pointer and its version.
+    #[inline]
+    pub fn load<'g>(&self, guard: &'g Guard) -> (Snapshot<'g, T>, u64) {
+        let (ptr, version) = Self::unpack(self.pair.load());
+        (Snapshot::from_raw(ptr, guard), version)
+    }
+
+    /// Returns the current version.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.pair.load()[1]
+    }
+
+    /// Stores an [`Rc`] pointer into this `VersionedAtomicRc` and increments the version.
+    #[inline]
+    pub fn store(&self, ptr: Rc<T>, guard: &Guard) {
+        ptr.as_raw().check_domain(guard);
+        let old = self.swap(ptr).into_raw();
+        unsafe {
+            // Did not use `Rc::drop`, to reuse the given `guard`.
+            if let Some(cnt) = old.as_raw().as_mut() {
+                RcInner::decrement_strong(cnt, 1, Some(guard));
+            }
+        }
+    }
+
+    /// Stores an [`Rc`] pointer into this `VersionedAtomicRc` and increments the version,
+    /// returning the previous pointer.
+    pub fn swap(&self, new: Rc<T>) -> Rc<T> {
+        let new_ptr = new.into_raw().with_timestamp();
+        let mut current = self.pair.load();
+        loop {
+            let version = Self::unpack(current).1;
+            match self
+                .pair
+                .compare_exchange(current, Self::pack(new_ptr, version.wrapping_add(1)))
+            {
+                Ok(old) => return Rc::from_raw(Self::unpack(old).0),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Stores `desired` and increments the version if the current pointer is the same as
+    /// `expected` and the current version is `version`. The tag is also taken into account.
+    ///
+    /// On success the pointer that was in this `VersionedAtomicRc` is returned. On failure the
+    /// current pointer and version, and `desired`, are returned.
+    pub fn compare_exchange<'g>(
+        &self,
+        expected: Snapshot<'g, T>,
+        version: u64,
+        desired: Rc<T>,
+        guard: &'g Guard,
+    ) -> Result<Rc<T>, VersionedError<'g, T>> {
+        desired.as_raw().check_domain(guard);
+        let desired_raw = desired.as_raw().with_timestamp();
+        let mut current = Self::pack(expected.ptr, version);
+        loop {
+            match self
+                .pair
+                .compare_exchange(current, Self::pack(desired_raw, version.wrapping_add(1)))
+            {
+                Ok(_) => {
+                    // Skip decrementing a strong count of the inserted pointer.
+                    forget(desired);
+                    return Ok(Rc::from_raw(Self::unpack(current).0));
+                }
+                Err(actual) => {
+                    let (actual_ptr, actual_version) = Self::unpack(actual);
+                    if actual_version == version && actual_ptr.ptr_eq(expected.ptr) {
+                        // Only the epoch in the high bits of the pointer differs.
+                        current = actual;
+                    } else {
+                        let current = (Snapshot::from_raw(actual_ptr, guard), actual_version);
+                        return Err(CompareExchangeError { desired, current });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes the underlying [`Rc`] from this `VersionedAtomicRc`, leaving a null pointer and
+    /// incrementing the version.
+    #[inline]
+    pub fn take(&mut self) -> Rc<T> {
+        self.swap(Rc::null())
+    }
+}
+
+impl<T: RcObject> Drop for VersionedAtomicRc<T> {
+    #[inline]
+    fn drop(&mut self) {
+        let ptr = Self::unpack(self.pair.load()).0;
+        unsafe {
+            if let Some(cnt) = ptr.as_raw().as_mut() {
+                RcInner::decrement_strong(cnt, 1, None);
+            }
+        }
+    }
+}
+
+impl<T: RcObject> Default for VersionedAtomicRc<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: RcObject> From<Rc<T>> for VersionedAtomicRc<T> {
+    #[inline]
+    fn from(value: Rc<T>) -> Self {
+        Self {
+            pair: Pair::new(Self::pack(value.into_raw(), 0)),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RcObject> Debug for VersionedAtomicRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (ptr, version) = Self::unpack(self.pair.load());
+        f.debug_struct("VersionedAtomicRc")
+            .field("ptr", &ptr)
+            .field("version", &version)
+            .finish()
+    }
+}
+
+impl<T: RcObject> Pointer for VersionedAtomicRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&Self::unpack(self.pair.load()).0, f)
+    }
+}
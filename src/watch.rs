@@ -0,0 +1,284 @@
+//! A single-producer, multi-consumer channel that only retains the last sent value.
+//!
+//! The value is kept in an [`RcuCell`], so receivers read it as a [`Snapshot`] without touching
+//! the reference counter, and detect new values by comparing the generation of the cell with the
+//! one they have seen last. Receivers can block on a new value with [`Receiver::wait_changed`] or
+//! await it with [`Receiver::changed`].
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::{cs, watch, RcObject, Rc};
+//!
+//! struct Config {
+//!     threads: usize,
+//! }
+//!
+//! unsafe impl RcObject for Config {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let (tx, mut rx) = watch::channel(Rc::new(Config { threads: 4 }));
+//! assert!(!rx.has_changed().unwrap());
+//!
+//! tx.send(Rc::new(Config { threads: 8 }));
+//! assert!(rx.has_changed().unwrap());
+//!
+//! let guard = &cs();
+//! assert_eq!(rx.borrow_and_update(guard).as_ref().unwrap().threads, 8);
+//! assert!(!rx.has_changed().unwrap());
+//! ```
+
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::{Guard, Rc, RcObject, RcuCell, Snapshot};
+
+struct Shared<T: RcObject> {
+    cell: RcuCell<T>,
+    /// Whether the sender has been dropped.
+    closed: AtomicBool,
+    /// The tasks waiting for a new value.
+    wakers: Mutex<Vec<Waker>>,
+    /// Notified together with `wakers`, for the threads waiting for a new value.
+    condvar: Condvar,
+}
+
+impl<T: RcObject> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Vec<Waker>> {
+        // The lock protects no invariant, so poisoning is harmless.
+        self.wakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Checks for a new value, marking it as seen if there is one.
+    fn poll_version(&self, seen: &mut usize) -> Option<Result<(), RecvError>> {
+        let version = self.cell.generation();
+        if version != *seen {
+            *seen = version;
+            Some(Ok(()))
+        } else if self.closed.load(Ordering::Acquire) {
+            Some(Err(RecvError))
+        } else {
+            None
+        }
+    }
+
+    fn notify(&self) {
+        // Taking the lock orders the notification after the checks of the waiting receivers.
+        let wakers = std::mem::take(&mut *self.lock());
+        self.condvar.notify_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// Creates a new watch channel with an initial value, returning the sender and a receiver.
+pub fn channel<T: RcObject>(init: Rc<T>) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        cell: RcuCell::from(init),
+        closed: AtomicBool::new(false),
+        wakers: Mutex::new(Vec::new()),
+        condvar: Condvar::new(),
+    });
+    let receiver = Receiver {
+        shared: shared.clone(),
+        seen: 0,
+    };
+    (Sender { shared }, receiver)
+}
+
+/// The error returned when the sender of a channel has been dropped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RecvError;
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the sender of the watch channel has been dropped")
+    }
+}
+
+impl Error for RecvError {}
+
+/// The sending half of a watch channel.
+pub struct Sender<T: RcObject> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: RcObject> Sender<T> {
+    /// Replaces the value of the channel with `value` and notifies the receivers.
+    pub fn send(&self, value: Rc<T>) {
+        drop(self.send_replace(value));
+    }
+
+    /// Replaces the value of the channel with `value`, notifies the receivers, and returns the
+    /// previous value.
+    pub fn send_replace(&self, value: Rc<T>) -> Rc<T> {
+        let old = self.shared.cell.swap(value);
+        self.shared.notify();
+        old
+    }
+
+    /// Reads the current value.
+    #[inline]
+    pub fn borrow<'g>(&self, guard: &'g Guard) -> Snapshot<'g, T> {
+        self.shared.cell.read(guard)
+    }
+
+    /// Creates a new receiver which has seen the current value.
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver {
+            seen: self.shared.cell.generation(),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Returns the number of receivers of the channel.
+    pub fn receiver_count(&self) -> usize {
+        Arc::strong_count(&self.shared) - 1
+    }
+}
+
+impl<T: RcObject> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.notify();
+    }
+}
+
+impl<T: RcObject> Debug for Sender<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+/// The receiving half of a watch channel.
+///
+/// A receiver remembers the version of the value it has seen last, which is updated by
+/// [`Receiver::borrow_and_update`] and by waiting for a new value.
+pub struct Receiver<T: RcObject> {
+    shared: Arc<Shared<T>>,
+    /// The generation of the cell that has been seen last.
+    seen: usize,
+}
+
+impl<T: RcObject> Receiver<T> {
+    /// Reads the current value, without marking it as seen.
+    #[inline]
+    pub fn borrow<'g>(&self, guard: &'g Guard) -> Snapshot<'g, T> {
+        self.shared.cell.read(guard)
+    }
+
+    /// Reads the current value and marks it as seen.
+    #[inline]
+    pub fn borrow_and_update<'g>(&mut self, guard: &'g Guard) -> Snapshot<'g, T> {
+        // Reading the generation first may mark an older value as seen, but never skips a value.
+        self.seen = self.shared.cell.generation();
+        self.shared.cell.read(guard)
+    }
+
+    /// Returns the current value as an [`Rc`] pointer and marks it as seen.
+    pub fn load(&mut self) -> Rc<T> {
+        self.seen = self.shared.cell.generation();
+        self.shared.cell.load()
+    }
+
+    /// Returns the number of values sent through the channel.
+    #[inline]
+    pub fn version(&self) -> usize {
+        self.shared.cell.generation()
+    }
+
+    /// Returns `true` if a value has been sent since the value was seen last.
+    ///
+    /// Returns an error if the sender has been dropped.
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        if self.shared.closed.load(Ordering::Acquire) {
+            return Err(RecvError);
+        }
+        Ok(self.version() != self.seen)
+    }
+
+    /// Blocks the current thread until a new value is sent, and marks it as seen.
+    ///
+    /// Returns an error if the sender is dropped before sending a new value.
+    pub fn wait_changed(&mut self) -> Result<(), RecvError> {
+        let mut lock = self.shared.lock();
+        loop {
+            if let Some(result) = self.shared.poll_version(&mut self.seen) {
+                return result;
+            }
+            lock = self
+                .shared
+                .condvar
+                .wait(lock)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Waits asynchronously until a new value is sent, and marks it as seen.
+    ///
+    /// The returned future resolves to an error if the sender is dropped before sending a new
+    /// value.
+    pub fn changed(&mut self) -> Changed<'_, T> {
+        Changed { receiver: self }
+    }
+}
+
+impl<T: RcObject> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T: RcObject> Debug for Receiver<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver")
+            .field("seen", &self.seen)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A future that resolves when a new value is sent to a watch channel.
+///
+/// This is created by [`Receiver::changed`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Changed<'r, T: RcObject> {
+    receiver: &'r mut Receiver<T>,
+}
+
+impl<'r, T: RcObject> Future for Changed<'r, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Receiver { shared, seen } = &mut *self.get_mut().receiver;
+        if let Some(result) = shared.poll_version(seen) {
+            return Poll::Ready(result);
+        }
+        let mut wakers = shared.lock();
+        // Checked again under the lock, so that a value sent in the meantime is not missed.
+        if let Some(result) = shared.poll_version(seen) {
+            return Poll::Ready(result);
+        }
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<'r, T: RcObject> Debug for Changed<'r, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad("Changed { .. }")
+    }
+}
@@ -52,6 +52,21 @@ impl<T> AtomicWeak<T> {
         WeakSnapshot::from_raw(self.link.load(order), guard)
     }
 
+    /// Loads the pointer and upgrades it to a [`Snapshot`] protected by `guard`, or returns
+    /// `None` if the object has been destructed.
+    ///
+    /// This is [`load`](AtomicWeak::load) followed by [`WeakSnapshot::upgrade`], which does not
+    /// touch the strong reference count of the object. A null pointer is upgraded to a null
+    /// snapshot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn upgrade_in<'g>(&self, order: Ordering, guard: &'g Guard) -> Option<Snapshot<'g, T>> {
+        self.load(order, guard).upgrade()
+    }
+
     /// Stores a [`Weak`] pointer into this `AtomicWeak`.
     ///
     /// This method takes an [`Ordering`] argument which describes the memory ordering of
@@ -292,6 +307,10 @@ impl<T> Default for AtomicWeak<T> {
 /// The pointer must be properly aligned. Since it is aligned, a tag can be stored into the unused
 /// least significant bits of the address. For example, the tag for a pointer to a sized type `T`
 /// should be less than `(1 << align_of::<T>().trailing_zeros())`.
+///
+/// It has the layout of a pointer, and its null pointer is a valid value. See the
+/// [layout of `Rc`](crate::Rc#layout).
+#[repr(transparent)]
 pub struct Weak<T> {
     ptr: Raw<T>,
 }
@@ -389,6 +408,39 @@ impl<T: RcObject> Weak<T> {
         }
         None
     }
+
+    /// Upgrades the `Weak` pointer to a [`Snapshot`] protected by `guard`, or returns `None` if
+    /// the object has been destructed.
+    ///
+    /// Unlike [`upgrade`](Weak::upgrade), this does not increment and later decrement the strong
+    /// reference count of the object, which suits hot paths that follow a weak reference and
+    /// drop the result within the critical section. A null pointer is upgraded to a null
+    /// snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use circ::{cs, Rc, RcObject};
+    ///
+    /// struct Entry(usize);
+    ///
+    /// unsafe impl RcObject for Entry {
+    ///     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    /// }
+    ///
+    /// # let context = dyntls_host::get();
+    /// # unsafe { context.initialize() };
+    /// let rc = Rc::new(Entry(42));
+    /// let weak = rc.downgrade();
+    ///
+    /// let guard = &cs();
+    /// let snapshot = weak.upgrade_in(guard).unwrap();
+    /// assert_eq!(snapshot.as_ref().unwrap().0, 42);
+    /// ```
+    #[inline]
+    pub fn upgrade_in<'g>(&self, guard: &'g Guard) -> Option<Snapshot<'g, T>> {
+        self.snapshot(guard).upgrade()
+    }
 }
 
 impl<T> Drop for Weak<T> {
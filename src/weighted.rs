@@ -0,0 +1,168 @@
+//! Weighted reference counting for handles that are cloned into many tasks.
+//!
+//! Each [`WeightedRc`] carries a weight, and the shared counter holds the sum of the weights of
+//! all the live handles. Cloning a handle splits its weight in half without touching shared
+//! memory, so a fork-join workload that clones a handle into many tasks performs no atomic
+//! operations on the way down. Only dropping a handle subtracts its weight from the shared
+//! counter, and a clone of a handle whose weight is exhausted adds a fresh weight to it.
+//!
+//! # Examples
+//!
+//! ```
+//! use circ::{Rc, RcObject};
+//!
+//! struct Input(Vec<u64>);
+//!
+//! unsafe impl RcObject for Input {
+//!     fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+//! }
+//!
+//! fn sum(input: circ::weighted::WeightedRc<Input>, range: std::ops::Range<usize>) -> u64 {
+//!     if range.len() <= 4 {
+//!         return input.as_ref().unwrap().0[range].iter().sum();
+//!     }
+//!     let mid = range.start + range.len() / 2;
+//!     // Cloning splits the weight of `input` locally.
+//!     let left = input.clone();
+//!     sum(left, range.start..mid) + sum(input, mid..range.end)
+//! }
+//!
+//! # let context = dyntls_host::get();
+//! # unsafe { context.initialize() };
+//! let input = Rc::new_weighted(Input((1..=64).collect()));
+//! assert_eq!(sum(input, 0..64), 64 * 65 / 2);
+//! ```
+
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter, Pointer};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use crate::{Rc, RcObject};
+
+/// The weight given to a new handle, and added to the shared counter when a handle whose weight
+/// is exhausted is cloned.
+const INITIAL_WEIGHT: usize = 1 << 16;
+
+/// The shared counter of the `WeightedRc`s of an object and the strong reference they share.
+struct Weights<T: RcObject> {
+    /// The sum of the weights of the live `WeightedRc`s.
+    total: AtomicUsize,
+    /// The strong reference that the handles share.
+    rc: Rc<T>,
+}
+
+/// A reference-counted pointer whose clones split a weight instead of updating a shared counter.
+///
+/// A `WeightedRc` can be sent to other threads, but not shared between them, as its weight is
+/// updated by [`Clone::clone`]. See the [module documentation](self) for details.
+pub struct WeightedRc<T: RcObject> {
+    weights: NonNull<Weights<T>>,
+    /// The weight of this handle, which is at least one.
+    weight: Cell<usize>,
+    _marker: PhantomData<Weights<T>>,
+}
+
+unsafe impl<T: RcObject + Send + Sync> Send for WeightedRc<T> {}
+
+impl<T: RcObject> WeightedRc<T> {
+    /// Constructs a new `WeightedRc` that takes over the strong reference of `rc`.
+    pub fn from_rc(rc: Rc<T>) -> Self {
+        let weights = Box::new(Weights {
+            total: AtomicUsize::new(INITIAL_WEIGHT),
+            rc,
+        });
+        Self {
+            weights: NonNull::from(Box::leak(weights)),
+            weight: Cell::new(INITIAL_WEIGHT),
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn weights(&self) -> &Weights<T> {
+        // SAFETY: The shared counter is alive while any handle is, as it includes the weight of
+        // each one.
+        unsafe { self.weights.as_ref() }
+    }
+
+    /// Returns the weight of this handle.
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.weight.get()
+    }
+
+    /// Returns the strong reference shared by the clones of this `WeightedRc`.
+    #[inline]
+    pub fn as_rc(&self) -> &Rc<T> {
+        &self.weights().rc
+    }
+
+    /// Returns a new [`Rc`] to the object.
+    ///
+    /// This increments the shared reference count of the object.
+    #[inline]
+    pub fn to_rc(&self) -> Rc<T> {
+        self.weights().rc.clone()
+    }
+
+    /// Dereferences the pointer and returns an immutable reference if it is not null.
+    #[inline]
+    pub fn as_ref(&self) -> Option<&T> {
+        self.weights().rc.as_ref()
+    }
+
+    /// Returns `true` if the two pointers point to the same object.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.weights().rc.ptr_eq(&other.weights().rc)
+    }
+}
+
+impl<T: RcObject> Clone for WeightedRc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        let weight = self.weight.get();
+        let split = if weight > 1 {
+            self.weight.set(weight - weight / 2);
+            weight / 2
+        } else {
+            // The weight is exhausted. `self` keeps the counter alive meanwhile.
+            self.weights()
+                .total
+                .fetch_add(INITIAL_WEIGHT, Ordering::Relaxed);
+            INITIAL_WEIGHT
+        };
+        Self {
+            weights: self.weights,
+            weight: Cell::new(split),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RcObject> Drop for WeightedRc<T> {
+    #[inline]
+    fn drop(&mut self) {
+        let weight = self.weight.get();
+        if self.weights().total.fetch_sub(weight, Ordering::Release) == weight {
+            fence(Ordering::Acquire);
+            // SAFETY: The weight of this handle was the last one, and the shared counter was
+            // leaked from a `Box` in `from_rc`. Dropping it releases its strong reference.
+            drop(unsafe { Box::from_raw(self.weights.as_ptr()) });
+        }
+    }
+}
+
+impl<T: RcObject + Debug> Debug for WeightedRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.weights().rc, f)
+    }
+}
+
+impl<T: RcObject> Pointer for WeightedRc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Pointer::fmt(&self.weights().rc, f)
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+use circ::affinity::{self, ThreadBound};
+use circ::{Rc, RcObject};
+
+/// Records the thread it is dropped on.
+struct Payload {
+    dropped_on: Arc<Mutex<Option<ThreadId>>>,
+}
+
+impl Drop for Payload {
+    fn drop(&mut self) {
+        *self.dropped_on.lock().unwrap() = Some(thread::current().id());
+    }
+}
+
+struct Node {
+    payload: ThreadBound<Payload>,
+    destructed: Arc<Mutex<bool>>,
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        *self.destructed.lock().unwrap() = true;
+    }
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn routes_drop_to_owner() {
+    common::init();
+
+    let dropped_on = Arc::new(Mutex::new(None));
+    let destructed = Arc::new(Mutex::new(false));
+    let node = Rc::new(Node {
+        payload: ThreadBound::new(Payload {
+            dropped_on: dropped_on.clone(),
+        }),
+        destructed: destructed.clone(),
+    });
+    assert!(node.as_ref().unwrap().payload.is_owner());
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            common::init();
+            assert!(node.as_ref().unwrap().payload.get().is_none());
+            drop(node);
+            while !*destructed.lock().unwrap() {
+                circ::default_collector().collect_all(Duration::from_millis(100));
+            }
+        });
+    });
+
+    // The object has been destructed on the other thread, but not its payload.
+    assert_eq!(*dropped_on.lock().unwrap(), None);
+    assert_eq!(affinity::drain(), 1);
+    assert_eq!(*dropped_on.lock().unwrap(), Some(thread::current().id()));
+    assert_eq!(affinity::drain(), 0);
+}
+
+#[test]
+fn drops_in_place_on_owner() {
+    common::init();
+
+    let dropped_on = Arc::new(Mutex::new(None));
+    let bound = ThreadBound::new(Payload {
+        dropped_on: dropped_on.clone(),
+    });
+    drop(bound);
+    assert_eq!(*dropped_on.lock().unwrap(), Some(thread::current().id()));
+
+    let bound = ThreadBound::new(7);
+    assert_eq!(bound.into_inner().ok(), Some(7));
+}
+
+#[test]
+fn drains_on_owner_exit() {
+    common::init();
+
+    let dropped_on = Arc::new(Mutex::new(None));
+    let (tx, rx) = std::sync::mpsc::channel();
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let owner = thread::spawn({
+        let dropped_on = dropped_on.clone();
+        move || {
+            common::init();
+            tx.send(ThreadBound::new(Payload { dropped_on })).unwrap();
+            done_rx.recv().unwrap();
+            thread::current().id()
+        }
+    });
+
+    drop(rx.recv().unwrap());
+    assert_eq!(*dropped_on.lock().unwrap(), None);
+    done_tx.send(()).unwrap();
+    let owner = owner.join().unwrap();
+    assert_eq!(*dropped_on.lock().unwrap(), Some(owner));
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use circ::{cs, ds::AppendLog, Rc, RcObject};
+use crossbeam_utils::thread;
+
+struct Entry(usize);
+
+unsafe impl RcObject for Entry {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn push_get() {
+    common::init();
+
+    let log = AppendLog::new();
+    let guard = &cs();
+    assert!(log.is_empty());
+    assert!(log.get(0, guard).is_none());
+
+    // Spans several levels of the tree.
+    for i in 0..10_000 {
+        assert_eq!(log.push(Rc::new(Entry(i)), guard), i);
+    }
+    assert_eq!(log.len(), 10_000);
+    for i in [0, 63, 64, 4095, 4096, 9999] {
+        assert_eq!(log.get(i, guard).unwrap().as_ref().unwrap().0, i);
+    }
+    assert!(log.get(10_000, guard).is_none());
+    assert!(log.get(usize::MAX, guard).is_none());
+
+    let entries: Vec<_> = log
+        .iter(guard)
+        .map(|(i, e)| (i, e.as_ref().unwrap().0))
+        .collect();
+    assert_eq!(entries.len(), 10_000);
+    assert!(entries.iter().all(|(i, e)| i == e));
+}
+
+#[test]
+fn trim() {
+    common::init();
+
+    let log = AppendLog::new();
+    let guard = &cs();
+    for i in 0..5000 {
+        log.push(Rc::new(Entry(i)), guard);
+    }
+
+    log.trim(100, guard);
+    assert_eq!(log.start(), 100);
+    assert!(log.get(99, guard).is_none());
+    assert_eq!(log.get(100, guard).unwrap().as_ref().unwrap().0, 100);
+    assert_eq!(log.iter(guard).next().unwrap().0, 100);
+
+    // Trimming backwards does nothing.
+    log.trim(50, guard);
+    assert_eq!(log.start(), 100);
+
+    log.trim(4097, guard);
+    assert_eq!(log.iter(guard).count(), 5000 - 4097);
+    assert_eq!(log.push(Rc::new(Entry(5000)), guard), 5000);
+    assert_eq!(log.get(5000, guard).unwrap().as_ref().unwrap().0, 5000);
+
+    // The position is clamped to the length.
+    log.trim(usize::MAX, guard);
+    assert_eq!(log.start(), 5001);
+    assert_eq!(log.iter(guard).count(), 0);
+}
+
+#[test]
+fn concurrent_push() {
+    const THREADS: usize = 8;
+    const ELEMENTS_PER_THREAD: usize = 2000;
+
+    common::init();
+
+    let log = &AppendLog::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let guard = &cs();
+                    let value = t * ELEMENTS_PER_THREAD + i;
+                    let index = log.push(Rc::new(Entry(value)), guard);
+                    // The entry may have been trimmed already by thread 0.
+                    if let Some(entry) = log.get(index, guard) {
+                        assert_eq!(entry.as_ref().unwrap().0, value);
+                    }
+                    if t == 0 && i % 100 == 0 {
+                        log.trim(index / 2, guard);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    assert_eq!(log.len(), THREADS * ELEMENTS_PER_THREAD);
+    let start = log.start();
+    let mut values: Vec<_> = log
+        .iter(guard)
+        .map(|(_, e)| e.as_ref().unwrap().0)
+        .collect();
+    assert_eq!(values.len(), THREADS * ELEMENTS_PER_THREAD - start);
+    values.sort();
+    values.dedup();
+    assert_eq!(values.len(), THREADS * ELEMENTS_PER_THREAD - start);
+}
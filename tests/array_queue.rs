@@ -0,0 +1,98 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{ds::ArrayQueue, Rc, RcObject};
+use crossbeam_utils::thread;
+
+struct Item(usize);
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn push_pop() {
+    common::init();
+
+    let queue = ArrayQueue::new(3);
+    assert!(queue.is_empty());
+    for i in 0..3 {
+        assert!(queue.try_push(Rc::new(Item(i))).is_ok());
+    }
+    assert!(queue.is_full());
+    let rejected = queue.try_push(Rc::new(Item(3))).unwrap_err();
+    assert_eq!(rejected.as_ref().unwrap().0, 3);
+
+    for i in 0..3 {
+        assert_eq!(queue.try_pop().unwrap().as_ref().unwrap().0, i);
+    }
+    assert!(queue.try_pop().is_none());
+
+    // Wrap around the ring buffer a few times.
+    for i in 0..10 {
+        assert!(queue.try_push(Rc::new(Item(i))).is_ok());
+        assert_eq!(queue.try_pop().unwrap().as_ref().unwrap().0, i);
+    }
+}
+
+#[test]
+fn single_slot() {
+    common::init();
+
+    let queue = ArrayQueue::new(1);
+    for i in 0..4 {
+        assert!(queue.try_push(Rc::new(Item(i))).is_ok());
+        assert!(queue.is_full());
+        // A full slot must not look empty to the producer of the next lap.
+        let rejected = queue.try_push(Rc::new(Item(i + 1))).unwrap_err();
+        assert_eq!(rejected.as_ref().unwrap().0, i + 1);
+        assert_eq!(queue.len(), 1);
+
+        assert_eq!(queue.try_pop().unwrap().as_ref().unwrap().0, i);
+        assert!(queue.try_pop().is_none());
+        assert!(queue.is_empty());
+    }
+}
+
+#[test]
+fn mpmc() {
+    const THREADS: usize = 8;
+    const ELEMENTS_PER_THREAD: usize = 10000;
+
+    common::init();
+
+    let queue = &ArrayQueue::new(64);
+    let found = &(0..THREADS * ELEMENTS_PER_THREAD)
+        .map(|_| AtomicUsize::new(0))
+        .collect::<Vec<_>>();
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let mut item = Rc::new(Item(t * ELEMENTS_PER_THREAD + i));
+                    while let Err(rejected) = queue.try_push(item) {
+                        item = rejected;
+                    }
+                }
+            });
+            s.spawn(move |_| {
+                common::init();
+                let mut popped = 0;
+                while popped < ELEMENTS_PER_THREAD {
+                    if let Some(item) = queue.try_pop() {
+                        let index = item.as_ref().unwrap().0;
+                        assert_eq!(found[index].fetch_add(1, Ordering::Relaxed), 0);
+                        popped += 1;
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    assert!(queue.is_empty());
+    assert!(found.iter().all(|v| v.load(Ordering::Relaxed) == 1));
+}
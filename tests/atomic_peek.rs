@@ -0,0 +1,35 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc, Rc, RcObject};
+
+struct Node(usize);
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn peek_without_guard() {
+    common::init();
+
+    let slot = AtomicRc::<Node>::null();
+    assert!(slot.is_null(Ordering::Relaxed));
+    assert!(slot.peek_raw(Ordering::Relaxed).is_null());
+
+    let node = Rc::new(Node(7));
+    slot.store(node.clone(), Ordering::Release, &cs());
+    assert!(!slot.is_null(Ordering::Acquire));
+    let ptr = slot.peek_raw(Ordering::Acquire);
+    assert_eq!(ptr, node.as_ref().unwrap() as *const Node);
+    assert_eq!(unsafe { (*ptr).0 }, 7);
+
+    // The tag is ignored by both.
+    slot.store(node.clone().with_tag(1), Ordering::Release, &cs());
+    assert_eq!(slot.peek_raw(Ordering::Acquire), ptr);
+
+    let tagged = AtomicRc::<Node>::null_with_tag(1);
+    assert!(tagged.is_null(Ordering::Relaxed));
+    assert!(tagged.peek_raw(Ordering::Relaxed).is_null());
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+#[derive(Clone)]
+struct Set(Vec<usize>);
+
+unsafe impl RcObject for Set {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn concurrent_copy_updates() {
+    common::init();
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 100;
+
+    let set = AtomicRc::new(Set(Vec::new()));
+    scope(|s| {
+        for t in 0..THREADS {
+            let set = &set;
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ITERS {
+                    let guard = &cs();
+                    let value = t * ITERS + i;
+                    let updated = set.rcu(guard, |old| {
+                        let mut new = old.clone();
+                        new.0.push(value);
+                        new
+                    });
+                    assert!(updated.as_ref().unwrap().0.contains(&value));
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    let mut values = set
+        .load(Ordering::Acquire, guard)
+        .as_ref()
+        .unwrap()
+        .0
+        .clone();
+    values.sort_unstable();
+    assert_eq!(values, (0..THREADS * ITERS).collect::<Vec<_>>());
+}
+
+#[test]
+#[should_panic(expected = "on a null pointer")]
+fn null() {
+    common::init();
+
+    let set = AtomicRc::<Set>::null();
+    set.rcu(&cs(), |old| old.clone());
+}
+
+#[test]
+fn abort() {
+    common::init();
+
+    let set = AtomicRc::new(Set(vec![1]));
+    let guard = &cs();
+    let before = set.load(Ordering::Acquire, guard);
+
+    let result = set.try_rcu(guard, |old| {
+        if old.0.contains(&1) {
+            Err(1)
+        } else {
+            Ok(old.clone())
+        }
+    });
+    assert_eq!(result.err(), Some(1));
+    assert!(set.load(Ordering::Acquire, guard).ptr_eq(before));
+
+    let updated = set
+        .try_rcu(guard, |old| {
+            Ok::<_, ()>(Set(old.0.iter().map(|v| v + 1).collect()))
+        })
+        .unwrap();
+    assert_eq!(updated.as_ref().unwrap().0, [2]);
+}
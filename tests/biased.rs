@@ -0,0 +1,24 @@
+mod common;
+
+use circ::biased::BiasedRc;
+use common::{flush_until_dropped, Counted};
+
+#[test]
+fn owner_and_shared_references() {
+    common::init();
+
+    let local = BiasedRc::new(Counted(7));
+    let clones: Vec<_> = (0..100).map(|_| local.clone()).collect();
+    assert_eq!(local.local_count(), 101);
+    assert!(clones.iter().all(|clone| clone.ptr_eq(&local)));
+    drop(clones);
+    assert_eq!(local.local_count(), 1);
+
+    // The object outlives the owner thread's references while a shared one remains.
+    let shared = local.to_rc();
+    drop(local);
+    assert_eq!(shared.as_ref().unwrap().0, 7);
+
+    drop(shared);
+    flush_until_dropped(1);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use circ::{cs, ds::BstMap};
+use crossbeam_utils::thread;
+
+#[test]
+fn insert_remove() {
+    common::init();
+
+    let map = BstMap::new();
+    let guard = &cs();
+    assert!(map.is_empty(guard));
+    for key in [5, 3, 8, 1, 4, 7, 9] {
+        assert!(map.insert(key, key * 10, guard));
+    }
+    assert!(!map.insert(4, 0, guard));
+    assert!(!map.is_empty(guard));
+
+    assert_eq!(map.get(&4, guard), Some(&40));
+    assert_eq!(map.get(&6, guard), None);
+    assert!(map.contains_key(&9, guard));
+
+    assert_eq!(map.remove(&5, guard), Some(&50));
+    assert_eq!(map.remove(&5, guard), None);
+    assert_eq!(map.remove(&1, guard), Some(&10));
+    let entries: Vec<_> = map.iter(guard).map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(entries, [(3, 30), (4, 40), (7, 70), (8, 80), (9, 90)]);
+
+    for key in [3, 4, 7, 8, 9] {
+        assert!(map.remove(&key, guard).is_some());
+    }
+    assert!(map.is_empty(guard));
+    assert!(map.insert(1, 1, guard));
+    assert_eq!(map.get(&1, guard), Some(&1));
+}
+
+#[test]
+fn range() {
+    common::init();
+
+    let map = BstMap::new();
+    let guard = &cs();
+    // Insert in a scrambled order so that the tree is not a list.
+    for i in 0..100u32 {
+        let key = (i * 37) % 100;
+        map.insert(key, (), guard);
+    }
+    let keys = |range: std::ops::Range<u32>| -> Vec<u32> {
+        map.range(range, guard).map(|(k, _)| *k).collect()
+    };
+    assert_eq!(keys(10..15), [10, 11, 12, 13, 14]);
+    assert_eq!(keys(98..200), [98, 99]);
+    assert!(keys(50..50).is_empty());
+    assert_eq!(map.range(..=2, guard).count(), 3);
+    assert_eq!(map.range(97.., guard).count(), 3);
+    assert_eq!(map.iter(guard).count(), 100);
+}
+
+#[test]
+fn concurrent_insert_remove() {
+    const THREADS: u32 = 8;
+    const ELEMENTS_PER_THREAD: u32 = 1000;
+
+    common::init();
+
+    // Scrambles the keys so that the tree stays shallow.
+    let key_of = |i: u32, t: u32| (i * THREADS + t).wrapping_mul(0x9e37_79b9);
+    let map = &BstMap::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let key = key_of(i, t);
+                    let guard = &cs();
+                    assert!(map.insert(key, i, guard));
+                    if i % 2 == 0 {
+                        assert_eq!(map.remove(&key, guard), Some(&i));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    let keys: Vec<_> = map.iter(guard).map(|(k, _)| *k).collect();
+    let mut expected: Vec<_> = (0..THREADS)
+        .flat_map(|t| {
+            (0..ELEMENTS_PER_THREAD)
+                .filter(|i| i % 2 == 1)
+                .map(move |i| key_of(i, t))
+        })
+        .collect();
+    expected.sort();
+    assert_eq!(keys, expected);
+}
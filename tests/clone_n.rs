@@ -0,0 +1,28 @@
+mod common;
+
+use circ::{cs, Rc};
+use common::{drops, flush_until_dropped, Counted};
+
+#[test]
+fn fan_out() {
+    common::init();
+
+    let job = Rc::new(Counted(0));
+    let handles = job.clone_n(16);
+    let [first, second] = job.clone_many::<2>();
+    assert!(Rc::<Counted>::null().clone_n(3).iter().all(Rc::is_null));
+    assert!(job.clone_n(0).is_empty());
+    drop(job);
+
+    // The object survives until the last of the clones is dropped.
+    for handle in handles {
+        drop(handle);
+        cs().flush();
+    }
+    drop(first);
+    cs().flush();
+    assert_eq!(drops(), 0);
+
+    drop(second);
+    flush_until_dropped(1);
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the integration tests.
+//!
+//! Each test file is a separate binary with its own copy of this module, so the drop counter is
+//! only shared by the tests of one file.
+
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, Rc, RcObject};
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Initializes the `dyntls` context on the current thread, which every thread must do before it
+/// uses the crate.
+pub fn init() {
+    let context = dyntls_host::get();
+    unsafe { context.initialize() };
+}
+
+/// An object without edges whose drops are counted by [`drops`].
+pub struct Counted(pub usize);
+
+unsafe impl RcObject for Counted {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of [`Counted`]s dropped so far.
+pub fn drops() -> usize {
+    DROPS.load(Ordering::Relaxed)
+}
+
+/// Flushes the garbage of the current thread until at least `count` [`Counted`]s have been
+/// dropped.
+pub fn flush_until_dropped(count: usize) {
+    while drops() < count {
+        cs().flush();
+    }
+}
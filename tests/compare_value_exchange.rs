@@ -0,0 +1,86 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+#[derive(Debug)]
+struct Counter(usize);
+
+unsafe impl RcObject for Counter {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn rejects_null_and_mismatch() {
+    common::init();
+
+    let guard = &cs();
+    let null = AtomicRc::<Counter>::null();
+    let err = null
+        .compare_value_exchange(
+            |_| true,
+            Rc::new(Counter(1)),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            guard,
+        )
+        .unwrap_err();
+    assert!(err.current.is_null());
+    assert_eq!(err.desired.as_ref().unwrap().0, 1);
+
+    let counter = AtomicRc::new(Counter(0));
+    let err = counter
+        .compare_value_exchange(
+            |c| c.0 == 1,
+            Rc::new(Counter(2)),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            guard,
+        )
+        .unwrap_err();
+    assert_eq!(err.current.as_ref().unwrap().0, 0);
+}
+
+#[test]
+fn concurrent_increments() {
+    common::init();
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 1000;
+
+    let counter = AtomicRc::new(Counter(0));
+    scope(|s| {
+        for _ in 0..THREADS {
+            let counter = &counter;
+            s.spawn(move |_| {
+                common::init();
+                for _ in 0..ITERS {
+                    let guard = &cs();
+                    loop {
+                        let seen = counter.load(Ordering::Acquire, guard).as_ref().unwrap().0;
+                        let next = Rc::new(Counter(seen + 1));
+                        if counter
+                            .compare_value_exchange(
+                                |c| c.0 == seen,
+                                next,
+                                Ordering::AcqRel,
+                                Ordering::Acquire,
+                                guard,
+                            )
+                            .is_ok()
+                        {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    let total = counter.load(Ordering::Acquire, guard).as_ref().unwrap().0;
+    assert_eq!(total, THREADS * ITERS);
+}
@@ -0,0 +1,115 @@
+//! A Treiber stack written against crossbeam-epoch, ported by swapping the import.
+
+mod common;
+
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::Ordering;
+
+use circ::compat::epoch::{self, Atomic, Owned};
+use crossbeam_utils::thread;
+
+struct TreiberStack<T> {
+    head: Atomic<Node<T>>,
+}
+
+struct Node<T> {
+    data: ManuallyDrop<T>,
+    next: Atomic<Node<T>>,
+}
+
+impl<T> TreiberStack<T> {
+    fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+
+    fn push(&self, t: T) {
+        let mut n = Owned::new(Node {
+            data: ManuallyDrop::new(t),
+            next: Atomic::null(),
+        });
+        let guard = epoch::pin();
+        loop {
+            let head = self.head.load(Ordering::Relaxed, &guard);
+            n.next.store(head, Ordering::Relaxed);
+            match self
+                .head
+                .compare_exchange(head, n, Ordering::Release, Ordering::Relaxed, &guard)
+            {
+                Ok(_) => break,
+                Err(e) => n = e.new,
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+        loop {
+            let head = self.head.load(Ordering::Acquire, &guard);
+            match unsafe { head.as_ref() } {
+                Some(h) => {
+                    let next = h.next.load(Ordering::Relaxed, &guard);
+                    if self
+                        .head
+                        .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, &guard)
+                        .is_ok()
+                    {
+                        unsafe {
+                            guard.defer_destroy(head);
+                            return Some(ManuallyDrop::into_inner(ptr::read(&(*h).data)));
+                        }
+                    }
+                }
+                None => return None,
+            }
+        }
+    }
+}
+
+impl<T> Drop for TreiberStack<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[test]
+fn treiber_stack() {
+    const THREADS: usize = 8;
+    const ELEMENTS_PER_THREAD: usize = 10_000;
+
+    common::init();
+
+    let stack = &TreiberStack::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    stack.push(Box::new(i * THREADS + t));
+                    assert!(stack.pop().is_some());
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(stack.pop().is_none());
+}
+
+#[test]
+fn tags() {
+    common::init();
+
+    let atomic = Atomic::new(0u64);
+    let guard = &epoch::pin();
+    let prev = atomic.fetch_or(1, Ordering::Relaxed, guard);
+    assert_eq!(prev.tag(), 0);
+    let current = atomic.load(Ordering::Relaxed, guard);
+    assert_eq!(current.tag(), 1);
+    assert_eq!(current.as_raw(), prev.as_raw());
+    assert_eq!(unsafe { *current.deref() }, 0);
+    atomic.fetch_and(0, Ordering::Relaxed, guard);
+    assert_eq!(atomic.load(Ordering::Relaxed, guard).tag(), 0);
+    unsafe { drop(atomic.into_owned()) };
+}
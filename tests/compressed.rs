@@ -0,0 +1,117 @@
+mod common;
+
+use std::mem::{size_of, take};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::compressed::{Arena, ArenaObject, AtomicCompactRc};
+use circ::cs;
+use crossbeam_utils::thread::scope;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Node {
+    value: usize,
+    next: AtomicCompactRc<Node>,
+}
+
+unsafe impl ArenaObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<AtomicCompactRc<Self>>) {
+        out.push(take(&mut self.next));
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn compact_stack() {
+    common::init();
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 1000;
+
+    assert_eq!(size_of::<AtomicCompactRc<Node>>(), 4);
+
+    let arena = Arena::new();
+    let head = AtomicCompactRc::null();
+    scope(|s| {
+        for t in 0..THREADS {
+            let (arena, head) = (&arena, &head);
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ITERS {
+                    let guard = &cs();
+                    // Push a node, then pop one. All the pointers only store objects of
+                    // `arena`.
+                    let mut node = arena.alloc(Node {
+                        value: t * ITERS + i,
+                        next: AtomicCompactRc::null(),
+                    });
+                    loop {
+                        let curr = unsafe { head.load_rc(Ordering::Acquire, arena, guard) };
+                        let snapshot = curr.snapshot(guard);
+                        let next = &node.as_ref().unwrap().next;
+                        unsafe { next.store(curr, Ordering::Relaxed, guard) };
+                        match unsafe {
+                            head.compare_exchange(
+                                snapshot,
+                                node,
+                                Ordering::AcqRel,
+                                Ordering::Acquire,
+                            )
+                        } {
+                            Ok(old) => {
+                                drop(old);
+                                break;
+                            }
+                            Err(e) => node = e.desired,
+                        }
+                    }
+                    loop {
+                        let curr = unsafe { head.load(Ordering::Acquire, arena, guard) };
+                        let Some(obj) = curr.as_ref() else { break };
+                        assert!(obj.value < THREADS * ITERS);
+                        let next = unsafe { obj.next.load_rc(Ordering::Acquire, arena, guard) };
+                        if unsafe {
+                            head.compare_exchange(curr, next, Ordering::AcqRel, Ordering::Acquire)
+                        }
+                        .is_ok()
+                        {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    drop(unsafe { head.into_rc(&arena) });
+    while DROPS.load(Ordering::Relaxed) < THREADS * ITERS {
+        cs().flush();
+    }
+}
+
+#[test]
+fn recycle_slots() {
+    common::init();
+
+    struct Leaf;
+
+    unsafe impl ArenaObject for Leaf {
+        fn pop_edges(&mut self, _: &mut Vec<AtomicCompactRc<Self>>) {}
+    }
+
+    const ITERS: usize = 1000;
+
+    let arena = Arena::new();
+    for _ in 0..ITERS {
+        drop(arena.alloc(Leaf));
+        cs().flush();
+    }
+    // The slots of destroyed objects are reused after a grace period.
+    assert!(arena.capacity() < ITERS);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use circ::{cs, AtomicCowRc, CowRc};
+use crossbeam_utils::thread::scope;
+
+fn ptr(cow: &CowRc<Vec<i32>>) -> *const Vec<i32> {
+    &**cow
+}
+
+#[test]
+fn make_mut_clones_only_when_shared() {
+    common::init();
+
+    let mut a = CowRc::new(vec![1]);
+    let a_ptr = ptr(&a);
+    a.make_mut().push(2);
+    assert_eq!(ptr(&a), a_ptr);
+
+    let b = a.clone();
+    a.make_mut().push(3);
+    assert_ne!(ptr(&a), a_ptr);
+    assert_eq!(*a, [1, 2, 3]);
+    assert_eq!(*b, [1, 2]);
+
+    // A value that has been published may be read without a reference.
+    let atomic = AtomicCowRc::from(a.clone());
+    drop(atomic);
+    let a_ptr = ptr(&a);
+    a.make_mut().push(4);
+    assert_ne!(ptr(&a), a_ptr);
+}
+
+#[test]
+fn concurrent_updates() {
+    common::init();
+
+    const THREADS: usize = 8;
+    const ITERS: usize = 500;
+
+    let counter = AtomicCowRc::new(0usize);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                common::init();
+                for _ in 0..ITERS {
+                    let old = counter.update(|count| *count += 1);
+                    assert!(*old < THREADS * ITERS);
+                    let guard = &cs();
+                    assert!(*counter.read(guard) > 0);
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(*counter.load(), THREADS * ITERS);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use circ::{cs, Collector, DeferredFuture};
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+#[test]
+fn spawned_after_grace_period() {
+    common::init();
+
+    let spawned = Arc::new(Mutex::new(Vec::<DeferredFuture>::new()));
+    let done = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder().deterministic(true).build();
+    collector.set_async_spawner({
+        let spawned = spawned.clone();
+        move |future| spawned.lock().unwrap().push(future)
+    });
+
+    collector.enter(|| {
+        let done = done.clone();
+        cs().defer_async(async move {
+            done.fetch_add(1, Ordering::Relaxed);
+        });
+    });
+    assert!(spawned.lock().unwrap().is_empty());
+
+    collector.collect_all(Duration::from_secs(10));
+    let mut futures = std::mem::take(&mut *spawned.lock().unwrap());
+    assert_eq!(futures.len(), 1);
+    // The future is only handed over, not run, by the reclaiming thread.
+    assert_eq!(done.load(Ordering::Relaxed), 0);
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    assert_eq!(futures[0].as_mut().poll(&mut cx), Poll::Ready(()));
+    assert_eq!(done.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn inline_without_spawner() {
+    common::init();
+
+    let done = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder().deterministic(true).build();
+    collector.enter(|| {
+        let done = done.clone();
+        cs().defer_async(async move {
+            done.fetch_add(1, Ordering::Relaxed);
+        });
+    });
+
+    collector.collect_all(Duration::from_secs(10));
+    assert_eq!(done.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::ds::{Steal, Worker};
+use crossbeam_utils::thread;
+
+#[test]
+fn push_pop_steal() {
+    common::init();
+
+    let w = Worker::new();
+    let s = w.stealer();
+    assert!(w.is_empty());
+    assert_eq!(s.steal(), Steal::Empty);
+
+    // Push enough tasks to grow the buffer a few times.
+    for i in 0..1000 {
+        w.push(i);
+    }
+    assert_eq!(w.len(), 1000);
+    assert_eq!(s.steal(), Steal::Success(0));
+    assert_eq!(w.pop(), Some(999));
+
+    let w2 = Worker::new();
+    assert_eq!(s.steal_batch_and_pop(&w2), Steal::Success(1));
+    assert!(!w2.is_empty());
+
+    while w.pop().is_some() {}
+    assert_eq!(s.steal(), Steal::Empty);
+}
+
+#[test]
+fn drops_remaining_tasks() {
+    common::init();
+
+    dyntls::lazy_static! {
+        static ref DROPS: AtomicUsize = AtomicUsize::new(0);
+    }
+
+    struct Task;
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let w = Worker::new();
+    for _ in 0..100 {
+        w.push(Task);
+    }
+    drop(w.pop());
+    drop(w);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 100);
+}
+
+#[test]
+fn stress() {
+    const THIEVES: usize = 8;
+    const COUNT: usize = 100_000;
+
+    common::init();
+
+    let w = Worker::new();
+    let s = w.stealer();
+    let hits = &AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..THIEVES {
+            let s = s.clone();
+            scope.spawn(move |_| {
+                common::init();
+                while hits.load(Ordering::SeqCst) < COUNT {
+                    if s.steal().is_success() {
+                        hits.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+
+        for i in 0..COUNT {
+            w.push(i);
+            if i % 4 == 0 && w.pop().is_some() {
+                hits.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        while hits.load(Ordering::SeqCst) < COUNT {
+            if w.pop().is_some() {
+                hits.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    })
+    .unwrap();
+
+    assert_eq!(hits.load(Ordering::SeqCst), COUNT);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use circ::{cs, Collector, Rc, RcObject, ReclamationSchedule};
+
+struct Item {
+    drops: Arc<AtomicUsize>,
+}
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Item {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Retires a few items per step and records how many have been reclaimed after each step.
+fn run(seed: u64) -> Vec<usize> {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder().deterministic(true).build();
+    let mut schedule = ReclamationSchedule::new(seed);
+    collector.enter(|| {
+        (0..100)
+            .map(|_| {
+                {
+                    let guard = &cs();
+                    for _ in 0..10 {
+                        let drops = drops.clone();
+                        Rc::new(Item { drops }).finalize(guard);
+                    }
+                }
+                schedule.step(&collector);
+                drops.load(Ordering::Relaxed)
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn replay() {
+    common::init();
+
+    let trace = run(7);
+    assert_eq!(trace, run(7));
+    assert!(*trace.last().unwrap() > 0);
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::cell::RefCell;
+
+use circ::{cs, AtomicRc};
+use common::{drops, flush_until_dropped, Counted};
+use crossbeam_utils::thread;
+
+/// A container whose destructor has no guard in scope.
+struct Container {
+    slots: Vec<AtomicRc<Counted>>,
+}
+
+impl Container {
+    fn new(len: usize) -> Self {
+        Self {
+            slots: (0..len).map(|_| AtomicRc::new(Counted(0))).collect(),
+        }
+    }
+}
+
+std::thread_local! {
+    static LOCAL: RefCell<Option<Container>> = const { RefCell::new(None) };
+}
+
+#[test]
+fn drop_in_any_context() {
+    common::init();
+
+    // Outside of any critical section.
+    drop(Container::new(8));
+    flush_until_dropped(8);
+
+    // In a critical section, with and without the guard.
+    {
+        let guard = &cs();
+        drop(Container::new(8));
+        for slot in Container::new(8).slots {
+            slot.finalize(guard);
+        }
+    }
+    flush_until_dropped(24);
+
+    // In the destructor of a thread-local variable, while the thread exits.
+    thread::scope(|s| {
+        s.spawn(|_| {
+            common::init();
+            LOCAL.with(|local| *local.borrow_mut() = Some(Container::new(8)));
+        });
+    })
+    .unwrap();
+    flush_until_dropped(32);
+    assert_eq!(drops(), 32);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use circ::{cs, Collector};
+
+static TASKS: Mutex<Vec<Box<dyn FnOnce() + Send>>> = Mutex::new(Vec::new());
+
+fn executor(task: Box<dyn FnOnce() + Send>) {
+    TASKS.lock().unwrap().push(task);
+}
+
+#[test]
+fn offloaded() {
+    common::init();
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder().deterministic(true).build();
+    collector.set_drop_executor(executor);
+    collector.enter(|| {
+        for _ in 0..10 {
+            let dropped = dropped.clone();
+            cs().defer(move || dropped.fetch_add(1, Ordering::Relaxed));
+        }
+    });
+
+    collector.collect_all(Duration::from_secs(10));
+    assert_eq!(dropped.load(Ordering::Relaxed), 0);
+    let tasks = std::mem::take(&mut *TASKS.lock().unwrap());
+    assert!(!tasks.is_empty());
+    for task in tasks {
+        task();
+    }
+    assert_eq!(dropped.load(Ordering::Relaxed), 10);
+
+    collector.remove_drop_executor();
+    collector.enter(|| {
+        let dropped = dropped.clone();
+        cs().defer(move || dropped.fetch_add(1, Ordering::Relaxed));
+    });
+    collector.collect_all(Duration::from_secs(10));
+    assert_eq!(dropped.load(Ordering::Relaxed), 11);
+    assert!(TASKS.lock().unwrap().is_empty());
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use circ::{cs, Rc};
+use common::{drops, flush_until_dropped, Counted};
+
+const NODES: usize = 1000;
+
+#[test]
+fn release_batch() {
+    common::init();
+
+    let nodes: Vec<_> = (0..NODES).map(|_| Rc::new(Counted(0))).collect();
+    // Half of the nodes stay alive through the clones.
+    let kept: Vec<_> = nodes.iter().step_by(2).cloned().collect();
+    {
+        let guard = &cs();
+        Rc::drop_many(nodes.into_iter().chain([Rc::null()]), guard);
+        guard.retire_all(Vec::<Rc<Counted>>::new());
+        guard.retire(Rc::<Counted>::null());
+    }
+    flush_until_dropped(NODES / 2);
+    assert_eq!(drops(), NODES / 2);
+
+    cs().retire_all(kept);
+    flush_until_dropped(NODES);
+}
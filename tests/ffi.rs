@@ -0,0 +1,55 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::ffi::*;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn drop_payload(data: *mut c_void) {
+    drop(Box::from_raw(data.cast::<usize>()));
+    DROPS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn payload(value: usize) -> *mut c_void {
+    Box::into_raw(Box::new(value)).cast()
+}
+
+#[test]
+fn c_api() {
+    common::init();
+
+    unsafe {
+        let first = circ_rc_new(payload(1), Some(drop_payload));
+        let clone = circ_rc_clone(&first);
+        assert_eq!(*circ_rc_get(&clone).cast::<usize>(), 1);
+        circ_rc_drop(clone);
+
+        let atomic = circ_atomic_new(first);
+        let guard = circ_cs_enter();
+        let current = circ_atomic_load(atomic, guard);
+        assert_eq!(*circ_snapshot_get(current).cast::<usize>(), 1);
+
+        let second = circ_rc_new(payload(2), Some(drop_payload));
+        assert!(circ_atomic_compare_exchange(atomic, current, second, guard));
+        let third = circ_rc_new(payload(3), Some(drop_payload));
+        assert!(!circ_atomic_compare_exchange(atomic, current, third, guard));
+
+        let current = circ_atomic_load(atomic, guard);
+        let counted = circ_snapshot_counted(current, guard);
+        assert_eq!(*circ_rc_get(&counted).cast::<usize>(), 2);
+        circ_cs_exit(guard);
+
+        let old = circ_atomic_swap(atomic, circ_rc_null());
+        circ_rc_drop(old);
+        circ_rc_drop(counted);
+        circ_atomic_free(atomic);
+    }
+
+    while DROPS.load(Ordering::Relaxed) < 3 {
+        circ::cs().flush();
+    }
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use circ::Collector;
+
+#[test]
+fn reclaims_own_bags() {
+    common::init();
+
+    let collector = Collector::builder()
+        .deterministic(true)
+        .fixed_bags(2)
+        .build();
+    let handle = collector.register();
+    let reclaimed = Arc::new(AtomicUsize::new(0));
+    let retire = |guard: &circ::Guard| {
+        let reclaimed = reclaimed.clone();
+        guard.try_defer(move || reclaimed.fetch_add(1, Ordering::Relaxed))
+    };
+
+    // The current bag and the two fixed bags fill up in a critical section.
+    let guard = handle.pin();
+    let mut capacity = 0;
+    while retire(&guard).is_ok() {
+        capacity += 1;
+    }
+    assert!(capacity > 0);
+    assert_eq!(capacity % 3, 0);
+    assert_eq!(guard.pending_deferred().count, capacity);
+    assert_eq!(collector.queued_garbage(), 0);
+
+    // Falling back to the global queue is counted.
+    guard.defer(|| ());
+    assert_eq!(collector.fixed_bag_overflows(), 1);
+    drop(guard);
+
+    // The sealed bags expire, and the participant reclaims them by itself once its current bag
+    // is full again.
+    let advancer = collector.register();
+    for _ in 0..3 {
+        assert!(collector.try_advance(&advancer.pin()).is_ok());
+    }
+    let guard = handle.pin();
+    let mut retired = 0;
+    while reclaimed.load(Ordering::Relaxed) == 0 {
+        assert!(retire(&guard).is_ok());
+        retired += 1;
+    }
+    assert_eq!(reclaimed.load(Ordering::Relaxed), capacity / 3 * 2);
+    drop(guard);
+
+    // The remaining bags are orphaned when the participant exits.
+    drop(handle);
+    collector.collect_all(Duration::from_secs(10));
+    assert_eq!(reclaimed.load(Ordering::Relaxed), capacity + retired);
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use circ::{cs, Collector, Rc};
+use common::{drops, Counted};
+
+const ITEMS: usize = 1000;
+
+#[test]
+fn reclaim_only_at_frame_boundaries() {
+    common::init();
+
+    let collector = Collector::builder().frame_based(true).build();
+    collector.enter(|| {
+        {
+            let guard = &cs();
+            for _ in 0..ITEMS {
+                Rc::new(Counted(0)).finalize(guard);
+            }
+            guard.flush();
+        }
+        // Pinning and flushing never reclaim anything implicitly.
+        for _ in 0..100 {
+            cs().flush();
+        }
+        assert_eq!(drops(), 0);
+
+        collector.advance_frame();
+        collector.advance_frame();
+        assert_eq!(drops(), 0);
+        collector.advance_frame();
+        assert_eq!(drops(), ITEMS);
+    });
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, AtomicRc, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+static INITS: AtomicUsize = AtomicUsize::new(0);
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Node {
+    id: usize,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn racing_initializers() {
+    common::init();
+
+    const THREADS: usize = 8;
+
+    let slot = AtomicRc::null();
+    let winners: Vec<usize> = scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let slot = &slot;
+                s.spawn(move |_| {
+                    common::init();
+                    let guard = &cs();
+                    let node = slot.get_or_init(guard, || {
+                        INITS.fetch_add(1, Ordering::Relaxed);
+                        Rc::new(Node { id: t })
+                    });
+                    node.as_ref().unwrap().id
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    })
+    .unwrap();
+
+    // Every thread sees the value of the single winner.
+    assert!(winners.iter().all(|&id| id == winners[0]));
+    let inits = INITS.load(Ordering::Relaxed);
+    assert!(inits >= 1);
+
+    drop(slot);
+    // The values of the losers and the winner are all reclaimed.
+    while DROPS.load(Ordering::Relaxed) < inits {
+        cs().flush();
+    }
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use circ::Collector;
+
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn resolves_when_idle() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let start = collector.global_epoch();
+    let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+    let mut cx = Context::from_waker(&waker);
+
+    // No participant holds the epoch back, so the future advances it on its own.
+    let mut grace_period = pin!(collector.grace_period());
+    assert_eq!(grace_period.as_mut().poll(&mut cx), Poll::Ready(()));
+    assert!(collector.global_epoch().wrapping_sub(start) >= 2);
+}
+
+#[test]
+fn woken_by_another_advance() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let handle = collector.register();
+    let guard = handle.pin();
+    let mut grace_period = pin!(collector.grace_period());
+    // The pinned participant lets the epoch advance only once.
+    assert_eq!(grace_period.as_mut().poll(&mut cx), Poll::Pending);
+    drop(guard);
+
+    let advancer = collector.register();
+    let woken = counter.0.load(Ordering::Relaxed);
+    assert!(collector.try_advance(&advancer.pin()).is_ok());
+    assert!(counter.0.load(Ordering::Relaxed) > woken);
+    while grace_period.as_mut().poll(&mut cx).is_pending() {
+        assert!(collector.try_advance(&advancer.pin()).is_ok());
+    }
+}
+
+#[test]
+fn dropped_before_completion() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let handle = collector.register();
+    let guard = handle.pin();
+    let mut grace_period = Box::pin(collector.grace_period());
+    assert_eq!(grace_period.as_mut().poll(&mut cx), Poll::Pending);
+    drop(grace_period);
+    drop(guard);
+
+    // The hook of the dropped future has been removed.
+    let woken = counter.0.load(Ordering::Relaxed);
+    assert!(collector.try_advance(&handle.pin()).is_ok());
+    assert_eq!(counter.0.load(Ordering::Relaxed), woken);
+}
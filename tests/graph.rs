@@ -0,0 +1,86 @@
+mod common;
+
+use std::collections::HashSet;
+
+use circ::{cs, graph::Graph};
+use crossbeam_utils::thread;
+
+#[test]
+fn add_remove() {
+    common::init();
+
+    let graph = Graph::new();
+    let guard = &cs();
+    let ids: Vec<_> = (0..4).map(|i| graph.add_node(i)).collect();
+    // A cycle 0 -> 1 -> 2 -> 3 -> 0.
+    for i in 0..4 {
+        assert!(graph.add_edge(ids[i], ids[(i + 1) % 4], i, guard));
+    }
+    assert!(graph.add_edge(ids[0], ids[1], 10, guard));
+    assert_eq!(graph.len(), 4);
+
+    // Walk around the cycle twice.
+    let mut node = graph.node(ids[0], guard).unwrap();
+    for step in 0..8 {
+        assert_eq!(*node.value(), step % 4);
+        let (next, _) = node.edges().next().unwrap();
+        node = next;
+    }
+    let (_, weight) = graph.node(ids[0], guard).unwrap().edges().next().unwrap();
+    assert_eq!(*weight, 10);
+
+    assert!(graph.remove_edge(ids[1], ids[2], guard));
+    assert!(!graph.remove_edge(ids[1], ids[2], guard));
+    assert_eq!(graph.node(ids[1], guard).unwrap().edges().count(), 0);
+
+    let removed = graph.node(ids[3], guard).unwrap();
+    assert!(graph.remove_node(ids[3], guard));
+    assert!(!graph.remove_node(ids[3], guard));
+    assert!(removed.is_removed());
+    assert!(graph.node(ids[3], guard).is_none());
+    assert!(!graph.add_edge(ids[2], ids[3], 0, guard));
+    assert_eq!(graph.node(ids[2], guard).unwrap().edges().count(), 0);
+    assert_eq!(graph.len(), 3);
+}
+
+#[test]
+fn concurrent_edges() {
+    const THREADS: usize = 8;
+    const NODES: usize = 64;
+
+    common::init();
+
+    let graph = &Graph::new();
+    let ids = &(0..NODES).map(|i| graph.add_node(i)).collect::<Vec<_>>();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..NODES {
+                    let guard = &cs();
+                    let (from, to) = (ids[i], ids[(i * 7 + t) % NODES]);
+                    assert!(graph.add_edge(from, to, t, guard));
+                    if t % 2 == 1 {
+                        assert!(graph.remove_edge(from, to, guard));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    for (i, &id) in ids.iter().enumerate() {
+        let targets: HashSet<_> = graph
+            .node(id, guard)
+            .unwrap()
+            .edges()
+            .map(|(node, _)| *node.value())
+            .collect();
+        let expected: HashSet<_> = (0..THREADS)
+            .filter(|t| t % 2 == 0)
+            .map(|t| (i * 7 + t) % NODES)
+            .collect();
+        assert_eq!(targets, expected);
+    }
+}
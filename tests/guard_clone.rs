@@ -0,0 +1,40 @@
+mod common;
+
+use circ::{unprotected_cs, Collector, Guard};
+
+struct Component {
+    guard: Guard,
+}
+
+#[test]
+fn clone_keeps_critical_section() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let handle = collector.register();
+
+    let guard = handle.pin();
+    let components = [
+        Component {
+            guard: guard.clone(),
+        },
+        Component {
+            guard: guard.clone(),
+        },
+    ];
+    drop(guard);
+
+    // The epoch cannot advance twice while a clone keeps the participant pinned.
+    let advancer = collector.register();
+    assert!(collector.try_advance(&advancer.pin()).is_ok());
+    for component in components {
+        assert!(collector.try_advance(&advancer.pin()).is_err());
+        drop(component.guard);
+    }
+    assert!(collector.try_advance(&advancer.pin()).is_ok());
+    assert!(collector.try_advance(&advancer.pin()).is_ok());
+
+    let unprotected = unsafe { unprotected_cs() };
+    let clone = unprotected.clone();
+    assert_eq!(clone.pending_deferred().count, 0);
+}
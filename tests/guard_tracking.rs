@@ -0,0 +1,37 @@
+#![cfg(feature = "guard-tracking")]
+
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use circ::Collector;
+
+#[test]
+fn reports_long_guards() {
+    common::init();
+
+    let collector = Collector::builder()
+        .long_guard_threshold(Duration::from_millis(20))
+        .build();
+    let handle = collector.register();
+
+    drop(handle.pin());
+    assert_eq!(collector.long_guards(), 0);
+
+    // Only the outermost guard is checked.
+    let outer = handle.pin();
+    let inner = outer.clone();
+    sleep(Duration::from_millis(40));
+    drop(inner);
+    assert_eq!(collector.long_guards(), 0);
+    drop(outer);
+    assert_eq!(collector.long_guards(), 1);
+
+    let mut guard = handle.pin();
+    sleep(Duration::from_millis(40));
+    guard.reactivate();
+    assert_eq!(collector.long_guards(), 2);
+    drop(guard);
+    assert_eq!(collector.long_guards(), 2);
+}
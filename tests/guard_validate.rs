@@ -0,0 +1,25 @@
+mod common;
+
+use circ::{unprotected_cs, Collector};
+
+#[test]
+fn stale_after_advance() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let handle = collector.register();
+
+    let advancer = collector.register();
+
+    let mut guard = handle.pin();
+    assert!(guard.validate());
+    assert!(collector.try_advance(&advancer.pin()).is_ok());
+    assert!(!guard.validate());
+
+    guard.reactivate();
+    assert!(guard.validate());
+    drop(guard);
+
+    let unprotected = unsafe { unprotected_cs() };
+    assert!(unprotected.validate());
+}
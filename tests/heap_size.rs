@@ -0,0 +1,115 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::ds::{PersistentMap, Stack, Trie};
+use circ::heap_size::{retained_size, HeapSize, Visited};
+use circ::{cs, AtomicRc, Rc, RcObject};
+
+struct Node {
+    payload: Vec<u8>,
+    next: AtomicRc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.next.take());
+    }
+}
+
+impl HeapSize for Node {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.payload.heap_size(visited) + self.next.heap_size(visited)
+    }
+}
+
+fn node(len: usize) -> Rc<Node> {
+    Rc::new(Node {
+        payload: Vec::with_capacity(len),
+        next: AtomicRc::null(),
+    })
+}
+
+#[test]
+fn shared_counted_once() {
+    common::init();
+
+    let shared = node(1000);
+    let alone = retained_size(&shared);
+    assert!(alone >= 1000);
+
+    let first = node(10);
+    let second = node(10);
+    first
+        .as_ref()
+        .unwrap()
+        .next
+        .store(shared.clone(), Ordering::Relaxed, &cs());
+    second
+        .as_ref()
+        .unwrap()
+        .next
+        .store(shared, Ordering::Relaxed, &cs());
+
+    let mut visited = Visited::new();
+    let total = first.heap_size(&mut visited) + second.heap_size(&mut visited);
+    assert_eq!(total, alone + 2 * retained_size(&node(10)));
+    assert_eq!(visited.len(), 3);
+    assert_eq!(retained_size(&Rc::<Node>::null()), 0);
+}
+
+#[test]
+fn cycle_terminates() {
+    common::init();
+
+    let a = node(100);
+    let b = node(100);
+    a.as_ref()
+        .unwrap()
+        .next
+        .store(b.clone(), Ordering::Relaxed, &cs());
+    b.as_ref()
+        .unwrap()
+        .next
+        .store(a.clone(), Ordering::Relaxed, &cs());
+    assert_eq!(retained_size(&a), retained_size(&b));
+    assert!(retained_size(&a) >= 200);
+
+    // Break the cycle so that both nodes are reclaimed.
+    a.as_ref()
+        .unwrap()
+        .next
+        .store(Rc::null(), Ordering::Relaxed, &cs());
+}
+
+#[test]
+fn containers() {
+    common::init();
+    let guard = &cs();
+
+    let shared = node(4096);
+    let stack = Stack::new();
+    let empty = retained_size(&stack);
+    for _ in 0..3 {
+        stack.push(shared.clone(), guard);
+    }
+    let grown = retained_size(&stack);
+    // The shared item is counted once, in addition to the three stack nodes.
+    assert!(grown - empty >= 4096);
+    assert!(grown - empty < 2 * 4096);
+
+    let map = PersistentMap::new();
+    assert_eq!(retained_size(&map), 0);
+    map.insert(1u64, String::from("one"), guard);
+    let snapshot = map.snapshot();
+    let mut visited = Visited::new();
+    let map_size = map.heap_size(&mut visited);
+    assert!(map_size >= 3);
+    // The snapshot shares the root with the map.
+    assert_eq!(snapshot.heap_size(&mut visited), 0);
+
+    let trie = Trie::new();
+    let before = retained_size(&trie);
+    trie.insert(b"key", 0u32, guard);
+    assert!(retained_size(&trie) > before + 3);
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use circ::cs;
+use circ::intrusive::{AtomicIntrusiveRc, IntrusiveRc, IntrusiveRcObject};
+use crossbeam_utils::thread::scope;
+
+static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+/// An object with the reference count in a C-compatible header.
+#[repr(C)]
+struct Header {
+    refcount: AtomicUsize,
+    value: usize,
+}
+
+unsafe impl IntrusiveRcObject for Header {
+    fn counter(&self) -> &AtomicUsize {
+        &self.refcount
+    }
+
+    unsafe fn destroy(this: *mut Self) {
+        DESTROYED.fetch_add(1, Ordering::Relaxed);
+        drop(Box::from_raw(this));
+    }
+}
+
+/// An object that records its destruction instead of being freed, so that the pointers to it can
+/// still be checked afterwards.
+struct Tracked {
+    refcount: AtomicUsize,
+    destroyed: AtomicBool,
+}
+
+unsafe impl IntrusiveRcObject for Tracked {
+    fn counter(&self) -> &AtomicUsize {
+        &self.refcount
+    }
+
+    unsafe fn destroy(this: *mut Self) {
+        let destroyed = (*this).destroyed.swap(true, Ordering::Relaxed);
+        assert!(!destroyed, "an object is destroyed twice");
+    }
+}
+
+fn tracked() -> (&'static Tracked, IntrusiveRc<Tracked>) {
+    let ptr = Box::into_raw(Box::new(Tracked {
+        refcount: AtomicUsize::new(1),
+        destroyed: AtomicBool::new(false),
+    }));
+    (unsafe { &*ptr }, unsafe { IntrusiveRc::from_raw(ptr) })
+}
+
+fn header(value: usize) -> IntrusiveRc<Header> {
+    let obj = Box::new(Header {
+        refcount: AtomicUsize::new(1),
+        value,
+    });
+    unsafe { IntrusiveRc::from_raw(Box::into_raw(obj)) }
+}
+
+#[test]
+fn swap_and_load() {
+    common::init();
+
+    const THREADS: usize = 8;
+    const ITERS: usize = 1000;
+
+    let shared = AtomicIntrusiveRc::new(header(0));
+    scope(|s| {
+        for t in 0..THREADS {
+            let shared = &shared;
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ITERS {
+                    let guard = &cs();
+                    if i % 2 == 0 {
+                        shared.store(header(t * ITERS + i), Ordering::AcqRel, guard);
+                    } else {
+                        let rc = shared.load_rc(Ordering::Acquire, guard);
+                        let obj = rc.as_ref().unwrap();
+                        assert!(obj.refcount.load(Ordering::Relaxed) >= 1);
+                        assert_eq!(
+                            shared.load(Ordering::Acquire, guard).is_some(),
+                            !rc.is_null()
+                        );
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let last = shared.load_rc(Ordering::Acquire, &cs());
+    assert_eq!(last.as_ref().unwrap().refcount.load(Ordering::Relaxed), 2);
+    drop(shared);
+    drop(last);
+
+    let stored = THREADS * ITERS / 2 + 1;
+    while DESTROYED.load(Ordering::Relaxed) < stored {
+        cs().flush();
+    }
+    assert_eq!(DESTROYED.load(Ordering::Relaxed), stored);
+}
+
+#[test]
+fn load_rc_while_storing() {
+    common::init();
+
+    const READERS: usize = 4;
+    const STORES: usize = 1000;
+
+    let (first, rc) = tracked();
+    let shared = AtomicIntrusiveRc::new(rc);
+    let done = AtomicBool::new(false);
+    let objects = scope(|s| {
+        for _ in 0..READERS {
+            let (shared, done) = (&shared, &done);
+            s.spawn(move |_| {
+                common::init();
+                while !done.load(Ordering::Acquire) {
+                    let rc = shared.load_rc(Ordering::Acquire, &cs());
+                    // Let the replaced objects be destroyed while the reference is held.
+                    cs().flush();
+                    let obj = rc.as_ref().unwrap();
+                    assert!(!obj.destroyed.load(Ordering::Relaxed));
+                    assert!(obj.refcount.load(Ordering::Relaxed) >= 1);
+                }
+            });
+        }
+
+        let writer = s.spawn(|_| {
+            common::init();
+            let mut objects = vec![first];
+            for _ in 0..STORES {
+                let (obj, rc) = tracked();
+                objects.push(obj);
+                shared.store(rc, Ordering::AcqRel, &cs());
+            }
+            done.store(true, Ordering::Release);
+            objects
+        });
+        writer.join().unwrap()
+    })
+    .unwrap();
+
+    // Every object is destroyed exactly once, and only after its last reference is released.
+    drop(shared);
+    for _ in 0..1000 {
+        if objects
+            .iter()
+            .all(|obj| obj.destroyed.load(Ordering::Relaxed))
+        {
+            break;
+        }
+        cs().flush();
+    }
+    for obj in objects {
+        assert!(obj.destroyed.load(Ordering::Relaxed));
+        assert_eq!(obj.refcount.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(debug_assertions)]
+
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use circ::invariants::{check_atomic_rc, check_collector, check_rc, check_snapshot};
+use circ::{cs, AtomicRc, Collector, Rc, RcObject};
+use rand::{thread_rng, Rng};
+
+struct Node {
+    next: Rc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(std::mem::take(&mut self.next));
+    }
+}
+
+#[test]
+fn random_operations() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let mut rng = thread_rng();
+    collector.enter(|| {
+        let head = AtomicRc::<Node>::null();
+        let mut held = Vec::new();
+        let mut weaks = Vec::new();
+        for _ in 0..1000 {
+            let guard = &cs();
+            match rng.gen_range(0..5) {
+                0 => {
+                    let next = Rc::from(head.load(Ordering::Acquire, guard));
+                    head.store(Rc::new(Node { next }), Ordering::Release, guard);
+                }
+                1 => {
+                    let snapshot = head.load(Ordering::Acquire, guard);
+                    if let Some(node) = snapshot.as_ref() {
+                        head.store(node.next.clone(), Ordering::Release, guard);
+                    }
+                }
+                2 => held.push(Rc::from(head.load(Ordering::Acquire, guard))),
+                3 => {
+                    if let Some(rc) = held.pop() {
+                        weaks.push(rc.downgrade());
+                    }
+                }
+                _ => {
+                    let _ = collector.try_advance(guard);
+                    collector.collect();
+                }
+            }
+
+            let snapshot = head.load(Ordering::Acquire, guard);
+            check_snapshot(&snapshot).unwrap();
+            check_atomic_rc(&head, guard).unwrap();
+            for rc in &held {
+                check_rc(rc).unwrap();
+            }
+            check_collector(&collector).unwrap();
+        }
+    });
+    collector.collect_all(Duration::from_secs(10));
+    check_collector(&collector).unwrap();
+}
@@ -0,0 +1,20 @@
+use std::mem::{align_of, size_of};
+
+use circ::{Rc, RcObject, Snapshot, Weak};
+
+struct Node {
+    _next: Rc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn pointer_sized() {
+    assert_eq!(size_of::<Rc<Node>>(), size_of::<usize>());
+    assert_eq!(size_of::<Weak<Node>>(), size_of::<usize>());
+    assert_eq!(size_of::<Snapshot<'_, Node>>(), size_of::<usize>());
+    assert_eq!(align_of::<Rc<Node>>(), align_of::<usize>());
+    assert_eq!(size_of::<Node>(), size_of::<usize>());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{Collector, LazyRc, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+static INITS: AtomicUsize = AtomicUsize::new(0);
+
+struct Config {
+    threads: usize,
+}
+
+unsafe impl RcObject for Config {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+static CONFIG: LazyRc<Config> = LazyRc::new(|| {
+    INITS.fetch_add(1, Ordering::Relaxed);
+    Config { threads: 8 }
+});
+
+#[test]
+fn initialized_once() {
+    common::init();
+
+    assert!(!CONFIG.is_initialized());
+    // The first access in a scoped collector still allocates in the default collector.
+    let collector = Collector::new();
+    scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|_| {
+                common::init();
+                collector.enter(|| {
+                    let config = CONFIG.get();
+                    assert_eq!(config.as_ref().unwrap().threads, 8);
+                    assert!(config.ptr_eq(CONFIG.as_rc()));
+                });
+            });
+        }
+    })
+    .unwrap();
+
+    assert!(CONFIG.is_initialized());
+    assert_eq!(CONFIG.as_ref().threads, 8);
+    assert_eq!(INITS.load(Ordering::Relaxed), 1);
+}
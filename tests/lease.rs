@@ -0,0 +1,50 @@
+mod common;
+
+use circ::lease::{Lease, LeaseExpired, LeaseOptions};
+use circ::{cs, default_collector, Rc, RcObject};
+
+struct Item;
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+fn retire(count: usize) {
+    let guard = &cs();
+    for _ in 0..count {
+        Rc::new(Item).finalize(guard);
+    }
+    guard.flush();
+}
+
+#[test]
+fn withheld_budget() {
+    common::init();
+
+    let mut lease = Lease::new(LeaseOptions::new().max_withheld(100));
+    assert!(default_collector().active_leases() >= 1);
+    assert_eq!(lease.checkpoint(), Ok(()));
+
+    retire(200);
+    assert!(lease.withheld() >= 200);
+    assert!(default_collector().queued_garbage() >= 200);
+    assert_eq!(lease.checkpoint(), Err(LeaseExpired));
+
+    // A new view is taken after the expiry.
+    assert_eq!(lease.withheld(), 0);
+    assert_eq!(lease.checkpoint(), Ok(()));
+}
+
+#[test]
+fn forced_expiry() {
+    common::init();
+
+    let mut lease = Lease::new(LeaseOptions::new());
+    let handle = lease.expire_handle();
+    assert_eq!(lease.checkpoint(), Ok(()));
+
+    std::thread::spawn(move || handle.expire()).join().unwrap();
+    assert!(lease.is_expired());
+    assert_eq!(lease.checkpoint(), Err(LeaseExpired));
+    assert!(!lease.is_expired());
+}
@@ -0,0 +1,454 @@
+#![cfg(feature = "lincheck")]
+
+mod common;
+
+use std::collections::{BTreeMap, VecDeque};
+
+use circ::ds::{
+    ArrayQueue, BstMap, EliminationStack, LruCache, PersistentMap, PriorityQueue, Stack, Trie,
+};
+use circ::lincheck::{Checker, Concurrent, Model, Rng};
+use circ::{cs, Rc, RcObject};
+use common::init;
+
+struct Item(u8);
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+fn checker() -> Checker {
+    Checker::new().iterations(200)
+}
+
+#[derive(Clone, Debug)]
+enum StackOp {
+    Push(u8),
+    Pop,
+}
+
+#[derive(Clone, Default)]
+struct StackModel(Vec<u8>);
+
+impl Model for StackModel {
+    type Op = StackOp;
+    type Ret = Option<u8>;
+
+    fn generate(rng: &mut Rng) -> StackOp {
+        if rng.below(2) == 0 {
+            StackOp::Push(rng.below(100) as u8)
+        } else {
+            StackOp::Pop
+        }
+    }
+
+    fn apply(&mut self, op: &StackOp) -> Option<u8> {
+        match op {
+            StackOp::Push(item) => {
+                self.0.push(*item);
+                None
+            }
+            StackOp::Pop => self.0.pop(),
+        }
+    }
+}
+
+impl Concurrent<StackModel> for Stack<Item> {
+    fn apply(&self, op: &StackOp) -> Option<u8> {
+        let guard = &cs();
+        match op {
+            StackOp::Push(item) => {
+                self.push(Rc::new(Item(*item)), guard);
+                None
+            }
+            StackOp::Pop => self.pop(guard).map(|item| item.as_ref().unwrap().0),
+        }
+    }
+}
+
+impl Concurrent<StackModel> for EliminationStack<Item> {
+    fn apply(&self, op: &StackOp) -> Option<u8> {
+        let guard = &cs();
+        match op {
+            StackOp::Push(item) => {
+                self.push(Rc::new(Item(*item)), guard);
+                None
+            }
+            StackOp::Pop => self.pop(guard).map(|item| item.as_ref().unwrap().0),
+        }
+    }
+}
+
+#[test]
+fn stack() {
+    init();
+    checker()
+        .check(Stack::<Item>::new, StackModel::default, init)
+        .unwrap();
+}
+
+#[test]
+fn elimination_stack() {
+    init();
+    checker()
+        .check(
+            || EliminationStack::<Item>::with_slots(1),
+            StackModel::default,
+            init,
+        )
+        .unwrap();
+}
+
+const QUEUE_CAPACITY: usize = 3;
+
+#[derive(Clone, Debug)]
+enum QueueOp {
+    Push(u8),
+    Pop,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum QueueRet {
+    Pushed(bool),
+    Popped(Option<u8>),
+}
+
+#[derive(Clone, Default)]
+struct QueueModel(VecDeque<u8>);
+
+impl Model for QueueModel {
+    type Op = QueueOp;
+    type Ret = QueueRet;
+
+    fn generate(rng: &mut Rng) -> QueueOp {
+        if rng.below(2) == 0 {
+            QueueOp::Push(rng.below(100) as u8)
+        } else {
+            QueueOp::Pop
+        }
+    }
+
+    fn apply(&mut self, op: &QueueOp) -> QueueRet {
+        match op {
+            QueueOp::Push(item) => {
+                let pushed = self.0.len() < QUEUE_CAPACITY;
+                if pushed {
+                    self.0.push_back(*item);
+                }
+                QueueRet::Pushed(pushed)
+            }
+            QueueOp::Pop => QueueRet::Popped(self.0.pop_front()),
+        }
+    }
+}
+
+impl Concurrent<QueueModel> for ArrayQueue<Item> {
+    fn apply(&self, op: &QueueOp) -> QueueRet {
+        match op {
+            QueueOp::Push(item) => QueueRet::Pushed(self.try_push(Rc::new(Item(*item))).is_ok()),
+            QueueOp::Pop => QueueRet::Popped(self.try_pop().map(|item| item.as_ref().unwrap().0)),
+        }
+    }
+}
+
+#[test]
+fn array_queue() {
+    init();
+    checker()
+        .check(
+            || ArrayQueue::<Item>::new(QUEUE_CAPACITY),
+            QueueModel::default,
+            init,
+        )
+        .unwrap();
+}
+
+#[derive(Clone, Default)]
+struct PriorityModel(Vec<u8>);
+
+impl Model for PriorityModel {
+    type Op = StackOp;
+    type Ret = Option<u8>;
+
+    fn generate(rng: &mut Rng) -> StackOp {
+        StackModel::generate(rng)
+    }
+
+    fn apply(&mut self, op: &StackOp) -> Option<u8> {
+        match op {
+            StackOp::Push(priority) => {
+                self.0.push(*priority);
+                None
+            }
+            StackOp::Pop => {
+                let (i, _) = self.0.iter().enumerate().min_by_key(|(_, p)| **p)?;
+                Some(self.0.swap_remove(i))
+            }
+        }
+    }
+}
+
+impl Concurrent<PriorityModel> for PriorityQueue<u8, ()> {
+    fn apply(&self, op: &StackOp) -> Option<u8> {
+        let guard = &cs();
+        match op {
+            StackOp::Push(priority) => {
+                self.push(*priority, (), guard);
+                None
+            }
+            StackOp::Pop => self.pop_min(guard).map(|(priority, _)| *priority),
+        }
+    }
+}
+
+#[test]
+fn priority_queue() {
+    init();
+    checker()
+        .check(PriorityQueue::<u8, ()>::new, PriorityModel::default, init)
+        .unwrap();
+}
+
+#[derive(Clone, Debug)]
+enum MapOp {
+    Insert(u8, u8),
+    Remove(u8),
+    Get(u8),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum MapRet {
+    Inserted(Option<u8>),
+    Removed(Option<u8>),
+    Got(Option<u8>),
+}
+
+/// A model of a map, whose keys are drawn from a small range so that operations collide.
+#[derive(Clone, Default)]
+struct MapModel(BTreeMap<u8, u8>);
+
+impl Model for MapModel {
+    type Op = MapOp;
+    type Ret = MapRet;
+
+    fn generate(rng: &mut Rng) -> MapOp {
+        let key = rng.below(4) as u8;
+        match rng.below(3) {
+            0 => MapOp::Insert(key, rng.below(100) as u8),
+            1 => MapOp::Remove(key),
+            _ => MapOp::Get(key),
+        }
+    }
+
+    fn apply(&mut self, op: &MapOp) -> MapRet {
+        match op {
+            MapOp::Insert(key, value) => MapRet::Inserted(self.0.insert(*key, *value)),
+            MapOp::Remove(key) => MapRet::Removed(self.0.remove(key)),
+            MapOp::Get(key) => MapRet::Got(self.0.get(key).copied()),
+        }
+    }
+}
+
+impl Concurrent<MapModel> for Trie<u8> {
+    fn apply(&self, op: &MapOp) -> MapRet {
+        let guard = &cs();
+        match op {
+            MapOp::Insert(key, value) => {
+                MapRet::Inserted(self.insert(&[*key], *value, guard).copied())
+            }
+            MapOp::Remove(key) => MapRet::Removed(self.remove(&[*key], guard).copied()),
+            MapOp::Get(key) => MapRet::Got(self.get(&[*key], guard).copied()),
+        }
+    }
+}
+
+#[test]
+fn trie() {
+    init();
+    checker()
+        .check(Trie::<u8>::new, MapModel::default, init)
+        .unwrap();
+}
+
+/// A model of [`PersistentMap`], whose updates do not return the previous value.
+#[derive(Clone, Default)]
+struct PersistentMapModel(MapModel);
+
+impl Model for PersistentMapModel {
+    type Op = MapOp;
+    type Ret = MapRet;
+
+    fn generate(rng: &mut Rng) -> MapOp {
+        MapModel::generate(rng)
+    }
+
+    fn apply(&mut self, op: &MapOp) -> MapRet {
+        match self.0.apply(op) {
+            MapRet::Inserted(_) => MapRet::Inserted(None),
+            MapRet::Removed(old) => MapRet::Removed(old.map(|_| 0)),
+            got => got,
+        }
+    }
+}
+
+impl Concurrent<PersistentMapModel> for PersistentMap<u8, u8> {
+    fn apply(&self, op: &MapOp) -> MapRet {
+        let guard = &cs();
+        match op {
+            MapOp::Insert(key, value) => {
+                self.insert(*key, *value, guard);
+                MapRet::Inserted(None)
+            }
+            MapOp::Remove(key) => MapRet::Removed(self.remove(key, guard).then_some(0)),
+            MapOp::Get(key) => MapRet::Got(self.get(key, guard).copied()),
+        }
+    }
+}
+
+#[test]
+fn persistent_map() {
+    init();
+    checker()
+        .check(
+            PersistentMap::<u8, u8>::new,
+            PersistentMapModel::default,
+            init,
+        )
+        .unwrap();
+}
+
+/// A model of [`BstMap`], whose insertions do not replace an existing value.
+#[derive(Clone, Default)]
+struct BstMapModel(MapModel);
+
+impl Model for BstMapModel {
+    type Op = MapOp;
+    type Ret = MapRet;
+
+    fn generate(rng: &mut Rng) -> MapOp {
+        MapModel::generate(rng)
+    }
+
+    fn apply(&mut self, op: &MapOp) -> MapRet {
+        match op {
+            MapOp::Insert(key, value) => {
+                // Only whether the key was present is reported.
+                let present = self.0 .0.contains_key(key);
+                self.0 .0.entry(*key).or_insert(*value);
+                MapRet::Inserted(present.then_some(0))
+            }
+            _ => self.0.apply(op),
+        }
+    }
+}
+
+impl Concurrent<BstMapModel> for BstMap<u8, u8> {
+    fn apply(&self, op: &MapOp) -> MapRet {
+        let guard = &cs();
+        match op {
+            MapOp::Insert(key, value) => {
+                MapRet::Inserted((!self.insert(*key, *value, guard)).then_some(0))
+            }
+            MapOp::Remove(key) => MapRet::Removed(self.remove(key, guard).copied()),
+            MapOp::Get(key) => MapRet::Got(self.get(key, guard).copied()),
+        }
+    }
+}
+
+#[test]
+fn bst_map() {
+    init();
+    checker()
+        .check(BstMap::<u8, u8>::new, BstMapModel::default, init)
+        .unwrap();
+}
+
+/// A model of an [`LruCache`] with a single shard, whose order of recency is kept in a vector
+/// from the least to the most recently used entry.
+#[derive(Clone, Default)]
+struct LruModel(Vec<(u8, u8)>);
+
+const LRU_CAPACITY: usize = 2;
+
+impl Model for LruModel {
+    type Op = MapOp;
+    type Ret = MapRet;
+
+    fn generate(rng: &mut Rng) -> MapOp {
+        MapModel::generate(rng)
+    }
+
+    fn apply(&mut self, op: &MapOp) -> MapRet {
+        let position = |model: &Self, key: &u8| model.0.iter().position(|(k, _)| k == key);
+        match op {
+            MapOp::Insert(key, value) => {
+                if let Some(i) = position(self, key) {
+                    self.0.remove(i);
+                } else if self.0.len() == LRU_CAPACITY {
+                    self.0.remove(0);
+                }
+                self.0.push((*key, *value));
+                MapRet::Inserted(None)
+            }
+            MapOp::Remove(key) => MapRet::Removed(position(self, key).map(|i| self.0.remove(i).1)),
+            MapOp::Get(key) => MapRet::Got(position(self, key).map(|i| {
+                let entry = self.0.remove(i);
+                self.0.push(entry);
+                entry.1
+            })),
+        }
+    }
+}
+
+impl Concurrent<LruModel> for LruCache<u8, u8> {
+    fn apply(&self, op: &MapOp) -> MapRet {
+        let guard = &cs();
+        match op {
+            MapOp::Insert(key, value) => {
+                self.put(*key, *value, guard);
+                MapRet::Inserted(None)
+            }
+            MapOp::Remove(key) => MapRet::Removed(self.remove(key, guard).copied()),
+            MapOp::Get(key) => MapRet::Got(self.get(key, guard).copied()),
+        }
+    }
+}
+
+#[test]
+fn lru_cache() {
+    init();
+    checker()
+        .check(
+            || LruCache::with_shards(LRU_CAPACITY, 1),
+            LruModel::default,
+            init,
+        )
+        .unwrap();
+}
+
+/// A stack that pops from the bottom, which the checker must reject.
+struct Broken(std::sync::Mutex<VecDeque<u8>>);
+
+impl Concurrent<StackModel> for Broken {
+    fn apply(&self, op: &StackOp) -> Option<u8> {
+        let mut items = self.0.lock().unwrap();
+        match op {
+            StackOp::Push(item) => {
+                items.push_back(*item);
+                None
+            }
+            StackOp::Pop => items.pop_front(),
+        }
+    }
+}
+
+#[test]
+fn violation_detected() {
+    let violation = Checker::new()
+        .threads(1)
+        .ops_per_thread(8)
+        .check(|| Broken(Default::default()), StackModel::default, || {})
+        .unwrap_err();
+    assert!(!violation.history.is_empty());
+    assert!(violation.to_string().contains("not linearizable"));
+}
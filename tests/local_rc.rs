@@ -0,0 +1,99 @@
+mod common;
+
+use std::cell::Cell;
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+use circ::local::{LocalAtomicRc, LocalRc, LocalRcObject};
+use circ::{affinity, cs};
+
+thread_local! {
+    static DROPPED: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Node {
+    owner: ThreadId,
+    // Makes the node neither `Send` nor `Sync`.
+    _local: std::rc::Rc<()>,
+    next: LocalRc<Node>,
+}
+
+impl Node {
+    fn new(next: LocalRc<Node>) -> Self {
+        Self {
+            owner: thread::current().id(),
+            _local: std::rc::Rc::new(()),
+            next,
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        assert_eq!(thread::current().id(), self.owner);
+        DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+    }
+}
+
+unsafe impl LocalRcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<LocalRc<Self>>) {
+        out.push(std::mem::take(&mut self.next));
+    }
+}
+
+#[test]
+fn list() {
+    common::init();
+
+    let mut head = LocalAtomicRc::null();
+    for _ in 0..100 {
+        let next = head.load();
+        head.store(LocalRc::new(Node::new(next)));
+    }
+
+    let mut node = head.load();
+    let mut len = 0;
+    while let Some(n) = node.as_ref() {
+        len += 1;
+        node = n.next.clone();
+    }
+    assert_eq!(len, 100);
+
+    let first = head.take();
+    assert!(head.load().is_null());
+    drop(first);
+
+    // The nodes are dropped on this thread, either by its own collection or, if another thread
+    // has reclaimed them, by draining its mailbox.
+    while DROPPED.with(Cell::get) < 100 {
+        circ::default_collector().collect_all(Duration::from_millis(100));
+        affinity::drain();
+    }
+    assert_eq!(DROPPED.with(Cell::get), 100);
+}
+
+#[test]
+fn reclaimed_on_another_thread() {
+    common::init();
+
+    let before = DROPPED.with(Cell::get);
+    let a = LocalRc::new(Node::new(LocalRc::null()));
+    let b = LocalRc::new(Node::new(a.clone()));
+    assert!(b.as_ref().unwrap().next.ptr_eq(&a));
+    drop(a);
+    drop(b);
+    cs().flush();
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            common::init();
+            circ::default_collector().collect_all(Duration::from_secs(1));
+        });
+    });
+
+    while DROPPED.with(Cell::get) < before + 2 {
+        affinity::drain();
+        circ::default_collector().collect_all(Duration::from_millis(100));
+    }
+    assert_eq!(DROPPED.with(Cell::get), before + 2);
+}
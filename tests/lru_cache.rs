@@ -0,0 +1,75 @@
+mod common;
+
+use circ::{cs, ds::LruCache};
+use crossbeam_utils::thread;
+
+#[test]
+fn evicts_least_recently_used() {
+    common::init();
+
+    let cache = LruCache::with_shards(3, 1);
+    let guard = &cs();
+    for i in 0..3 {
+        cache.put(i, i.to_string(), guard);
+    }
+    assert_eq!(cache.len(), 3);
+
+    // Touch `0` so that `1` becomes the least recently used entry.
+    assert_eq!(cache.get(&0, guard).map(String::as_str), Some("0"));
+    cache.put(3, "3".to_string(), guard);
+    assert!(!cache.contains_key(&1));
+    assert!(cache.contains_key(&0));
+    assert_eq!(cache.len(), 3);
+
+    // Replacing a value does not evict anything.
+    cache.put(0, "zero".to_string(), guard);
+    assert_eq!(cache.get(&0, guard).map(String::as_str), Some("zero"));
+    assert_eq!(cache.len(), 3);
+
+    assert_eq!(cache.remove(&2, guard).map(String::as_str), Some("2"));
+    assert_eq!(cache.evict(guard), 1);
+    assert_eq!(cache.len(), 1);
+    cache.clear(guard);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn evicted_value_outlives_guard() {
+    common::init();
+
+    let cache = LruCache::with_shards(1, 1);
+    let guard = &cs();
+    cache.put(0, vec![0u8; 64], guard);
+    let value = cache.get(&0, guard).unwrap();
+    cache.put(1, vec![1u8; 64], guard);
+    assert!(cache.get(&0, guard).is_none());
+    // The evicted value is still accessible in the current critical section.
+    assert_eq!(value, &vec![0u8; 64]);
+}
+
+#[test]
+fn smoke() {
+    const THREADS: usize = 16;
+    const ELEMENTS_PER_THREAD: usize = 1000;
+
+    common::init();
+
+    let cache = &LruCache::new(256);
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let key = (t * ELEMENTS_PER_THREAD + i) % 512;
+                    let guard = &cs();
+                    cache.put(key, key.to_string(), guard);
+                    if let Some(value) = cache.get(&key, guard) {
+                        assert_eq!(value.parse::<usize>().unwrap(), key);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(cache.len() <= cache.capacity());
+}
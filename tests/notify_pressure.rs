@@ -0,0 +1,35 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use circ::{cs, Collector};
+
+#[test]
+fn drains_idle_garbage() {
+    common::init();
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::new();
+    collector.enter(|| {
+        for _ in 0..10 {
+            let dropped = dropped.clone();
+            cs().defer(move || dropped.fetch_add(1, Ordering::Relaxed));
+        }
+    });
+
+    collector.notify_pressure();
+    assert_eq!(dropped.load(Ordering::Relaxed), 10);
+    assert_eq!(collector.queued_garbage(), 0);
+}
+
+#[test]
+fn explicit_collector_is_not_advanced() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    collector.enter(|| cs().defer(|| ()));
+    let epoch = collector.global_epoch();
+    collector.notify_pressure();
+    assert_eq!(collector.global_epoch(), epoch);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::sync::Barrier;
+use std::thread;
+
+use circ::Collector;
+
+#[test]
+fn reports_participants() {
+    common::init();
+
+    // A deterministic collector never flushes the bags on its own.
+    let collector = Collector::builder().deterministic(true).build();
+    assert_eq!(collector.participants().count(), 0);
+
+    let handle = collector.register();
+    let guard = handle.pin();
+    for i in 0..3 {
+        guard.defer(move || i);
+    }
+    let participants: Vec<_> = collector.participants().collect();
+    assert_eq!(participants.len(), 1);
+    assert!(participants[0].pinned);
+    assert_eq!(participants[0].pending, None);
+    assert_eq!(participants[0].epoch, collector.global_epoch());
+    drop(guard);
+
+    let participant = collector.participants().next().unwrap();
+    assert!(!participant.pinned);
+    assert_eq!(participant.pending, Some(3));
+    assert_eq!(participant.thread, thread::current().id());
+
+    let (pinned, checked) = (Barrier::new(2), Barrier::new(2));
+    thread::scope(|s| {
+        thread::Builder::new()
+            .name("worker".into())
+            .spawn_scoped(s, || {
+                common::init();
+                let handle = collector.register();
+                let guard = handle.pin();
+                pinned.wait();
+                checked.wait();
+                drop(guard);
+            })
+            .unwrap();
+
+        pinned.wait();
+        let participants: Vec<_> = collector.participants().collect();
+        assert_eq!(participants.len(), 2);
+        let worker = participants
+            .iter()
+            .find(|p| p.thread_name.as_deref() == Some("worker"))
+            .unwrap();
+        assert!(worker.pinned);
+        assert_ne!(worker.thread, thread::current().id());
+        checked.wait();
+    });
+
+    // The participant of the worker has been unregistered with its thread.
+    assert_eq!(collector.participants().count(), 1);
+    drop(handle);
+    assert_eq!(collector.participants().count(), 0);
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::time::Duration;
+
+use circ::{Collector, Rc, RcObject};
+
+struct Blob(#[allow(dead_code)] [u8; 1024]);
+
+unsafe impl RcObject for Blob {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn peak() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    assert_eq!(collector.unreclaimed_bytes(), 0);
+    assert_eq!(collector.peak_unreclaimed_bytes(), 0);
+
+    let kept = collector.enter(|| {
+        for _ in 0..64 {
+            drop(Rc::new(Blob([0; 1024])));
+        }
+        Rc::new(Blob([0; 1024]))
+    });
+    assert!(collector.unreclaimed_bytes() >= 64 * 1024);
+    collector.collect_all(Duration::from_secs(10));
+    assert_eq!(collector.unreclaimed_bytes(), 0);
+    let peak = collector.peak_unreclaimed_bytes();
+    assert!(peak >= 64 * 1024);
+
+    // A smaller burst does not lower the peak.
+    collector.enter(|| drop(kept));
+    collector.collect_all(Duration::from_secs(10));
+    assert_eq!(collector.peak_unreclaimed_bytes(), peak);
+
+    collector.reset_peak_unreclaimed_bytes();
+    assert_eq!(collector.peak_unreclaimed_bytes(), 0);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use circ::{unprotected_cs, Collector, PendingDeferred, Rc, RcObject};
+
+struct Node([u64; 8]);
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn counts_local_bags() {
+    common::init();
+
+    // A deterministic collector never flushes the bags on its own.
+    let collector = Collector::builder().deterministic(true).build();
+    let handle = collector.register();
+    assert_eq!(handle.pending_deferred(), PendingDeferred::default());
+
+    let guard = handle.pin();
+    for i in 0..3 {
+        guard.defer(move || i);
+    }
+    let pending = guard.pending_deferred();
+    assert_eq!(pending.count, 3);
+    assert_eq!(pending.bytes, 0);
+
+    for _ in 0..2 {
+        let node = Rc::new(Node([1; 8]));
+        assert_eq!(node.as_ref().unwrap().0, [1; 8]);
+        node.finalize(&guard);
+    }
+    let pending = handle.pending_deferred();
+    assert_eq!(pending.count, 5);
+    assert!(pending.bytes >= 2 * std::mem::size_of::<Node>());
+
+    guard.flush();
+    assert_eq!(guard.pending_deferred(), PendingDeferred::default());
+    drop(guard);
+
+    let unprotected = unsafe { unprotected_cs() };
+    assert_eq!(unprotected.pending_deferred(), PendingDeferred::default());
+}
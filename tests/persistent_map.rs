@@ -0,0 +1,67 @@
+mod common;
+
+use circ::{cs, ds::PersistentMap};
+use crossbeam_utils::thread;
+
+#[test]
+fn insert_remove() {
+    common::init();
+
+    let map = PersistentMap::new();
+    let guard = &cs();
+    for i in (0..100).rev() {
+        map.insert(i, i * 10, guard);
+    }
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.get(&42, guard), Some(&420));
+
+    let snapshot = map.snapshot();
+    for i in (0..100).filter(|i| i % 2 == 0) {
+        assert!(map.remove(&i, guard));
+    }
+    assert!(!map.remove(&0, guard));
+    assert_eq!(map.len(), 50);
+    assert!(!map.contains_key(&42, guard));
+
+    // The snapshot still observes every entry, in order.
+    assert_eq!(snapshot.len(), 100);
+    let keys: Vec<_> = snapshot.iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys, (0..100).collect::<Vec<_>>());
+
+    let odd: Vec<_> = map.snapshot().iter().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(
+        odd,
+        (1..100).step_by(2).map(|i| (i, i * 10)).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn concurrent_insert() {
+    const THREADS: usize = 8;
+    const ELEMENTS_PER_THREAD: usize = 500;
+
+    common::init();
+
+    let map = &PersistentMap::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let key = i * THREADS + t;
+                    map.insert(key, key.to_string(), &cs());
+                    let snapshot = map.snapshot();
+                    assert_eq!(snapshot.get(&key), Some(&key.to_string()));
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let snapshot = map.snapshot();
+    assert_eq!(snapshot.len(), THREADS * ELEMENTS_PER_THREAD);
+    assert!(snapshot
+        .iter()
+        .map(|(k, _)| *k)
+        .eq(0..THREADS * ELEMENTS_PER_THREAD));
+}
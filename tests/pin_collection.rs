@@ -0,0 +1,78 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use circ::{cs, Collector, PinCollection, Rc, RcObject};
+
+struct Item {
+    drops: Arc<AtomicUsize>,
+}
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Item {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+const ITEMS: usize = 1024;
+
+/// Retires `ITEMS` items, one per critical section, and returns how many have been reclaimed.
+fn retire(collector: &Collector, drops: &Arc<AtomicUsize>) -> usize {
+    collector.enter(|| {
+        for _ in 0..ITEMS {
+            let guard = &cs();
+            let drops = drops.clone();
+            Rc::new(Item { drops }).finalize(guard);
+        }
+    });
+    drops.load(Ordering::Relaxed)
+}
+
+#[test]
+fn never() {
+    common::init();
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder()
+        .pin_collection(PinCollection::Never)
+        .build();
+    assert_eq!(retire(&collector, &drops), 0);
+
+    collector.advance_frame();
+    collector.advance_frame();
+    collector.advance_frame();
+    assert_eq!(drops.load(Ordering::Relaxed), ITEMS);
+}
+
+#[test]
+fn every_pin() {
+    common::init();
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder()
+        .pin_collection(PinCollection::Every(1))
+        .build();
+    assert!(retire(&collector, &drops) > 0);
+}
+
+#[test]
+fn per_handle() {
+    common::init();
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::new();
+    collector.enter(|| {
+        circ::set_pin_collection(PinCollection::Never);
+        for _ in 0..ITEMS {
+            let guard = &cs();
+            let drops = drops.clone();
+            Rc::new(Item { drops }).finalize(guard);
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+    });
+}
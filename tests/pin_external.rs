@@ -0,0 +1,47 @@
+mod common;
+
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use circ::Collector;
+
+#[test]
+fn grace_period() {
+    common::init();
+
+    // A slot of a structure that does not use `Rc`, read in critical sections of `collector`.
+    let collector = Collector::builder().deterministic(true).build();
+    let slot = AtomicPtr::new(Box::into_raw(Box::new(1)));
+
+    let reader = collector.register();
+    let guard = reader.pin();
+    let value = unsafe { &*slot.load(Ordering::Acquire) };
+
+    let old = slot.swap(Box::into_raw(Box::new(2)), Ordering::AcqRel);
+    let token = collector.pin_external();
+    assert!(!collector.is_expired(token));
+
+    // The reader holds the epoch back, so the token cannot expire.
+    let writer = collector.register();
+    for _ in 0..4 {
+        let _ = collector.try_advance(&writer.pin());
+    }
+    assert!(!collector.is_expired(token));
+    assert_eq!(*value, 1);
+    drop(guard);
+
+    while !collector.is_expired(token) {
+        assert!(collector.try_advance(&writer.pin()).is_ok());
+    }
+    assert!(collector.global_epoch().wrapping_sub(token.epoch()) >= 3);
+    drop(unsafe { Box::from_raw(old) });
+    drop(unsafe { Box::from_raw(slot.load(Ordering::Relaxed)) });
+}
+
+#[test]
+#[should_panic(expected = "another collector")]
+fn token_of_another_collector() {
+    common::init();
+
+    let (first, second) = (Collector::new(), Collector::new());
+    second.is_expired(first.pin_external());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, pool::Slab};
+use crossbeam_utils::thread;
+
+#[test]
+fn insert_remove() {
+    common::init();
+
+    let slab = Slab::new();
+    let keys: Vec<_> = (0..100).map(|i| slab.insert(i)).collect();
+    let guard = &cs();
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(slab.get(*key, guard), Some(&i));
+    }
+    assert!(slab.remove(keys[42], guard));
+    assert!(!slab.remove(keys[42], guard));
+    assert!(!slab.contains(keys[42], guard));
+    assert!(slab.contains(keys[43], guard));
+}
+
+#[test]
+fn recycle_after_grace_period() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Tracked(usize);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    common::init();
+
+    let slab = Slab::new();
+    let key = slab.insert(Tracked(1));
+    {
+        let guard = &cs();
+        let value = slab.get(key, guard).unwrap();
+        assert!(slab.remove(key, guard));
+        // The value is still readable in the critical section.
+        assert_eq!(value.0, 1);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+
+        // The slot is not reused during the grace period.
+        let other = slab.insert(Tracked(2));
+        assert_ne!(other.index(), key.index());
+    }
+
+    while DROPS.load(Ordering::Relaxed) == 0 {
+        cs().flush();
+    }
+    // The slot is reused eventually, with a new generation.
+    loop {
+        let new = slab.insert(Tracked(3));
+        if new.index() == key.index() {
+            assert!(slab.get(key, &cs()).is_none());
+            assert!(slab.get(new, &cs()).is_some());
+            break;
+        }
+    }
+}
+
+#[test]
+fn concurrent_insert_remove() {
+    const THREADS: usize = 8;
+    const ELEMENTS_PER_THREAD: usize = 10_000;
+
+    common::init();
+
+    let slab = &Slab::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let value = i * THREADS + t;
+                    let key = slab.insert(value);
+                    let guard = &cs();
+                    assert_eq!(slab.get(key, guard), Some(&value));
+                    assert!(slab.remove(key, guard));
+                }
+            });
+        }
+    })
+    .unwrap();
+}
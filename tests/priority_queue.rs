@@ -0,0 +1,79 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, ds::PriorityQueue};
+use crossbeam_utils::thread;
+
+#[test]
+fn push_pop() {
+    common::init();
+
+    let queue = PriorityQueue::new();
+    let guard = &cs();
+    assert!(queue.is_empty(guard));
+    for i in [5, 3, 8, 1, 3, 9, 0] {
+        queue.push(i, i * 10, guard);
+    }
+    queue.push(3, 300, guard);
+    assert!(!queue.is_empty(guard));
+
+    let popped: Vec<_> = std::iter::from_fn(|| queue.pop_min(guard))
+        .map(|(k, v)| (*k, *v))
+        .collect();
+    assert_eq!(
+        popped,
+        [
+            (0, 0),
+            (1, 10),
+            (3, 30),
+            (3, 30),
+            (3, 300),
+            (5, 50),
+            (8, 80),
+            (9, 90)
+        ]
+    );
+    assert!(queue.is_empty(guard));
+}
+
+#[test]
+fn concurrent_push_pop() {
+    const THREADS: usize = 8;
+    const ELEMENTS_PER_THREAD: usize = 2000;
+
+    common::init();
+
+    let queue = &PriorityQueue::new();
+    let sum = &AtomicUsize::new(0);
+    let popped = &AtomicUsize::new(0);
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let value = i * THREADS + t;
+                    let guard = &cs();
+                    queue.push(value % 97, value, guard);
+                    if let Some((_, v)) = queue.pop_min(guard) {
+                        sum.fetch_add(*v, Ordering::Relaxed);
+                        popped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    let mut last = 0;
+    while let Some((k, v)) = queue.pop_min(guard) {
+        assert!(last <= *k);
+        last = *k;
+        sum.fetch_add(*v, Ordering::Relaxed);
+        popped.fetch_add(1, Ordering::Relaxed);
+    }
+    let count = THREADS * ELEMENTS_PER_THREAD;
+    assert_eq!(popped.load(Ordering::Relaxed), count);
+    assert_eq!(sum.load(Ordering::Relaxed), count * (count - 1) / 2);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, AtomicRc, Collector, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Node {
+    next: AtomicRc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.next.take());
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+const THREADS: usize = 4;
+const NODES: usize = 1000;
+
+#[test]
+fn churn() {
+    common::init();
+
+    let collector = Collector::builder().rc_arena(true).build();
+    let head = collector.enter(|| {
+        // Objects of the same size are carved next to each other.
+        let first = Rc::new(Node {
+            next: AtomicRc::null(),
+        });
+        let second = Rc::new(Node {
+            next: AtomicRc::null(),
+        });
+        let first_addr = first.as_ref().unwrap() as *const Node as usize;
+        let second_addr = second.as_ref().unwrap() as *const Node as usize;
+        assert!(first_addr.abs_diff(second_addr) <= 1024);
+        AtomicRc::from(first)
+    });
+
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                common::init();
+                collector.enter(|| {
+                    for _ in 0..NODES {
+                        let guard = &cs();
+                        let node = Rc::new(Node {
+                            next: AtomicRc::null(),
+                        });
+                        head.swap(node, Ordering::AcqRel).finalize(guard);
+                    }
+                });
+            });
+        }
+    })
+    .unwrap();
+
+    collector.enter(|| drop(head));
+    drop(collector);
+    assert_eq!(DROPS.load(Ordering::Relaxed), THREADS * NODES + 2);
+}
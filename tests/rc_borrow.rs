@@ -0,0 +1,43 @@
+mod common;
+
+use circ::{cs, Rc, RcBorrow, RcObject};
+
+struct Node {
+    value: usize,
+    next: Rc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(std::mem::take(&mut self.next));
+    }
+}
+
+fn sum(node: RcBorrow<'_, Node>) -> usize {
+    match node.as_ref() {
+        Some(n) => n.value + sum(n.next.borrow_rc()),
+        None => 0,
+    }
+}
+
+#[test]
+fn borrow_and_upgrade() {
+    common::init();
+
+    let mut list = Rc::null();
+    for value in 1..=10 {
+        list = Rc::new(Node { value, next: list });
+    }
+    let borrowed = list.borrow_rc();
+    assert_eq!(sum(borrowed), 55);
+    assert!(borrowed.with_rc(|rc| rc.ptr_eq(&list)));
+
+    let upgraded = borrowed.to_rc();
+    let guard = &cs();
+    assert!(upgraded.snapshot(guard).ptr_eq(borrowed.snapshot(guard)));
+
+    // The list is alive as long as one of the two strong references is.
+    drop(list);
+    assert_eq!(sum(upgraded.borrow_rc()), 55);
+    assert!(RcBorrow::from(&Rc::<Node>::null()).is_null());
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, Rc, RcObject, RcRef};
+use crossbeam_utils::thread::scope;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Record {
+    id: usize,
+    tags: Vec<String>,
+}
+
+unsafe impl RcObject for Record {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Record {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn first_tag(record: Rc<Record>) -> RcRef<Record, str> {
+    record
+        .project(|r| r.tags.as_slice())
+        .map(|tags| tags[0].as_str())
+}
+
+#[test]
+fn projection_keeps_object_alive() {
+    common::init();
+
+    let record = Rc::new(Record {
+        id: 7,
+        tags: vec!["hot".into(), "new".into()],
+    });
+    let tag = first_tag(record);
+    assert_eq!(&*tag, "hot");
+
+    scope(|s| {
+        let tag = tag.clone();
+        s.spawn(move |_| {
+            common::init();
+            assert_eq!(&*tag, "hot");
+            assert_eq!(tag.as_rc().as_ref().unwrap().id, 7);
+        });
+    })
+    .unwrap();
+
+    cs().flush();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+    drop(tag);
+    while DROPS.load(Ordering::Relaxed) == 0 {
+        cs().flush();
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use circ::{cs, Cache, Rc, RcObject, RcuCell};
+use crossbeam_utils::thread;
+
+struct Counter {
+    value: usize,
+}
+
+unsafe impl RcObject for Counter {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn read_store() {
+    common::init();
+
+    let cell = RcuCell::new(Counter { value: 1 });
+    let guard = &cs();
+    let old = cell.read(guard);
+    cell.store(Rc::new(Counter { value: 2 }), guard);
+    assert_eq!(old.as_ref().unwrap().value, 1);
+    assert_eq!(cell.read(guard).as_ref().unwrap().value, 2);
+
+    let prev = cell.swap(Rc::new(Counter { value: 3 }));
+    assert_eq!(prev.as_ref().unwrap().value, 2);
+    assert_eq!(cell.load().as_ref().unwrap().value, 3);
+}
+
+#[test]
+fn concurrent_update() {
+    const THREADS: usize = 16;
+    const UPDATES_PER_THREAD: usize = 1000;
+
+    common::init();
+
+    let cell = &RcuCell::new(Counter { value: 0 });
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for _ in 0..UPDATES_PER_THREAD {
+                    let guard = &cs();
+                    cell.update(|c| Counter { value: c.value + 1 }, guard);
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    assert_eq!(
+        cell.load().as_ref().unwrap().value,
+        THREADS * UPDATES_PER_THREAD
+    );
+}
+
+#[test]
+fn cache_revalidates() {
+    common::init();
+
+    let cell = RcuCell::new(Counter { value: 0 });
+    let mut cache = Cache::new(&cell);
+    assert_eq!(cache.load().as_ref().unwrap().value, 0);
+
+    let generation = cell.generation();
+    cell.swap(Rc::new(Counter { value: 1 }));
+    assert_eq!(cell.generation(), generation + 1);
+    // `peek` does not revalidate, so it still sees the old value.
+    assert_eq!(cache.peek().as_ref().unwrap().value, 0);
+    assert_eq!(cache.load().as_ref().unwrap().value, 1);
+
+    cell.update(|c| Counter { value: c.value + 1 }, &cs());
+    assert_eq!(cache.load().as_ref().unwrap().value, 2);
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use circ::{cs, Collector, Rc, RcObject};
+use common::{drops, flush_until_dropped, Counted};
+use crossbeam_utils::thread::scope;
+
+struct Plain;
+
+unsafe impl RcObject for Plain {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+const ITEMS: usize = 256;
+
+#[test]
+fn handoff_to_other_threads() {
+    common::init();
+
+    scope(|s| {
+        s.spawn(|_| {
+            common::init();
+            circ::set_realtime(ITEMS);
+            for _ in 0..ITEMS {
+                let guard = &cs();
+                Rc::new(Counted(0)).finalize(guard);
+            }
+            // A real-time thread never reclaims by itself.
+            assert_eq!(drops(), 0);
+        });
+    })
+    .unwrap();
+
+    flush_until_dropped(ITEMS);
+}
+
+#[test]
+fn overflow() {
+    common::init();
+
+    let collector = Collector::new();
+    let handle = collector.register();
+    handle.set_realtime(4);
+    collector.enter(|| {
+        let guard = &handle.pin();
+        for _ in 0..10 {
+            Rc::new(Plain).finalize(guard);
+        }
+    });
+    assert_eq!(handle.realtime_overflows(), 6);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use circ::{cs, Collector, Rc};
+use common::{drops, flush_until_dropped, Counted};
+
+fn addr(rc: &Rc<Counted>) -> usize {
+    rc.as_ref().unwrap() as *const Counted as usize
+}
+
+#[test]
+fn reuse_reclaimed_allocation() {
+    common::init();
+
+    let collector = Collector::new();
+    collector.enter(|| {
+        let first = Rc::recycle_or_new(&collector, || Counted(1));
+        let first_addr = addr(&first);
+        drop(first);
+        flush_until_dropped(1);
+        // The allocation may be freed by a later deferred function than the object.
+        for _ in 0..8 {
+            cs().flush();
+        }
+
+        // The allocation of the reclaimed object is reused.
+        let second = Rc::recycle_or_new(&collector, || Counted(2));
+        assert_eq!(addr(&second), first_addr);
+        assert_eq!(second.as_ref().unwrap().0, 2);
+    });
+    drop(collector);
+    assert_eq!(drops(), 2);
+}
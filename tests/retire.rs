@@ -0,0 +1,44 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc, Rc};
+use common::{drops, flush_until_dropped, Counted};
+
+#[test]
+fn retire_unlinked() {
+    common::init();
+
+    let slot = AtomicRc::new(Counted(1));
+    {
+        let guard = &cs();
+        let snapshot = slot.load(Ordering::Acquire, guard);
+        let removed = slot.swap(Rc::new(Counted(2)), Ordering::AcqRel);
+        guard.retire(removed);
+
+        // The node is destructed only after the critical section.
+        guard.flush();
+        assert_eq!(snapshot.as_ref().unwrap().0, 1);
+        assert_eq!(drops(), 0);
+    }
+    flush_until_dropped(1);
+
+    // A node with other strong references survives.
+    let kept = slot.load(Ordering::Acquire, &cs()).counted();
+    cs().retire(slot.swap(Rc::null(), Ordering::AcqRel));
+    for _ in 0..16 {
+        cs().flush();
+    }
+    assert_eq!(drops(), 1);
+    assert_eq!(kept.as_ref().unwrap().0, 2);
+
+    // The count itself is released after the critical section, so the node can still be upgraded
+    // to in it.
+    let node = Rc::new(Counted(3));
+    let weak = node.downgrade();
+    {
+        let guard = &cs();
+        guard.retire(node);
+        assert!(weak.upgrade().is_some());
+    }
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use circ::runtime::{self, AdoptError};
+use circ::{cs, Collector};
+
+#[test]
+fn export_and_adopt() {
+    common::init();
+
+    // The default collector is already in use, so only itself can be adopted.
+    drop(cs());
+    assert_eq!(unsafe { runtime::adopt(runtime::export()) }, Ok(()));
+
+    let other = Collector::new();
+    let handle = other.enter(runtime::export);
+    assert_eq!(
+        unsafe { runtime::adopt(handle) },
+        Err(AdoptError::AlreadyInitialized)
+    );
+
+    let handle = other.enter(runtime::export);
+    unsafe { runtime::release(handle) };
+}
+
+#[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+#[test]
+#[should_panic(expected = "another collector")]
+fn mixed_collectors() {
+    use circ::{AtomicRc, Rc, RcObject};
+    use std::sync::atomic::Ordering;
+
+    struct Item;
+
+    unsafe impl RcObject for Item {
+        fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+    }
+
+    common::init();
+
+    let other = Collector::new();
+    let item = other.enter(|| AtomicRc::new(Item));
+    let guard = &cs();
+    let _ = item.load(Ordering::Acquire, guard);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, AtomicRc, Collector, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Node {
+    next: AtomicRc<Node>,
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.next.take());
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+const THREADS: usize = 4;
+const NODES: usize = 1000;
+
+#[test]
+fn isolated_domain() {
+    common::init();
+
+    let collector = Collector::new();
+    let head = collector.enter(AtomicRc::<Node>::null);
+
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                common::init();
+                collector.enter(|| {
+                    for _ in 0..NODES {
+                        let guard = &cs();
+                        let node = Rc::new(Node {
+                            next: AtomicRc::null(),
+                        });
+                        // Replace the head, leaving the old one as garbage of `collector`.
+                        head.swap(node, Ordering::AcqRel).finalize(guard);
+                    }
+                });
+            });
+        }
+    })
+    .unwrap();
+
+    collector.enter(|| drop(head));
+    // Tearing down the collector reclaims everything it was withholding.
+    drop(collector);
+    assert_eq!(DROPS.load(Ordering::Relaxed), THREADS * NODES);
+}
+
+#[test]
+#[cfg(all(debug_assertions, not(feature = "unsafe-layout")))]
+#[should_panic(expected = "another collector")]
+fn store_across_collectors() {
+    common::init();
+
+    let collector = Collector::new();
+    let head = collector.enter(|| AtomicRc::<Node>::null());
+    let node = Rc::new(Node {
+        next: AtomicRc::null(),
+    });
+    // The node belongs to the default collector, but is stored in a critical section of another.
+    collector.enter(|| head.store(node, Ordering::Release, &cs()));
+}
@@ -0,0 +1,280 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+use circ::serde::{DeserializeShared, Ids, Registry, SerializeShared};
+use circ::{cs, AtomicRc, Rc, RcObject};
+use serde::de::{DeserializeSeed, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+
+struct Node {
+    value: u32,
+    children: Vec<Rc<Node>>,
+}
+
+impl Node {
+    fn new(value: u32, children: Vec<Rc<Node>>) -> Rc<Self> {
+        Rc::new(Self { value, children })
+    }
+}
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.append(&mut self.children);
+    }
+}
+
+impl SerializeShared for Node {
+    fn serialize_shared<S: Serializer>(
+        &self,
+        serializer: S,
+        ids: &Ids<Self>,
+    ) -> Result<S::Ok, S::Error> {
+        let children: Vec<_> = self.children.iter().map(|child| ids.rc(child)).collect();
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&self.value)?;
+        tuple.serialize_element(&children)?;
+        tuple.end()
+    }
+}
+
+struct NodeVisitor<'r>(&'r Registry<Node>);
+
+impl<'de> Visitor<'de> for NodeVisitor<'_> {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a node")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let value = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let children = seq
+            .next_element_seed(Children(self.0))?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        Ok(Node { value, children })
+    }
+}
+
+struct Children<'r>(&'r Registry<Node>);
+
+impl<'de> DeserializeSeed<'de> for Children<'_> {
+    type Value = Vec<Rc<Node>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Children<'_> {
+    type Value = Vec<Rc<Node>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut children = Vec::new();
+        while let Some(child) = seq.next_element_seed(self.0.seed())? {
+            children.push(child);
+        }
+        Ok(children)
+    }
+}
+
+impl<'de> DeserializeShared<'de> for Node {
+    fn deserialize_shared<D: Deserializer<'de>>(
+        deserializer: D,
+        registry: &Registry<Self>,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, NodeVisitor(registry))
+    }
+}
+
+fn from_json(json: &str, registry: &Registry<Node>) -> serde_json::Result<Rc<Node>> {
+    registry
+        .seed()
+        .deserialize(&mut serde_json::Deserializer::from_str(json))
+}
+
+#[test]
+fn shared_nodes_round_trip() {
+    common::init();
+
+    // Every layer points to both nodes of the layer below, which makes a tree of 2^10 leaves.
+    let mut layer = vec![Node::new(0, Vec::new()), Node::new(1, Vec::new())];
+    for depth in 1..10 {
+        layer = (0..2)
+            .map(|i| Node::new(depth * 2 + i, layer.clone()))
+            .collect();
+    }
+    let root = Node::new(100, layer);
+
+    let ids = Ids::new();
+    let json = serde_json::to_string(&ids.rc(&root)).unwrap();
+    let registry = Registry::new();
+    let copy = from_json(&json, &registry).unwrap();
+    assert_eq!(registry.len(), 21);
+
+    let mut node = copy.as_ref().unwrap();
+    assert_eq!(node.value, 100);
+    while !node.children.is_empty() {
+        let (a, b) = (&node.children[0], &node.children[1]);
+        let (a, b) = (a.as_ref().unwrap(), b.as_ref().unwrap());
+        assert!(a.children.iter().zip(&b.children).all(|(x, y)| x.ptr_eq(y)));
+        node = a;
+    }
+    assert_eq!(node.value, 0);
+}
+
+#[test]
+fn null() {
+    common::init();
+
+    let ids = Ids::<Node>::new();
+    let json = serde_json::to_string(&ids.rc(&Rc::null())).unwrap();
+    assert_eq!(json, "null");
+    let registry = Registry::new();
+    assert!(from_json(&json, &registry).unwrap().is_null());
+    assert!(registry.is_empty());
+}
+
+#[test]
+fn malformed() {
+    common::init();
+
+    let registry = Registry::new();
+    let err = from_json("[0,[1,[[1,null]]]]", &registry).err().unwrap();
+    assert!(err
+        .to_string()
+        .contains("node 1 is referenced by an `Rc` before"));
+
+    let registry = Registry::new();
+    let err = from_json("[0,[1,[[1,[2,[]]],[1,[3,[]]]]]]", &registry)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("defined twice"));
+}
+
+/// A node of a ring, whose last edge closes a cycle.
+struct Ring {
+    value: u32,
+    next: AtomicRc<Ring>,
+}
+
+unsafe impl RcObject for Ring {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.next.take());
+    }
+}
+
+impl SerializeShared for Ring {
+    fn serialize_shared<S: Serializer>(
+        &self,
+        serializer: S,
+        ids: &Ids<Self>,
+    ) -> Result<S::Ok, S::Error> {
+        let guard = &cs();
+        let next = ids.snapshot(self.next.load(Ordering::Acquire, guard));
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&self.value)?;
+        tuple.serialize_element(&next)?;
+        tuple.end()
+    }
+}
+
+struct RingVisitor<'r>(&'r Registry<Ring>);
+
+impl<'de> Visitor<'de> for RingVisitor<'_> {
+    type Value = Ring;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a ring node")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Ring, A::Error> {
+        let value = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let next = seq
+            .next_element_seed(self.0.atomic_seed())?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        Ok(Ring { value, next })
+    }
+}
+
+impl<'de> DeserializeShared<'de> for Ring {
+    fn deserialize_shared<D: Deserializer<'de>>(
+        deserializer: D,
+        registry: &Registry<Self>,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, RingVisitor(registry))
+    }
+
+    fn atomic_edges(&self) -> Vec<&AtomicRc<Self>> {
+        vec![&self.next]
+    }
+}
+
+#[test]
+fn cycle_round_trip() {
+    common::init();
+
+    let nodes: Vec<_> = (0..5)
+        .map(|value| {
+            Rc::new(Ring {
+                value,
+                next: AtomicRc::null(),
+            })
+        })
+        .collect();
+    let guard = &cs();
+    for (i, node) in nodes.iter().enumerate() {
+        let next = nodes[(i + 1) % nodes.len()].clone();
+        node.as_ref()
+            .unwrap()
+            .next
+            .store(next, Ordering::Release, guard);
+    }
+
+    let ids = Ids::new();
+    let json = serde_json::to_string(&ids.rc(&nodes[0])).unwrap();
+    let registry = Registry::<Ring>::new();
+    let copy = registry
+        .seed()
+        .deserialize(&mut serde_json::Deserializer::from_str(&json))
+        .unwrap();
+    assert_eq!(registry.len(), 5);
+
+    let mut node = copy.snapshot(guard);
+    for i in 0..5 {
+        assert_eq!(node.as_ref().unwrap().value, i);
+        node = node.as_ref().unwrap().next.load(Ordering::Acquire, guard);
+    }
+    assert!(node.ptr_eq(copy.snapshot(guard)));
+
+    // Break the cycles, so that the nodes are reclaimed.
+    for node in nodes.iter().chain([&copy]) {
+        node.as_ref()
+            .unwrap()
+            .next
+            .store(Rc::null(), Ordering::Release, guard);
+    }
+}
+
+#[test]
+fn cycle_through_rc() {
+    common::init();
+
+    // The child of the root points back to the root through an `Rc`.
+    let registry = Registry::new();
+    let err = from_json("[0,[1,[[1,[2,[[0,null]]]]]]]", &registry)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("only an `AtomicRc` edge may do"));
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use circ::{cs, Collector, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+struct Item {
+    drops: Arc<AtomicUsize>,
+}
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Item {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+const THREADS: usize = 16;
+const ITEMS: usize = 1024;
+
+#[test]
+fn reclaim_across_shards() {
+    common::init();
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder().shards(8).build();
+    assert_eq!(collector.shards(), 8);
+
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                common::init();
+                collector.enter(|| {
+                    for _ in 0..ITEMS {
+                        let guard = &cs();
+                        let drops = drops.clone();
+                        Rc::new(Item { drops }).finalize(guard);
+                    }
+                    cs().flush();
+                });
+            });
+        }
+    })
+    .unwrap();
+
+    // A single thread steals the expired garbage of every shard.
+    for _ in 0..8 {
+        collector.advance_frame();
+    }
+    assert_eq!(drops.load(Ordering::Relaxed), THREADS * ITEMS);
+}
+
+#[test]
+#[should_panic]
+fn zero_shards() {
+    let _ = Collector::builder().shards(0);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use circ::Rc;
+use common::{drops, flush_until_dropped, Counted};
+use crossbeam_utils::thread::scope;
+
+const THREADS: usize = 8;
+
+#[test]
+fn clone_and_drop_across_threads() {
+    common::init();
+
+    let root = Rc::new_sharded(Counted(3), 4);
+    assert_eq!(root.shard_count(), 4);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                common::init();
+                let root = root.clone();
+                let mut clones = Vec::new();
+                for _ in 0..1000 {
+                    clones.push(root.clone());
+                    if clones.len() > 16 {
+                        clones.clear();
+                    }
+                }
+                assert!(clones.iter().all(|clone| clone.ptr_eq(&root)));
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(root.as_ref().unwrap().0, 3);
+    assert_eq!(drops(), 0);
+
+    drop(root);
+    flush_until_dropped(1);
+}
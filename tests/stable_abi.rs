@@ -0,0 +1,47 @@
+#![cfg(feature = "abi_stable")]
+
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use abi_stable::StableAbi;
+use circ::stable_abi::{StableAtomicRc, StableRc};
+use circ::{cs, Rc, RcObject};
+
+#[repr(C)]
+#[derive(StableAbi)]
+struct Item {
+    value: u64,
+}
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn round_trip() {
+    common::init();
+
+    let _ = <StableRc<Item> as StableAbi>::LAYOUT;
+    let _ = <StableAtomicRc<Item> as StableAbi>::LAYOUT;
+
+    let first = StableRc::new(Item { value: 1 });
+    let atomic = StableAtomicRc::from(first.clone());
+    let guard = &cs();
+    assert!(atomic
+        .load(Ordering::Acquire, guard)
+        .ptr_eq(first.snapshot(guard)));
+
+    let second = StableRc::new(Item { value: 2 });
+    let old = StableRc::from(atomic.swap(second.into_rc(), Ordering::AcqRel));
+    assert!(old.ptr_eq(&first));
+    assert_eq!(
+        atomic
+            .load(Ordering::Acquire, guard)
+            .as_ref()
+            .unwrap()
+            .value,
+        2
+    );
+    assert_eq!(Rc::from(old).as_ref().unwrap().value, 1);
+}
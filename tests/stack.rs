@@ -0,0 +1,70 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{
+    cs,
+    ds::{EliminationStack, Stack},
+    Rc, RcObject,
+};
+use crossbeam_utils::thread;
+
+struct Item(usize);
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn push_pop() {
+    common::init();
+
+    let stack = Stack::new();
+    let guard = &cs();
+    assert!(stack.is_empty(guard));
+    for i in 0..10 {
+        stack.push(Rc::new(Item(i)), guard);
+    }
+    assert!(!stack.is_empty(guard));
+    for i in (0..10).rev() {
+        assert_eq!(stack.pop(guard).unwrap().as_ref().unwrap().0, i);
+    }
+    assert!(stack.pop(guard).is_none());
+}
+
+#[test]
+fn elimination_push_pop() {
+    const THREADS: usize = 16;
+    const ELEMENTS_PER_THREAD: usize = 10_000;
+
+    common::init();
+
+    let stack = &EliminationStack::with_slots(4);
+    let sum = &AtomicUsize::new(0);
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let guard = &cs();
+                    if t % 2 == 0 {
+                        stack.push(Rc::new(Item(i)), guard);
+                    } else if let Some(item) = stack.pop(guard) {
+                        sum.fetch_add(item.as_ref().unwrap().0, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    while let Some(item) = stack.pop(guard) {
+        sum.fetch_add(item.as_ref().unwrap().0, Ordering::Relaxed);
+    }
+    let pushers = THREADS / 2;
+    assert_eq!(
+        sum.load(Ordering::Relaxed),
+        pushers * ELEMENTS_PER_THREAD * (ELEMENTS_PER_THREAD - 1) / 2
+    );
+}
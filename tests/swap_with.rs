@@ -0,0 +1,51 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc};
+use common::{drops, flush_until_dropped, Counted};
+use crossbeam_utils::thread::scope;
+
+#[test]
+fn double_buffer() {
+    common::init();
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 1000;
+
+    let front = AtomicRc::new(Counted(0));
+    let back = AtomicRc::new(Counted(1));
+    {
+        let guard = &cs();
+        assert!(front.swap_with(&front, guard));
+        assert!(front.swap_with(&back, guard));
+        assert_eq!(front.load(Ordering::Acquire, guard).as_ref().unwrap().0, 1);
+        assert_eq!(back.load(Ordering::Acquire, guard).as_ref().unwrap().0, 0);
+    }
+
+    scope(|s| {
+        for i in 0..THREADS {
+            let (front, back) = (&front, &back);
+            s.spawn(move |_| {
+                common::init();
+                for _ in 0..ITERS {
+                    let guard = &cs();
+                    if i % 2 == 0 {
+                        front.swap_with(back, guard);
+                    } else {
+                        back.swap_with(front, guard);
+                    }
+                    assert!(!front.load(Ordering::Acquire, guard).is_null());
+                    assert!(!back.load(Ordering::Acquire, guard).is_null());
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    // Every buffer is released once both slots are dropped.
+    drop(front);
+    drop(back);
+    flush_until_dropped(2);
+    assert_eq!(drops(), 2);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc, Rc, RcObject, Snapshot};
+
+#[derive(Debug)]
+struct Item(usize);
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn load_and_store() {
+    common::init();
+
+    let guard = &cs();
+    let slot = AtomicRc::<Item>::null_with_tag(2);
+    let current = slot.load(Ordering::Acquire, guard);
+    assert!(current.is_null());
+    assert_eq!(current.tag(), 2);
+    assert!(current.ptr_eq(Snapshot::null_with_tag(2)));
+    assert!(!current.ptr_eq(Snapshot::null()));
+
+    slot.store(Rc::new(Item(1)), Ordering::Release, guard);
+    assert_eq!(slot.load(Ordering::Acquire, guard).tag(), 0);
+
+    slot.store(Rc::null_with_tag(3), Ordering::Release, guard);
+    let current = slot.load(Ordering::Acquire, guard);
+    assert!(current.is_null());
+    assert_eq!(current.tag(), 3);
+
+    let old = slot.swap(Rc::null(), Ordering::AcqRel);
+    assert!(old.is_null());
+    assert_eq!(old.tag(), 3);
+}
+
+#[test]
+fn compare_exchange() {
+    common::init();
+
+    let guard = &cs();
+    let slot = AtomicRc::null_with_tag(1);
+
+    // An untagged null does not match a tagged one.
+    let err = slot
+        .compare_exchange(
+            Snapshot::null(),
+            Rc::new(Item(1)),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            guard,
+        )
+        .unwrap_err();
+    assert!(err.current.is_null());
+    assert_eq!(err.current.tag(), 1);
+
+    // From a tagged null to a real pointer.
+    let Ok(old) = slot.compare_exchange(
+        err.current,
+        err.desired,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        guard,
+    ) else {
+        panic!("the exchange from the tagged null failed");
+    };
+    assert!(old.is_null());
+    assert_eq!(old.tag(), 1);
+    let current = slot.load(Ordering::Acquire, guard);
+    assert_eq!(current.as_ref().unwrap().0, 1);
+
+    // From a real pointer back to a tagged null.
+    let Ok(old) = slot.compare_exchange(
+        current,
+        Rc::null_with_tag(2),
+        Ordering::AcqRel,
+        Ordering::Acquire,
+        guard,
+    ) else {
+        panic!("the exchange to the tagged null failed");
+    };
+    assert_eq!(old.as_ref().unwrap().0, 1);
+    old.finalize(guard);
+
+    // Between tagged nulls.
+    let current = slot.load(Ordering::Acquire, guard);
+    assert_eq!(current.tag(), 2);
+    assert!(slot
+        .compare_exchange_tag(current, 3, Ordering::AcqRel, Ordering::Acquire, guard)
+        .is_ok());
+    assert_eq!(slot.load(Ordering::Acquire, guard).tag(), 3);
+}
+
+#[test]
+fn mark_empty_slot() {
+    common::init();
+
+    let guard = &cs();
+    let slot = AtomicRc::<Item>::null();
+    let marked = slot.try_mark(Snapshot::null(), guard).unwrap();
+    assert!(marked.is_null() && marked.is_marked());
+    assert!(slot.try_mark(Snapshot::null(), guard).is_err());
+    assert!(slot.load(Ordering::Acquire, guard).ptr_eq(marked));
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::cs;
+use circ::thin::{AtomicThinRc, ThinRc, ThinSlice};
+use crossbeam_utils::thread::scope;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+struct Elem(u64);
+
+impl Drop for Elem {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn single_word() {
+    assert_eq!(size_of::<ThinRc<Elem>>(), size_of::<usize>());
+    assert_eq!(size_of::<AtomicThinRc<Elem>>(), size_of::<usize>());
+}
+
+#[test]
+fn compare_exchange_slices() {
+    common::init();
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 500;
+
+    let shared = AtomicThinRc::new(ThinSlice::from_vec(vec![]));
+    scope(|s| {
+        for _ in 0..THREADS {
+            let shared = &shared;
+            s.spawn(move |_| {
+                common::init();
+                for _ in 0..ITERS {
+                    let guard = &cs();
+                    loop {
+                        let old = shared.load(Ordering::Acquire, guard).unwrap();
+                        let mut elems = old.to_vec();
+                        elems.push(Elem(elems.len() as u64));
+                        let new = ThinSlice::from_vec(elems);
+                        if shared
+                            .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire)
+                            .is_ok()
+                        {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    {
+        let guard = &cs();
+        let last = shared.load(Ordering::Acquire, guard).unwrap();
+        assert_eq!(last.len(), THREADS * ITERS);
+        assert!(last.iter().enumerate().all(|(i, e)| e.0 == i as u64));
+    }
+    drop(shared);
+
+    // Every slice but the last one was replaced and all of its elements dropped.
+    let total: usize = (1..=THREADS * ITERS).sum();
+    while DROPS.load(Ordering::Relaxed) < total {
+        cs().flush();
+    }
+}
+
+#[test]
+fn conversions() {
+    common::init();
+
+    let from_vec = ThinRc::from(vec![String::from("a"), String::from("b")]);
+    assert_eq!(from_vec.as_ref().unwrap().as_slice(), ["a", "b"]);
+
+    let boxed: Box<[u32]> = vec![1, 2, 3].into_boxed_slice();
+    let from_box = ThinRc::from(boxed);
+    assert_eq!(&from_box.as_ref().unwrap()[..], &[1, 2, 3]);
+
+    let from_string = ThinRc::from(String::from("circ"));
+    assert_eq!(from_string.as_ref().unwrap().as_slice(), b"circ");
+
+    let from_slice = ThinRc::from(&[4u32, 5][..]);
+    let shared = AtomicThinRc::from(from_slice);
+    let guard = &cs();
+    assert_eq!(&shared.load(Ordering::Acquire, guard).unwrap()[..], &[4, 5]);
+}
+
+#[test]
+fn string_conversions() {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::path::Path;
+
+    common::init();
+
+    let c_string = CString::new("circ").unwrap();
+    let from_c_str = ThinRc::from(c_string.as_c_str());
+    let bytes = from_c_str.as_ref().unwrap().as_slice();
+    assert_eq!(
+        CStr::from_bytes_with_nul(bytes).unwrap(),
+        c_string.as_c_str()
+    );
+
+    let path = Path::new("/tmp/circ");
+    let from_path = ThinRc::from(path);
+    let bytes = from_path.as_ref().unwrap().as_slice();
+    let os_str = unsafe { OsStr::from_encoded_bytes_unchecked(bytes) };
+    assert_eq!(Path::new(os_str), path);
+
+    let from_str = ThinRc::from("circ");
+    assert_eq!(from_str.as_ref().unwrap().as_slice(), b"circ");
+}
+
+#[test]
+fn iterate() {
+    common::init();
+
+    let rc = ThinRc::from(vec![1u32, 2, 3]);
+    let mut sum = 0;
+    for elem in &rc {
+        sum += elem;
+    }
+    assert_eq!(sum, 6);
+    assert!((&ThinRc::<u32>::null()).into_iter().next().is_none());
+
+    let shared = AtomicThinRc::from(rc);
+    let guard = &cs();
+    let slice = shared.load(Ordering::Acquire, guard).unwrap();
+    let doubled: Vec<u32> = slice.into_iter().map(|elem| elem * 2).collect();
+    assert_eq!(doubled, [2, 4, 6]);
+}
+
+#[test]
+fn collect() {
+    common::init();
+
+    let exact: ThinRc<u64> = (0..100).map(|i| i * i).collect();
+    assert_eq!(exact.as_ref().unwrap().len(), 100);
+    assert!(exact
+        .as_ref()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .all(|(i, &e)| e == (i * i) as u64));
+
+    let filtered: ThinRc<String> = (0..10)
+        .filter(|i| i % 3 == 0)
+        .map(|i| i.to_string())
+        .collect();
+    assert_eq!(filtered.as_ref().unwrap().as_slice(), ["0", "3", "6", "9"]);
+
+    let empty: ThinRc<String> = std::iter::empty().collect();
+    assert!(empty.as_ref().unwrap().is_empty());
+}
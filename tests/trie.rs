@@ -0,0 +1,94 @@
+mod common;
+
+use circ::{cs, ds::Trie};
+use crossbeam_utils::thread;
+
+#[test]
+fn insert_remove() {
+    common::init();
+
+    let trie = Trie::new();
+    let guard = &cs();
+    assert_eq!(trie.insert(b"", 0, guard), None);
+    assert_eq!(trie.insert(b"ab", 1, guard), None);
+    assert_eq!(trie.insert(b"abc", 2, guard), None);
+    assert_eq!(trie.insert(b"b", 3, guard), None);
+    assert_eq!(trie.insert(b"ab", 4, guard), Some(&1));
+
+    assert_eq!(trie.get(b"", guard), Some(&0));
+    assert_eq!(trie.get(b"ab", guard), Some(&4));
+    assert_eq!(trie.get(b"a", guard), None);
+    assert!(!trie.contains_key(b"abcd", guard));
+
+    let entries: Vec<_> = trie.iter(guard).map(|(k, v)| (k.to_vec(), *v)).collect();
+    assert_eq!(
+        entries,
+        [
+            (b"".to_vec(), 0),
+            (b"ab".to_vec(), 4),
+            (b"abc".to_vec(), 2),
+            (b"b".to_vec(), 3)
+        ]
+    );
+
+    assert_eq!(trie.remove(b"abc", guard), Some(&2));
+    assert_eq!(trie.remove(b"abc", guard), None);
+    assert_eq!(trie.get(b"ab", guard), Some(&4));
+    assert_eq!(trie.remove(b"ab", guard), Some(&4));
+    let keys: Vec<_> = trie.iter(guard).map(|(k, _)| k).collect();
+    assert_eq!(keys, [&b""[..], &b"b"[..]]);
+}
+
+#[test]
+fn prefix_iter() {
+    common::init();
+
+    let trie = Trie::new();
+    let guard = &cs();
+    for i in 0..1000u32 {
+        trie.insert(format!("key{}", i).as_bytes(), i, guard);
+    }
+    let values: Vec<_> = trie.prefix_iter(b"key99", guard).map(|(_, v)| *v).collect();
+    assert_eq!(
+        values,
+        [99, 990, 991, 992, 993, 994, 995, 996, 997, 998, 999]
+    );
+    assert_eq!(trie.prefix_iter(b"kex", guard).count(), 0);
+    assert_eq!(trie.prefix_iter(b"", guard).count(), 1000);
+}
+
+#[test]
+fn concurrent_insert_remove() {
+    const THREADS: u32 = 8;
+    const ELEMENTS_PER_THREAD: u32 = 1000;
+
+    common::init();
+
+    let trie = &Trie::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move |_| {
+                common::init();
+                // Neighbouring keys share most of their paths, so removals race with insertions
+                // on the same nodes.
+                for i in 0..ELEMENTS_PER_THREAD {
+                    let key = (i * THREADS + t).to_be_bytes();
+                    let guard = &cs();
+                    assert_eq!(trie.insert(&key, i, guard), None);
+                    if i % 2 == 0 {
+                        assert_eq!(trie.remove(&key, guard), Some(&i));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    let keys: Vec<_> = trie.iter(guard).map(|(k, _)| k.to_vec()).collect();
+    let expected: Vec<_> = (0..THREADS * ELEMENTS_PER_THREAD)
+        .filter(|k| (k / THREADS) % 2 == 1)
+        .map(|k| k.to_be_bytes().to_vec())
+        .collect();
+    assert_eq!(keys, expected);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use circ::{cs, AtomicRc, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+#[derive(Debug)]
+struct Node(usize);
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn keeps_other_tag_bits() {
+    common::init();
+
+    let guard = &cs();
+    let link = AtomicRc::from(Rc::new(Node(0)).with_tag(2));
+    let current = link.load(Ordering::Acquire, guard);
+    assert!(!current.is_marked());
+    assert_eq!(current.as_ref().unwrap().0, 0);
+
+    let marked = link.try_mark(current, guard).unwrap();
+    assert!(marked.is_marked());
+    assert_eq!(marked.tag(), 3);
+    assert_eq!(marked.unmarked().tag(), 2);
+    assert!(marked.unmarked().ptr_eq(current));
+    assert!(link.load(Ordering::Acquire, guard).ptr_eq(marked));
+
+    // A marked pointer cannot be marked again.
+    let err = link.try_mark(marked, guard).unwrap_err();
+    assert!(err.ptr_eq(marked));
+}
+
+#[test]
+fn one_winner() {
+    common::init();
+
+    const THREADS: usize = 8;
+
+    for _ in 0..100 {
+        let link = AtomicRc::new(Node(1));
+        let winners = AtomicUsize::new(0);
+        scope(|s| {
+            for _ in 0..THREADS {
+                let (link, winners) = (&link, &winners);
+                s.spawn(move |_| {
+                    common::init();
+                    let guard = &cs();
+                    let current = link.load(Ordering::Acquire, guard);
+                    if link.try_mark(current.unmarked(), guard).is_ok() {
+                        winners.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(winners.load(Ordering::Relaxed), 1);
+    }
+}
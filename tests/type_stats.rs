@@ -0,0 +1,67 @@
+#![cfg(feature = "type-stats")]
+
+mod common;
+
+use std::time::Duration;
+
+use circ::{cs, Collector, Rc, RcObject};
+
+struct Leaf;
+
+unsafe impl RcObject for Leaf {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+struct Branch(Rc<Branch>);
+
+unsafe impl RcObject for Branch {
+    fn pop_edges(&mut self, out: &mut Vec<Rc<Self>>) {
+        out.push(self.0.clone());
+    }
+}
+
+#[test]
+fn counts_per_type() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    collector.enter(|| {
+        let guard = &cs();
+        for _ in 0..3 {
+            Rc::new(Leaf).finalize(guard);
+        }
+        Rc::new(Branch(Rc::null())).finalize(guard);
+    });
+
+    let stats = collector.type_stats();
+    assert_eq!(stats.len(), 2);
+    assert!(stats[0].type_name.ends_with("Leaf"));
+    assert_eq!(stats[0].retired, 3);
+    assert_eq!(stats[0].pending(), 3);
+    assert!(stats[1].type_name.ends_with("Branch"));
+    assert_eq!(stats[1].retired, 1);
+
+    assert_eq!(collector.collect_all(Duration::from_secs(10)).remaining, 0);
+    let stats = collector.type_stats();
+    assert!(stats.iter().all(|stats| stats.reclaimed == stats.retired));
+}
+
+#[test]
+fn deferred_release_is_not_an_object() {
+    common::init();
+
+    let collector = Collector::builder().deterministic(true).build();
+    let leaf = collector.enter(|| {
+        let leaf = Rc::new(Leaf);
+        cs().retire(leaf.clone());
+        leaf
+    });
+    assert_eq!(collector.collect_all(Duration::from_secs(10)).remaining, 0);
+    // Releasing the reference has not destructed the object, which is still alive.
+    assert!(collector.type_stats().is_empty());
+
+    collector.enter(|| leaf.finalize(&cs()));
+    let stats = collector.type_stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].retired, 1);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "unsafe-layout")]
+
+mod common;
+
+use std::mem::align_of;
+
+use circ::layout::{
+    from_raw_payload, header_of, into_raw_payload, payload_of, payload_offset, rc_from_ref,
+    HEADER_ALIGN, HEADER_SIZE,
+};
+use circ::{cs, AtomicRc, Rc, RcObject};
+
+struct Node(u64);
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[repr(align(64))]
+struct Padded(u8);
+
+unsafe impl RcObject for Padded {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn offsets() {
+    assert_eq!(HEADER_SIZE, 8);
+    assert_eq!(HEADER_ALIGN, align_of::<u64>());
+    assert_eq!(payload_offset::<Node>(), HEADER_SIZE);
+    assert_eq!(payload_offset::<Padded>(), 64);
+
+    let payload = 0x1000 as *const Padded;
+    assert_eq!(header_of(payload) as usize, 0x1000 - 64);
+    assert_eq!(payload_of::<Padded>(header_of(payload)), payload);
+}
+
+#[test]
+fn payload_round_trip() {
+    common::init();
+
+    let rc = Rc::new(Padded(3));
+    let expected: *const Padded = rc.as_ref().unwrap();
+    let payload = into_raw_payload(rc);
+    assert_eq!(payload, expected);
+    assert_eq!(payload as usize % 64, 0);
+    let rc = unsafe { from_raw_payload(payload) };
+    assert_eq!(rc.as_ref().unwrap().0, 3);
+
+    assert!(into_raw_payload(Rc::<Node>::null()).is_null());
+    assert!(unsafe { from_raw_payload::<Node>(std::ptr::null()) }.is_null());
+}
+
+#[test]
+fn strong_reference_from_payload() {
+    common::init();
+
+    let slot = AtomicRc::new(Node(7));
+    let rc = {
+        let guard = &cs();
+        let node = slot.load(std::sync::atomic::Ordering::Acquire, guard);
+        unsafe { rc_from_ref(node.as_ref().unwrap()) }
+    };
+    drop(slot);
+
+    // The new reference keeps the object alive.
+    for _ in 0..16 {
+        cs().flush();
+    }
+    assert_eq!(rc.as_ref().unwrap().0, 7);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use circ::versioned::VersionedAtomicRc;
+use circ::{cs, Rc, RcObject};
+use crossbeam_utils::thread::scope;
+
+#[derive(Debug)]
+struct Node(usize);
+
+unsafe impl RcObject for Node {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn detects_aba() {
+    common::init();
+
+    let head = VersionedAtomicRc::new(Node(0));
+    let guard = &cs();
+    let (first, version) = head.load(guard);
+    assert_eq!(version, 0);
+
+    let old = head.swap(Rc::new(Node(1)));
+    head.store(old, guard);
+    assert_eq!(head.version(), 2);
+
+    let err = head
+        .compare_exchange(first, version, Rc::new(Node(2)), guard)
+        .unwrap_err();
+    let (current, current_version) = err.current;
+    assert!(current.ptr_eq(first));
+    assert_eq!(current_version, 2);
+
+    let Ok(old) = head.compare_exchange(current, current_version, err.desired, guard) else {
+        panic!("the exchange with the current version failed");
+    };
+    assert_eq!(old.as_ref().unwrap().0, 0);
+    let (current, version) = head.load(guard);
+    assert_eq!(current.as_ref().unwrap().0, 2);
+    assert_eq!(version, 3);
+}
+
+#[test]
+fn concurrent_updates() {
+    common::init();
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 1000;
+
+    let head = VersionedAtomicRc::new(Node(0));
+    scope(|s| {
+        for _ in 0..THREADS {
+            let head = &head;
+            s.spawn(move |_| {
+                common::init();
+                for _ in 0..ITERS {
+                    let guard = &cs();
+                    let (mut current, mut version) = head.load(guard);
+                    let mut desired = Rc::new(Node(current.as_ref().unwrap().0 + 1));
+                    while let Err(err) = head.compare_exchange(current, version, desired, guard) {
+                        (current, version) = err.current;
+                        desired = Rc::new(Node(current.as_ref().unwrap().0 + 1));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = &cs();
+    let (current, version) = head.load(guard);
+    assert_eq!(current.as_ref().unwrap().0, THREADS * ITERS);
+    assert_eq!(version, (THREADS * ITERS) as u64);
+}
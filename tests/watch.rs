@@ -0,0 +1,97 @@
+mod common;
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+
+use circ::{cs, watch, Rc, RcObject};
+use crossbeam_utils::thread;
+
+struct Config(usize);
+
+unsafe impl RcObject for Config {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn send_receive() {
+    common::init();
+
+    let (tx, mut rx) = watch::channel(Rc::new(Config(0)));
+    let mut rx2 = rx.clone();
+    assert_eq!(tx.receiver_count(), 2);
+    assert_eq!(rx.has_changed(), Ok(false));
+
+    tx.send(Rc::new(Config(1)));
+    tx.send(Rc::new(Config(2)));
+    assert_eq!(rx.has_changed(), Ok(true));
+    assert_eq!(rx.version(), 2);
+
+    // Only the latest value is observed.
+    assert_eq!(rx.wait_changed(), Ok(()));
+    assert_eq!(rx.load().as_ref().unwrap().0, 2);
+    assert_eq!(rx.has_changed(), Ok(false));
+    assert_eq!(rx2.borrow_and_update(&cs()).as_ref().unwrap().0, 2);
+
+    let rx3 = tx.subscribe();
+    assert_eq!(rx3.has_changed(), Ok(false));
+
+    drop(tx);
+    assert!(rx.has_changed().is_err());
+    assert!(rx.wait_changed().is_err());
+    assert_eq!(rx.borrow(&cs()).as_ref().unwrap().0, 2);
+}
+
+#[test]
+fn wait_changed() {
+    const VALUES: usize = 100;
+
+    common::init();
+
+    let (tx, rx) = watch::channel(Rc::new(Config(0)));
+    thread::scope(|s| {
+        for _ in 0..4 {
+            let mut rx = rx.clone();
+            s.spawn(move |_| {
+                common::init();
+                let mut last = 0;
+                while rx.wait_changed().is_ok() {
+                    let value = rx.borrow(&cs()).as_ref().unwrap().0;
+                    assert!(value >= last);
+                    last = value;
+                }
+                assert_eq!(rx.borrow(&cs()).as_ref().unwrap().0, VALUES);
+            });
+        }
+        for i in 1..=VALUES {
+            tx.send(Rc::new(Config(i)));
+        }
+        drop(tx);
+    })
+    .unwrap();
+}
+
+#[test]
+fn changed_future() {
+    struct Flag(std::sync::atomic::AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    common::init();
+
+    let (tx, mut rx) = watch::channel(Rc::new(Config(0)));
+    let flag = Arc::new(Flag(Default::default()));
+    let waker = flag.clone().into();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut changed = pin!(rx.changed());
+    assert_eq!(changed.as_mut().poll(&mut cx), Poll::Pending);
+    tx.send(Rc::new(Config(1)));
+    assert!(flag.0.load(std::sync::atomic::Ordering::SeqCst));
+    assert_eq!(changed.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+}
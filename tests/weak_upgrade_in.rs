@@ -0,0 +1,55 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use circ::{cs, AtomicWeak, Collector, Rc, RcObject, Weak};
+
+struct Item {
+    value: usize,
+    drops: Arc<AtomicUsize>,
+}
+
+unsafe impl RcObject for Item {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+impl Drop for Item {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn upgrade_in() {
+    common::init();
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let collector = Collector::builder().deterministic(true).build();
+    collector.enter(|| {
+        let rc = Rc::new(Item {
+            value: 7,
+            drops: drops.clone(),
+        });
+        let weak = rc.downgrade();
+        let atomic = AtomicWeak::from(&rc);
+
+        {
+            let guard = &cs();
+            let snapshot = weak.upgrade_in(guard).unwrap();
+            assert_eq!(snapshot.as_ref().unwrap().value, 7);
+            let snapshot = atomic.upgrade_in(Ordering::Acquire, guard).unwrap();
+            assert_eq!(snapshot.as_ref().unwrap().value, 7);
+            assert!(Weak::<Item>::null().upgrade_in(guard).unwrap().is_null());
+        }
+
+        drop(rc);
+        collector.collect_all(Duration::from_secs(10));
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+        let guard = &cs();
+        assert!(weak.upgrade_in(guard).is_none());
+        assert!(atomic.upgrade_in(Ordering::Acquire, guard).is_none());
+    });
+}
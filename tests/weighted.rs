@@ -0,0 +1,40 @@
+mod common;
+
+use circ::weighted::WeightedRc;
+use circ::Rc;
+use common::{flush_until_dropped, Counted};
+use crossbeam_utils::thread::scope;
+
+/// Forks `depth` levels of tasks, each holding a clone of `task`.
+fn fork(task: WeightedRc<Counted>, depth: usize) -> usize {
+    if depth == 0 {
+        return task.as_ref().unwrap().0;
+    }
+    let left = task.clone();
+    scope(|s| {
+        let left = s.spawn(move |_| {
+            common::init();
+            fork(left, depth - 1)
+        });
+        fork(task, depth - 1) + left.join().unwrap()
+    })
+    .unwrap()
+}
+
+#[test]
+fn fork_join() {
+    common::init();
+
+    let task = Rc::new_weighted(Counted(1));
+    // Exhaust the weight of a handle so that its clones refill it.
+    let mut handle = task.clone();
+    while handle.weight() > 1 {
+        handle = handle.clone();
+    }
+    let refilled = handle.clone();
+    assert!(refilled.weight() > 1);
+    drop((handle, refilled));
+
+    assert_eq!(fork(task, 5), 32);
+    flush_until_dropped(1);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use circ::{cs, AtomicRc, Collector, Rc, RcObject};
+
+struct Token;
+
+unsafe impl RcObject for Token {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+struct Other;
+
+unsafe impl RcObject for Other {
+    fn pop_edges(&mut self, _: &mut Vec<Rc<Self>>) {}
+}
+
+#[test]
+fn shared_object() {
+    common::init();
+
+    let token = Rc::new_zst(Token);
+    let other = Rc::new_zst(Other);
+    assert!(token.ptr_eq(&Rc::new_zst(Token)));
+    assert_eq!(
+        token.as_ref().unwrap() as *const Token as usize,
+        other.as_ref().unwrap() as *const Other as usize
+    );
+
+    // The shared object is never destructed.
+    let weak = token.downgrade();
+    drop((token, other));
+    for _ in 0..16 {
+        cs().flush();
+    }
+    assert!(weak.upgrade().is_some());
+}
+
+#[test]
+fn stored_in_other_collectors() {
+    common::init();
+
+    let collector = Collector::new();
+    collector.enter(|| {
+        let slot = AtomicRc::from(Rc::new_zst(Token));
+        let guard = &cs();
+        assert!(!slot.load(Ordering::Acquire, guard).is_null());
+        slot.swap(Rc::new_zst(Token), Ordering::AcqRel)
+            .finalize(guard);
+    });
+}
+
+#[test]
+fn concurrent_clones() {
+    common::init();
+
+    let token = Rc::new_zst(Token);
+    crossbeam_utils::thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|_| {
+                common::init();
+                for _ in 0..1000 {
+                    let clones = token.clone_n(4);
+                    cs().retire_all(clones);
+                    drop(Rc::new_zst(Token));
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(token.ptr_eq(&Rc::new_zst(Token)));
+}